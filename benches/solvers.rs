@@ -0,0 +1,120 @@
+// Criterion benchmarks for the hot solver functions of each day.
+// Run with `cargo bench`, or `cargo bench -- day12` for a single day.
+// Several solvers print progress, so redirect stdout for readable output.
+
+use advent_of_code_2025::days::{day02, day03, day06, day08, day09, day10, day12};
+use advent_of_code_2025::days::day08::PairSearch;
+use advent_of_code_2025::input::Input;
+use advent_of_code_2025::util::cancel::CancellationToken;
+use advent_of_code_2025::util::polyomino::{Packer, SolverKind, Strategy};
+use advent_of_code_2025::util::progress::Hidden;
+use advent_of_code_2025::util::reporter::Silent;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use std::time::Duration;
+
+fn bench_day02(c: &mut Criterion) {
+    let input = Input::for_day(2).read("ranges").unwrap();
+    let ranges = day02::merge_ranges(&day02::parse_ranges(input.trim()).unwrap()).unwrap();
+
+    c.bench_function("day02 invalid id scan", |b| {
+        b.iter(|| {
+            for &range in ranges.intervals() {
+                black_box(day02::find_invalid_ids_in_range(range, day02::RepeatMode::AnyCount, &CancellationToken::new()).unwrap());
+            }
+        })
+    });
+}
+
+fn bench_day03(c: &mut Criterion) {
+    let banks = day03::parse_banks_file(Input::for_day(3).read("banks").unwrap()).unwrap();
+
+    c.bench_function("day03 largest joltage", |b| {
+        b.iter(|| {
+            for bank in &banks {
+                black_box(day03::find_largest_joltage_settings(bank, 12).unwrap());
+            }
+        })
+    });
+}
+
+fn bench_day06(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day06");
+    group.sample_size(10);
+    // Generated worksheets, from below the parallel cut-off to far above it
+    for columns in [1_000, 10_000, 100_000] {
+        let sheet = day06::wide_worksheet(columns, 4, 6);
+        group.bench_with_input(BenchmarkId::new("rows", columns), &sheet, |b, sheet| b.iter(|| day06::part1(sheet).unwrap()));
+        group.bench_with_input(BenchmarkId::new("columns", columns), &sheet, |b, sheet| b.iter(|| day06::part2(sheet).unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_day08(c: &mut Criterion) {
+    let coordinates = day08::parse_input(Input::for_day(8).read("coordinates").unwrap()).unwrap();
+
+    let mut group = c.benchmark_group("day08");
+    group.sample_size(10);
+    group.bench_function("1000 connections", |b| b.iter(|| day08::create_clusters(black_box(&coordinates), 1000, PairSearch::Exact, &Hidden, &Silent)));
+    group.bench_function("single cluster", |b| b.iter(|| day08::connect_until_single_cluster(black_box(&coordinates), PairSearch::Exact, &Hidden, &Silent)));
+    let approximate = PairSearch::approximate(&coordinates);
+    group.bench_function("1000 connections approximate", |b| {
+        b.iter(|| day08::create_clusters(black_box(&coordinates), 1000, approximate, &Hidden, &Silent))
+    });
+    group.finish();
+}
+
+fn bench_day09(c: &mut Criterion) {
+    let coordinates = day09::parse_input(Input::for_day(9).read("tiles2").unwrap()).unwrap();
+    let boundary = day09::boundary(&coordinates).unwrap();
+
+    let mut group = c.benchmark_group("day09");
+    group.sample_size(10);
+    group.bench_function("largest rectangle", |b| b.iter(|| day09::find_largest_rectangle(black_box(&coordinates))));
+    group.bench_function("largest rectangle in polygon", |b| {
+        b.iter(|| day09::find_largest_rectangle_in_polygon(black_box(&boundary), &Silent))
+    });
+    group.finish();
+}
+
+fn bench_day10(c: &mut Criterion) {
+    let machines = day10::parse_input(Input::for_day(10).read("machines2").unwrap()).unwrap();
+
+    let mut group = c.benchmark_group("day10");
+    group.sample_size(10);
+    group.bench_function("joltage solver", |b| {
+        b.iter(|| {
+            for machine in &machines {
+                black_box(day10::solve_joltage(machine));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_day12(c: &mut Criterion) {
+    let (shapes, spaces) = day12::parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+    let (big_shapes, big_spaces) = day12::parse_input(Input::for_day(12).read("trees2").unwrap()).unwrap();
+
+    let mut group = c.benchmark_group("day12");
+    group.sample_size(10);
+    // The solvable 12x5 space from Part 1, on each search backend
+    for kind in [SolverKind::Backtracking, SolverKind::Dlx] {
+        let mut packer = Packer::with_shapes(&shapes);
+        packer.set_strategy(Strategy::Fixed(kind), Duration::MAX).set_board(spaces[1].clone());
+        group.bench_function(format!("part 1 {}", kind.label()), |b| b.iter(|| packer.solve().unwrap()));
+    }
+    let mut packer = Packer::with_shapes(&big_shapes);
+    packer.set_strategy(Strategy::Auto, Packer::DEFAULT_TIMEOUT);
+    group.bench_function("part 2 auto", |b| {
+        b.iter(|| {
+            for space in &big_spaces {
+                black_box(packer.set_board(space.clone()).solve().unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_day02, bench_day03, bench_day06, bench_day08, bench_day09, bench_day10, bench_day12);
+criterion_main!(benches);
@@ -1,11 +1,21 @@
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use crate::input::{self, Input};
+use crate::util::output::OutputSink;
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::theme::{self, Style, Theme};
+use clap::Parser;
+use console::{Key, Term};
+use std::io::BufRead;
+
 // Constants for the dial mechanics
 const DIAL_MIN: i32 = 0;
 const DIAL_MAX: i32 = 99;
 const DIAL_SIZE: i32 = 100;
 const START_VALUE: i32 = 50;
 
-#[derive(Debug, Clone, Copy)]
-enum Direction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     Left,
     Right,
 }
@@ -22,7 +32,57 @@ impl TryFrom<char> for Direction {
     }
 }
 
-struct Safe {
+/// The letter the input uses
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Direction::Left => "L",
+            Direction::Right => "R",
+        })
+    }
+}
+
+/// One rotation of the dial, written like the input lines ("L5", "R10")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turn {
+    pub direction: Direction,
+    pub amount: i32,
+}
+
+impl std::fmt::Display for Turn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.direction, self.amount)
+    }
+}
+
+/// Where a turn from `start` leaves the dial, and how many times it passes
+/// or stops on zero along the way
+fn apply_turn(start: i32, amount: i32, direction: Direction) -> (i32, i32) {
+    // How much the dial changes, even with large spins
+    let net_change: i32 = amount % DIAL_SIZE;
+
+    // Apply rotation using a multiplier for cleaner code
+    let direction_multiplier = match direction {
+        Direction::Left => -1,
+        Direction::Right => 1,
+    };
+    let value = start + direction_multiplier * net_change;
+
+    // Register full-round trips past zero
+    let mut zero_visits = amount / DIAL_SIZE;
+
+    // If the net amount also causes a zero visit, count it
+    // but only if we didn't start at zero
+    if start != 0 && (!(DIAL_MIN..=DIAL_MAX).contains(&value) || value == 0) {
+        zero_visits += 1;
+    }
+
+    // Normalize the dial to 0-99 range
+    (((value % DIAL_SIZE) + DIAL_SIZE) % DIAL_SIZE, zero_visits)
+}
+
+#[derive(Debug)]
+pub struct Safe {
     // Current position on the dial (0-99)
     dial_value: i32,
     // Number of times the dial stopped exactly on zero
@@ -40,75 +100,599 @@ impl Safe {
         }
     }
 
-    fn rotate(&mut self, amount: i32, direction: Direction) {
+    /// Turns the dial without printing anything
+    fn turn(&mut self, amount: i32, direction: Direction) {
+        let (dial_value, zero_visits) = apply_turn(self.dial_value, amount, direction);
+        self.dial_value = dial_value;
+        self.visits_zero += zero_visits;
+
+        // Check for landed-on-zero case
+        if self.dial_value == 0 {
+            self.stops_on_zero += 1;
+        }
+    }
+
+    /// Turns the dial, reporting how the value and zero counts changed
+    fn rotate(&mut self, amount: i32, direction: Direction, out: &dyn Reporter) {
         let before_value = self.dial_value;
         let before_zero_visits = self.visits_zero;
         let before_stops_on_zero = self.stops_on_zero;
         
-        // How much the dial changes, even with large spins
-        let net_change: i32 = amount % DIAL_SIZE;
+        self.turn(amount, direction);
+        
+        out.line(&format!("{} -> {:?}{} -> {}", before_value, direction, amount, self.dial_value));
+        out.line(&format!("Zero visits: {} -> {}", before_zero_visits, self.visits_zero));
+        out.line(&format!("Stops on zero: {} -> {}", before_stops_on_zero, self.stops_on_zero));
+        out.line("--------------------------------");
+    }
 
-        // Apply rotation using a multiplier for cleaner code
-        let direction_multiplier = match direction {
-            Direction::Left => -1,
-            Direction::Right => 1,
-        };
-        self.dial_value += direction_multiplier * net_change;
+    pub fn dial_value(&self) -> i32 {
+        self.dial_value
+    }
 
-        // Register full-round trips past zero
-        self.visits_zero += amount / DIAL_SIZE;
+    pub fn stops_on_zero(&self) -> i32 {
+        self.stops_on_zero
+    }
 
-        // If the net amount also causes a zero visit, count it
-        // but only if we didn't start at zero
-        if before_value != 0 && (self.dial_value < DIAL_MIN || self.dial_value > DIAL_MAX || self.dial_value == 0) {
-            self.visits_zero += 1;
-        }
+    pub fn visits_zero(&self) -> i32 {
+        self.visits_zero
+    }
 
-        // Normalize the dial to 0-99 range
-        self.dial_value = ((self.dial_value % DIAL_SIZE) + DIAL_SIZE) % DIAL_SIZE;
+    /// Fewest turns, and then the smallest total rotation, that bring the
+    /// dial to `target` (taken modulo the dial size)
+    pub fn plan_to(&self, target: i32) -> Vec<Turn> {
+        self.plan(target, None).unwrap_or_default()
+    }
 
-        // Check for landed-on-zero case
-        if self.dial_value == 0 {
-            self.stops_on_zero += 1;
+    /// Like `plan_to`, but the turns must pass or stop on zero exactly
+    /// `times` times. `None` if no sequence of turns can do that.
+    pub fn plan_to_passing_zero(&self, target: i32, times: i32) -> Option<Vec<Turn>> {
+        self.plan(target, Some(times))
+    }
+
+    /// Zero visits only ever add up, and every extra full spin adds exactly
+    /// one, so the best plan is at most one turn: the shortest landing turn
+    /// in either direction, padded with full spins to reach `times`.
+    fn plan(&self, target: i32, times: Option<i32>) -> Option<Vec<Turn>> {
+        let target = target.rem_euclid(DIAL_SIZE);
+        if self.dial_value == target && times.unwrap_or(0) == 0 {
+            return Some(Vec::new());
         }
-        
-        println!("{} -> {:?}{} -> {}", before_value, direction, amount, self.dial_value);
-        println!("Zero visits: {} -> {}", before_zero_visits, self.visits_zero);
-        println!("Stops on zero: {} -> {}", before_stops_on_zero, self.stops_on_zero);
-        println!("--------------------------------");
+
+        [Direction::Left, Direction::Right]
+            .into_iter()
+            .filter_map(|direction| {
+                let shortest = match direction {
+                    Direction::Left => (self.dial_value - target).rem_euclid(DIAL_SIZE),
+                    Direction::Right => (target - self.dial_value).rem_euclid(DIAL_SIZE),
+                };
+                // Already on target: the shortest landing turn is a full spin
+                let shortest = if shortest == 0 { DIAL_SIZE } else { shortest };
+                let (_, visits) = apply_turn(self.dial_value, shortest, direction);
+                let extra_spins = match times {
+                    None => 0,
+                    Some(times) if times >= visits => times - visits,
+                    Some(_) => return None,
+                };
+                Some(Turn { direction, amount: shortest + extra_spins * DIAL_SIZE })
+            })
+            .min_by_key(|turn| turn.amount)
+            .map(|turn| vec![turn])
     }
 }
 
 /// Parse a turn string like "L5" or "R10" into a direction and amount
-fn parse_turn(line: &str) -> Result<(Direction, i32), Box<dyn std::error::Error>> {
-    let direction = line.chars().next()
-        .ok_or("Empty line")?
+fn parse_turn(token: &str) -> Result<(Direction, i32), String> {
+    let direction = token.chars().next()
+        .ok_or("Empty turn")?
         .try_into()?;
-    let amount = line.get(1..)
-        .ok_or("Invalid turn format")?
-        .parse()?;
+    let amount = token.get(1..).ok_or("Invalid turn format")?;
+    if !amount.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(format!("Invalid amount '{}': expected digits", amount));
+    }
+    let amount = amount.parse().map_err(|e| format!("Invalid amount '{}': {}", amount, e))?;
     Ok((direction, amount))
 }
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// Every turn on one line. Turns are separated by whitespace and/or commas,
+/// and a `#` starts a comment that runs to the end of the line.
+fn parse_line(line: &str, line_no: usize) -> Result<Vec<(Direction, i32)>> {
+    let code = line.split_once('#').map_or(line, |(code, _)| code);
+    let is_separator = |c: char| c.is_whitespace() || c == ',';
+
+    let mut turns = Vec::new();
+    let mut rest = code;
+    while let Some(start) = rest.find(|c| !is_separator(c)) {
+        let column = code.len() - rest.len() + start + 1;
+        let token = &rest[start..];
+        let token = &token[..token.find(is_separator).unwrap_or(token.len())];
+        let turn = parse_turn(token).map_err(|message| {
+            // Point at the amount when the direction letter was fine
+            let offset = if token.starts_with(['L', 'R']) { 1 } else { 0 };
+            AocError::parse_at_column(line_no, column + offset, message)
+        })?;
+        turns.push(turn);
+        rest = &rest[start + token.len()..];
+    }
+    Ok(turns)
+}
+
+fn parse_turns(content: &str) -> Result<Vec<(Direction, i32)>> {
+    let mut turns = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        turns.extend(parse_line(line, i + 1)?);
+    }
+    Ok(turns)
+}
+
+/// The safe after every turn in `input`
+pub fn simulate(input: &str) -> Result<Safe> {
     let mut safe = Safe::new();
-    let turns = std::fs::read_to_string("assets/day01turns.txt")?;
+    for (direction, amount) in parse_turns(input)? {
+        safe.rotate(amount, direction, &Silent);
+    }
+    Ok(safe)
+}
+
+/// Times the dial stops on zero
+pub fn part1(input: &str) -> Result<i32> {
+    Ok(simulate(input)?.stops_on_zero)
+}
+
+/// Times the dial passes or stops on zero
+pub fn part2(input: &str) -> Result<i32> {
+    Ok(simulate(input)?.visits_zero)
+}
+
+/// Most dials in a bank, so `SafeBank::combined_value` fits in a u128
+const MAX_DIALS: usize = 19;
+
+/// How far a dial turns when a rule couples it to the dial being turned:
+/// `numerator / denominator` of that turn, rounded down, in `direction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coupling {
+    pub direction: Direction,
+    pub numerator: i32,
+    pub denominator: i32,
+}
+
+impl Coupling {
+    fn amount(&self, driving: i32) -> Option<i32> {
+        i32::try_from(i64::from(driving) * i64::from(self.numerator) / i64::from(self.denominator)).ok()
+    }
+}
+
+/// Several named dials, each a `Safe`, where turning one can turn others.
+/// Couplings don't chain: only the dial an input line names drives the rest.
+#[derive(Debug)]
+pub struct SafeBank {
+    names: Vec<String>,
+    dials: Vec<Safe>,
+    /// One row per dial and direction turned (see `coupling_row`), one
+    /// column per dial it moves
+    coupling: Vec<Vec<Option<Coupling>>>,
+}
+
+fn coupling_row(dial: usize, direction: Direction) -> usize {
+    2 * dial + usize::from(direction == Direction::Right)
+}
+
+impl SafeBank {
+    fn new(names: Vec<String>) -> Self {
+        let dials = names.iter().map(|_| Safe::new()).collect();
+        let coupling = vec![vec![None; names.len()]; 2 * names.len()];
+        SafeBank { names, dials, coupling }
+    }
+
+    /// The dial called `name`, if there is one
+    pub fn dial(&self, name: &str) -> Option<&Safe> {
+        self.names.iter().position(|n| n == name).map(|i| &self.dials[i])
+    }
+
+    /// Every dial with its name, in the order the header listed them
+    pub fn dials(&self) -> impl Iterator<Item = (&str, &Safe)> {
+        self.names.iter().map(String::as_str).zip(&self.dials)
+    }
+
+    /// What turning dial `from` in `direction` does to dial `to`
+    pub fn coupling(&self, from: usize, direction: Direction, to: usize) -> Option<Coupling> {
+        self.coupling[coupling_row(from, direction)][to]
+    }
+
+    /// Turns dial `dial` and every dial coupled to it; fails only if a
+    /// coupled turn is too large to represent
+    pub fn turn(&mut self, dial: usize, amount: i32, direction: Direction) -> Result<()> {
+        let row = coupling_row(dial, direction);
+        let mut coupled = Vec::new();
+        for (to, coupling) in self.coupling[row].iter().enumerate() {
+            if let Some(coupling) = coupling {
+                let moved = coupling.amount(amount).ok_or_else(|| AocError::solve(format!(
+                    "Turning dial {} by {} turns dial {} too far", self.names[dial], amount, self.names[to]
+                )))?;
+                coupled.push((to, moved, coupling.direction));
+            }
+        }
+        self.dials[dial].turn(amount, direction);
+        for (to, moved, direction) in coupled {
+            self.dials[to].turn(moved, direction);
+        }
+        Ok(())
+    }
+
+    pub fn stops_on_zero(&self) -> i32 {
+        self.dials.iter().map(|dial| dial.stops_on_zero).sum()
+    }
+
+    pub fn visits_zero(&self) -> i32 {
+        self.dials.iter().map(|dial| dial.visits_zero).sum()
+    }
 
-    for turn in turns.lines() {
-        let (direction, amount) = parse_turn(turn)?;
-        safe.rotate(amount, direction);
+    /// The dials read in order as the digits of one base-100 number, the
+    /// way a combination lock reads "12-03-45" as 120345
+    pub fn combined_value(&self) -> u128 {
+        self.dials.iter().fold(0, |value, dial| value * DIAL_SIZE as u128 + dial.dial_value as u128)
     }
+}
 
-    println!("Safe value: {}", safe.dial_value);
-    println!("Zero hits: {}", safe.stops_on_zero);
-    println!("Zero visits: {}", safe.visits_zero);
+/// Strips a `#` comment and surrounding whitespace
+fn code(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(code, _)| code).trim()
+}
 
+fn parse_dial_names(code: &str, line_no: usize) -> Result<Vec<String>> {
+    let names: Vec<String> = match code.strip_prefix("dials") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.split_whitespace().map(str::to_string).collect(),
+        _ => return Err(AocError::parse_at(line_no, "Expected \"dials\" followed by the dial names")),
+    };
+    if names.len() > MAX_DIALS {
+        return Err(AocError::parse_at(line_no, format!("At most {} dials fit in a bank, got {}", MAX_DIALS, names.len())));
+    }
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(AocError::parse_at(line_no, format!("Dial {} is named twice", name)));
+        }
+        if name.contains(':') {
+            return Err(AocError::parse_at(line_no, format!("Dial name '{}' can't contain ':'", name)));
+        }
+    }
+    Ok(names)
+}
+
+/// A rule like "A R -> B L 1/2": turning A right turns B left by half as
+/// much. The factor defaults to 1.
+fn parse_rule(code: &str, names: &[String], line_no: usize) -> Result<(usize, Direction, usize, Coupling)> {
+    let error = |message: String| AocError::parse_at(line_no, message);
+    let dial = |name: &str| names.iter().position(|n| n == name).ok_or_else(|| error(format!("Unknown dial {}", name)));
+    let direction = |text: &str| match text {
+        "L" => Ok(Direction::Left),
+        "R" => Ok(Direction::Right),
+        _ => Err(error(format!("Invalid direction: {}", text))),
+    };
+    let number = |text: &str| text.parse::<i32>().ok().filter(|&n| n > 0)
+        .ok_or_else(|| error(format!("Invalid factor '{}': expected a positive whole number or fraction", text)));
+
+    let tokens: Vec<&str> = code.split_whitespace().collect();
+    let (from, from_direction, to, to_direction, factor) = match tokens[..] {
+        [from, from_direction, "->", to, to_direction] => (from, from_direction, to, to_direction, "1"),
+        [from, from_direction, "->", to, to_direction, factor] => (from, from_direction, to, to_direction, factor),
+        _ => return Err(error(format!("Expected a rule like \"A R -> B L 1/2\", got '{}'", code))),
+    };
+    let (numerator, denominator) = match factor.split_once('/') {
+        Some((numerator, denominator)) => (number(numerator)?, number(denominator)?),
+        None => (number(factor)?, 1),
+    };
+    let (from, to) = (dial(from)?, dial(to)?);
+    if from == to {
+        return Err(error(format!("Dial {} can't be coupled to itself", names[from])));
+    }
+    Ok((from, direction(from_direction)?, to, Coupling { direction: direction(to_direction)?, numerator, denominator }))
+}
+
+/// Which dial of a bank to turn, which way and how far
+type BankTurn = (usize, Direction, i32);
+
+/// A bank of dials and the turns to apply to it. The header names the dials
+/// ("dials A B C") and then lists coupling rules, one per line, up to the
+/// first blank line; after it every line is a dial name, a colon and that
+/// dial's turns, written as for a single safe ("A: R10 L5").
+fn parse_bank(content: &str) -> Result<(SafeBank, Vec<BankTurn>)> {
+    let mut lines = content.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let mut bank: Option<SafeBank> = None;
+    for (line_no, line) in lines.by_ref() {
+        let code = code(line);
+        match bank.as_mut() {
+            None if code.is_empty() => {}
+            None => bank = Some(SafeBank::new(parse_dial_names(code, line_no)?)),
+            Some(_) if code.is_empty() => break,
+            Some(bank) => {
+                let (from, direction, to, coupling) = parse_rule(code, &bank.names, line_no)?;
+                let cell = &mut bank.coupling[coupling_row(from, direction)][to];
+                if cell.is_some() {
+                    return Err(AocError::parse_at(line_no, format!(
+                        "Turning dial {} {} already has a rule for dial {}", bank.names[from], direction, bank.names[to]
+                    )));
+                }
+                *cell = Some(coupling);
+            }
+        }
+    }
+    let bank = bank.ok_or_else(|| AocError::parse("Missing the \"dials\" line naming the safe's dials"))?;
+
+    let mut turns = Vec::new();
+    for (line_no, line) in lines {
+        if code(line).is_empty() {
+            continue;
+        }
+        let (name, rest) = line.split_once(':')
+            .ok_or_else(|| AocError::parse_at(line_no, "Expected a dial name and ':' before the turns"))?;
+        let dial = bank.names.iter().position(|n| n == name.trim())
+            .ok_or_else(|| AocError::parse_at(line_no, format!("Unknown dial {}", name.trim())))?;
+        // Blank out the name so parse_line reports columns within the whole line
+        let padded = format!("{}{}", " ".repeat(name.len() + 1), rest);
+        turns.extend(parse_line(&padded, line_no)?.into_iter().map(|(direction, amount)| (dial, direction, amount)));
+    }
+    Ok((bank, turns))
+}
+
+/// The bank of dials after every turn in `input`
+pub fn simulate_bank(input: &str) -> Result<SafeBank> {
+    let (mut bank, turns) = parse_bank(input)?;
+    for (dial, direction, amount) in turns {
+        bank.turn(dial, amount, direction)?;
+    }
+    Ok(bank)
+}
+
+/// Columns and rows of the dial drawn by `render_dial`; twice as wide as
+/// tall so it comes out round in a terminal
+const DIAL_COLUMNS: usize = 41;
+const DIAL_ROWS: usize = 21;
+
+/// The dial as a ring of notches, zero at the top and values increasing
+/// clockwise: `0` marks zero, `+` every tenth notch, `.` the rest and `@`
+/// the pointer, with the pointer's value in the middle
+pub fn render_dial(value: i32, theme: Theme) -> String {
+    let mut cells = vec![vec![(' ', Style::Plain); DIAL_COLUMNS]; DIAL_ROWS];
+    let (cx, cy) = ((DIAL_COLUMNS / 2) as f64, (DIAL_ROWS / 2) as f64);
+    let mut place = |notch: i32, ch: char, style: Style| {
+        let angle = f64::from(notch) / f64::from(DIAL_SIZE) * std::f64::consts::TAU;
+        let x = (cx + cx * angle.sin()).round() as usize;
+        let y = (cy - cy * angle.cos()).round() as usize;
+        cells[y][x] = (ch, style);
+    };
+    for notch in DIAL_MIN..=DIAL_MAX {
+        match notch {
+            0 => place(notch, '0', Style::Good),
+            n if n % 10 == 0 => place(notch, '+', Style::Plain),
+            _ => place(notch, '.', Style::Muted),
+        }
+    }
+    place(value, '@', Style::Highlight);
+
+    let label = format!("{:>2}", value);
+    let start = DIAL_COLUMNS / 2 - 1;
+    for (i, ch) in label.chars().enumerate() {
+        cells[DIAL_ROWS / 2][start + i] = (ch, Style::Heading);
+    }
+
+    let mut out = String::new();
+    for row in cells {
+        let line: String = row.iter().map(|&(ch, style)| theme.paint(style, ch)).collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// What the user asked for in `--interactive`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Apply one turn
+    Next,
+    /// Keep turning until a turn passes or stops on zero
+    NextZero,
+    Quit,
+}
+
+/// Waits for a key on a terminal, or reads a line when stdin is piped:
+/// enter, space or n for one turn, z to run to the next zero, q to stop
+fn read_step(term: &Term) -> Result<Step> {
+    let io_error = |e| AocError::io("stdin", e);
+    if term.is_term() {
+        loop {
+            match term.read_key().map_err(io_error)? {
+                Key::Enter | Key::ArrowRight | Key::Char(' ' | 'n') => return Ok(Step::Next),
+                Key::Char('z') => return Ok(Step::NextZero),
+                Key::Char('q') | Key::Escape => return Ok(Step::Quit),
+                _ => {}
+            }
+        }
+    }
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line).map_err(io_error)? == 0 {
+        return Ok(Step::Quit);
+    }
+    Ok(match line.trim() {
+        "z" => Step::NextZero,
+        "q" => Step::Quit,
+        _ => Step::Next,
+    })
+}
+
+/// Steps through `turns` as the user asks, redrawing the dial and the
+/// counters after every turn
+fn run_interactive(turns: &[(Direction, i32)]) -> Result<()> {
+    let term = Term::stdout();
+    let theme = theme::current();
+    let mut safe = Safe::new();
+    let mut last: Option<(Turn, i32)> = None;
+    let mut done = 0;
+
+    loop {
+        if term.is_term() {
+            term.clear_screen().map_err(|e| AocError::io("stdout", e))?;
+        }
+        print!("{}", render_dial(safe.dial_value, theme));
+        match last {
+            Some((turn, visits)) => {
+                let style = if visits > 0 { Style::Good } else { Style::Plain };
+                let visits = theme.paint(style, format!("{} zero visit(s)", visits));
+                println!("Turn {}/{}: {}, {}", done, turns.len(), turn, visits);
+            }
+            None => println!("Turn 0/{}: starting at {}", turns.len(), START_VALUE),
+        }
+        println!("Stops on zero: {}   Zero visits: {}", safe.stops_on_zero, safe.visits_zero);
+        if done == turns.len() {
+            return Ok(());
+        }
+        println!("{}", theme.paint(Style::Muted, "[enter] next turn  [z] next zero  [q] quit"));
+
+        let step = read_step(&term)?;
+        if step == Step::Quit {
+            return Ok(());
+        }
+        while done < turns.len() {
+            let (direction, amount) = turns[done];
+            let before = safe.visits_zero;
+            safe.turn(amount, direction);
+            done += 1;
+            last = Some((Turn { direction, amount }, safe.visits_zero - before));
+            if step == Step::Next || safe.visits_zero > before {
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day01", about = "Day 1: Secret Entrance")]
+struct Options {
+    /// Step through the turns one keypress at a time, drawing the dial
+    #[arg(long)]
+    interactive: bool,
+    /// Simulate a bank of coupled dials described in FILE instead of the
+    /// single safe
+    #[arg(long, value_name = "FILE", conflicts_with = "interactive")]
+    bank: Option<String>,
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Secret Entrance",
+        input_files: &["turns"],
+        parts: [
+            "Times the dial stops on zero",
+            "Times the dial passes or stops on zero",
+        ],
+        notes: "Both parts share one simulation of the dial; --interactive steps through it, --bank simulates several coupled dials",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day01".to_string()).chain(args.iter().cloned()));
+    if let Some(path) = &options.bank {
+        return run_bank(path, out);
+    }
+    let turns = Input::for_day(1).parse("turns", parse_turns)?;
+    if options.interactive {
+        return run_interactive(&turns);
+    }
+
+    let mut safe = Safe::new();
+    for (direction, amount) in turns {
+        safe.rotate(amount, direction, out);
+    }
+
+    out.line(&format!("Safe value: {}", safe.dial_value));
+    out.answer(1, "Zero hits", &safe.stops_on_zero);
+    out.answer(2, "Zero visits", &safe.visits_zero);
+
+    Ok(())
+}
+
+fn run_bank(path: &str, out: &dyn OutputSink) -> Result<()> {
+    let bank = simulate_bank(input::read_file(path)?).map_err(|e| e.in_file(path))?;
+    let width = bank.dials().map(|(name, _)| name.len()).max().unwrap_or(0);
+    out.line(&format!("Bank of {} dials:", bank.dials.len()));
+    for (name, dial) in bank.dials() {
+        out.line(&format!(
+            "  {:<w$}  value {:>2}, {} stop(s) on zero, {} zero visit(s)",
+            name, dial.dial_value, dial.stops_on_zero, dial.visits_zero, w = width
+        ));
+    }
+    out.line(&format!("Total stops on zero: {}", bank.stops_on_zero()));
+    out.line(&format!("Total zero visits: {}", bank.visits_zero()));
+    out.line(&format!("Combined value: {}", bank.combined_value()));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
+    use proptest::prelude::*;
+
+    /// Reference dial that moves one notch at a time, so every pass over
+    /// zero is seen directly instead of worked out with modular arithmetic
+    struct SteppingSafe {
+        dial_value: i32,
+        stops_on_zero: i32,
+        visits_zero: i32,
+    }
+
+    impl SteppingSafe {
+        fn new() -> Self {
+            SteppingSafe { dial_value: START_VALUE, stops_on_zero: 0, visits_zero: 0 }
+        }
+
+        fn rotate(&mut self, amount: i32, direction: Direction) {
+            let step = match direction {
+                Direction::Left => DIAL_SIZE - 1,
+                Direction::Right => 1,
+            };
+            for _ in 0..amount {
+                self.dial_value = (self.dial_value + step) % DIAL_SIZE;
+                if self.dial_value == 0 {
+                    self.visits_zero += 1;
+                }
+            }
+            if self.dial_value == 0 {
+                self.stops_on_zero += 1;
+            }
+        }
+    }
+
+    fn turn() -> impl Strategy<Value = (Direction, i32)> {
+        let direction = prop_oneof![Just(Direction::Left), Just(Direction::Right)];
+        // Mostly short turns, with some landing exactly on or spinning past zero
+        let amount = prop_oneof![0..=DIAL_SIZE, Just(DIAL_SIZE), Just(2 * DIAL_SIZE), 0..=10 * DIAL_SIZE];
+        (direction, amount)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_rotation_matches_stepping(turns in prop::collection::vec(turn(), 0..40)) {
+            let mut fast = Safe::new();
+            let mut slow = SteppingSafe::new();
+            for (i, &(direction, amount)) in turns.iter().enumerate() {
+                fast.rotate(amount, direction, &Silent);
+                slow.rotate(amount, direction);
+                prop_assert_eq!(
+                    (fast.dial_value, fast.stops_on_zero, fast.visits_zero),
+                    (slow.dial_value, slow.stops_on_zero, slow.visits_zero),
+                    "after turn {} ({:?}{})", i + 1, direction, amount
+                );
+            }
+        }
+
+        #[test]
+        fn prop_apply_turn_from_any_position(start in DIAL_MIN..=DIAL_MAX, (direction, amount) in turn()) {
+            let mut slow = SteppingSafe { dial_value: start, stops_on_zero: 0, visits_zero: 0 };
+            slow.rotate(amount, direction);
+            prop_assert_eq!(apply_turn(start, amount, direction), (slow.dial_value, slow.visits_zero));
+        }
+    }
 
     #[test]
     fn test_parse_turn_left() {
@@ -124,38 +708,110 @@ mod tests {
         matches!(dir, Direction::Right);
     }
     
+    #[test]
+    fn test_parse_compound_lines_and_comments() {
+        let turns = parse_turns("L5 R10, L3\n\n  R7   # warm-up\n# nothing here\nL1,R2,\t\n").unwrap();
+        let amounts: Vec<i32> = turns.iter().map(|&(_, amount)| amount).collect();
+        assert_eq!(amounts, vec![5, 10, 3, 7, 1, 2]);
+        assert_eq!(turns[1].0, Direction::Right);
+
+        let err = parse_turns("L5\nR10, X3\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2, column 6: Invalid direction: X");
+        let err = parse_turns("R1 L2x").unwrap_err();
+        assert!(err.to_string().starts_with("Line 1, column 5: Invalid amount '2x'"), "{}", err);
+        assert!(parse_turns("L-5").is_err());
+    }
+
     #[test]
     fn test_rotate_right_simple() {
         let mut safe = Safe::new();
-        safe.rotate(10, Direction::Right);
+        safe.rotate(10, Direction::Right, &Silent);
         assert_eq!(safe.dial_value, 60);
     }
 
     #[test]
     fn test_rotate_left_simple() {
         let mut safe = Safe::new();
-        safe.rotate(10, Direction::Left);
+        safe.rotate(10, Direction::Left, &Silent);
         assert_eq!(safe.dial_value, 40);
     }
 
     #[test]
     fn test_rotate_wraps_around() {
         let mut safe = Safe::new();
-        safe.rotate(60, Direction::Right); // 50 + 60 = 110, wraps to 10
+        safe.rotate(60, Direction::Right, &Silent); // 50 + 60 = 110, wraps to 10
         assert_eq!(safe.dial_value, 10);
     }
 
     #[test]
     fn test_full_solution_visits_zero() {
         let mut safe = Safe::new();
-        let turns = std::fs::read_to_string("assets/day01turns.txt")
+        let turns = Input::for_day(1).read("turns")
             .expect("Failed to read input file");
 
         for turn in turns.lines() {
             let (direction, amount) = parse_turn(turn).unwrap();
-            safe.rotate(amount, direction);
+            safe.rotate(amount, direction, &Silent);
         }
 
-        assert_eq!(safe.visits_zero, 6789);
+        snapshot::check("day01", "visits_zero", safe.visits_zero);
+    }
+
+    #[test]
+    fn test_safe_bank_coupling() {
+        let bank = simulate_bank(Input::for_day(1).read("bank").unwrap()).unwrap();
+        assert_eq!(bank.coupling(0, Direction::Right, 1), Some(Coupling { direction: Direction::Left, numerator: 1, denominator: 2 }));
+        assert_eq!(bank.coupling(0, Direction::Left, 1), None);
+
+        // A R50 lands A on zero and turns B left 25; B L25 lands B on zero
+        // and spins C right 50 onto zero; C L150 takes A left 150 with it
+        let dials: Vec<(&str, i32, i32, i32)> = bank.dials()
+            .map(|(name, dial)| (name, dial.dial_value(), dial.stops_on_zero(), dial.visits_zero()))
+            .collect();
+        assert_eq!(dials, vec![("A", 50, 1, 2), ("B", 0, 1, 1), ("C", 50, 1, 2)]);
+        assert_eq!((bank.stops_on_zero(), bank.visits_zero()), (3, 5));
+        assert_eq!(bank.combined_value(), 50_00_50);
+
+        let error = |input: &str| simulate_bank(input).unwrap_err().to_string();
+        assert_eq!(error("A: R5\n"), "Line 1: Expected \"dials\" followed by the dial names");
+        assert_eq!(error("dials A B\nA R -> A L\n"), "Line 2: Dial A can't be coupled to itself");
+        assert_eq!(error("dials A B\nA R -> B L 1/0\n"), "Line 2: Invalid factor '0': expected a positive whole number or fraction");
+        assert_eq!(error("dials A B\nA R -> B L\nA R -> B R\n"), "Line 3: Turning dial A R already has a rule for dial B");
+        assert_eq!(error("dials A B\n\nC: R5\n"), "Line 3: Unknown dial C");
+        assert_eq!(error("dials A B\n\nB: R5 X2\n"), "Line 3, column 7: Invalid direction: X");
+    }
+
+    #[test]
+    fn test_plan_to() {
+        let safe = Safe::new();
+        assert!(safe.plan_to(50).is_empty());
+        assert_eq!(safe.plan_to(40), vec![Turn { direction: Direction::Left, amount: 10 }]);
+        assert_eq!(safe.plan_to(-1), vec![Turn { direction: Direction::Right, amount: 49 }]);
+        assert_eq!(safe.plan_to_passing_zero(0, 0), None);
+        assert_eq!(
+            safe.plan_to_passing_zero(50, 1).unwrap(),
+            vec![Turn { direction: Direction::Left, amount: 100 }]
+        );
+
+        // Left 190 crosses zero twice, beating Right 210
+        let plan = safe.plan_to_passing_zero(60, 2).unwrap();
+        assert_eq!(plan.iter().map(Turn::to_string).collect::<Vec<_>>(), vec!["L190"]);
+        let mut replay = Safe::new();
+        for turn in &plan {
+            replay.rotate(turn.amount, turn.direction, &Silent);
+        }
+        assert_eq!((replay.dial_value(), replay.visits_zero), (60, 2));
+    }
+
+    #[test]
+    fn test_render_dial() {
+        let dial = render_dial(25, Theme::PLAIN);
+        let rows: Vec<&str> = dial.lines().collect();
+        assert_eq!(rows.len(), DIAL_ROWS);
+        // Zero at the top, a quarter turn clockwise is the right edge
+        assert_eq!(rows[0].trim().chars().nth(rows[0].trim().len() / 2), Some('0'));
+        assert!(rows[DIAL_ROWS / 2].ends_with('@'));
+        assert!(rows[DIAL_ROWS / 2].contains("25"));
+        assert_eq!(dial.matches('@').count(), 1);
     }
 }
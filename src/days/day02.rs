@@ -1,19 +1,58 @@
-use anyhow::{anyhow, Result};
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use crate::input::Input;
+use crate::util::cancel::CancellationToken;
+use crate::util::interval::{Interval, IntervalSet};
+use crate::util::output::OutputSink;
+use crate::util::strings;
+use clap::{Parser, ValueEnum};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::str::FromStr;
 
 #[derive(Clone, Copy)]
-enum RepeatMode {
+pub enum RepeatMode {
     ExactlyTwice,
     AnyCount,
 }
 
-fn parse_ranges(line: &str) -> Result<Vec<(&str, &str)>> {
+/// How an invalid ID that falls in more than one range is counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DuplicatePolicy {
+    /// Once for every range it falls in
+    CountDuplicates,
+    /// Once, however many ranges it falls in
+    DistinctOnly,
+}
+
+pub fn parse_ranges(line: &str) -> Result<Vec<(&str, &str)>> {
+    let mut column = 1;
     line.split(',')
         .map(|range| {
-            range.split_once('-').ok_or_else(|| anyhow!("Invalid range format: {}", range))
+            let parsed = range.split_once('-').ok_or_else(|| {
+                AocError::parse_at_column(1, column, format!("Invalid range format: {}", range))
+            });
+            column += range.len() + 1;
+            parsed
         })
         .collect()
 }
 
+/// Parses the ranges' IDs, keeping overlapping ranges apart
+pub fn parse_intervals(ranges: &[(&str, &str)]) -> Result<Vec<Interval<u128>>> {
+    let parse_id = |id: &str| id.parse::<u128>().map_err(|e| AocError::parse(format!("Invalid ID '{}': {}", id, e)));
+    ranges
+        .iter()
+        .map(|&(start, end)| Ok(Interval::new(parse_id(start)?, parse_id(end)?)))
+        .collect()
+}
+
+/// Parses the ranges' IDs and merges any that overlap, so no ID gets
+/// scanned (and counted) twice
+pub fn merge_ranges(ranges: &[(&str, &str)]) -> Result<IntervalSet<u128>> {
+    Ok(parse_intervals(ranges)?.into_iter().collect())
+}
+
 fn is_invalid_id(id: u128, repeat_mode: RepeatMode) -> bool {
     let s = id.to_string();
 
@@ -25,59 +64,356 @@ fn is_invalid_id(id: u128, repeat_mode: RepeatMode) -> bool {
             let (first, second) = s.split_at(s.len() / 2);
             first == second
         }
-        RepeatMode::AnyCount => {
-        let mut num_parts = 2;
-        while s.len() / num_parts >= 1 {
-            if !s.len().is_multiple_of(num_parts) {
-                num_parts += 1;
-                continue;
+        // Repeated at least twice exactly when the shortest repeating block is shorter than the ID
+        RepeatMode::AnyCount => strings::smallest_period(s.as_bytes()) < s.len(),
+    }
+}
+
+/// IDs checked between polls of `cancel`
+const CANCEL_CHECK_INTERVAL: u128 = 1 << 16;
+
+/// The invalid IDs of one range, in increasing order, found as they're
+/// asked for. Yields an error and stops if `cancel` fires.
+pub struct InvalidIds<'a> {
+    next: u128,
+    end: u128,
+    scanned: u128,
+    repeat_mode: RepeatMode,
+    cancel: &'a CancellationToken,
+}
+
+impl<'a> InvalidIds<'a> {
+    pub fn new(range: Interval<u128>, repeat_mode: RepeatMode, cancel: &'a CancellationToken) -> Self {
+        // Single digits can't repeat
+        InvalidIds { next: range.start.max(10), end: range.end, scanned: 0, repeat_mode, cancel }
+    }
+}
+
+impl Iterator for InvalidIds<'_> {
+    type Item = Result<u128>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next <= self.end {
+            if self.scanned.is_multiple_of(CANCEL_CHECK_INTERVAL) {
+                if let Err(err) = self.cancel.check() {
+                    // Empty the range so the stream ends here
+                    (self.next, self.end) = (1, 0);
+                    return Some(Err(err));
+                }
+            }
+            let id = self.next;
+            match id.checked_add(1) {
+                Some(next) => self.next = next,
+                // `id` is u128::MAX, the last there is
+                None => (self.next, self.end) = (1, 0),
             }
-            let chunk_size = s.len() / num_parts;
+            self.scanned += 1;
+            if is_invalid_id(id, self.repeat_mode) {
+                return Some(Ok(id));
+            }
+        }
+        None
+    }
+}
 
-            // Get the slices of the string.
-            let chunks: Vec<&str> = (0..num_parts)
-                .map(|i| &s[i * chunk_size..(i + 1) * chunk_size])
-                .collect();
+/// Every invalid ID of several possibly overlapping ranges, each once and in
+/// increasing order: a k-way merge of the ranges' `InvalidIds` streams, so
+/// it holds one pending ID per range rather than a set of everything seen
+pub struct DistinctIds<'a> {
+    streams: Vec<InvalidIds<'a>>,
+    /// Smallest pending ID of each stream that still has one
+    heads: BinaryHeap<Reverse<(u128, usize)>>,
+    last: Option<u128>,
+    started: bool,
+}
+
+impl<'a> DistinctIds<'a> {
+    pub fn new(ranges: &[Interval<u128>], repeat_mode: RepeatMode, cancel: &'a CancellationToken) -> Self {
+        DistinctIds {
+            streams: ranges.iter().map(|&range| InvalidIds::new(range, repeat_mode, cancel)).collect(),
+            heads: BinaryHeap::new(),
+            last: None,
+            started: false,
+        }
+    }
 
-            // Compare adjacent chunks to see if they match.
-            if chunks.windows(2).all(|w| w[0] == w[1]) {
-                return true;
+    /// Moves stream `i` on to its next ID
+    fn advance(&mut self, i: usize) -> Result<()> {
+        if let Some(id) = self.streams[i].next().transpose()? {
+            self.heads.push(Reverse((id, i)));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for DistinctIds<'_> {
+    type Item = Result<u128>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            for i in 0..self.streams.len() {
+                if let Err(err) = self.advance(i) {
+                    return Some(Err(err));
+                }
+            }
+        }
+        while let Some(Reverse((id, i))) = self.heads.pop() {
+            if let Err(err) = self.advance(i) {
+                return Some(Err(err));
             }
-                num_parts += 1;
+            if self.last != Some(id) {
+                self.last = Some(id);
+                return Some(Ok(id));
+            }
+        }
+        None
+    }
+}
+
+/// Passes every invalid ID in `range` to `on_invalid`, in increasing order,
+/// without keeping any of them
+pub fn scan_range(
+    range: Interval<u128>,
+    repeat_mode: RepeatMode,
+    cancel: &CancellationToken,
+    mut on_invalid: impl FnMut(u128),
+) -> Result<()> {
+    for id in InvalidIds::new(range, repeat_mode, cancel) {
+        on_invalid(id?);
+    }
+    Ok(())
+}
+
+/// Number and sum of the invalid IDs in `ranges`, with IDs in several
+/// ranges counted as `policy` says
+pub fn tally_invalid_ids(
+    ranges: &[Interval<u128>],
+    repeat_mode: RepeatMode,
+    policy: DuplicatePolicy,
+    cancel: &CancellationToken,
+) -> Result<(u64, u128)> {
+    let (mut count, mut sum) = (0u64, 0u128);
+    let mut add = |id: u128| {
+        count += 1;
+        sum += id;
+    };
+    match policy {
+        DuplicatePolicy::CountDuplicates => {
+            for &range in ranges {
+                scan_range(range, repeat_mode, cancel, &mut add)?;
+            }
+        }
+        DuplicatePolicy::DistinctOnly => {
+            for id in DistinctIds::new(ranges, repeat_mode, cancel) {
+                add(id?);
+            }
+        }
+    }
+    Ok((count, sum))
+}
+
+/// Every invalid ID in `range`; `sum_invalid_ids_in_range` needs no memory
+/// per ID when only the total matters
+pub fn find_invalid_ids_in_range(
+    range: Interval<u128>,
+    repeat_mode: RepeatMode,
+    cancel: &CancellationToken,
+) -> Result<Vec<u128>> {
+    let mut invalid = Vec::new();
+    scan_range(range, repeat_mode, cancel, |id| invalid.push(id))?;
+    Ok(invalid)
+}
+
+pub fn sum_invalid_ids_in_range(
+    range: Interval<u128>,
+    repeat_mode: RepeatMode,
+    cancel: &CancellationToken,
+) -> Result<u128> {
+    let mut sum = 0;
+    scan_range(range, repeat_mode, cancel, |id| sum += id)?;
+    Ok(sum)
+}
+
+/// Distinct prime factors of `n`, smallest first
+fn prime_factors(mut n: u32) -> Vec<u32> {
+    let mut primes = Vec::new();
+    let mut p = 2;
+    while p * p <= n {
+        if n.is_multiple_of(p) {
+            primes.push(p);
+            while n.is_multiple_of(p) {
+                n /= p;
+            }
+        }
+        p += 1;
+    }
+    if n > 1 {
+        primes.push(n);
+    }
+    primes
+}
+
+/// IDs of `len` digits that are a `block`-digit sequence repeated, and at
+/// most `limit` when there is one (which then has `len` digits too)
+fn count_repeating(len: u32, block: u32, limit: Option<u128>) -> u128 {
+    // Repeating b gives b * 10..010..01, one 1 per copy of the block
+    let mut multiplier = 0u128;
+    for _ in 0..len / block {
+        multiplier = multiplier * 10u128.pow(block) + 1;
+    }
+    let largest = 10u128.pow(block) - 1;
+    let largest = limit.map_or(largest, |limit| largest.min(limit / multiplier));
+    (largest + 1).saturating_sub(10u128.pow(block - 1))
+}
+
+/// Invalid IDs with exactly `len` digits, at most `limit` as in `count_repeating`
+fn count_invalid_of_length(len: u32, repeat_mode: RepeatMode, limit: Option<u128>) -> u128 {
+    match repeat_mode {
+        RepeatMode::ExactlyTwice if len.is_multiple_of(2) => count_repeating(len, len / 2, limit),
+        RepeatMode::ExactlyTwice => 0,
+        RepeatMode::AnyCount => {
+            // An ID repeats exactly when its blocks can be len / p digits for
+            // some prime p dividing len. Inclusion-exclusion over those primes
+            // counts IDs like 111111, which repeat several ways, only once.
+            let primes = prime_factors(len);
+            let mut total = 0i128;
+            for subset in 1..1u32 << primes.len() {
+                let product: u32 = primes.iter().enumerate().filter(|&(i, _)| subset >> i & 1 == 1).map(|(_, &p)| p).product();
+                let count = count_repeating(len, len / product, limit) as i128;
+                total += if subset.count_ones() % 2 == 1 { count } else { -count };
             }
-            false
+            total as u128
+        }
+    }
+}
+
+/// Invalid IDs up to and including `x`
+fn count_invalid_up_to(x: u128, repeat_mode: RepeatMode) -> u128 {
+    let Some(digits) = x.checked_ilog10().map(|log| log + 1) else { return 0 };
+    let shorter: u128 = (1..digits).map(|len| count_invalid_of_length(len, repeat_mode, None)).sum();
+    shorter + count_invalid_of_length(digits, repeat_mode, Some(x))
+}
+
+/// Invalid IDs below `x`, counted a digit length at a time instead of enumerated
+pub fn count_invalid_below(x: u128, repeat_mode: RepeatMode) -> u128 {
+    x.checked_sub(1).map_or(0, |x| count_invalid_up_to(x, repeat_mode))
+}
+
+/// The n-th smallest invalid ID, counting from 1, by binary search over the
+/// counts; `None` for n = 0 or when fewer than n invalid IDs fit in a u128
+pub fn nth_invalid(n: u128, repeat_mode: RepeatMode) -> Option<u128> {
+    if n == 0 || count_invalid_up_to(u128::MAX, repeat_mode) < n {
+        return None;
+    }
+    let (mut lo, mut hi) = (1, u128::MAX);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if count_invalid_up_to(mid, repeat_mode) >= n {
+            hi = mid;
+        } else {
+            lo = mid + 1;
         }
     }
+    Some(lo)
 }
 
-fn find_invalid_ids_in_range(range: (&str, &str), repeat_mode: RepeatMode) -> Result<Vec<u128>, Box<dyn std::error::Error>> {
-    if range.1.len() == 1 {
-        return Ok(vec![]);
+fn sum_invalid_ids(input: &str, repeat_mode: RepeatMode) -> Result<u128> {
+    let cancel = CancellationToken::new();
+    let mut sum = 0;
+    for &range in merge_ranges(&parse_ranges(input.trim())?)?.intervals() {
+        sum += sum_invalid_ids_in_range(range, repeat_mode, &cancel)?;
     }
+    Ok(sum)
+}
+
+/// Sum of IDs made of a digit sequence repeated exactly twice
+pub fn part1(input: &str) -> Result<u128> {
+    sum_invalid_ids(input, RepeatMode::ExactlyTwice)
+}
+
+/// Sum of IDs made of a digit sequence repeated any number of times
+pub fn part2(input: &str) -> Result<u128> {
+    sum_invalid_ids(input, RepeatMode::AnyCount)
+}
 
-    let start: u128 = range.0.parse()?;
-    let end: u128 = range.1.parse()?;
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Gift Shop",
+        input_files: &["ranges"],
+        parts: [
+            "Sum of IDs made of a digit sequence repeated exactly twice",
+            "Sum of IDs made of a digit sequence repeated any number of times",
+        ],
+        notes: "Honours --timeout; --duplicates picks how IDs in overlapping ranges count; --query counts and finds invalid IDs without enumerating them",
+    }
+}
 
-    Ok((start..=end)
-        .filter(|&id| is_invalid_id(id, repeat_mode))
-        .collect())
+#[derive(Parser, Debug)]
+#[command(name = "day02", about = "Day 2: Gift Shop")]
+struct Options {
+    /// How to count an invalid ID that falls in more than one range
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::DistinctOnly)]
+    duplicates: DuplicatePolicy,
+    /// Instead of the puzzle, answer questions about every possible ID:
+    /// below:X counts the invalid IDs below X, nth:N finds the N-th smallest
+    #[arg(long, value_name = "QUERY")]
+    query: Vec<Query>,
 }
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let input = std::fs::read_to_string("assets/day02ranges.txt")?;
-    let ranges = parse_ranges(input.trim())?;
+/// A `--query`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Query {
+    Below(u128),
+    Nth(u128),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').ok_or_else(|| format!("Expected below:X or nth:N, got '{}'", s))?;
+        let value = value.trim().parse::<u128>().map_err(|e| format!("Invalid number '{}': {}", value.trim(), e))?;
+        match kind.trim() {
+            "below" => Ok(Query::Below(value)),
+            "nth" => Ok(Query::Nth(value)),
+            other => Err(format!("Unknown query '{}', expected below or nth", other)),
+        }
+    }
+}
+
+fn run_queries(queries: &[Query], out: &dyn OutputSink) {
+    let modes = [("repeated twice", RepeatMode::ExactlyTwice), ("repeated any number of times", RepeatMode::AnyCount)];
+    for &query in queries {
+        match query {
+            Query::Below(x) => out.line(&format!("Invalid IDs below {}:", x)),
+            Query::Nth(n) => out.line(&format!("Invalid ID number {}:", n)),
+        }
+        for (label, repeat_mode) in modes {
+            let answer = match query {
+                Query::Below(x) => count_invalid_below(x, repeat_mode).to_string(),
+                Query::Nth(n) => nth_invalid(n, repeat_mode).map_or("none".to_string(), |id| id.to_string()),
+            };
+            out.line(&format!("  {:<28} {}", label, answer));
+        }
+    }
+}
+
+pub fn run(args: &[String], cancel: &CancellationToken, out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day02".to_string()).chain(args.iter().cloned()));
+    if !options.query.is_empty() {
+        run_queries(&options.query, out);
+        return Ok(());
+    }
+    let ranges = Input::for_day(2).parse("ranges", |input| parse_intervals(&parse_ranges(input.trim())?))?;
 
     let do_exactly_twice = false;
     let repeat_mode = if do_exactly_twice { RepeatMode::ExactlyTwice } else { RepeatMode::AnyCount };
 
-    let mut invalid_ids: Vec<u128> = Vec::new();
-    for range in ranges {
-        invalid_ids.extend(find_invalid_ids_in_range(range, repeat_mode)?);
-    }
+    let (count, sum) = tally_invalid_ids(&ranges, repeat_mode, options.duplicates, cancel)?;
 
-    let sum: u128 = invalid_ids.iter().sum();
-    println!("{:?}", invalid_ids);
-    println!("Sum: {}", sum);
+    out.line(&format!("Invalid IDs: {}", count));
+    out.answer(2, "Sum", &sum);
 
     Ok(())
 }
@@ -85,11 +421,13 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_parse_ranges() {
         let ranges = parse_ranges("10-20,30-40").unwrap();
         assert_eq!(ranges, vec![("10", "20"), ("30", "40")]);
+        assert_eq!(parse_ranges("10-20,3040").unwrap_err().to_string(), "Line 1, column 7: Invalid range format: 3040");
     }
 
     #[test]
@@ -124,24 +462,121 @@ mod tests {
     fn test_find_invalid_ids_in_range() {
         // Range 11-13 with AnyCount should find 11, 12 (no, 12 isn't repeating), 13 (no)
         // Actually 11 = "11" = "1" repeated twice
-        let ids = find_invalid_ids_in_range(("11", "13"), RepeatMode::AnyCount).unwrap();
+        let ids = find_invalid_ids_in_range(Interval::new(11, 13), RepeatMode::AnyCount, &CancellationToken::new()).unwrap();
         assert!(ids.contains(&11));
         assert!(!ids.contains(&12));
         assert!(!ids.contains(&13));
+
+        // A range ending at u128::MAX stops there instead of wrapping around
+        let cancel = CancellationToken::new();
+        assert_eq!(InvalidIds::new(Interval::new(u128::MAX - 3, u128::MAX), RepeatMode::AnyCount, &cancel).count(), 0);
+    }
+
+    #[test]
+    fn test_scan_range_matches_collecting() {
+        let cancel = CancellationToken::new();
+        let range = Interval::new(1, 250_000);
+        let ids = find_invalid_ids_in_range(range, RepeatMode::AnyCount, &cancel).unwrap();
+        assert_eq!(ids[..4], [11, 22, 33, 44]);
+        assert!(ids.is_sorted());
+
+        let mut seen = 0;
+        scan_range(range, RepeatMode::AnyCount, &cancel, |id| {
+            assert_eq!(id, ids[seen]);
+            seen += 1;
+        })
+        .unwrap();
+        assert_eq!(seen, ids.len());
+        assert_eq!(sum_invalid_ids_in_range(range, RepeatMode::AnyCount, &cancel).unwrap(), ids.iter().sum::<u128>());
     }
 
     #[test]
     fn test_full_solution_sum() {
-        let input = std::fs::read_to_string("assets/day02ranges.txt")
+        let input = Input::for_day(2).read("ranges")
             .expect("Failed to read input file");
-        let ranges = parse_ranges(input.trim()).unwrap();
+        let ranges = merge_ranges(&parse_ranges(input.trim()).unwrap()).unwrap();
 
         let mut invalid_ids: Vec<u128> = Vec::new();
-        for range in ranges {
-            invalid_ids.extend(find_invalid_ids_in_range(range, RepeatMode::AnyCount).unwrap());
+        for &range in ranges.intervals() {
+            invalid_ids.extend(find_invalid_ids_in_range(range, RepeatMode::AnyCount, &CancellationToken::new()).unwrap());
         }
 
         let sum: u128 = invalid_ids.iter().sum();
-        assert_eq!(sum, 22471660255);
+        snapshot::check("day02", "invalid_id_sum", sum);
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let cancel = CancellationToken::new();
+        let ranges = parse_intervals(&parse_ranges("11-22,15-33,20-25,100-120,111-111").unwrap()).unwrap();
+        let count = tally_invalid_ids(&ranges, RepeatMode::AnyCount, DuplicatePolicy::CountDuplicates, &cancel).unwrap();
+        assert_eq!(count, (7, 11 + 22 + 22 + 22 + 33 + 111 + 111));
+        let distinct = tally_invalid_ids(&ranges, RepeatMode::AnyCount, DuplicatePolicy::DistinctOnly, &cancel).unwrap();
+        assert_eq!(distinct, (4, 11 + 22 + 33 + 111));
+
+        let ids: Vec<u128> = DistinctIds::new(&ranges, RepeatMode::AnyCount, &cancel).map(Result::unwrap).collect();
+        assert_eq!(ids, vec![11, 22, 33, 111]);
+        assert_eq!(DistinctIds::new(&[], RepeatMode::AnyCount, &cancel).count(), 0);
+
+        // The merge agrees with scanning merged intervals on the real input
+        let input = Input::for_day(2).read("ranges").unwrap();
+        let raw = parse_ranges(input.trim()).unwrap();
+        let merged: u128 = merge_ranges(&raw)
+            .unwrap()
+            .intervals()
+            .iter()
+            .map(|&range| sum_invalid_ids_in_range(range, RepeatMode::ExactlyTwice, &cancel).unwrap())
+            .sum();
+        let intervals = parse_intervals(&raw).unwrap();
+        let (_, sum) = tally_invalid_ids(&intervals, RepeatMode::ExactlyTwice, DuplicatePolicy::DistinctOnly, &cancel).unwrap();
+        assert_eq!(sum, merged);
+
+        let fired = CancellationToken::with_timeout(std::time::Duration::ZERO);
+        assert!(tally_invalid_ids(&ranges, RepeatMode::AnyCount, DuplicatePolicy::DistinctOnly, &fired).is_err());
+    }
+
+    #[test]
+    fn test_overlapping_ranges_count_once() {
+        // 22 falls in both ranges; scanning them separately used to add it twice
+        let ranges = parse_ranges("11-22,15-33").unwrap();
+        let separately: u128 = ranges
+            .iter()
+            .map(|&(start, end)| Interval::new(start.parse().unwrap(), end.parse().unwrap()))
+            .flat_map(|range| find_invalid_ids_in_range(range, RepeatMode::ExactlyTwice, &CancellationToken::new()).unwrap())
+            .sum();
+        assert_eq!(separately, 11 + 22 + 22 + 33);
+
+        assert_eq!(merge_ranges(&ranges).unwrap().intervals(), &[Interval::new(11, 33)]);
+        assert_eq!(part1("11-22,15-33").unwrap(), 11 + 22 + 33);
+    }
+
+    #[test]
+    fn test_count_and_nth_invalid() {
+        for repeat_mode in [RepeatMode::ExactlyTwice, RepeatMode::AnyCount] {
+            let mut below = 0;
+            for id in 0..=250_000u128 {
+                assert_eq!(count_invalid_below(id, repeat_mode), below, "below {}", id);
+                if is_invalid_id(id, repeat_mode) {
+                    below += 1;
+                    assert_eq!(nth_invalid(below, repeat_mode), Some(id));
+                }
+            }
+            assert_eq!(nth_invalid(0, repeat_mode), None);
+
+            let n = 10_000_000;
+            let id = nth_invalid(n, repeat_mode).unwrap();
+            assert!(is_invalid_id(id, repeat_mode));
+            assert_eq!(count_invalid_below(id, repeat_mode), n - 1);
+        }
+        assert_eq!(count_invalid_below(1000, RepeatMode::ExactlyTwice), 9);
+        assert_eq!(count_invalid_below(1000, RepeatMode::AnyCount), 9 + 9);
+        assert_eq!(nth_invalid(10_000_000, RepeatMode::ExactlyTwice), Some(1_000_000_010_000_000));
+        // Blocks of up to 19 digits, the longest a u128 holds twice
+        assert_eq!(count_invalid_below(u128::MAX, RepeatMode::ExactlyTwice), 10u128.pow(19) - 1);
+        assert_eq!(nth_invalid(10u128.pow(19), RepeatMode::ExactlyTwice), None);
+
+        assert_eq!("nth:5".parse(), Ok(Query::Nth(5)));
+        assert_eq!("below: 100".parse(), Ok(Query::Below(100)));
+        assert!("above:5".parse::<Query>().is_err());
     }
 }
@@ -1,101 +1,554 @@
-use anyhow::{anyhow, Result};
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use crate::input::{self, Input};
+use crate::util::output::OutputSink;
+use crate::util::stats;
+use clap::Parser;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
 
-// Parse a line of digits into a vector of integers
-fn parse_bank_line(line: &str) -> Result<Vec<u32>> {
-    line.chars()
-        .map(|c| {
-            c.to_digit(10)
-                .ok_or_else(|| anyhow!("Invalid digit: {}", c))
-        })
-        .collect()
+/// Streams banks out of a reader one line at a time without holding whole
+/// lines as text. A line is either a run of digits, one battery each, or
+/// multi-digit values separated by whitespace and/or commas; the format is
+/// picked per line by whether it has more than one value.
+pub struct BankReader<R> {
+    reader: R,
+    /// Named in I/O errors
+    source: String,
+    line_no: usize,
+    done: bool,
 }
 
-// Parse the banks file, returning a vector of vectors (one per line)
-fn parse_banks_file(file_path: &str) -> Result<Vec<Vec<u32>>> {
-    let contents = std::fs::read_to_string(file_path)?;
-    contents
-        .lines()
-        .map(|line| parse_bank_line(line.trim()))
-        .collect()
+/// What's been seen of the current line so far
+#[derive(Default)]
+struct BankLine {
+    /// Every digit as its own battery
+    digits: Vec<u32>,
+    /// Whitespace/comma separated values with their 1-based start columns;
+    /// `None` for a value too big for u32
+    values: Vec<(Option<u32>, usize)>,
+    column: usize,
+    /// A separator was seen since the last digit
+    gap: bool,
 }
 
-fn find_largest_joltage_settings(bank: &[u32], n: usize) -> Result<u64> {
+impl BankLine {
+    fn push(&mut self, line_no: usize, byte: u8) -> Result<()> {
+        self.column += 1;
+        match byte {
+            b'0'..=b'9' => {
+                let digit = (byte - b'0') as u32;
+                self.digits.push(digit);
+                match self.values.last_mut() {
+                    Some((value, _)) if !self.gap => {
+                        *value = value.and_then(|v| v.checked_mul(10)).and_then(|v| v.checked_add(digit));
+                    }
+                    _ => self.values.push((Some(digit), self.column)),
+                }
+                self.gap = false;
+            }
+            b' ' | b'\t' | b'\r' | b',' => self.gap = true,
+            _ if byte.is_ascii() => {
+                return Err(AocError::parse_at_column(line_no, self.column, format!("Invalid character {:?}", byte as char)));
+            }
+            _ => {
+                return Err(AocError::parse_at_column(line_no, self.column, format!("Invalid non-ASCII byte 0x{:02X}", byte)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The bank, or None for a blank line. A single value is a digit run,
+    /// so only separated values have to fit in u32.
+    fn finish(self, line_no: usize) -> Result<Option<Vec<u32>>> {
+        match self.values.len() {
+            0 => Ok(None),
+            1 => Ok(Some(self.digits)),
+            _ => self
+                .values
+                .into_iter()
+                .map(|(value, column)| {
+                    value.ok_or_else(|| AocError::parse_at_column(line_no, column, "Value too large for a battery"))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(Some),
+        }
+    }
+}
+
+impl<R: BufRead> BankReader<R> {
+    pub fn new(reader: R, source: impl Into<String>) -> Self {
+        BankReader { reader, source: source.into(), line_no: 0, done: false }
+    }
+
+    /// Reads up to the end of the next line, `Ok(None)` at the end of input
+    fn next_line(&mut self) -> Result<Option<BankLine>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.line_no += 1;
+        let mut line = BankLine::default();
+        loop {
+            let buffer = self.reader.fill_buf().map_err(|e| AocError::io(&self.source, e))?;
+            if buffer.is_empty() {
+                self.done = true;
+                return Ok((line.column > 0).then_some(line));
+            }
+            let (chunk, end_of_line) = match buffer.iter().position(|&b| b == b'\n') {
+                Some(newline) => (&buffer[..newline], true),
+                None => (buffer, false),
+            };
+            for &byte in chunk {
+                line.push(self.line_no, byte)?;
+            }
+            let consumed = chunk.len() + end_of_line as usize;
+            self.reader.consume(consumed);
+            if end_of_line {
+                return Ok(Some(line));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for BankReader<R> {
+    type Item = Result<Vec<u32>>;
+
+    /// The next non-blank line's bank
+    fn next(&mut self) -> Option<Result<Vec<u32>>> {
+        loop {
+            let line = match self.next_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => return None,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            match line.finish(self.line_no) {
+                Ok(Some(bank)) => return Some(Ok(bank)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Parses one bank line, digits or separated values
+pub fn parse_bank_line(line_no: usize, line: &str) -> Result<Vec<u32>> {
+    let mut bank = BankLine::default();
+    for byte in line.bytes() {
+        bank.push(line_no, byte)?;
+    }
+    Ok(bank.finish(line_no)?.unwrap_or_default())
+}
+
+// Parse the banks file, returning a vector of vectors (one per non-blank line)
+pub fn parse_banks_file(contents: &str) -> Result<Vec<Vec<u32>>> {
+    BankReader::new(contents.as_bytes(), "<input>").collect()
+}
+
+pub fn find_largest_joltage_settings(bank: &[u32], n: usize) -> Result<u64> {
+    find_largest_with_constraints(bank, n, &[], &[])
+}
+
+/// What a position may do in the selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Free,
+    Required,
+    Forbidden,
+}
+
+/// `find_largest_joltage_settings` where the batteries at `required`
+/// positions (0-based) must be part of the selection and those at
+/// `forbidden` positions must be skipped
+pub fn find_largest_with_constraints(bank: &[u32], n: usize, required: &[usize], forbidden: &[usize]) -> Result<u64> {
     // Validate that n is not greater than bank size
     if n > bank.len() {
-        return Err(anyhow!("n ({}) must be <= bank size ({})", n, bank.len()));
+        return Err(AocError::solve(format!("n ({}) must be <= bank size ({})", n, bank.len())));
     }
-    
-    if n == 0 {
-        return Ok(0);
+
+    let mut slots = vec![Slot::Free; bank.len()];
+    for (positions, slot) in [(required, Slot::Required), (forbidden, Slot::Forbidden)] {
+        for &position in positions {
+            match slots.get(position) {
+                None => {
+                    return Err(AocError::usage(format!("Position {} is outside the bank of {} batteries", position, bank.len())));
+                }
+                Some(&existing) if existing != Slot::Free && existing != slot => {
+                    return Err(AocError::usage(format!("Position {} can't be both required and forbidden", position)));
+                }
+                Some(_) => slots[position] = slot,
+            }
+        }
     }
 
-    // Dynamic programming approach: dp[position][digits_used] = max number we can form
-    let mut dp = vec![vec![None; n + 1]; bank.len()];
-    
-    // Base case 1: using 0 digits gives 0
-    for row in &mut dp {
-        row[0] = Some(0u64);
+    // Multi-digit values are appended by shifting past all of their digits;
+    // appending is monotonic in the prefix, so the largest prefix still wins
+    let overflow = || AocError::solve(format!("Joltage with {} batteries overflows u64", n));
+    let append = |prev: u64, value: u32| -> Result<u64> {
+        let shift = 10u64.pow(value.checked_ilog10().unwrap_or(0) + 1);
+        prev.checked_mul(shift).and_then(|p| p.checked_add(value as u64)).ok_or_else(overflow)
+    };
+
+    // Dynamic programming over positions: best[j] = max number formed from
+    // the positions seen so far using j of them, None if the constraints
+    // leave no way to do that
+    let mut best: Vec<Option<u64>> = vec![None; n + 1];
+    best[0] = Some(0);
+    for (&value, &slot) in bank.iter().zip(&slots) {
+        // Counts go down so best[j - 1] still excludes this battery
+        for j in (0..=n).rev() {
+            // Option 1: Don't use this battery
+            let skip = if slot == Slot::Required { None } else { best[j] };
+
+            // Option 2: Use it
+            let take = match (slot, j) {
+                (Slot::Forbidden, _) | (_, 0) => None,
+                _ => best[j - 1].map(|prev| append(prev, value)).transpose()?,
+            };
+
+            best[j] = skip.into_iter().chain(take).max();
+        }
     }
-    
-    // Base case 2: using 1 digit from position 0
-    dp[0][1] = Some(bank[0] as u64);
-    
-    // Fill the DP table
-    for i in 1..bank.len() {
-        let digit = bank[i] as u64;
 
-        for j in 1..=n.min(i + 1) {
-            // Option 1: Don't use digit at position i
-            let option1 = dp[i - 1][j];
+    best[n].ok_or_else(|| AocError::solve(format!("Could not form a number with {} digits", n)))
+}
 
-            // Option 2: Use digit at position i
-            let option2 = dp[i - 1][j - 1].map(|prev| prev * 10 + digit);
+/// Best `n`-battery joltage of every run of `width` consecutive batteries,
+/// in order of where the run starts; empty when the bank is shorter than
+/// `width`. Banks of single digits take the greedy pick, largest digit
+/// first, for each of the `n` places, with one monotonic queue per place:
+/// every place's search range only moves right as the window does, so each
+/// battery enters and leaves each queue once. Multi-digit values break the
+/// greedy, so those banks fall back to the DP on every window.
+pub fn window_joltages(bank: &[u32], n: usize, width: usize) -> Result<Vec<u64>> {
+    if n > width {
+        return Err(AocError::usage(format!("Can't pick {} batteries from a window of {}", n, width)));
+    }
+    if width > bank.len() {
+        return Ok(Vec::new());
+    }
+    let windows = bank.len() - width + 1;
+    stats::add("day03.windows", windows as u64);
+    if bank.iter().any(|&value| value > 9) {
+        return bank.windows(width).map(|window| find_largest_joltage_settings(window, n)).collect();
+    }
 
-            // Take the maximum of both options
-            dp[i][j] = option1.into_iter().chain(option2).max();
+    let overflow = || AocError::solve(format!("Joltage with {} batteries overflows u64", n));
+    // queues[k] holds candidates for place k, values decreasing from the
+    // front but with ties kept in order, so the front is the leftmost best
+    let mut queues = vec![VecDeque::new(); n];
+    let mut pushed = vec![0; n];
+    let mut joltages = Vec::with_capacity(windows);
+    for start in 0..windows {
+        let mut joltage = 0u64;
+        let mut next = start;
+        for (k, queue) in queues.iter_mut().enumerate() {
+            // Place k must leave room for the n - k - 1 places after it
+            let last = start + width - n + k;
+            while pushed[k] <= last {
+                let value = bank[pushed[k]];
+                while queue.back().is_some_and(|&i| bank[i] < value) {
+                    queue.pop_back();
+                }
+                queue.push_back(pushed[k]);
+                pushed[k] += 1;
+            }
+            while queue.front().is_some_and(|&i| i < next) {
+                queue.pop_front();
+            }
+            let pick = *queue.front().expect("the range always holds a battery");
+            joltage = joltage.checked_mul(10).and_then(|j| j.checked_add(u64::from(bank[pick]))).ok_or_else(overflow)?;
+            next = pick + 1;
         }
+        joltages.push(joltage);
     }
-    
-    // The answer is dp[bank.len() - 1][n]
-    dp[bank.len() - 1][n]
-        .ok_or_else(|| anyhow!("Could not form a number with {} digits", n))
+    Ok(joltages)
 }
 
-// Day 3: Exercise description
-pub fn run() -> Result<()> {
-    let banks = parse_banks_file("assets/day03banks.txt")?;
+/// FNV-1a over a bank and its constraints; banks can be long, so the
+/// cache keeps this rather than the bank itself
+fn fingerprint(bank: &[u32], required: &[usize], forbidden: &[usize]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut mix = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for &value in bank {
+        mix(&value.to_le_bytes());
+    }
+    // Separators so a position can't pass for a battery
+    for positions in [required, forbidden] {
+        mix(&[0xff; 8]);
+        for &position in positions {
+            mix(&(position as u64).to_le_bytes());
+        }
+    }
+    hash
+}
+
+const CACHE_HEADER: &str = "day03-cache 1";
 
+/// Largest joltages already worked out, keyed by the bank's fingerprint and
+/// the number of batteries, optionally kept in a file between runs
+#[derive(Debug, Default)]
+pub struct JoltageCache {
+    answers: HashMap<(u64, usize), u64>,
+    lookups: usize,
+    hits: usize,
+}
+
+impl JoltageCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The answers saved in `path` by `save`, or an empty cache when the
+    /// file doesn't exist yet
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::new());
+        }
+        Self::parse(input::read_file(path)?).map_err(|e| e.in_file(path))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        if lines.next().map(|(_, line)| line) != Some(CACHE_HEADER) {
+            return Err(AocError::parse_at(1, "Not a day 3 cache"));
+        }
+        let mut cache = Self::new();
+        for (line_no, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let entry = match fields[..] {
+                [hash, n, joltage] => u64::from_str_radix(hash, 16)
+                    .ok()
+                    .zip(n.parse().ok())
+                    .zip(joltage.parse().ok()),
+                _ => None,
+            };
+            let (key, joltage) = entry.ok_or_else(|| AocError::parse_at(line_no, format!("Expected 'hash n joltage', got '{}'", line)))?;
+            cache.answers.insert(key, joltage);
+        }
+        Ok(cache)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut entries: Vec<_> = self.answers.iter().collect();
+        entries.sort();
+        let mut text = format!("{}\n", CACHE_HEADER);
+        for ((hash, n), joltage) in entries {
+            let _ = writeln!(text, "{:016x} {} {}", hash, n, joltage);
+        }
+        fs::write(path, text).map_err(|e| AocError::io(path, e))
+    }
+
+    /// `find_largest_with_constraints`, answered from the cache when this
+    /// bank, `n` and constraints have been seen before; errors aren't cached
+    pub fn largest(&mut self, bank: &[u32], n: usize, required: &[usize], forbidden: &[usize]) -> Result<u64> {
+        self.lookups += 1;
+        stats::add("day03.cache_lookups", 1);
+        let key = (fingerprint(bank, required, forbidden), n);
+        if let Some(&joltage) = self.answers.get(&key) {
+            self.hits += 1;
+            stats::add("day03.cache_hits", 1);
+            return Ok(joltage);
+        }
+        let joltage = find_largest_with_constraints(bank, n, required, forbidden)?;
+        self.answers.insert(key, joltage);
+        Ok(joltage)
+    }
+
+    pub fn len(&self) -> usize {
+        self.answers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.answers.is_empty()
+    }
+
+    pub fn lookups(&self) -> usize {
+        self.lookups
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
+
+fn total_joltage(input: &str, n: usize) -> Result<u64> {
+    let mut cache = JoltageCache::new();
+    parse_banks_file(input)?
+        .iter()
+        .map(|bank| cache.largest(bank, n, &[], &[]))
+        .sum()
+}
+
+/// Total joltage with two batteries per bank
+pub fn part1(input: &str) -> Result<u64> {
+    total_joltage(input, 2)
+}
+
+/// Total joltage with twelve batteries per bank
+pub fn part2(input: &str) -> Result<u64> {
+    total_joltage(input, 12)
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day03", about = "Day 3: Lobby")]
+struct Options {
+    /// Batteries to turn on in each bank
+    #[arg(long, value_name = "N", default_value_t = 12)]
+    batteries: usize,
+    /// Comma-separated positions (from 0) every bank must turn on
+    #[arg(long, value_delimiter = ',', value_name = "POS")]
+    require: Vec<usize>,
+    /// Comma-separated positions (from 0) every bank must leave off
+    #[arg(long, value_delimiter = ',', value_name = "POS")]
+    forbid: Vec<usize>,
+    /// Keep answers in this file and reuse them on later runs
+    #[arg(long, value_name = "FILE")]
+    cache: Option<String>,
+    /// Pick the best batteries within every window of W consecutive ones
+    /// instead of the whole bank, reporting the best and the sum
+    #[arg(long, value_name = "W", conflicts_with_all = ["require", "forbid", "cache"])]
+    window: Option<usize>,
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Lobby",
+        input_files: &["banks"],
+        parts: [
+            "Total joltage with two batteries per bank",
+            "Total joltage with twelve batteries per bank",
+        ],
+        notes: "--batteries, --require and --forbid constrain the selection; --cache keeps answers between runs; --window picks within every window of a bank",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day03".to_string()).chain(args.iter().cloned()));
+    // Streamed straight from disk, since banks may be too long to want as text
+    let path = Input::for_day(3).path("banks").to_string_lossy().into_owned();
+    let file = File::open(&path).map_err(|e| AocError::io(&path, e))?;
+    let banks = BankReader::new(BufReader::new(file), &path)
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| e.in_file(&path))?;
+    if let Some(width) = options.window {
+        return run_windows(&banks, options.batteries, width, out);
+    }
+
+    let mut cache = match &options.cache {
+        Some(path) => JoltageCache::load(path)?,
+        None => JoltageCache::new(),
+    };
     let mut largest_settings = Vec::new();
-    let do_only_two_batteries = false;
 
     for bank in &banks {
         // Print the values in the bank
-        println!("Bank: {:?}", bank);
+        out.line(&format!("Bank: {:?}", bank));
 
-        // Find the largest setting for this bank (using 2 elements by default)
-        let largest = find_largest_joltage_settings(bank, if do_only_two_batteries { 2 } else { 12 })?;
-        println!("Largest setting: {}", largest);
+        let largest = cache.largest(bank, options.batteries, &options.require, &options.forbid)?;
+        out.line(&format!("Largest setting: {}", largest));
 
         largest_settings.push(largest);
     }
 
     // Sum all the largest settings
     let sum: u64 = largest_settings.iter().sum();
-    println!("\nFinal sum: {}", sum);
+    out.line("");
+    // Only the puzzle's own battery counts, unconstrained, answer a part
+    let part = match options.batteries {
+        2 => Some(1),
+        12 => Some(2),
+        _ => None,
+    };
+    match part {
+        Some(part) if options.require.is_empty() && options.forbid.is_empty() => out.answer(part, "Final sum", &sum),
+        _ => out.line(&format!("Final sum: {}", sum)),
+    }
+
+    if let Some(path) = &options.cache {
+        cache.save(path)?;
+        out.line(&format!("Cache: {} of {} banks answered from {}, {} answers saved", cache.hits(), cache.lookups(), path, cache.len()));
+    }
+
+    Ok(())
+}
+
+fn run_windows(banks: &[Vec<u32>], n: usize, width: usize, out: &dyn OutputSink) -> Result<()> {
+    let mut best: Option<(u64, usize, usize)> = None;
+    let mut total = 0u128;
+    for (i, bank) in banks.iter().enumerate() {
+        let joltages = window_joltages(bank, n, width)?;
+        let Some((start, &max)) = joltages.iter().enumerate().max_by_key(|&(start, &joltage)| (joltage, Reverse(start))) else {
+            out.line(&format!("Bank {}: shorter than the window of {}", i + 1, width));
+            continue;
+        };
+        let sum: u128 = joltages.iter().map(|&joltage| u128::from(joltage)).sum();
+        out.line(&format!("Bank {}: {} windows, best {} starting at {}, sum {}", i + 1, joltages.len(), max, start, sum));
+        total += sum;
+        if best.is_none_or(|(joltage, _, _)| max > joltage) {
+            best = Some((max, i + 1, start));
+        }
+    }
 
+    match best {
+        Some((joltage, bank, start)) => out.line(&format!("\nBest window: {} in bank {} starting at {}", joltage, bank, start)),
+        None => out.line(&format!("\nNo bank is as long as the window of {}", width)),
+    }
+    out.line(&format!("Sum over all windows: {}", total));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_parse_bank_line() {
-        let bank = parse_bank_line("1234").unwrap();
+        let bank = parse_bank_line(1, "1234").unwrap();
         assert_eq!(bank, vec![1, 2, 3, 4]);
     }
 
+    #[test]
+    fn test_separated_values_and_errors() {
+        assert_eq!(parse_bank_line(1, " 12, 7 300\t4 ").unwrap(), vec![12, 7, 300, 4]);
+        // One value is still a digit run, even past u32
+        assert_eq!(parse_bank_line(1, "98765432109876,").unwrap().len(), 14);
+
+        let err = parse_bank_line(3, "12 3x4").unwrap_err();
+        assert_eq!(err.to_string(), "Line 3, column 5: Invalid character 'x'");
+        let err = parse_bank_line(1, "1 99999999999").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 3: Value too large for a battery");
+
+        // Multi-digit values are appended whole: 9 then 12 makes 912
+        assert_eq!(find_largest_joltage_settings(&[9, 12, 3], 2).unwrap(), 912);
+    }
+
+    #[test]
+    fn test_bank_reader_streams_long_lines() {
+        // A tiny buffer makes every line span many reads
+        let long = "9".repeat(10_000);
+        let input = format!("123\n\n{}\r\n4 5,6", long);
+        let reader = BankReader::new(BufReader::with_capacity(16, input.as_bytes()), "test");
+        let banks: Vec<Vec<u32>> = reader.collect::<Result<_>>().unwrap();
+        assert_eq!(banks.len(), 3);
+        assert_eq!(banks[1].len(), 10_000);
+        assert_eq!(banks[2], vec![4, 5, 6]);
+
+        let err = parse_banks_file("12\n\n34é\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 3, column 3: Invalid non-ASCII byte 0xC3");
+    }
+
     #[test]
     fn test_find_largest_simple() {
         // Bank [3, 1, 5, 2], pick 2 digits
@@ -144,9 +597,53 @@ mod tests {
         assert_eq!(result, 0);
     }
 
+    #[test]
+    fn test_constraints() {
+        let bank = [9, 1, 8, 7];
+        assert_eq!(find_largest_with_constraints(&bank, 2, &[], &[]).unwrap(), 98);
+        // Must keep the 1: best is 9 then 1
+        assert_eq!(find_largest_with_constraints(&bank, 2, &[1], &[]).unwrap(), 91);
+        // Without the 9 the 8 leads
+        assert_eq!(find_largest_with_constraints(&bank, 2, &[], &[0]).unwrap(), 87);
+        assert_eq!(find_largest_with_constraints(&bank, 2, &[3], &[0, 2]).unwrap(), 17);
+
+        // Too few positions left, or more required than chosen
+        assert!(find_largest_with_constraints(&bank, 3, &[], &[0, 1]).is_err());
+        assert!(find_largest_with_constraints(&bank, 1, &[0, 2], &[]).is_err());
+        let err = find_largest_with_constraints(&bank, 2, &[4], &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Position 4 is outside the bank of 4 batteries");
+        let err = find_largest_with_constraints(&bank, 2, &[2], &[2]).unwrap_err();
+        assert_eq!(err.to_string(), "Position 2 can't be both required and forbidden");
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = JoltageCache::new();
+        assert_eq!(cache.largest(&[9, 1, 8, 7], 2, &[], &[]).unwrap(), 98);
+        assert_eq!(cache.largest(&[9, 1, 8, 7], 2, &[], &[]).unwrap(), 98);
+        // Another n or other constraints are separate answers
+        assert_eq!(cache.largest(&[9, 1, 8, 7], 3, &[], &[]).unwrap(), 987);
+        assert_eq!(cache.largest(&[9, 1, 8, 7], 2, &[1], &[]).unwrap(), 91);
+        assert!(cache.largest(&[9, 1, 8, 7], 5, &[], &[]).is_err());
+        assert_eq!((cache.hits(), cache.lookups(), cache.len()), (1, 5, 3));
+
+        let path = std::env::temp_dir().join(format!("day03-cache-{}.txt", std::process::id()));
+        let path = path.to_string_lossy();
+        cache.save(&path).unwrap();
+        let mut reloaded = JoltageCache::parse(&fs::read_to_string(&*path).unwrap()).unwrap();
+        fs::remove_file(&*path).unwrap();
+        assert_eq!(reloaded.len(), 3);
+        assert_eq!(reloaded.largest(&[9, 1, 8, 7], 2, &[1], &[]).unwrap(), 91);
+        assert_eq!(reloaded.hits(), 1);
+
+        let err = JoltageCache::parse("day03-cache 1\n00ff 2\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: Expected 'hash n joltage', got '00ff 2'");
+        assert!(JoltageCache::parse("something else\n").is_err());
+    }
+
     #[test]
     fn test_full_solution_sum() {
-        let banks = parse_banks_file("assets/day03banks.txt")
+        let banks = parse_banks_file(Input::for_day(3).read("banks").unwrap())
             .expect("Failed to read input file");
 
         let mut largest_settings = Vec::new();
@@ -156,7 +653,23 @@ mod tests {
         }
 
         let sum: u64 = largest_settings.iter().sum();
-        assert_eq!(sum, 169347417057382);
+        snapshot::check("day03", "joltage_sum", sum);
     }
-}
 
+    #[test]
+    fn test_window_joltages() {
+        let banks = parse_banks_file(Input::for_day(3).read("banks").unwrap()).unwrap();
+        for bank in &banks[..3] {
+            for (n, width) in [(1, 1), (2, 5), (3, 3), (12, 40)] {
+                let expected: Vec<u64> = bank.windows(width).map(|window| find_largest_joltage_settings(window, n).unwrap()).collect();
+                assert_eq!(window_joltages(bank, n, width).unwrap(), expected, "n = {}, width = {}", n, width);
+            }
+        }
+
+        assert_eq!(window_joltages(&[3, 9, 1, 9, 2], 2, 3).unwrap(), vec![91, 99, 92]);
+        assert_eq!(window_joltages(&[3, 9], 2, 3).unwrap(), Vec::<u64>::new());
+        assert!(window_joltages(&[3, 9, 1], 3, 2).is_err());
+        // Two-digit values go through the DP instead of the greedy
+        assert_eq!(window_joltages(&[9, 10, 2], 2, 2).unwrap(), vec![910, 102]);
+    }
+}
@@ -1,55 +1,81 @@
-use anyhow::Result;
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::Parser;
 use std::fmt;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use crate::input::{self, Input};
+use crate::util::grid::Grid;
+use crate::util::output::OutputSink;
+use crate::util::parse;
+use crate::util::stats;
+use crate::util::theme::{self, Style, Theme};
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum PositionState {
     Initial,
     Empty,
     Unmovable,
     Movable,
+    /// Unmovable for good: kept in place by enough other frozen rolls
+    Frozen,
 }
 
-impl fmt::Debug for PositionState {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl PositionState {
+    fn symbol(self) -> char {
         match self {
-            PositionState::Initial => write!(f, "?"),
-            PositionState::Empty => write!(f, "."),
-            PositionState::Unmovable => write!(f, "@"),
-            PositionState::Movable => write!(f, "x"),
+            PositionState::Initial => '?',
+            PositionState::Empty => '.',
+            PositionState::Unmovable => '@',
+            PositionState::Movable => 'x',
+            PositionState::Frozen => '#',
         }
     }
 }
 
+impl fmt::Debug for PositionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// Cells frozen by one `Lot::freeze_stable` call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FreezeReport {
+    cells: usize,
+    /// Connected groups the newly frozen cells form
+    regions: usize,
+}
+
+#[derive(Clone)]
 struct Lot {
-    positions: Vec<Vec<PositionState>>,
+    positions: Grid<PositionState>,
+    /// Rolls that are neither removed nor frozen, as (row, col), in row-major
+    /// order; the only cells stage scans need to look at
+    live: Vec<(usize, usize)>,
+    /// Rolls fall into the empty cells below them after every stage
+    gravity: bool,
 }
 
 impl Lot {
-    // The 8 neighbor offsets (cardinal and diagonal directions)
-    const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
-        (-1, -1), (-1, 0), (-1, 1),
-        (0, -1),           (0, 1),
-        (1, -1),  (1, 0),  (1, 1),
-    ];
-    
-    fn new() -> Self {
+    /// A roll with at least this many rolls around it can't be moved
+    const MIN_BLOCKING_NEIGHBORS: usize = 4;
+
+    fn new(width: usize, height: usize) -> Self {
         Lot {
-            positions: Vec::new(),
+            positions: Grid::filled(width, height, PositionState::Initial),
+            live: Vec::new(),
+            gravity: false,
         }
     }
     
     /// Get all movable positions in the lot
     fn get_movable(&self) -> Vec<(usize, usize)> {
-        let mut movable = Vec::new();
-        for (row_idx, row) in self.positions.iter().enumerate() {
-            for (col_idx, &state) in row.iter().enumerate() {
-                if matches!(state, PositionState::Movable) {
-                    movable.push((row_idx, col_idx));
-                }
-            }
-        }
-        movable
+        self.live
+            .iter()
+            .copied()
+            .filter(|&(row, col)| matches!(self.positions[(col, row)], PositionState::Movable))
+            .collect()
     }
     
     /// Count the number of movable positions in the lot
@@ -71,14 +97,6 @@ impl Lot {
     }
     
     fn add_position(&mut self, row: usize, col: usize, is_empty: bool) {
-        // Ensure the grid is large enough to place the new position
-        while self.positions.len() <= row {
-            self.positions.push(Vec::new());
-        }
-        while self.positions[row].len() <= col {
-            self.positions[row].push(PositionState::Initial);
-        }
-        
         let old_state = PositionState::Initial;
         
         // Determine what the starting state should be
@@ -91,7 +109,10 @@ impl Lot {
         
         // Place it in the lot BEFORE updating neighbors
         // This ensures neighbors can see the correct state when they check this position
-        self.positions[row][col] = new_state;
+        self.positions[(col, row)] = new_state;
+        if !is_empty {
+            self.live.push((row, col));
+        }
         
         // Update neighbors if needed
         if Self::should_update_neighbors(old_state, new_state) {
@@ -103,24 +124,21 @@ impl Lot {
     /// Only works if the position is currently Movable.
     /// Returns an error if position doesn't exist or isn't Movable.
     pub fn remove_roll_at(&mut self, row: usize, col: usize) -> Result<()> {
-        // Check bounds
-        if row >= self.positions.len() || col >= self.positions[row].len() {
-            return Err(anyhow::anyhow!("Position ({}, {}) does not exist", row, col));
-        }
-        
-        let old_state = self.positions[row][col];
+        let Some(&old_state) = self.positions.get(col, row) else {
+            return Err(AocError::solve(format!("Position ({}, {}) does not exist", row, col)));
+        };
         
         // Check if position is Movable
         if !matches!(old_state, PositionState::Movable) {
-            return Err(anyhow::anyhow!(
+            return Err(AocError::solve(format!(
                 "Position ({}, {}) is {:?}, not Movable",
                 row, col, old_state
-            ));
+            )));
         }
         
         // Set the position to Empty
         let new_state = PositionState::Empty;
-        self.positions[row][col] = new_state;
+        self.positions[(col, row)] = new_state;
         
         // Update neighbors if needed
         if Self::should_update_neighbors(old_state, new_state) {
@@ -132,38 +150,24 @@ impl Lot {
     
     /// Count non-empty neighbors for a position at (row, col)
     fn count_non_empty_neighbors(lot: &Lot, row: usize, col: usize) -> usize {
-        let mut count = 0;
-        for (row_offset, col_offset) in Self::NEIGHBOR_OFFSETS {
-            let neighbor_row = row as i32 + row_offset;
-            let neighbor_col = col as i32 + col_offset;
-            
-            if neighbor_row >= 0 && neighbor_col >= 0 {
-                let neighbor_row = neighbor_row as usize;
-                let neighbor_col = neighbor_col as usize;
-                
-                if neighbor_row < lot.positions.len() && neighbor_col < lot.positions[neighbor_row].len() {
-                    match lot.positions[neighbor_row][neighbor_col] {
-                        PositionState::Initial | PositionState::Empty => {},
-                        PositionState::Unmovable | PositionState::Movable => count += 1,
-                    }
-                }
-            }
-        }
-        count
+        lot.positions
+            .moore_neighbors(col, row)
+            .filter(|&neighbor| {
+                matches!(lot.positions[neighbor], PositionState::Unmovable | PositionState::Movable | PositionState::Frozen)
+            })
+            .count()
     }
     
     /// Determine the state for a position based on its neighbors
     pub(crate) fn determine_state(lot: &Lot, row: usize, col: usize) -> PositionState {
-        // If position is Empty, it stays Empty regardless of neighbors
-        if row < lot.positions.len() && 
-           col < lot.positions[row].len() && 
-           matches!(lot.positions[row][col], PositionState::Empty) {
-            return PositionState::Empty;
+        // Empty and Frozen positions keep their state regardless of neighbors
+        if let Some(&state @ (PositionState::Empty | PositionState::Frozen)) = lot.positions.get(col, row) {
+            return state;
         }
         
         // For non-empty positions, determine Movable/Unmovable based on neighbors
         let non_empty_count = Self::count_non_empty_neighbors(lot, row, col);
-        if non_empty_count < 4 {
+        if non_empty_count < Self::MIN_BLOCKING_NEIGHBORS {
             PositionState::Movable
         } else {
             PositionState::Unmovable
@@ -186,40 +190,26 @@ impl Lot {
             // Collect neighbor updates for this position
             let mut updates = Vec::new();
             
-            for (row_offset, col_offset) in Self::NEIGHBOR_OFFSETS {
-                let neighbor_row = current_row as i32 + row_offset;
-                let neighbor_col = current_col as i32 + col_offset;
+            for (neighbor_col, neighbor_row) in self.positions.moore_neighbors(current_col, current_row) {
+                let current_state = self.positions[(neighbor_col, neighbor_row)];
                 
-                if neighbor_row >= 0 && neighbor_col >= 0 {
-                    let neighbor_row = neighbor_row as usize;
-                    let neighbor_col = neighbor_col as usize;
-                    
-                    // Ensure neighbor exists
-                    if neighbor_row >= self.positions.len() || 
-                       neighbor_col >= self.positions[neighbor_row].len() {
-                        continue;
-                    }
-                    
-                    let current_state = self.positions[neighbor_row][neighbor_col];
-                    
-                    // Skip if neighbor is Initial or Empty
-                    if matches!(current_state, PositionState::Initial | PositionState::Empty) {
-                        continue;
-                    }
-                    
-                    // Determine new state for neighbor
-                    let new_state = Self::determine_state(self, neighbor_row, neighbor_col);
-                    
-                    // Check if state actually needs to change
-                    if current_state != new_state {
-                        updates.push((neighbor_row, neighbor_col, current_state, new_state));
-                    }
+                // Skip if neighbor is Initial, Empty or Frozen
+                if matches!(current_state, PositionState::Initial | PositionState::Empty | PositionState::Frozen) {
+                    continue;
+                }
+                
+                // Determine new state for neighbor
+                let new_state = Self::determine_state(self, neighbor_row, neighbor_col);
+                
+                // Check if state actually needs to change
+                if current_state != new_state {
+                    updates.push((neighbor_row, neighbor_col, current_state, new_state));
                 }
             }
             
             // Apply updates and collect further neighbor updates
             for (neighbor_row, neighbor_col, old_state, new_state) in updates {
-                self.positions[neighbor_row][neighbor_col] = new_state;
+                self.positions[(neighbor_col, neighbor_row)] = new_state;
                 
                 if Self::should_update_neighbors(old_state, new_state) && 
                    !processed.contains(&(neighbor_row, neighbor_col)) {
@@ -229,12 +219,122 @@ impl Lot {
         }
     }
     
+    /// Freezes every Unmovable roll that keeps at least `MIN_BLOCKING_NEIGHBORS`
+    /// neighbors which can never move either, and drops removed and frozen
+    /// rolls from the live list.
+    ///
+    /// Starts from all Unmovable and Frozen rolls and peels off any with too
+    /// few neighbors left in the set; whatever survives props itself up, so
+    /// later removals can't free it.
+    fn freeze_stable(&mut self) -> FreezeReport {
+        let positions = &self.positions;
+        let is_stable = |state: PositionState| matches!(state, PositionState::Unmovable | PositionState::Frozen);
+
+        let mut candidate = Grid::filled(positions.width(), positions.height(), false);
+        for &(row, col) in &self.live {
+            if positions[(col, row)] == PositionState::Unmovable {
+                candidate[(col, row)] = true;
+            }
+        }
+
+        let mut support = Grid::filled(positions.width(), positions.height(), 0);
+        let mut peel = Vec::new();
+        for &(row, col) in &self.live {
+            if candidate[(col, row)] {
+                let count = positions.moore_neighbors(col, row).filter(|&n| is_stable(positions[n])).count();
+                support[(col, row)] = count;
+                if count < Self::MIN_BLOCKING_NEIGHBORS {
+                    peel.push((col, row));
+                }
+            }
+        }
+
+        while let Some(cell) = peel.pop() {
+            if !candidate[cell] {
+                continue;
+            }
+            candidate[cell] = false;
+            for neighbor in positions.moore_neighbors(cell.0, cell.1) {
+                if candidate[neighbor] {
+                    support[neighbor] -= 1;
+                    if support[neighbor] < Self::MIN_BLOCKING_NEIGHBORS {
+                        peel.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        let mut report = FreezeReport { cells: 0, regions: 0 };
+        for &(row, col) in &self.live {
+            if !candidate[(col, row)] {
+                continue;
+            }
+            // Flood-fill the new region, freezing as we go
+            report.regions += 1;
+            let mut stack = vec![(col, row)];
+            candidate[(col, row)] = false;
+            while let Some(cell) = stack.pop() {
+                self.positions[cell] = PositionState::Frozen;
+                report.cells += 1;
+                for neighbor in self.positions.moore_neighbors(cell.0, cell.1) {
+                    if candidate[neighbor] {
+                        candidate[neighbor] = false;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        let positions = &self.positions;
+        self.live.retain(|&(row, col)| !matches!(positions[(col, row)], PositionState::Empty | PositionState::Frozen));
+        report
+    }
+
+    /// Drops every roll straight down into the empty cells below it, each
+    /// column packing its rolls against the bottom row, then settles the
+    /// states around every cell that changed. Returns how many rolls moved.
+    fn fall(&mut self) -> usize {
+        let (width, height) = (self.positions.width(), self.positions.height());
+        let mut changed = Vec::new();
+        for col in 0..width {
+            // Next cell to fill, from the bottom up
+            let mut floor = height;
+            for row in (0..height).rev() {
+                if self.positions[(col, row)] == PositionState::Empty {
+                    continue;
+                }
+                floor -= 1;
+                if floor != row {
+                    self.positions[(col, floor)] = self.positions[(col, row)];
+                    self.positions[(col, row)] = PositionState::Empty;
+                    changed.push((row, col));
+                    changed.push((floor, col));
+                }
+            }
+        }
+
+        for &(row, col) in &changed {
+            let state = Self::determine_state(self, row, col);
+            self.positions[(col, row)] = state;
+        }
+        for &(row, col) in &changed {
+            self.update_neighbors_at(row, col);
+        }
+
+        let positions = &self.positions;
+        self.live = positions
+            .iter()
+            .filter(|(_, &state)| state != PositionState::Empty)
+            .map(|((col, row), _)| (row, col))
+            .collect();
+        changed.len() / 2
+    }
 }
 
 impl fmt::Debug for Lot {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Lot (movable: {})", self.count_movable())?;
-        for row in &self.positions {
+        for row in self.positions.rows() {
             for &state in row {
                 write!(f, "{:?}", state)?;
             }
@@ -244,110 +344,470 @@ impl fmt::Debug for Lot {
     }
 }
 
-/// Day 4: Exercise description
-pub fn run() -> Result<()> {
-    let input = std::fs::read_to_string("assets/day04rolls.txt")?;
-    
-    let mut lot = Lot::new();
-    
-    // Build the initial lot from the input file
-    for (row, line) in input.lines().enumerate() {
-        for (col, ch) in line.chars().enumerate() {
-            let is_empty = match ch {
-                '.' => true,
-                '@' => false,
-                _ => {
-                    eprintln!("Warning: Unexpected character '{}' at row {}, col {}, treating as empty", ch, row, col);
-                    true
-                }
+impl Lot {
+    /// The `Debug` drawing with rolls about to move and frozen rolls colored
+    fn paint(&self, theme: Theme) -> String {
+        let mut out = format!("Lot (movable: {})\n", self.count_movable());
+        out.push_str(&theme.paint_grid(&self.positions, |&state| {
+            let style = match state {
+                PositionState::Movable => Style::Warning,
+                PositionState::Frozen => Style::Highlight,
+                PositionState::Empty => Style::Muted,
+                PositionState::Initial | PositionState::Unmovable => Style::Plain,
             };
-            lot.add_position(row, col, is_empty);
+            (state.symbol(), style)
+        }));
+        out
+    }
+}
+
+/// A cell whose state differs between two lots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CellChange {
+    row: usize,
+    col: usize,
+    before: PositionState,
+    after: PositionState,
+}
+
+impl Lot {
+    /// Cells whose state is different in `other`, in row-major order; cells
+    /// off the edge of the smaller lot count as empty there
+    fn diff(&self, other: &Lot) -> Vec<CellChange> {
+        let width = self.positions.width().max(other.positions.width());
+        let height = self.positions.height().max(other.positions.height());
+        let state = |lot: &Lot, row: usize, col: usize| lot.positions.get(col, row).copied().unwrap_or(PositionState::Empty);
+
+        let mut changes = Vec::new();
+        for row in 0..height {
+            for col in 0..width {
+                let (before, after) = (state(self, row, col), state(other, row, col));
+                if before != after {
+                    changes.push(CellChange { row, col, before, after });
+                }
+            }
         }
+        changes
     }
+
+    /// A copy with every movable and unmovable roll's state worked out again
+    /// from its neighbors, ignoring the incremental updates; what the
+    /// cascade in `update_neighbors_at` has to agree with
+    fn recomputed(&self) -> Lot {
+        let mut lot = self.clone();
+        for ((col, row), &state) in self.positions.iter() {
+            if matches!(state, PositionState::Movable | PositionState::Unmovable) {
+                lot.positions[(col, row)] = Self::determine_state(self, row, col);
+            }
+        }
+        lot
+    }
+}
+
+/// Changes listed one per line before the rest are only counted
+const DIFF_LISTED: usize = 20;
+
+/// `after` drawn with the changed cells colored by how they changed, rolls
+/// gone in red and new ones in green, then the changes themselves
+fn render_diff(before: &Lot, after: &Lot, theme: Theme) -> String {
+    let changes = before.diff(after);
+    let width = before.positions.width().max(after.positions.width());
+    let height = before.positions.height().max(after.positions.height());
+    let mut drawing = Grid::filled(width, height, (PositionState::Empty, Style::Muted));
+    for ((col, row), &state) in after.positions.iter() {
+        drawing[(col, row)] = (state, Style::Muted);
+    }
+    let mut kinds: BTreeMap<(PositionState, PositionState), usize> = BTreeMap::new();
+    for change in &changes {
+        let style = match (change.before, change.after) {
+            (_, PositionState::Empty) => Style::Bad,
+            (PositionState::Empty, _) => Style::Good,
+            _ => Style::Warning,
+        };
+        drawing[(change.col, change.row)].1 = style;
+        *kinds.entry((change.before, change.after)).or_default() += 1;
+    }
+
+    let mut out = theme.paint_grid(&drawing, |&(state, style)| (state.symbol(), style));
+    out.push_str(&format!("{} cells changed\n", changes.len()));
+    for ((before, after), count) in kinds {
+        out.push_str(&format!("  {:?} -> {:?}: {}\n", before, after, count));
+    }
+    for change in changes.iter().take(DIFF_LISTED) {
+        out.push_str(&format!("  row {}, col {}: {:?} -> {:?}\n", change.row, change.col, change.before, change.after));
+    }
+    if changes.len() > DIFF_LISTED {
+        out.push_str(&format!("  ... and {} more\n", changes.len() - DIFF_LISTED));
+    }
+    out
+}
+
+fn parse_lot(input: &str) -> Result<Lot> {
+    let map = parse::char_grid(input)?;
+    let mut lot = Lot::new(map.width(), map.height());
     
-    println!("Initial lot:");
-    println!("{:?}", lot);
-    println!();
-    
-    let mut total_removed = 0;
-    let mut stage = 1;
-    
+    for ((col, row), &ch) in map.iter() {
+        let is_empty = match ch {
+            '.' => true,
+            '@' => false,
+            _ => {
+                eprintln!("Warning: Unexpected character '{}' at row {}, col {}, treating as empty", ch, row, col);
+                true
+            }
+        };
+        lot.add_position(row, col, is_empty);
+    }
+    Ok(lot)
+}
+
+/// Rolls that can be moved straight away
+pub fn part1(input: &str) -> Result<u32> {
+    Ok(parse_lot(input)?.count_movable())
+}
+
+/// What one removal stage did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stage {
+    /// Rolls taken away; 0 once nothing can move
+    removed: usize,
+    frozen: FreezeReport,
+    /// Rolls that fell afterwards, with gravity on
+    fell: usize,
+}
+
+impl Lot {
+    /// Sets aside rolls that can never move, then removes every movable roll,
+    /// passing the (row, col) of each one to `on_removed`. With gravity
+    /// nothing is frozen, since any roll can still fall, and the rest of the
+    /// rolls drop once the removals are done.
+    fn step(&mut self, mut on_removed: impl FnMut(usize, usize)) -> Result<Stage> {
+        let frozen = if self.gravity { FreezeReport { cells: 0, regions: 0 } } else { self.freeze_stable() };
+        let movable_positions = self.get_movable();
+        let removed = movable_positions.len();
+        for (row, col) in movable_positions {
+            self.remove_roll_at(row, col)?;
+            on_removed(row, col);
+        }
+        let fell = if self.gravity { self.fall() } else { 0 };
+        stats::add("day04.stages", u64::from(removed > 0));
+        stats::add("day04.rolls_removed", removed as u64);
+        stats::add("day04.rolls_fallen", fell as u64);
+        Ok(Stage { removed, frozen, fell })
+    }
+}
+
+/// Totals of a run of removal stages until no roll can move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SimulationSummary {
+    /// Stages that removed at least one roll
+    pub stages: usize,
+    pub removed: usize,
+    /// Rolls frozen along the way; without gravity, together with `removed`
+    /// that's every roll
+    pub frozen: usize,
+    /// Rolls that fell, once for every stage they fell after
+    pub fallen: usize,
+}
+
+impl SimulationSummary {
+    fn add(&mut self, stage: Stage) {
+        self.frozen += stage.frozen.cells;
+        self.fallen += stage.fell;
+        if stage.removed > 0 {
+            self.stages += 1;
+            self.removed += stage.removed;
+        }
+    }
+}
+
+/// Runs every stage without drawing anything
+fn simulate_to_completion(lot: &mut Lot) -> Result<SimulationSummary> {
+    simulate_recording(lot, &mut |_, _, _| {})
+}
+
+/// `simulate_to_completion`, passing (stage, row, col) for every roll
+/// removed to `on_removed`, stages counting from 1
+fn simulate_recording(lot: &mut Lot, on_removed: &mut dyn FnMut(usize, usize, usize)) -> Result<SimulationSummary> {
+    let mut summary = SimulationSummary::default();
     loop {
-        // Get all currently movable positions
-        let movable_positions = lot.get_movable();
-        
-        if movable_positions.is_empty() {
-            break;
+        let number = summary.stages + 1;
+        let stage = lot.step(|row, col| on_removed(number, row, col))?;
+        summary.add(stage);
+        if stage.removed == 0 {
+            return Ok(summary);
         }
-        
-        // Remove rolls at all movable positions
-        let removed_count = movable_positions.len();
-        for (row, col) in movable_positions {
-            lot.remove_roll_at(row, col)?;
+    }
+}
+
+/// Rolls removed by repeatedly taking every movable roll
+pub fn part2(input: &str) -> Result<usize> {
+    Ok(simulate_to_completion(&mut parse_lot(input)?)?.removed)
+}
+
+/// `part2` where the remaining rolls fall into the gaps below them after
+/// every stage, before the next stage looks at what can move
+pub fn removed_with_gravity(input: &str) -> Result<usize> {
+    let mut lot = parse_lot(input)?;
+    lot.gravity = true;
+    Ok(simulate_to_completion(&mut lot)?.removed)
+}
+
+/// When the roll in a cell was taken away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Removal {
+    /// The cell was empty from the start
+    #[default]
+    NoRoll,
+    /// Removed in this stage, counting from 1
+    Stage(usize),
+    /// Still there once nothing can move
+    Never,
+}
+
+/// `Never` for every roll still in `lot`, to be filled in as stages run
+fn unremoved(lot: &Lot) -> Grid<Removal> {
+    let mut removals = Grid::new(lot.positions.width(), lot.positions.height());
+    for &(row, col) in &lot.live {
+        removals.set(col, row, Removal::Never);
+    }
+    removals
+}
+
+/// The stage every roll of the lot was removed in
+pub fn removal_stages(input: &str) -> Result<Grid<Removal>> {
+    let mut lot = parse_lot(input)?;
+    let mut removals = unremoved(&lot);
+    simulate_recording(&mut lot, &mut |stage, row, col| {
+        removals.set(col, row, Removal::Stage(stage));
+    })?;
+    Ok(removals)
+}
+
+/// One CSV line per lot row: the removal stage of each roll, `-` for rolls
+/// never removed and an empty field where there was no roll
+fn removals_csv(removals: &Grid<Removal>) -> String {
+    let mut out = String::new();
+    for row in removals.rows() {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|removal| match removal {
+                Removal::NoRoll => String::new(),
+                Removal::Stage(stage) => stage.to_string(),
+                Removal::Never => "-".to_string(),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// One row per stage: the stage, a bar scaled to the busiest stage, and the
+/// rolls removed in it; rolls never removed get a final `never` row
+fn render_removal_histogram(removals: &Grid<Removal>) -> String {
+    let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut never = 0;
+    for (_, removal) in removals.iter() {
+        match removal {
+            Removal::NoRoll => {}
+            Removal::Stage(stage) => *counts.entry(*stage).or_default() += 1,
+            Removal::Never => never += 1,
         }
-        
-        total_removed += removed_count;
-        
-        println!("Stage {}:", stage);
-        println!("  Removed {} rolls", removed_count);
-        println!("  Total removed so far: {}", total_removed);
-        println!("{:?}", lot);
-        println!();
-        
-        stage += 1;
     }
+
+    let rows: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(stage, count)| (stage.to_string(), count))
+        .chain((never > 0).then(|| ("never".to_string(), never)))
+        .collect();
+    let max_count = rows.iter().map(|(_, count)| *count).max().unwrap_or(1);
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(1);
+
+    let mut out = String::new();
+    for (label, count) in rows {
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(max_count);
+        out.push_str(&format!("{:>w$} | {} {}\n", label, "#".repeat(bar), count, w = label_width));
+    }
+    out
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day04", about = "Day 4: Printing Department")]
+struct Options {
+    /// Draw the lot after every stage (slow on big lots)
+    #[arg(long)]
+    render: bool,
+    /// Print how many rolls each stage removed
+    #[arg(long)]
+    histogram: bool,
+    /// Write the stage each roll was removed in to this file as CSV
+    #[arg(long, value_name = "FILE")]
+    stages_csv: Option<String>,
+    /// Let the remaining rolls fall into empty cells after every stage
+    #[arg(long)]
+    gravity: bool,
+    /// Instead of the puzzle, diff the lot against FILE, or two FILEs
+    /// against each other
+    #[arg(long, value_name = "FILE", num_args = 1..=2)]
+    diff: Vec<String>,
+    /// Instead of the puzzle, diff the lot before and after N stages and
+    /// check every stage's incremental updates against a full recompute
+    #[arg(long, value_name = "N", conflicts_with = "diff")]
+    diff_stages: Option<usize>,
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Printing Department",
+        input_files: &["rolls"],
+        parts: [
+            "Rolls that can be moved straight away",
+            "Rolls removed by repeatedly taking every movable roll",
+        ],
+        notes: "Can draw each removal stage, a histogram and a CSV of stages; --gravity drops rolls after each stage; --diff and --diff-stages compare lots",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day04".to_string()).chain(args.iter().cloned()));
+    // Build the initial lot from the input file
+    let mut lot = Input::for_day(4).parse("rolls", parse_lot)?;
+    lot.gravity = options.gravity;
+    if let Some(stages) = options.diff_stages {
+        return run_diff_stages(lot, stages, out);
+    }
+    if !options.diff.is_empty() {
+        return run_diff(lot, &options.diff, out);
+    }
+    out.answer(1, "Initially movable", &lot.count_movable());
+
+    let mut removals = unremoved(&lot);
+    let mut record = |stage, row, col| {
+        removals.set(col, row, Removal::Stage(stage));
+    };
+    let summary = if options.render {
+        render_stages(&mut lot, &mut record, out)?
+    } else {
+        simulate_recording(&mut lot, &mut record)?
+    };
     
-    println!("Final result:");
-    println!("  Total stages: {}", stage - 1);
-    println!("  Total rolls removed: {}", total_removed);
+    out.line(&theme::current().paint(Style::Heading, "Final result:"));
+    out.line(&format!("  Total stages: {}", summary.stages));
+    // Falling rolls make it a different puzzle
+    if options.gravity {
+        out.line(&format!("  Total rolls removed: {}", summary.removed));
+    } else {
+        out.answer(2, "  Total rolls removed", &summary.removed);
+    }
+    if options.gravity {
+        out.line(&format!("  Total rolls fallen: {}", summary.fallen));
+    } else {
+        out.line(&format!("  Total rolls frozen: {}", summary.frozen));
+    }
+
+    if options.histogram {
+        out.line("\nRolls removed per stage:");
+        out.block(&render_removal_histogram(&removals));
+    }
+    if let Some(path) = &options.stages_csv {
+        fs::write(path, removals_csv(&removals)).map_err(|e| AocError::io(path, e))?;
+        out.line(&format!("\nWrote removal stages to {}", path));
+    }
     
     Ok(())
 }
 
+fn run_diff(puzzle: Lot, paths: &[String], out: &dyn OutputSink) -> Result<()> {
+    let load = |path: &String| parse_lot(input::read_file(path)?).map_err(|e| e.in_file(path));
+    let (before, after) = match paths {
+        [after] => (puzzle, load(after)?),
+        [before, after] => (load(before)?, load(after)?),
+        _ => unreachable!("clap takes one or two files"),
+    };
+    out.block(&render_diff(&before, &after, theme::current()));
+    Ok(())
+}
+
+/// Runs up to `stages` stages, comparing the lot with a full recompute after
+/// each, then diffs where it started against where it ended up; fails if
+/// any stage disagreed, so scripts see the mismatch in the exit code
+fn run_diff_stages(mut lot: Lot, stages: usize, out: &dyn OutputSink) -> Result<()> {
+    let theme = theme::current();
+    let start = lot.clone();
+    let mut mismatches = 0;
+    for number in 1..=stages {
+        if lot.step(|_, _| {})?.removed == 0 {
+            out.line(&format!("Nothing left to move after {} stages", number - 1));
+            break;
+        }
+        let wrong = lot.diff(&lot.recomputed());
+        if !wrong.is_empty() {
+            mismatches += wrong.len();
+            out.line(&theme.paint(Style::Bad, format!("Stage {}: {} cells disagree with a full recompute", number, wrong.len())));
+        }
+    }
+    if mismatches == 0 {
+        out.line(&theme.paint(Style::Good, "Incremental updates match a full recompute after every stage"));
+    }
+    out.block(&render_diff(&start, &lot, theme));
+    if mismatches > 0 {
+        return Err(AocError::solve(format!("{} cells disagreed with a full recompute", mismatches)));
+    }
+    Ok(())
+}
+
+/// `simulate_recording`, printing the lot before and after every stage
+fn render_stages(lot: &mut Lot, on_removed: &mut dyn FnMut(usize, usize, usize), out: &dyn OutputSink) -> Result<SimulationSummary> {
+    let theme = theme::current();
+    out.line(&theme.paint(Style::Heading, "Initial lot:"));
+    out.line(&lot.paint(theme));
+
+    let mut summary = SimulationSummary::default();
+    loop {
+        let number = summary.stages + 1;
+        let stage = lot.step(|row, col| on_removed(number, row, col))?;
+        summary.add(stage);
+        if stage.removed == 0 {
+            return Ok(summary);
+        }
+        
+        out.line(&theme.paint(Style::Heading, format!("Stage {}:", summary.stages)));
+        out.line(&format!("  Removed {} rolls", stage.removed));
+        if lot.gravity {
+            out.line(&format!("  {} rolls fell", stage.fell));
+        } else {
+            out.line(&format!("  Froze {} rolls in {} regions", stage.frozen.cells, stage.frozen.regions));
+        }
+        out.line(&format!("  Total removed so far: {}", summary.removed));
+        out.line(&lot.paint(theme));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_full_solution_lot_count() {
         // Ensure the solution to part 1 stays correct.
-        let input = std::fs::read_to_string("assets/day04rolls.txt")
+        let input = Input::for_day(4).read("rolls")
             .expect("Failed to read input file");
         
-        let mut lot = Lot::new();
+        let lot = parse_lot(input).expect("Failed to parse lot");
         
-        for (row, line) in input.lines().enumerate() {
-            for (col, ch) in line.chars().enumerate() {
-                let is_empty = match ch {
-                    '.' => true,
-                    '@' => false,
-                    _ => true,
-                };
-                lot.add_position(row, col, is_empty);
-            }
-        }
-        
-        assert_eq!(lot.count_movable(), 1433);
+        snapshot::check("day04", "movable", lot.count_movable());
     }
 
     #[test]
     fn test_full_solution_total_removed() {
         // Ensure the solution to part 2 stays correct.
-        let input = std::fs::read_to_string("assets/day04rolls.txt")
+        let input = Input::for_day(4).read("rolls")
             .expect("Failed to read input file");
         
-        let mut lot = Lot::new();
-        
-        for (row, line) in input.lines().enumerate() {
-            for (col, ch) in line.chars().enumerate() {
-                let is_empty = match ch {
-                    '.' => true,
-                    '@' => false,
-                    _ => true,
-                };
-                lot.add_position(row, col, is_empty);
-            }
-        }
+        let mut lot = parse_lot(input).expect("Failed to parse lot");
         
         let mut total_removed = 0;
         
@@ -366,6 +826,148 @@ mod tests {
             total_removed += removed_count;
         }
         
-        assert_eq!(total_removed, 8616);
+        snapshot::check("day04", "total_removed", total_removed);
+    }
+
+    #[test]
+    fn test_freeze_solid_block() {
+        // Only the corners of a 4x4 block have fewer than 4 neighbors
+        let mut lot = parse_lot("@@@@\n@@@@\n@@@@\n@@@@\n").unwrap();
+        assert_eq!(lot.freeze_stable(), FreezeReport { cells: 12, regions: 1 });
+        assert_eq!(lot.get_movable().len(), 4);
+        assert_eq!(part2("@@@@\n@@@@\n@@@@\n@@@@\n").unwrap(), 4);
+
+        // Two blocks apart from each other freeze as separate regions
+        let mut lot = parse_lot("@@@@.@@@@\n@@@@.@@@@\n@@@@.@@@@\n@@@@.@@@@\n").unwrap();
+        assert_eq!(lot.freeze_stable(), FreezeReport { cells: 24, regions: 2 });
+    }
+
+    #[test]
+    fn test_full_solution_frozen_rolls_stay() {
+        let input = Input::for_day(4).read("rolls")
+            .expect("Failed to read input file");
+        let rolls = input.chars().filter(|&c| c == '@').count();
+
+        let mut lot = parse_lot(input).expect("Failed to parse lot");
+        let mut total_removed = 0;
+        let mut total_frozen = 0;
+        loop {
+            total_frozen += lot.freeze_stable().cells;
+            let movable_positions = lot.get_movable();
+            if movable_positions.is_empty() {
+                break;
+            }
+            total_removed += movable_positions.len();
+            for (row, col) in movable_positions {
+                lot.remove_roll_at(row, col).expect("Failed to remove roll");
+            }
+        }
+
+        snapshot::check("day04", "total_removed", total_removed);
+        // Everything left at the end was frozen along the way
+        assert_eq!(total_removed + total_frozen, rolls);
+    }
+
+    #[test]
+    fn test_removal_stages() {
+        // A thin block peels from the corners inwards
+        let removals = removal_stages("@@@@@\n@@@@@\n@@@@@\n.....\n").unwrap();
+        assert_eq!(removals[(0, 0)], Removal::Stage(1));
+        assert_eq!(removals[(0, 3)], Removal::NoRoll);
+        assert_eq!(removals_csv(&removals), "1,3,4,3,1\n2,4,5,4,2\n1,3,4,3,1\n,,,,\n");
+
+        // Only the corners of a solid block ever move
+        let removals = removal_stages("@@@@\n@@@@\n@@@@\n@@@@\n").unwrap();
+        assert_eq!(removals_csv(&removals), "1,-,-,1\n-,-,-,-\n-,-,-,-\n1,-,-,1\n");
+        assert_eq!(
+            render_removal_histogram(&removals),
+            format!("    1 | {} 4\nnever | {} 12\n", "#".repeat(17), "#".repeat(50))
+        );
+
+        let input = Input::for_day(4).read("rolls").unwrap();
+        let removals = removal_stages(input).unwrap();
+        let removed = removals.iter().filter(|(_, r)| matches!(r, Removal::Stage(_))).count();
+        snapshot::check("day04", "total_removed", removed);
+    }
+
+    #[test]
+    fn test_simulate_to_completion() {
+        let input = Input::for_day(4).read("rolls").unwrap();
+        let rolls = input.chars().filter(|&c| c == '@').count();
+
+        let summary = simulate_to_completion(&mut parse_lot(input).unwrap()).unwrap();
+        snapshot::check("day04", "total_removed", summary.removed);
+        assert_eq!(summary.removed + summary.frozen, rolls);
+
+        let summary = simulate_to_completion(&mut parse_lot("@@@@\n@@@@\n@@@@\n@@@@\n").unwrap()).unwrap();
+        assert_eq!(summary, SimulationSummary { stages: 1, removed: 4, frozen: 12, fallen: 0 });
+    }
+
+    const EXAMPLE: &str = "\
+..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.
+";
+
+    #[test]
+    fn test_gravity() {
+        assert_eq!(part1(EXAMPLE).unwrap(), 13);
+        assert_eq!(part2(EXAMPLE).unwrap(), 43);
+        // Falling keeps exposing rolls until the whole lot is gone
+        assert_eq!(removed_with_gravity(EXAMPLE).unwrap(), 71);
+        let mut lot = parse_lot(EXAMPLE).unwrap();
+        lot.gravity = true;
+        let summary = simulate_to_completion(&mut lot).unwrap();
+        assert_eq!(summary, SimulationSummary { stages: 21, removed: 71, frozen: 0, fallen: 123 });
+
+        // A roll only falls as far as the next roll down
+        let mut lot = parse_lot("@.\n..\n@@\n").unwrap();
+        lot.remove_roll_at(2, 1).unwrap();
+        assert_eq!(lot.fall(), 1);
+        assert_eq!(format!("{:?}", lot), "Lot (movable: 2)\n..\nx.\nx.\n");
+        assert_eq!(lot.live, vec![(1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn test_diff_and_recompute() {
+        let before = parse_lot("@@@\n@@.\n").unwrap();
+        let mut after = before.clone();
+        after.remove_roll_at(0, 0).unwrap();
+        assert_eq!(format!("{:?}", before), "Lot (movable: 3)\nx@x\nx@.\n");
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                CellChange { row: 0, col: 0, before: PositionState::Movable, after: PositionState::Empty },
+                CellChange { row: 0, col: 1, before: PositionState::Unmovable, after: PositionState::Movable },
+                CellChange { row: 1, col: 1, before: PositionState::Unmovable, after: PositionState::Movable },
+            ]
+        );
+        assert_eq!(after.diff(&before).len(), 3);
+        assert_eq!(
+            render_diff(&before, &after, Theme::PLAIN),
+            ".xx\nxx.\n3 cells changed\n  @ -> x: 2\n  x -> .: 1\n  row 0, col 0: x -> .\n  row 0, col 1: @ -> x\n  row 1, col 1: @ -> x\n"
+        );
+        // A smaller lot reads as empty beyond its edge
+        let narrow = parse_lot("@@\n@@\n").unwrap();
+        assert_eq!(narrow.diff(&before).iter().filter(|c| c.col == 2).count(), 1);
+
+        // The incremental cascade agrees with a full recompute at every stage
+        let mut lot = parse_lot(Input::for_day(4).read("rolls").unwrap()).unwrap();
+        assert!(lot.diff(&lot.recomputed()).is_empty());
+        while lot.step(|_, _| {}).unwrap().removed > 0 {
+            assert!(lot.diff(&lot.recomputed()).is_empty());
+        }
+        let mut lot = parse_lot(EXAMPLE).unwrap();
+        lot.gravity = true;
+        while lot.step(|_, _| {}).unwrap().removed > 0 {
+            assert!(lot.diff(&lot.recomputed()).is_empty());
+        }
     }
 }
@@ -1,194 +1,336 @@
-use anyhow::{anyhow, Result};
-use std::fs;
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::Parser;
+use crate::input::{self, Input};
+use crate::util::interval::{Interval, IntervalSet, TaggedIntervalSet, Universe};
+use crate::util::output::OutputSink;
+use crate::util::parse::{self, Section};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct IdRange {
-    start: u64,
-    end: u64,
+type IdRange = Interval<u64>;
+
+/// Number of available IDs that fall in a fresh range
+pub fn part1(input: &str) -> Result<u64> {
+    let (ranges, queries) = parse_input(input)?;
+    let optimized_ranges = ranges.into_iter().collect::<IntervalSet<_>>();
+    queries.iter().try_fold(0u64, |total, &query| {
+        total.checked_add(fresh_in(&optimized_ranges, query)?).ok_or_else(|| AocError::solve("Fresh available IDs overflow u64"))
+    })
 }
 
-impl IdRange {
-    fn new(start: u64, end: u64) -> Self {
-        Self { start, end }
-    }
-    
-    fn contains(&self, id: u64) -> bool {
-        id >= self.start && id <= self.end
+/// Fresh IDs in `query`, counted by intersecting it with the fresh ranges
+fn fresh_in(set: &IntervalSet<u64>, query: IdRange) -> Result<u64> {
+    set.checked_intersect_range(query).ok_or_else(|| {
+        AocError::solve(format!("Every ID of {}-{} is fresh, which is more than a u64 count holds", query.start, query.end))
+    })
+}
+
+/// Number of IDs covered by the fresh ranges
+pub fn part2(input: &str) -> Result<u64> {
+    let (ranges, _) = parse_input(input)?;
+    fresh_count(&ranges.into_iter().collect())
+}
+
+/// IDs in `set`, which can be one more than u64 holds when it covers them all
+fn fresh_count(set: &IntervalSet<u64>) -> Result<u64> {
+    set.checked_count().ok_or_else(|| AocError::solve("Every u64 ID is covered, which is more than a u64 count holds"))
+}
+
+/// Every ID `--spoiled` looks at when given no window
+const ALL_IDS: &str = "0-18446744073709551615";
+
+/// Parses a `LO-HI` window of IDs
+fn parse_window(text: &str) -> Result<Universe<u64>> {
+    let (lo, hi) = text
+        .split_once('-')
+        .ok_or_else(|| AocError::usage(format!("Invalid window '{}', expected LO-HI", text)))?;
+    let bound = |id: &str| id.trim().parse::<u64>().map_err(|e| AocError::usage(format!("Invalid window bound '{}': {}", id, e)));
+    let (lo, hi) = (bound(lo)?, bound(hi)?);
+    if lo > hi {
+        return Err(AocError::usage(format!("Empty window '{}'", text)));
     }
-    
-    fn overlaps_or_adjacent(&self, other: &IdRange) -> bool {
-        other.start <= self.end + 1
+    Ok(Universe::new(lo, hi))
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day05", about = "Day 5: Cafeteria")]
+struct Options {
+    /// List the spoiled IDs (those no fresh range covers) in LO-HI as
+    /// ranges, or among every u64 ID without a window
+    #[arg(long, value_name = "LO-HI", value_parser = parse_window, num_args = 0..=1, default_missing_value = ALL_IDS)]
+    spoiled: Option<Universe<u64>>,
+    /// Merge the range lists in these files, showing which files each merged range came from
+    #[arg(long, value_name = "FILE", num_args = 1.., conflicts_with = "spoiled")]
+    merge: Vec<String>,
+}
+
+/// Ranged queries whose answers the puzzle run prints one by one
+const QUERIES_LISTED: usize = 50;
+
+/// Spoiled ranges printed by `--spoiled` before eliding the rest
+const SPOILED_LISTED: usize = 50;
+
+fn run_spoiled(fresh: &IntervalSet<u64>, universe: Universe<u64>, out: &dyn OutputSink) {
+    let spoiled = fresh.complement(universe);
+    let window = universe.bounds().expect("parse_window rejects empty windows");
+    // Only an empty fresh list over every ID has more spoiled IDs than u64 holds
+    let count = spoiled.checked_count().map_or_else(|| "2^64".to_string(), |count| count.to_string());
+    out.line(&format!("Spoiled IDs in {}-{}: {} in {} range(s)", window.start, window.end, count, spoiled.intervals().len()));
+    for range in spoiled.intervals().iter().take(SPOILED_LISTED) {
+        if range.start == range.end {
+            out.line(&format!("  {}", range.start));
+        } else {
+            out.line(&format!("  {}-{}", range.start, range.end));
+        }
     }
-    
-    fn merge(&self, other: &IdRange) -> IdRange {
-        IdRange::new(self.start, self.end.max(other.end))
+    if spoiled.intervals().len() > SPOILED_LISTED {
+        out.line(&format!("  ... ({} more)", spoiled.intervals().len() - SPOILED_LISTED));
     }
-    
-    fn count(&self) -> u64 {
-        self.end - self.start + 1
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Cafeteria",
+        input_files: &["ids"],
+        parts: [
+            "Number of available IDs that fall in a fresh range",
+            "Number of IDs covered by the fresh ranges",
+        ],
+        notes: "--merge combines range files, keeping where each range came from",
     }
 }
 
-pub fn run() -> Result<()> {
-    let (ranges, ids) = parse_input("assets/day05ids.txt")?;
-    println!("Day 5: Parsed {} ranges and {} IDs", ranges.len(), ids.len());
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day05".to_string()).chain(args.iter().cloned()));
+    if !options.merge.is_empty() {
+        return run_merge(&options.merge, out);
+    }
+    let (ranges, queries) = Input::for_day(5).parse("ids", parse_input)?;
+    if let Some(window) = options.spoiled {
+        run_spoiled(&ranges.into_iter().collect(), window, out);
+        return Ok(());
+    }
+    out.line(&format!("Day 5: Parsed {} ranges and {} queries", ranges.len(), queries.len()));
     
-    let optimized_ranges = optimize_ranges(ranges);
-    println!("Optimized to {} ranges", optimized_ranges.len());
+    let optimized_ranges = ranges.into_iter().collect::<IntervalSet<_>>();
+    out.line(&format!("Optimized to {} ranges", optimized_ranges.intervals().len()));
     
     // Calculate total fresh IDs based on optimized ranges
-    let total_fresh_from_ranges: u64 = optimized_ranges.iter()
-        .map(|range| range.count())
-        .sum();
-    println!("Total fresh IDs from ranges: {}", total_fresh_from_ranges);
+    let total_fresh_from_ranges = fresh_count(&optimized_ranges)?;
+    out.answer(2, "Total fresh IDs from ranges", &total_fresh_from_ranges);
     
-    // Check each ID to see if it's spoiled or fresh
-    // Ranges represent FRESH IDs, so if ID is in range = fresh, otherwise = spoiled
-    let fresh_count = ids.iter()
-        .filter(|&&id| is_fresh(&optimized_ranges, id))
-        .count();
-    let spoiled_count = ids.len() - fresh_count;
+    // Ranges represent FRESH IDs; a query is a single ID or a range of
+    // them, and everything in it that no range covers is spoiled
+    let (mut fresh_count, mut spoiled_count) = (0u128, 0u128);
+    let mut listed = 0;
+    for &query in &queries {
+        let fresh = fresh_in(&optimized_ranges, query)?;
+        let size = u128::from(query.end - query.start) + 1;
+        fresh_count += u128::from(fresh);
+        spoiled_count += size - u128::from(fresh);
+        if query.start != query.end && listed < QUERIES_LISTED {
+            out.line(&format!("Query {}-{}: {} of {} IDs fresh", query.start, query.end, fresh, size));
+            listed += 1;
+        }
+    }
     
-    println!("\nResults:");
-    println!("Spoiled IDs: {}", spoiled_count);
-    println!("Fresh IDs: {}", fresh_count);
+    out.line("\nResults:");
+    out.line(&format!("Spoiled IDs: {}", spoiled_count));
+    out.answer(1, "Fresh IDs", &fresh_count);
     
     Ok(())
 }
 
-fn is_fresh(ranges: &[IdRange], id: u64) -> bool {
-    // Use binary search to check if id falls within any range
-    // Ranges represent FRESH IDs (inclusive on both ends)
-    // Ranges are sorted by start value and non-overlapping
-    
-    // Binary search for the rightmost range where start <= id
-    let idx = match ranges.binary_search_by_key(&id, |range| range.start) {
-        Ok(idx) => idx,  // Exact match on start
-        Err(idx) => {
-            if idx == 0 {
-                return false;  // id is before all ranges
-            }
-            idx - 1  // Check the range just before
-        }
+/// The fresh ranges and the queries: available IDs, or `LO-HI` ranges of
+/// them, with a single ID read as a range of one
+fn parse_input(content: &str) -> Result<(Vec<IdRange>, Vec<IdRange>)> {
+    // Ranges come first, then a blank line, then the queries
+    let sections = parse::sections(content);
+    let [range_section, id_sections @ ..] = sections.as_slice() else {
+        return Err(AocError::parse("Input file must contain two sections separated by empty line"));
     };
+    if id_sections.is_empty() {
+        return Err(AocError::parse("Input file must contain two sections separated by empty line"));
+    }
+
+    let ranges = parse_range_section(range_section)?;
+
+    let queries = id_sections
+        .iter()
+        .flat_map(|section| section.numbered())
+        .map(|(line_no, line)| match line.contains('-') {
+            true => parse_range(line, line_no),
+            false => parse::number(line, line_no, 1).map(|id| IdRange::new(id, id)),
+        })
+        .collect::<Result<Vec<_>>>()?;
     
-    // Check if id falls within the range at idx (inclusive on both ends)
-    ranges[idx].contains(id)
+    Ok((ranges, queries))
+}
+
+/// One `LO-HI` range per line, with `LO <= HI`
+fn parse_range_section(section: &Section) -> Result<Vec<IdRange>> {
+    section.numbered().map(|(line_no, line)| parse_range(line, line_no)).collect()
 }
 
-fn optimize_ranges(mut ranges: Vec<IdRange>) -> Vec<IdRange> {
-    if ranges.is_empty() {
-        return ranges;
+fn parse_range(line: &str, line_no: usize) -> Result<IdRange> {
+    let (start, end) = line.split_once('-')
+        .ok_or_else(|| AocError::parse_at(line_no, format!("Invalid range format: {}", line)))?;
+    let range = IdRange::new(parse::number(start, line_no, 1)?, parse::number(end, line_no, start.len() + 2)?);
+    if range.start > range.end {
+        return Err(AocError::parse_at(line_no, format!("Range {} ends before it starts", line)));
     }
-    
-    // Step 1: Sort by the start value (ascending)
-    ranges.sort_by_key(|r| r.start);
-    
-    let mut optimized = Vec::new();
-    let mut current = ranges[0];
-    
-    // Step 2-3: Inspect each element and its successor for overlaps or adjacency
-    for &next in &ranges[1..] {
-        // Check if current and next overlap or are adjacent
-        if current.overlaps_or_adjacent(&next) {
-            // They overlap or are adjacent, combine them
-            current = current.merge(&next);
-        } else {
-            // No overlap or adjacency, save current and move to next
-            optimized.push(current);
-            current = next;
+    Ok(range)
+}
+
+/// Merges range lists from several sources, `(name, contents)`, keeping
+/// track of which sources each merged range came from. A source with more
+/// than one section (separated by blank lines) counts as one source per
+/// section, named `name#1`, `name#2` and so on.
+pub fn merge_sources(sources: &[(String, &str)]) -> Result<TaggedIntervalSet<u64, String>> {
+    let mut tagged = Vec::new();
+    for (name, content) in sources {
+        let sections = parse::sections(content);
+        for (i, section) in sections.iter().enumerate() {
+            let tag = if sections.len() == 1 { name.clone() } else { format!("{}#{}", name, i + 1) };
+            let ranges = parse_range_section(section).map_err(|e| e.in_file(name))?;
+            tagged.extend(ranges.into_iter().map(|range| (range, tag.clone())));
         }
     }
-    
-    // Don't forget to add the last range
-    optimized.push(current);
-    
-    optimized
+    Ok(tagged.into_iter().collect())
 }
 
-fn parse_input(filename: &str) -> Result<(Vec<IdRange>, Vec<u64>)> {
-    let content = fs::read_to_string(filename)?;
-    
-    // Split the content by empty line
-    let parts: Vec<&str> = content.split("\n\n").collect();
-    if parts.len() < 2 {
-        return Err(anyhow!("Input file must contain two sections separated by empty line"));
-    }
-    
-    // Parse ranges (first part)
-    let ranges: Vec<IdRange> = parts[0]
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            let nums: Vec<u64> = line
-                .split('-')
-                .map(|n| n.trim().parse())
-                .collect::<Result<_, _>>()?;
-            if nums.len() != 2 {
-                return Err(anyhow!("Invalid range format: {}", line));
-            }
-            Ok(IdRange::new(nums[0], nums[1]))
-        })
+/// Merged ranges printed by `--merge` before eliding the rest
+const MERGED_LISTED: usize = 50;
+
+fn run_merge(paths: &[String], out: &dyn OutputSink) -> Result<()> {
+    let sources = paths
+        .iter()
+        .map(|path| Ok((path.clone(), input::read_file(path)?)))
         .collect::<Result<Vec<_>>>()?;
-    
-    // Parse IDs (second part)
-    let ids: Vec<u64> = parts[1]
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| line.trim().parse())
-        .collect::<Result<_, _>>()?;
-    
-    Ok((ranges, ids))
+    let merged = merge_sources(&sources)?;
+
+    let ranges = merged.set().intervals().len();
+    let shared = merged.iter().filter(|(_, tags)| tags.len() > 1).count();
+    out.line(&format!("Merged {} file(s) into {} range(s) covering {} IDs", paths.len(), ranges, merged.set().count()));
+    out.line(&format!("{} range(s) combine more than one source", shared));
+    for (range, tags) in merged.iter().take(MERGED_LISTED) {
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        out.line(&format!("  {}-{}: {}", range.start, range.end, tags.join(", ")));
+    }
+    if ranges > MERGED_LISTED {
+        out.line(&format!("  ... ({} more)", ranges - MERGED_LISTED));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_full_solution_parse_counts() {
-        let (ranges, ids) = parse_input("assets/day05ids.txt")
+        let (ranges, queries) = parse_input(Input::for_day(5).read("ids").unwrap())
             .expect("Failed to read input file");
         
         assert_eq!(ranges.len(), 183, "Should parse 183 ranges");
-        assert_eq!(ids.len(), 1000, "Should parse 1000 IDs");
+        assert_eq!(queries.len(), 1000, "Should parse 1000 IDs");
+        assert!(queries.iter().all(|query| query.start == query.end));
     }
 
     #[test]
     fn test_full_solution_optimized_ranges() {
-        let (ranges, _) = parse_input("assets/day05ids.txt")
+        let (ranges, _) = parse_input(Input::for_day(5).read("ids").unwrap())
             .expect("Failed to read input file");
         
-        let optimized_ranges = optimize_ranges(ranges);
-        assert_eq!(optimized_ranges.len(), 78, "Should optimize to 78 ranges");
+        let optimized_ranges = ranges.into_iter().collect::<IntervalSet<_>>();
+        assert_eq!(optimized_ranges.intervals().len(), 78, "Should optimize to 78 ranges");
     }
 
     #[test]
     fn test_full_solution_total_fresh_ids() {
-        let (ranges, _) = parse_input("assets/day05ids.txt")
+        let (ranges, _) = parse_input(Input::for_day(5).read("ids").unwrap())
             .expect("Failed to read input file");
         
-        let optimized_ranges = optimize_ranges(ranges);
-        let total_fresh: u64 = optimized_ranges.iter()
-            .map(|range| range.count())
-            .sum();
+        let optimized_ranges = ranges.into_iter().collect::<IntervalSet<_>>();
+        let total_fresh = optimized_ranges.count();
         
-        assert_eq!(total_fresh, 369761800782619, "Total fresh IDs from ranges");
+        snapshot::check("day05", "total_fresh", total_fresh);
+    }
+
+    #[test]
+    fn test_spoiled_window() {
+        assert_eq!(parse_window("10-20").unwrap(), Universe::new(10, 20));
+        assert_eq!(parse_window(ALL_IDS).unwrap(), Universe::full());
+        assert!(parse_window("20-10").is_err());
+        assert!(parse_window("10").is_err());
+
+        let (ranges, _) = parse_input(Input::for_day(5).read("ids").unwrap()).unwrap();
+        let fresh: IntervalSet<u64> = ranges.into_iter().collect();
+        let first = fresh.intervals()[0];
+        let window = IdRange::new(first.start, fresh.intervals()[fresh.intervals().len() - 1].end);
+        let spoiled = fresh.complement_within(window.start, window.end);
+        assert_eq!(spoiled.count() + fresh.count(), window.count());
+        assert!(spoiled.intervals().iter().all(|gap| !fresh.contains(gap.start) && !fresh.contains(gap.end)));
+        let everywhere = fresh.complement(Universe::full());
+        assert_eq!(everywhere.checked_count().unwrap(), u64::MAX - fresh.count() + 1);
+    }
+
+    #[test]
+    fn test_ids_at_u64_max() {
+        let max = u64::MAX;
+        let input = format!("{}-{}\n5-{}\n\n{}\n4\n", max - 1, max, max - 2, max);
+        assert_eq!(part1(&input).unwrap(), 1);
+        assert_eq!(part2(&input).unwrap(), max - 4);
+
+        let err = part2(&format!("0-10\n11-{}\n\n1\n", max)).unwrap_err();
+        assert!(matches!(err, AocError::Solve(_)), "{}", err);
+        let err = part1("5-3\n\n4\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1: Range 5-3 ends before it starts");
+    }
+
+    #[test]
+    fn test_ranged_queries() {
+        let input = "3-5\n10-14\n16-20\n12-18\n\n1\n5\n8-11\n0-100\n17-17\n";
+        let (ranges, queries) = parse_input(input).unwrap();
+        assert_eq!(queries[2], IdRange::new(8, 11));
+        let fresh: IntervalSet<u64> = ranges.into_iter().collect();
+        let answers: Vec<u64> = queries.iter().map(|&query| fresh_in(&fresh, query).unwrap()).collect();
+        assert_eq!(answers, vec![0, 1, 2, 14, 1]);
+        assert_eq!(part1(input).unwrap(), 18);
+
+        assert_eq!(part1("3-5\n\n7-6\n").unwrap_err().to_string(), "Line 3: Range 7-6 ends before it starts");
+        let everything = format!("0-{}\n\n0-{}\n", u64::MAX, u64::MAX);
+        assert!(matches!(part1(&everything), Err(AocError::Solve(_))));
+    }
+
+    #[test]
+    fn test_merge_sources_tracks_provenance() {
+        let sources = [("alice".to_string(), "3-5\n10-14\n"), ("bob".to_string(), "12-18\n\n40-41\n6-7\n")];
+        let merged = merge_sources(&sources).unwrap();
+        let listed: Vec<String> = merged
+            .iter()
+            .map(|(range, tags)| format!("{}-{} {:?}", range.start, range.end, tags))
+            .collect();
+        assert_eq!(listed, [r#"3-7 {"alice", "bob#2"}"#, r#"10-18 {"alice", "bob#1"}"#, r#"40-41 {"bob#2"}"#]);
+        assert_eq!(merged.set().count(), 16);
+
+        let err = merge_sources(&[("carol".to_string(), "1-2\n3:4\n")]).unwrap_err();
+        assert_eq!(err.to_string(), "carol: Line 2: Invalid range format: 3:4");
     }
 
     #[test]
     fn test_full_solution_spoiled_and_fresh_counts() {
-        let (ranges, ids) = parse_input("assets/day05ids.txt")
+        let (ranges, queries) = parse_input(Input::for_day(5).read("ids").unwrap())
             .expect("Failed to read input file");
         
-        let optimized_ranges = optimize_ranges(ranges);
+        let optimized_ranges = ranges.into_iter().collect::<IntervalSet<_>>();
         
-        let fresh_count = ids.iter()
-            .filter(|&&id| is_fresh(&optimized_ranges, id))
+        let fresh_count = queries.iter()
+            .filter(|query| optimized_ranges.contains(query.start))
             .count();
-        let spoiled_count = ids.len() - fresh_count;
+        let spoiled_count = queries.len() - fresh_count;
         
-        assert_eq!(spoiled_count, 365, "Should have 365 spoiled IDs");
-        assert_eq!(fresh_count, 635, "Should have 635 fresh IDs");
+        snapshot::check("day05", "spoiled", spoiled_count);
+        snapshot::check("day05", "fresh", fresh_count);
     }
 }
@@ -1,5 +1,11 @@
-use anyhow::{anyhow, Result};
-use std::fs;
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use crate::input::{self, Input};
+use crate::util::expr;
+use crate::util::output::OutputSink;
+use clap::{ArgGroup, Parser, ValueEnum};
+use rayon::prelude::*;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -9,13 +15,13 @@ enum Operator {
 }
 
 impl FromStr for Operator {
-    type Err = anyhow::Error;
+    type Err = AocError;
     
     fn from_str(s: &str) -> Result<Self> {
         match s {
             "*" => Ok(Operator::Multiply),
             "+" => Ok(Operator::Add),
-            _ => Err(anyhow!("Unknown operator: {}", s)),
+            _ => Err(AocError::parse(format!("Unknown operator: {}", s))),
         }
     }
 }
@@ -29,40 +35,130 @@ impl Operator {
     }
 }
 
-fn parse_input(filename: &str) -> Result<(Vec<Vec<i64>>, Vec<Operator>)> {
-    let content = fs::read_to_string(filename)?;
-    let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
-    
-    if lines.is_empty() {
-        return Err(anyhow!("Input file is empty"));
+/// Which cells of the number grid an operator folds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Traversal {
+    /// Down the operator's own column, as in the puzzle
+    Column,
+    /// Along the row with the operator's index: the grid transposed
+    Row,
+    /// Down and to the right from the top of the operator's column,
+    /// wrapping around at the right edge
+    Diagonal,
+    /// Down and to the left, wrapping around at the left edge
+    AntiDiagonal,
+}
+
+impl Traversal {
+    /// (row, column) addresses of the cells that operator `index` folds, in
+    /// a grid of `rows` by `columns`; other reading orders only need
+    /// another arm here
+    fn cells(self, index: usize, rows: usize, columns: usize) -> impl Iterator<Item = (usize, usize)> {
+        let len = if self == Traversal::Row { columns } else { rows };
+        (0..len).map(move |i| match self {
+            Traversal::Column => (i, index),
+            Traversal::Row => (index, i),
+            Traversal::Diagonal => (i, (index + i) % columns),
+            Traversal::AntiDiagonal => (i, (index + columns - i % columns) % columns),
+        })
     }
+}
+
+/// An operator of the last line with the cells it folds: `*` or `+`, then
+/// optionally `|` for its column (the default), `-` for its row, `\\` for
+/// its diagonal or `/` for its anti-diagonal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fold {
+    operator: Operator,
+    traversal: Traversal,
+}
+
+impl FromStr for Fold {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let split = s.char_indices().nth(1).map_or(s.len(), |(i, _)| i);
+        let (operator, traversal) = s.split_at(split);
+        let traversal = match traversal {
+            "" | "|" => Traversal::Column,
+            "-" => Traversal::Row,
+            "\\" => Traversal::Diagonal,
+            "/" => Traversal::AntiDiagonal,
+            _ => return Err(AocError::parse(format!("Unknown operator: {}", s))),
+        };
+        Ok(Fold { operator: operator.parse()?, traversal })
+    }
+}
+
+impl fmt::Display for Fold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self.operator {
+            Operator::Multiply => "*",
+            Operator::Add => "+",
+        };
+        let traversal = match self.traversal {
+            Traversal::Column => "",
+            Traversal::Row => "-",
+            Traversal::Diagonal => "\\",
+            Traversal::AntiDiagonal => "/",
+        };
+        write!(f, "{}{}", operator, traversal)
+    }
+}
+
+fn parse_input(content: &str) -> Result<(Vec<Vec<i64>>, Vec<Fold>)> {
+    parse_input_with(content, |s| s.parse().map_err(|e| AocError::parse(format!("Invalid number '{}': {}", s, e))))
+}
+
+/// Like `parse_input`, but every cell is an infix expression such as
+/// `(2+3)*4`; cells are separated by whitespace, so they can't contain any
+fn parse_input_expr(content: &str) -> Result<(Vec<Vec<i64>>, Vec<Fold>)> {
+    parse_input_with(content, expr::eval)
+}
+
+/// Splits the rows above the operator line into cells and turns each into a
+/// number with `parse_cell`
+fn parse_input_with(content: &str, parse_cell: impl Fn(&str) -> Result<i64>) -> Result<(Vec<Vec<i64>>, Vec<Fold>)> {
+    // Keep 1-based line numbers for error messages
+    let lines: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, line))
+        .collect();
+    
+    let Some(&(operator_line_no, operator_line)) = lines.last() else {
+        return Err(AocError::parse("Input file is empty"));
+    };
     
     // Parse all lines except the last as integers
     let integer_lines = &lines[..lines.len() - 1];
     let grid: Vec<Vec<i64>> = integer_lines
         .iter()
-        .map(|line| {
+        .map(|&(line_no, line)| {
             line.split_whitespace()
-                .map(|s| s.parse())
-                .collect::<Result<Vec<_>, _>>()
+                .map(|s| parse_cell(s).map_err(|e| e.on_line(line_no)))
+                .collect::<Result<Vec<_>>>()
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Result<Vec<_>>>()?;
     
     // Parse the last line as operators
-    let operators: Vec<Operator> = lines[lines.len() - 1]
+    let operators: Vec<Fold> = operator_line
         .split_whitespace()
-        .map(str::parse)
+        .map(|s| s.parse().map_err(|_| AocError::parse_at(operator_line_no, format!("Unknown operator: {}", s))))
         .collect::<Result<Vec<_>>>()?;
     
     Ok((grid, operators))
 }
 
-fn parse_input_col(filename: &str) -> Result<(Vec<Vec<Vec<char>>>, Vec<Operator>)> {
-    let content = fs::read_to_string(filename)?;
+/// Each column holds one row of raw characters per data line
+type CharColumns = Vec<Vec<Vec<char>>>;
+
+fn parse_input_col(content: &str) -> Result<(CharColumns, Vec<Operator>)> {
     let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
     
     if lines.len() < 2 {
-        return Err(anyhow!("Input file must have at least 2 lines"));
+        return Err(AocError::parse("Input file must have at least 2 lines"));
     }
     
     // Separate data lines from operator line
@@ -70,7 +166,7 @@ fn parse_input_col(filename: &str) -> Result<(Vec<Vec<Vec<char>>>, Vec<Operator>
     let operator_line = lines[lines.len() - 1];
     
     if data_lines.is_empty() {
-        return Err(anyhow!("No data lines found"));
+        return Err(AocError::parse("No data lines found"));
     }
     
     // Parse each line to find number positions
@@ -120,7 +216,9 @@ fn parse_input_col(filename: &str) -> Result<(Vec<Vec<Vec<char>>>, Vec<Operator>
         }
     }
     
-    // Extract column data using these boundaries
+    // Extract column data using these boundaries; split each line into chars
+    // once, as wide worksheets have thousands of columns
+    let line_chars: Vec<Vec<char>> = data_lines.iter().map(|line| line.chars().collect()).collect();
     let mut columns = Vec::new();
     
     for col_idx in 0..num_columns {
@@ -128,8 +226,7 @@ fn parse_input_col(filename: &str) -> Result<(Vec<Vec<Vec<char>>>, Vec<Operator>
         let end = column_ends[col_idx];
         let mut column_data = Vec::new();
         
-        for line in data_lines {
-            let line_chars: Vec<char> = line.chars().collect();
+        for line_chars in &line_chars {
             let mut row_chars = Vec::new();
             
             // Extract characters for this column
@@ -156,122 +253,525 @@ fn parse_input_col(filename: &str) -> Result<(Vec<Vec<Vec<char>>>, Vec<Operator>
     Ok((columns, operators))
 }
 
-fn process_column(grid: &[Vec<i64>], col_idx: usize, operator: Operator) -> i64 {
-    grid.iter()
-        .map(|row| row[col_idx])
+/// How a worksheet's numbers are meant to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// Whitespace-separated numbers along each row (part 1)
+    Rows,
+    /// One number per character column, read top to bottom (part 2)
+    Columns,
+}
+
+/// Character ranges `start..end` of the fields the worksheet's blank
+/// character columns split it into, if every line has exactly one token per
+/// field; `None` when the tokens don't line up
+fn aligned_fields(lines: &[&[u8]]) -> Option<Vec<(usize, usize)>> {
+    let width = lines.iter().map(|line| line.len()).max()?;
+    let blank = |pos: usize| lines.iter().all(|line| line.get(pos).is_none_or(|&b| b == b' '));
+
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < width {
+        if blank(pos) {
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        while pos < width && !blank(pos) {
+            pos += 1;
+        }
+        fields.push((start, pos));
+    }
+
+    let one_token_each = lines.iter().all(|line| {
+        let tokens = line.split(|&b| b == b' ').filter(|token| !token.is_empty()).count();
+        tokens == fields.len() && fields.iter().all(|&(start, end)| {
+            let cell = &line[start.min(line.len())..end.min(line.len())];
+            cell.split(|&b| b == b' ').filter(|token| !token.is_empty()).count() == 1
+        })
+    });
+    one_token_each.then_some(fields)
+}
+
+/// Guesses the layout from how the numbers sit in their columns. A sheet
+/// meant to be read down the columns is laid out on a fixed grid with
+/// numbers padded on either side, since their digit positions matter; a
+/// sheet meant to be read along the rows is either ragged or aligned the
+/// same way throughout, like any table of numbers.
+pub fn classify(content: &str) -> Layout {
+    let lines: Vec<&[u8]> = content.lines().filter(|line| !line.trim().is_empty()).map(str::as_bytes).collect();
+    let Some((_, data)) = lines.split_last() else {
+        return Layout::Rows;
+    };
+    let Some(fields) = aligned_fields(&lines) else {
+        return Layout::Rows;
+    };
+
+    let (mut left_flush, mut right_flush) = (false, false);
+    for &(start, end) in &fields {
+        for line in data {
+            let cell = &line[start.min(line.len())..end.min(line.len())];
+            let padded_right = cell.len() < end - start || cell.last() == Some(&b' ');
+            let padded_left = cell.first() == Some(&b' ');
+            left_flush |= padded_right && !padded_left;
+            right_flush |= padded_left && !padded_right;
+        }
+    }
+    if left_flush && right_flush { Layout::Columns } else { Layout::Rows }
+}
+
+/// Grand total with the numbers read as `layout` says
+pub fn solve_as(input: &str, layout: Layout) -> Result<i64> {
+    match layout {
+        Layout::Rows => part1(input),
+        Layout::Columns => part2(input),
+    }
+}
+
+/// Character range `start..end` of one problem in the column model, and the
+/// share of data rows that put exactly one cell inside it without spilling
+/// over its edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnBoundary {
+    pub start: usize,
+    pub end: usize,
+    pub confidence: f64,
+}
+
+/// Runs of non-space characters in `line` as `start..end` character ranges
+fn cell_spans(line: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while pos < line.len() {
+        if line[pos] == b' ' {
+            pos += 1;
+            continue;
+        }
+        let start = pos;
+        while pos < line.len() && line[pos] != b' ' {
+            pos += 1;
+        }
+        spans.push((start, pos));
+    }
+    spans
+}
+
+/// Where each problem's column lies. Every problem starts at its operator
+/// and runs up to the blank character column before the next operator, or
+/// to the end of the widest line for the last one.
+pub fn column_boundaries(content: &str) -> Vec<ColumnBoundary> {
+    let lines: Vec<&[u8]> = content.lines().filter(|line| !line.trim().is_empty()).map(str::as_bytes).collect();
+    let Some((operator_line, data)) = lines.split_last() else {
+        return Vec::new();
+    };
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let starts: Vec<usize> = cell_spans(operator_line).into_iter().map(|(start, _)| start).collect();
+    let rows: Vec<Vec<(usize, usize)>> = data.iter().map(|line| cell_spans(line)).collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).map_or(width, |&next| next.saturating_sub(1).max(start + 1));
+            let fitting = rows
+                .iter()
+                .filter(|cells| {
+                    let inside = cells.iter().filter(|&&(s, e)| s >= start && e <= end).count();
+                    let spilling = cells.iter().any(|&(s, e)| s < end && e > start && (s < start || e > end));
+                    inside == 1 && !spilling
+                })
+                .count();
+            let confidence = if rows.is_empty() { 0.0 } else { fitting as f64 / rows.len() as f64 };
+            ColumnBoundary { start, end, confidence }
+        })
+        .collect()
+}
+
+/// Something `lint` found wrong with a worksheet; lines are 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The operator line doesn't have one operator per column of numbers
+    OperatorCount { operators: usize, columns: usize },
+    UnknownOperator { column: usize, operator: String },
+    /// A row with more or fewer cells than the worksheet has columns
+    CellCount { line: usize, cells: usize, expected: usize },
+    /// A row whose cells would all sit in their columns if it were moved
+    /// `by` characters to the left (negative) or right
+    Shifted { line: usize, by: isize },
+    /// A row with cells (1-based) that cross a column boundary
+    Misaligned { line: usize, cells: Vec<usize> },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::OperatorCount { operators, columns } => {
+                write!(f, "{} operators for {} columns of numbers", operators, columns)
+            }
+            Finding::UnknownOperator { column, operator } => write!(f, "Column {} has unknown operator '{}'", column, operator),
+            Finding::CellCount { line, cells, expected } => {
+                let kind = if cells < expected { "missing" } else { "extra" };
+                write!(f, "Line {} has {} cells, expected {} ({} {})", line, cells, expected, expected.abs_diff(*cells), kind)
+            }
+            Finding::Shifted { line, by } => {
+                // `by` is the fix, so the row sits the other way
+                let direction = if *by < 0 { "right" } else { "left" };
+                let plural = if by.unsigned_abs() == 1 { "" } else { "s" };
+                write!(f, "Line {} looks shifted {} by {} column{}", line, direction, by.unsigned_abs(), plural)
+            }
+            Finding::Misaligned { line, cells } => {
+                let cells: Vec<String> = cells.iter().map(usize::to_string).collect();
+                write!(f, "Line {} has cells crossing a column boundary: {}", line, cells.join(", "))
+            }
+        }
+    }
+}
+
+/// Furthest `lint` looks when guessing how far a row was shifted
+const MAX_SHIFT: usize = 4;
+
+/// Checks a worksheet against its column model: one operator per column,
+/// the same number of cells on every row, and every cell inside its column.
+/// Misaligned rows that would fit after moving them a little get a
+/// `Shifted` suggestion instead.
+pub fn lint(content: &str) -> (Vec<ColumnBoundary>, Vec<Finding>) {
+    let lines: Vec<(usize, &[u8])> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, line.as_bytes()))
+        .collect();
+    let boundaries = column_boundaries(content);
+    let mut findings = Vec::new();
+    let Some(((_, operator_line), data)) = lines.split_last() else {
+        return (boundaries, findings);
+    };
+
+    for (column, (start, end)) in cell_spans(operator_line).into_iter().enumerate() {
+        let operator = String::from_utf8_lossy(&operator_line[start..end]).into_owned();
+        if operator.parse::<Fold>().is_err() {
+            findings.push(Finding::UnknownOperator { column: column + 1, operator });
+        }
+    }
+
+    // The data's own idea of the column count: what most rows have
+    let rows: Vec<(usize, Vec<(usize, usize)>)> = data.iter().map(|&(line_no, line)| (line_no, cell_spans(line))).collect();
+    let mut counts: Vec<usize> = rows.iter().map(|(_, cells)| cells.len()).collect();
+    counts.sort_unstable();
+    let columns = counts
+        .chunk_by(|a, b| a == b)
+        .max_by_key(|run| run.len())
+        .map_or(0, |run| run[0]);
+    if boundaries.len() != columns {
+        findings.push(Finding::OperatorCount { operators: boundaries.len(), columns });
+    }
+
+    let fits = |cells: &[(usize, usize)], by: isize| {
+        cells.iter().zip(&boundaries).all(|(&(start, end), field)| {
+            start.checked_add_signed(by).is_some_and(|s| s >= field.start) && end.saturating_add_signed(by) <= field.end
+        })
+    };
+    for (line, cells) in &rows {
+        if cells.len() != columns {
+            findings.push(Finding::CellCount { line: *line, cells: cells.len(), expected: columns });
+            continue;
+        }
+        if cells.len() != boundaries.len() || fits(cells, 0) {
+            continue;
+        }
+        // Smallest move first, left before right
+        let shift = (1..=MAX_SHIFT as isize).flat_map(|d| [-d, d]).find(|&by| fits(cells, by));
+        match shift {
+            Some(by) => findings.push(Finding::Shifted { line: *line, by }),
+            None => {
+                let crossing = cells
+                    .iter()
+                    .zip(&boundaries)
+                    .enumerate()
+                    .filter(|(_, (&(start, end), field))| start < field.start || end > field.end)
+                    .map(|(i, _)| i + 1)
+                    .collect();
+                findings.push(Finding::Misaligned { line: *line, cells: crossing });
+            }
+        }
+    }
+    (boundaries, findings)
+}
+
+/// Columns each rayon task evaluates at least; narrower worksheets stay on
+/// one thread, where spawning would cost more than the work
+const PARALLEL_COLUMNS: usize = 1024;
+
+/// Folds the numbers at `cells`, (row, column) addresses into `grid`, with `operator`
+fn fold_cells(grid: &[Vec<i64>], cells: impl Iterator<Item = (usize, usize)>, operator: Operator) -> i64 {
+    cells
+        .map(|(row, col)| grid[row][col])
         .reduce(|acc, val| operator.apply(acc, val))
         .unwrap_or(0)
 }
 
-fn do_homework(grid: &[Vec<i64>], operators: &[Operator]) -> Result<Vec<i64>> {
+fn do_homework(grid: &[Vec<i64>], operators: &[Fold]) -> Result<Vec<i64>> {
     if grid.is_empty() {
-        return Err(anyhow!("Grid is empty"));
+        return Err(AocError::solve("Grid is empty"));
     }
     
     let num_columns = grid[0].len();
     if operators.len() != num_columns {
-        return Err(anyhow!(
+        return Err(AocError::solve(format!(
             "Number of operators ({}) doesn't match number of columns ({})",
             operators.len(),
             num_columns
-        ));
+        )));
+    }
+    // A row fold uses its operator's index as the row
+    if let Some(last) = operators.iter().rposition(|fold| fold.traversal == Traversal::Row) {
+        if last >= grid.len() {
+            return Err(AocError::solve(format!(
+                "Operator {} folds row {}, but there are only {} rows",
+                last + 1,
+                last + 1,
+                grid.len()
+            )));
+        }
     }
     
-    let results =
-        operators
-            .iter()
-            .enumerate()
-            .map(|(col_idx, &operator)| process_column(grid, col_idx, operator))
-            .collect()
-    ;
+    // Indexed parallel iterators collect in column order
+    let rows = grid.len();
+    let results = operators
+        .par_iter()
+        .with_min_len(PARALLEL_COLUMNS)
+        .enumerate()
+        .map(|(index, fold)| fold_cells(grid, fold.traversal.cells(index, rows, num_columns), fold.operator))
+        .collect();
     
     Ok(results)
 }
 
 fn do_homework_col(columns: &[Vec<Vec<char>>], operators: &[Operator]) -> Result<Vec<i64>> {
     if columns.is_empty() {
-        return Err(anyhow!("No columns provided"));
+        return Err(AocError::solve("No columns provided"));
     }
     
     if operators.len() != columns.len() {
-        return Err(anyhow!(
+        return Err(AocError::solve(format!(
             "Number of operators ({}) doesn't match number of columns ({})",
             operators.len(),
             columns.len()
-        ));
+        )));
     }
     
-    let mut results = Vec::new();
+    columns
+        .par_iter()
+        .with_min_len(PARALLEL_COLUMNS)
+        .zip(operators)
+        .enumerate()
+        .map(|(col_idx, (column, &operator))| process_char_column(column, col_idx, operator))
+        .collect()
+}
+
+/// Reads the numbers down `column`, one per character position, and folds them with `operator`
+fn process_char_column(column: &[Vec<char>], col_idx: usize, operator: Operator) -> Result<i64> {
+    if column.is_empty() {
+        return Err(AocError::solve(format!("Column {} is empty", col_idx)));
+    }
     
-    for (col_idx, column) in columns.iter().enumerate() {
-        let operator = operators[col_idx];
-        
-        if column.is_empty() {
-            return Err(anyhow!("Column {} is empty", col_idx));
-        }
-        
-        // Determine the width of this column (length of character arrays)
-        let width = column[0].len();
-        
-        // For each character position, read down all rows to form a number
-        let mut numbers = Vec::new();
+    // Determine the width of this column (length of character arrays)
+    let width = column[0].len();
+    
+    // For each character position, read down all rows to form a number
+    let mut numbers = Vec::new();
+    
+    for char_pos in 0..width {
+        let mut digit_string = String::new();
         
-        for char_pos in 0..width {
-            let mut digit_string = String::new();
-            
-            // Read down all rows at this character position
-            for row in column {
-                if char_pos < row.len() {
-                    let ch = row[char_pos];
-                    if ch.is_ascii_digit() {
-                        digit_string.push(ch);
-                    }
-                    // Skip non-digit characters (like spaces)
+        // Read down all rows at this character position
+        for row in column {
+            if char_pos < row.len() {
+                let ch = row[char_pos];
+                if ch.is_ascii_digit() {
+                    digit_string.push(ch);
                 }
-            }
-            
-            // Convert to number (if we found any digits)
-            if !digit_string.is_empty() {
-                let number: i64 = digit_string.parse()
-                    .map_err(|e| anyhow!("Failed to parse '{}': {}", digit_string, e))?;
-                numbers.push(number);
+                // Skip non-digit characters (like spaces)
             }
         }
         
-        // Apply the operator across all numbers in this column
-        let result = numbers
-            .iter()
-            .copied()
-            .reduce(|acc, val| operator.apply(acc, val))
-            .ok_or_else(|| anyhow!("No valid numbers found in column {}", col_idx))?;
-        
-        results.push(result);
+        // Convert to number (if we found any digits)
+        if !digit_string.is_empty() {
+            let number: i64 = digit_string.parse()
+                .map_err(|e| AocError::parse(format!("Failed to parse '{}': {}", digit_string, e)))?;
+            numbers.push(number);
+        }
     }
     
-    Ok(results)
+    // Apply the operator across all numbers in this column
+    numbers
+        .iter()
+        .copied()
+        .reduce(|acc, val| operator.apply(acc, val))
+        .ok_or_else(|| AocError::solve(format!("No valid numbers found in column {}", col_idx)))
+}
+
+/// A worksheet of `columns` problems over `rows` rows of numbers, for
+/// benchmarking; the same `seed` always gives the same worksheet
+pub fn wide_worksheet(columns: usize, rows: usize, seed: u64) -> String {
+    let mut state = seed;
+    let mut next = |bound: u64| {
+        // Knuth's MMIX LCG; the high bits are the well-mixed ones
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) % bound
+    };
+    let mut sheet = String::new();
+    for _ in 0..rows {
+        let cells: Vec<String> = (0..columns)
+            .map(|_| {
+                let value = 1 + next(999);
+                if next(2) == 0 { format!("{:<3}", value) } else { format!("{:>3}", value) }
+            })
+            .collect();
+        sheet.push_str(&cells.join(" "));
+        sheet.push('\n');
+    }
+    let operators: Vec<&str> = (0..columns).map(|_| if next(2) == 0 { "*  " } else { "+  " }).collect();
+    sheet.push_str(operators.join(" ").trim_end());
+    sheet.push('\n');
+    sheet
+}
+
+/// Grand total with numbers read along the rows
+pub fn part1(input: &str) -> Result<i64> {
+    let (grid, operators) = parse_input(input)?;
+    Ok(do_homework(&grid, &operators)?.iter().sum())
+}
+
+/// Grand total with numbers read down the columns
+pub fn part2(input: &str) -> Result<i64> {
+    let (columns, operators) = parse_input_col(input)?;
+    Ok(do_homework_col(&columns, &operators)?.iter().sum())
+}
+
+/// Grand total with each cell evaluated as an expression before the
+/// column fold
+pub fn part3(input: &str) -> Result<i64> {
+    let (grid, operators) = parse_input_expr(input)?;
+    Ok(do_homework(&grid, &operators)?.iter().sum())
 }
 
-pub fn run() -> Result<()> {
-    let (grid, operators) = parse_input("assets/day06problems.txt")?;
+/// How `--mode` picks the reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Decide from how the numbers are aligned
+    Auto,
+    Rows,
+    Columns,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day06", about = "Day 6: Trash Compactor")]
+#[command(group(ArgGroup::new("sheet").args(["mode", "lint"])))]
+struct Options {
+    /// Read the worksheet one way only, or let its alignment decide
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+    /// Check the worksheet's layout and suggest fixes instead of solving it
+    #[arg(long)]
+    lint: bool,
+    /// Worksheet for --mode or --lint [default: day06problems.txt in the input directory]
+    #[arg(long, requires = "sheet")]
+    input: Option<String>,
+}
+
+fn read_sheet(options: &Options) -> Result<&'static str> {
+    match &options.input {
+        Some(path) => input::read_file(path),
+        None => Input::for_day(6).read("problems"),
+    }
+}
+
+/// Columns the model is less sure of than this get listed by `--lint`
+const LOW_CONFIDENCE: f64 = 0.75;
+
+fn run_lint(options: &Options, out: &dyn OutputSink) -> Result<()> {
+    let (boundaries, findings) = lint(read_sheet(options)?);
+    let confident = boundaries.iter().filter(|b| b.confidence >= LOW_CONFIDENCE).count();
+    out.line(&format!("{} columns, {} with confidence of at least {:.0}%", boundaries.len(), confident, LOW_CONFIDENCE * 100.0));
+    for (i, boundary) in boundaries.iter().enumerate().filter(|(_, b)| b.confidence < LOW_CONFIDENCE) {
+        out.line(&format!("  Column {} (characters {}-{}): {:.0}%", i + 1, boundary.start + 1, boundary.end, boundary.confidence * 100.0));
+    }
+    if findings.is_empty() {
+        out.line("No problems found");
+        return Ok(());
+    }
+    for finding in &findings {
+        out.line(&finding.to_string());
+    }
+    let err = AocError::parse(format!("{} problem(s) found", findings.len()));
+    Err(match &options.input {
+        Some(path) => err.in_file(path),
+        None => err,
+    })
+}
+
+fn run_mode(options: &Options, mode: Mode, out: &dyn OutputSink) -> Result<()> {
+    let sheet = read_sheet(options)?;
+    let layout = match mode {
+        Mode::Auto => {
+            let layout = classify(sheet);
+            out.line(&format!("Detected layout: {:?}", layout));
+            layout
+        }
+        Mode::Rows => Layout::Rows,
+        Mode::Columns => Layout::Columns,
+    };
+    let sum = solve_as(sheet, layout).map_err(|e| match &options.input {
+        Some(path) => e.in_file(path),
+        None => e,
+    })?;
+    out.line(&format!("Sum ({:?}): {}", layout, sum));
+    Ok(())
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Trash Compactor",
+        input_files: &["problems"],
+        parts: [
+            "Grand total with numbers read along the rows",
+            "Grand total with numbers read down the columns",
+        ],
+        notes: "--mode auto guesses how a worksheet should be read; --lint checks its alignment; operators may end in - (row), \\ (diagonal) or / (anti-diagonal)",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day06".to_string()).chain(args.iter().cloned()));
+    if let Some(mode) = options.mode {
+        return run_mode(&options, mode, out);
+    }
+    if options.lint {
+        return run_lint(&options, out);
+    }
+
+    let (grid, operators) = Input::for_day(6).parse("problems", parse_input)?;
     
-    println!("Day 6: Parsed {} lines of integers", grid.len());
+    out.line(&format!("Day 6: Parsed {} lines of integers", grid.len()));
     for (i, row) in grid.iter().enumerate() {
-        println!("Line {}: {:?}", i, row);
+        out.line(&format!("Line {}: {:?}", i, row));
     }
     
-    println!("Operators: {:?}", operators);
+    let operators_line: Vec<String> = operators.iter().map(Fold::to_string).collect();
+    out.line(&format!("Operators: {}", operators_line.join(" ")));
     
     // Part 1: Standard mode
     let column_results = do_homework(&grid, &operators)?;
     let sum: i64 = column_results.iter().sum();
-    println!("\nPart 1 (Standard mode):");
-    println!("Column results: {:?}", column_results);
-    println!("Sum: {}", sum);
+    out.line("\nPart 1 (Standard mode):");
+    out.line(&format!("Column results: {:?}", column_results));
+    out.answer(1, "Sum", &sum);
     
     // Part 2: Column-based mode
-    let (columns, col_operators) = parse_input_col("assets/day06problems.txt")?;
-    println!("\n--- Part 2 (Column-based mode) ---");
-    println!("Parsed {} columns", columns.len());
+    let (columns, col_operators) = Input::for_day(6).parse("problems", parse_input_col)?;
+    out.line("\n--- Part 2 (Column-based mode) ---");
+    out.line(&format!("Parsed {} columns", columns.len()));
     
     // Show all columns
     // for (i, column) in columns.iter().enumerate() {
@@ -283,8 +783,13 @@ pub fn run() -> Result<()> {
     
     let col_results = do_homework_col(&columns, &col_operators)?;
     let col_sum: i64 = col_results.iter().sum();
-    println!("\nColumn results: {:?}", col_results);
-    println!("Sum: {}", col_sum);
+    out.line(&format!("\nColumn results: {:?}", col_results));
+    out.answer(2, "Sum", &col_sum);
+    
+    // Part 3: cells may hold expressions like (2+3)*4
+    let expr_sum = part3(Input::for_day(6).read("problems")?)?;
+    out.line("\n--- Part 3 (Expression cells) ---");
+    out.line(&format!("Sum: {}", expr_sum));
     
     Ok(())
 }
@@ -292,28 +797,146 @@ pub fn run() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_full_solution_part_one_sum() {
-        let (grid, operators) = parse_input("assets/day06problems.txt")
+        let (grid, operators) = parse_input(Input::for_day(6).read("problems").unwrap())
             .expect("Failed to read input file");
         
         let column_results = do_homework(&grid, &operators)
             .expect("Failed to process homework");
         let sum: i64 = column_results.iter().sum();
         
-        assert_eq!(sum, 4878670269096, "Part 1 final sum should be 4878670269096");
+        snapshot::check("day06", "part1_sum", sum);
     }
 
     #[test]
     fn test_full_solution_part_two_sum() {
-        let (columns, col_operators) = parse_input_col("assets/day06problems.txt")
+        let (columns, col_operators) = parse_input_col(Input::for_day(6).read("problems").unwrap())
             .expect("Failed to read input file");
         
         let col_results = do_homework_col(&columns, &col_operators)
             .expect("Failed to process column-based homework");
         let col_sum: i64 = col_results.iter().sum();
         
-        assert_eq!(col_sum, 8674740488592, "Part 2 final sum should be 8674740488592");
+        snapshot::check("day06", "part2_sum", col_sum);
+    }
+
+    #[test]
+    fn test_wide_worksheet_keeps_column_order() {
+        let sheet = wide_worksheet(3 * PARALLEL_COLUMNS, 4, 7);
+        assert_eq!(sheet, wide_worksheet(3 * PARALLEL_COLUMNS, 4, 7));
+
+        let (grid, operators) = parse_input(&sheet).unwrap();
+        assert_eq!(operators.len(), 3 * PARALLEL_COLUMNS);
+        let expected: Vec<i64> = (0..operators.len())
+            .map(|i| fold_cells(&grid, (0..grid.len()).map(|row| (row, i)), operators[i].operator))
+            .collect();
+        assert_eq!(do_homework(&grid, &operators).unwrap(), expected);
+
+        let (columns, operators) = parse_input_col(&sheet).unwrap();
+        let expected: Vec<i64> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| process_char_column(column, i, operators[i]).unwrap())
+            .collect();
+        assert_eq!(do_homework_col(&columns, &operators).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_classify_layout() {
+        // The puzzle's worksheet is meant to be read down the columns
+        let sheet = Input::for_day(6).read("problems").unwrap();
+        assert_eq!(classify(sheet), Layout::Columns);
+        assert_eq!(solve_as(sheet, Layout::Columns).unwrap(), part2(sheet).unwrap());
+        assert_eq!(classify(&wide_worksheet(20, 4, 3)), Layout::Columns);
+
+        // Ragged rows, and tables aligned the same way throughout
+        assert_eq!(classify("1 22 333\n4444 5 6\n* + *\n"), Layout::Rows);
+        assert_eq!(classify("  1  22\n333 4444\n*   +\n"), Layout::Rows);
+        assert_eq!(classify("1   22\n333 4444\n*   +\n"), Layout::Rows);
+        assert_eq!(classify("123 328  51 64\n 45 64  387 23\n  6 98  215 314\n*   +   *   +\n"), Layout::Columns);
+        assert_eq!(classify(""), Layout::Rows);
+    }
+
+    #[test]
+    fn test_lint() {
+        let sheet = Input::for_day(6).read("problems").unwrap();
+        let (boundaries, findings) = lint(sheet);
+        assert!(findings.is_empty(), "{:?}", findings);
+        assert!(boundaries.iter().all(|b| b.confidence == 1.0));
+
+        let sheet = "\
+123 328  51 64
+ 45 64  387 23
+  6 98  215 314
+*   +   *   +
+";
+        assert_eq!(lint(sheet).1, []);
+        let boundaries = column_boundaries(sheet);
+        assert_eq!(boundaries.iter().map(|b| (b.start, b.end)).collect::<Vec<_>>(), [(0, 3), (4, 7), (8, 11), (12, 15)]);
+
+        // Second row moved right by two, third missing a cell, bad operator
+        let sheet = "\
+123 328  51 64
+   45 64  387 23
+  6 98  215
+*   +   /   +
+";
+        let (boundaries, findings) = lint(sheet);
+        assert_eq!(
+            findings,
+            [
+                Finding::UnknownOperator { column: 3, operator: "/".to_string() },
+                Finding::Shifted { line: 2, by: -2 },
+                Finding::CellCount { line: 3, cells: 3, expected: 4 },
+            ]
+        );
+        assert_eq!(findings[1].to_string(), "Line 2 looks shifted right by 2 columns");
+        assert_eq!(findings[2].to_string(), "Line 3 has 3 cells, expected 4 (1 missing)");
+        assert!(boundaries[0].confidence < 1.0 && boundaries[3].confidence < 1.0);
+
+        let (_, findings) = lint("1 2 3\n4 5 6\n+ *\n");
+        assert_eq!(findings, [Finding::OperatorCount { operators: 2, columns: 3 }]);
+        assert_eq!(findings[0].to_string(), "2 operators for 3 columns of numbers");
+    }
+
+    #[test]
+    fn test_expression_cells() {
+        let input = "(1+2)*3  4\n2        1+1*5\n*        +\n";
+        // Column 1: 9 * 2, column 2: 4 + 6
+        assert_eq!(part3(input).unwrap(), 28);
+
+        // Plain numbers are expressions too
+        let plain = Input::for_day(6).read("problems").unwrap();
+        assert_eq!(part3(plain).unwrap(), part1(plain).unwrap());
+
+        let err = part3("1+2 (3\n+ +\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1: Expected ')' at position 3 of '(3'");
+        let err = parse_input("1 x\n+ +\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 1: Invalid number 'x': invalid digit found in string");
+    }
+
+    #[test]
+    fn test_traversals() {
+        let sheet = "1 2 3\n4 5 6\n7 8 9\n+ *- +\\\n";
+        let (grid, operators) = parse_input(sheet).unwrap();
+        assert_eq!(operators[1], Fold { operator: Operator::Multiply, traversal: Traversal::Row });
+        // Column 0 down: 1 + 4 + 7; row 1 across: 4 * 5 * 6; diagonal from
+        // column 2 wrapping: 3 + 4 + 8
+        assert_eq!(do_homework(&grid, &operators).unwrap(), vec![12, 120, 15]);
+
+        let (grid, operators) = parse_input("1 2 3\n4 5 6\n7 8 9\n*/ +| *\\\n").unwrap();
+        // Anti-diagonal from column 0 wrapping: 1 * 6 * 8
+        assert_eq!(do_homework(&grid, &operators).unwrap(), vec![48, 15, 3 * 4 * 8]);
+        let header: Vec<String> = operators.iter().map(Fold::to_string).collect();
+        assert_eq!(header, ["*/", "+", "*\\"]);
+
+        let err = part1("1 2 3\n4 5 6\n+ + *-\n").unwrap_err();
+        assert_eq!(err.to_string(), "Operator 3 folds row 3, but there are only 2 rows");
+        assert_eq!(parse_input("1\n+x\n").unwrap_err().to_string(), "Line 2: Unknown operator: +x");
+        // Reading down the character columns has no other traversals
+        assert!(part2("1 2\n3 4\n+ *-\n").is_err());
     }
 }
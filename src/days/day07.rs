@@ -1,22 +1,41 @@
-use anyhow::{anyhow, Result};
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::{Parser, ValueEnum};
 use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use crate::input::{self, Input};
+use crate::util::grid::Grid;
+use crate::util::output::OutputSink;
+use crate::util::stats;
+use crate::util::theme::{self, Style, Theme};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Cell {
     Empty,
     Start,
     Splitter,
+    /// Stochastic splitter: sends each timeline left with probability
+    /// n/10 and right otherwise, written as the digit n
+    Chance(u8),
+    /// Mover: a beam landing on it is pushed one cell left (`<`) or right
+    /// (`>`) along the row before it carries on down
+    Left,
+    Right,
     Beam,
 }
 
 impl Cell {
-    fn from_char(c: char) -> Result<Self> {
+    fn from_char(c: char) -> Option<Self> {
         match c {
-            'S' => Ok(Cell::Start),
-            '.' => Ok(Cell::Empty),
-            '^' => Ok(Cell::Splitter),
-            '|' => Ok(Cell::Beam),
-            _ => Err(anyhow!("Invalid cell character: {}", c)),
+            'S' => Some(Cell::Start),
+            '.' => Some(Cell::Empty),
+            '^' => Some(Cell::Splitter),
+            '|' => Some(Cell::Beam),
+            '0'..='9' => Some(Cell::Chance(c as u8 - b'0')),
+            '<' => Some(Cell::Left),
+            '>' => Some(Cell::Right),
+            _ => None,
         }
     }
 
@@ -25,6 +44,9 @@ impl Cell {
             Cell::Empty => '.',
             Cell::Start => 'S',
             Cell::Splitter => '^',
+            Cell::Chance(tenths) => (b'0' + tenths) as char,
+            Cell::Left => '<',
+            Cell::Right => '>',
             Cell::Beam => '|',
         }
     }
@@ -42,29 +64,97 @@ impl std::fmt::Debug for Cell {
     }
 }
 
-fn parse_input(file_path: &str) -> Result<Vec<Vec<Cell>>> {
-    let contents = std::fs::read_to_string(file_path)?;
-    contents
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            line.chars()
-                .map(Cell::from_char)
-                .collect()
+/// The grid with the beams `count_timelines_dp` traced drawn in
+fn paint_beams(grid: &Grid<Cell>, theme: Theme) -> String {
+    theme.paint_grid(grid, |&cell| {
+        let style = match cell {
+            Cell::Beam => Style::Highlight,
+            Cell::Splitter | Cell::Chance(_) => Style::Warning,
+            Cell::Left | Cell::Right => Style::Category(0),
+            Cell::Start => Style::Heading,
+            Cell::Empty => Style::Muted,
+        };
+        (cell.to_char(), style)
+    })
+}
+
+fn parse_input(contents: &str) -> Result<Grid<Cell>> {
+    Grid::parse(contents, Cell::from_char)
+}
+
+/// Where a beam landing on each cell of `row` leaves it downwards, after
+/// movers have pushed it along the row; `None` if it is pushed off the edge
+/// or round a loop like `><` forever. Found as the fixed point of "a
+/// mover's exit is its neighbour's": sweeps alternate direction until
+/// nothing changes, and movers still unresolved then are the loops.
+fn row_exits(row: &[Cell]) -> Vec<Option<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Exit {
+        Pending,
+        Down(usize),
+        Lost,
+    }
+
+    let mut exits: Vec<Exit> = row
+        .iter()
+        .enumerate()
+        .map(|(col, cell)| match cell {
+            Cell::Left | Cell::Right => Exit::Pending,
+            _ => Exit::Down(col),
+        })
+        .collect();
+    if !exits.contains(&Exit::Pending) {
+        return (0..row.len()).map(Some).collect();
+    }
+
+    let step = |exits: &mut Vec<Exit>, col: usize| -> bool {
+        if exits[col] != Exit::Pending {
+            return false;
+        }
+        let next = match row[col] {
+            Cell::Left => col.checked_sub(1),
+            _ => Some(col + 1).filter(|&c| c < row.len()),
+        };
+        exits[col] = match next {
+            Some(next) => exits[next],
+            None => Exit::Lost,
+        };
+        exits[col] != Exit::Pending
+    };
+    loop {
+        let mut changed = false;
+        for col in 0..row.len() {
+            changed |= step(&mut exits, col);
+        }
+        for col in (0..row.len()).rev() {
+            changed |= step(&mut exits, col);
+        }
+        if !changed {
+            break;
+        }
+    }
+    stats::add("day07.trapped_movers", exits.iter().filter(|&&exit| exit == Exit::Pending).count() as u64);
+
+    exits
+        .into_iter()
+        .map(|exit| match exit {
+            Exit::Down(col) => Some(col),
+            Exit::Pending | Exit::Lost => None,
         })
         .collect()
 }
 
-// Fast DP solution: track beams with their multiplicity (how many timelines they represent)
-fn count_timelines_dp(grid: &mut [Vec<Cell>]) -> Result<(usize, u64)> {
-    if grid.is_empty() {
+// Fast DP solution: track beams with their multiplicity (how many timelines they represent).
+// Stochastic splitters count as ordinary ones here, so every possible timeline is counted.
+fn count_timelines_dp(grid: &mut Grid<Cell>) -> Result<(usize, u64)> {
+    if grid.height() == 0 {
         return Ok((0, 0));
     }
 
     let mut split_count = 0;
 
     // Find the Start (S) in the first line
-    let first_line = &grid[0];
+    let first_line = grid.row(0);
     let start_idx = match first_line.iter().position(|&cell| cell == Cell::Start) {
         Some(idx) => idx,
         None => return Ok((0, 0)),
@@ -75,15 +165,16 @@ fn count_timelines_dp(grid: &mut [Vec<Cell>]) -> Result<(usize, u64)> {
     let mut active_beams: Vec<(usize, usize, u64)> = vec![];
     
     // Initialize with the first beam position (represents 1 timeline)
-    if grid.len() > 1 {
-        grid[1][start_idx] = Cell::Beam;
+    if grid.height() > 1 {
+        grid[(start_idx, 1)] = Cell::Beam;
         active_beams.push((1, start_idx, 1));
     }
 
     // Process each line from the second line onwards
-    for line_idx in 1..grid.len() - 1 {
+    for line_idx in 1..grid.height() - 1 {
         let next_line_idx = line_idx + 1;
-        let next_line = &mut grid[next_line_idx];
+        let exits = row_exits(grid.row(next_line_idx));
+        let next_line = grid.row_mut(next_line_idx);
 
         // Use a HashMap to merge beams at the same position
         let mut beam_map: HashMap<usize, u64> = HashMap::new();
@@ -95,27 +186,25 @@ fn count_timelines_dp(grid: &mut [Vec<Cell>]) -> Result<(usize, u64)> {
         for (beam_row, beam_col, multiplicity) in &active_beams {
             if *beam_row == line_idx {
                 // Check if the next line at this position is a splitter
-                if next_line[*beam_col] == Cell::Splitter {
+                if matches!(next_line[*beam_col], Cell::Splitter | Cell::Chance(_)) {
                     // Count this split only once per position
                     if split_positions.insert(*beam_col) {
                         split_count += 1;
                     }
                     
-                    // Place beams at both +1 and -1 positions
+                    // Place beams at both +1 and -1 positions, wherever movers take them
                     // Each new beam inherits the same multiplicity (same number of timelines)
-                    if *beam_col > 0 {
-                        next_line[*beam_col - 1] = Cell::Beam;
-                        *beam_map.entry(*beam_col - 1).or_insert(0) += *multiplicity;
+                    let left = beam_col.checked_sub(1).and_then(|col| exits[col]);
+                    let right = exits.get(*beam_col + 1).copied().flatten();
+                    for col in [left, right].into_iter().flatten() {
+                        next_line[col] = Cell::Beam;
+                        *beam_map.entry(col).or_insert(0) += *multiplicity;
                     }
-                    if *beam_col < next_line.len() - 1 {
-                        next_line[*beam_col + 1] = Cell::Beam;
-                        *beam_map.entry(*beam_col + 1).or_insert(0) += *multiplicity;
-                    }
-                } else {
-                    // Place beam at the same index in the next line
-                    next_line[*beam_col] = Cell::Beam;
+                } else if let Some(col) = exits[*beam_col] {
+                    // Place beam at the same index in the next line, or where movers take it
+                    next_line[col] = Cell::Beam;
                     // Beam continues with same multiplicity, merge if multiple beams reach same position
-                    *beam_map.entry(*beam_col).or_insert(0) += *multiplicity;
+                    *beam_map.entry(col).or_insert(0) += *multiplicity;
                 }
             }
         }
@@ -132,26 +221,589 @@ fn count_timelines_dp(grid: &mut [Vec<Cell>]) -> Result<(usize, u64)> {
     Ok((split_count, total_timelines))
 }
 
-pub fn run() -> Result<()> {
+/// `count_timelines_dp` reading the grid from `reader` one row at a time.
+/// Only the current row and the beams entering it are kept, so the grid can
+/// be far larger than memory; nothing is drawn, so use the grid-based DP to
+/// see the beams. `source` names the reader in I/O errors.
+pub fn count_timelines_streaming<R: BufRead>(mut reader: R, source: &str) -> Result<(usize, u64)> {
+    let mut line = String::new();
+    let mut row: Vec<Cell> = Vec::new();
+    let mut width = None;
+    let mut line_no = 0;
+    let mut rows = 0;
+    let mut split_count = 0;
+    // Column -> how many timelines the beam there represents
+    let mut beams: HashMap<usize, u64> = HashMap::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| AocError::io(source, e))? == 0 {
+            break;
+        }
+        line_no += 1;
+        let text = line.strip_suffix('\n').unwrap_or(&line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        // Same checks as `Grid::parse`
+        row.clear();
+        for (col, c) in text.chars().enumerate() {
+            let cell = Cell::from_char(c)
+                .ok_or_else(|| AocError::parse_at_column(line_no, col + 1, format!("Invalid cell character: {}", c)))?;
+            row.push(cell);
+        }
+        if *width.get_or_insert(row.len()) != row.len() {
+            return Err(AocError::parse_at(line_no, format!(
+                "Row has {} cells, expected {}", row.len(), width.unwrap_or_default()
+            )));
+        }
+
+        match rows {
+            0 => match row.iter().position(|&cell| cell == Cell::Start) {
+                Some(start) => {
+                    beams.insert(start, 1);
+                }
+                None => return Ok((0, 0)),
+            },
+            // The beam enters the second row straight below the start
+            1 => {}
+            _ => {
+                let (split, next) = advance_beams(&beams, &row, |_| false);
+                split_count += split.len();
+                beams = next;
+            }
+        }
+        rows += 1;
+    }
+
+    if rows < 2 {
+        return Ok((0, 0));
+    }
+    Ok((split_count, beams.values().sum()))
+}
+
+/// One row of the streaming DP: the beams entering `row` (column ->
+/// timelines) split or carry straight on, and movers push them along the
+/// row, giving the beams that leave it. Splitters in columns `removed` says
+/// are gone act as empty cells. Also returns the columns where a beam was
+/// split.
+fn advance_beams(beams: &HashMap<usize, u64>, row: &[Cell], removed: impl Fn(usize) -> bool) -> (Vec<usize>, HashMap<usize, u64>) {
+    let exits = row_exits(row);
+    let mut split = Vec::new();
+    let mut next: HashMap<usize, u64> = HashMap::new();
+    for (&col, &multiplicity) in beams {
+        let landed = if matches!(row[col], Cell::Splitter | Cell::Chance(_)) && !removed(col) {
+            split.push(col);
+            [col.checked_sub(1), Some(col + 1).filter(|&c| c < row.len())]
+        } else {
+            [Some(col), None]
+        };
+        for exit in landed.into_iter().flatten().filter_map(|col| exits[col]) {
+            *next.entry(exit).or_insert(0) += multiplicity;
+        }
+    }
+    (split, next)
+}
+
+/// Number of times the beam is split
+pub fn part1(input: &str) -> Result<usize> {
+    Ok(count_timelines_dp(&mut parse_input(input)?)?.0)
+}
+
+/// Number of distinct timelines
+pub fn part2(input: &str) -> Result<u64> {
+    Ok(count_timelines_dp(&mut parse_input(input)?)?.1)
+}
+
+/// Which way `optimize_removals` pushes the timeline count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Goal {
+    /// As few timelines as possible, or at most the target
+    Fewest,
+    /// As many timelines as possible, or at least the target. A removed
+    /// splitter's beam never reaches more than its two beams would have, so
+    /// on grids like the puzzle's the best is to remove nothing.
+    Most,
+}
+
+/// Splitters to turn into empty cells, as (x, y) in row-major order, and
+/// the timelines left once they're gone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovalPlan {
+    pub removed: Vec<(usize, usize)>,
+    pub timelines: u64,
+}
+
+/// Row-major order of an (x, y) position
+fn row_major((x, y): (usize, usize)) -> (usize, usize) {
+    (y, x)
+}
+
+/// What running the DP from a search node's first row found
+struct Evaluation {
+    timelines: u64,
+    /// Splitters a beam reached, in row-major order
+    hits: Vec<(usize, usize)>,
+    /// Beams entering each row, from the node's first row on
+    entering: Vec<HashMap<usize, u64>>,
+}
+
+/// Branch and bound over sets of splitters to remove. Only splitters a beam
+/// actually reaches are worth removing, and they're added in row-major
+/// order: removing a splitter only changes the rows below it, so every
+/// useful set is found exactly once, and each node only reruns the DP from
+/// its last removal down.
+struct RemovalSearch<'a> {
+    grid: &'a Grid<Cell>,
+    goal: Goal,
+    target: Option<u64>,
+    /// `bounds[b][(x, y)]`: the fewest (or most) timelines one timeline
+    /// entering (x, y) can turn into with up to `b` removals on or below row
+    /// y. Each side of a splitter gets the whole budget, so this never
+    /// promises less (or more) than any actual set of removals achieves.
+    bounds: Vec<Grid<u64>>,
+    best: Option<RemovalPlan>,
+    nodes: u64,
+}
+
+impl<'a> RemovalSearch<'a> {
+    fn new(grid: &'a Grid<Cell>, goal: Goal, target: Option<u64>, max_removed: usize) -> Self {
+        let (width, height) = (grid.width(), grid.height());
+        let pick = |a: u64, b: u64| match goal {
+            Goal::Fewest => a.min(b),
+            Goal::Most => a.max(b),
+        };
+        let exits: Vec<Vec<Option<usize>>> = (0..height).map(|y| row_exits(grid.row(y))).collect();
+        let mut bounds: Vec<Grid<u64>> = Vec::with_capacity(max_removed + 1);
+        for budget in 0..=max_removed {
+            // One row past the bottom, where every timeline counts once
+            let mut table = Grid::filled(width, height + 1, 1u64);
+            for y in (0..height).rev() {
+                for x in 0..width {
+                    // A beam landing on row y at x leaves it wherever the movers take it
+                    let below = |x: Option<usize>| {
+                        x.filter(|&x| x < width).and_then(|x| exits[y][x]).map_or(0, |x| table[(x, y + 1)])
+                    };
+                    // Beams only split from the third row on
+                    let value = if y >= 2 && matches!(grid[(x, y)], Cell::Splitter | Cell::Chance(_)) {
+                        let keep = below(x.checked_sub(1)).saturating_add(below(Some(x + 1)));
+                        match budget.checked_sub(1) {
+                            Some(rest) => pick(keep, bounds[rest][(x, y + 1)]),
+                            None => keep,
+                        }
+                    } else {
+                        below(Some(x))
+                    };
+                    table[(x, y)] = value;
+                }
+            }
+            bounds.push(table);
+        }
+        RemovalSearch { grid, goal, target, bounds, best: None, nodes: 0 }
+    }
+
+    /// The streaming DP from `beams` entering `row` down to the bottom, with
+    /// the `removed` splitters gone
+    fn evaluate(&self, row: usize, mut beams: HashMap<usize, u64>, removed: &[(usize, usize)]) -> Evaluation {
+        let mut hits = Vec::new();
+        let mut entering = Vec::new();
+        for y in row..self.grid.height() {
+            let (mut split, next) = advance_beams(&beams, self.grid.row(y), |x| removed.contains(&(x, y)));
+            split.sort_unstable();
+            hits.extend(split.into_iter().map(|x| (x, y)));
+            entering.push(std::mem::replace(&mut beams, next));
+        }
+        Evaluation { timelines: beams.values().sum(), hits, entering }
+    }
+
+    /// Whether `timelines` should replace the best plan so far
+    fn improves(&self, timelines: u64) -> bool {
+        match (self.target, &self.best) {
+            (Some(target), _) => self.within(timelines, target),
+            (None, None) => true,
+            (None, Some(best)) => timelines != best.timelines && self.within(timelines, best.timelines),
+        }
+    }
+
+    /// Whether a subtree bounded by `bound` could still improve on the best
+    /// plan or meet the target
+    fn worth(&self, bound: u64) -> bool {
+        match (self.target, &self.best) {
+            (Some(target), _) => self.within(bound, target),
+            (None, None) => true,
+            (None, Some(best)) => bound != best.timelines && self.within(bound, best.timelines),
+        }
+    }
+
+    /// `timelines` is no further from the goal than `limit`
+    fn within(&self, timelines: u64, limit: u64) -> bool {
+        match self.goal {
+            Goal::Fewest => timelines <= limit,
+            Goal::Most => timelines >= limit,
+        }
+    }
+
+    /// Searches below the node that removed `removed`, whose first changed
+    /// row `row` is entered by `beams`; true once the target is met
+    fn search(&mut self, row: usize, beams: HashMap<usize, u64>, removed: &mut Vec<(usize, usize)>, budget: usize) -> bool {
+        self.nodes += 1;
+        let evaluation = self.evaluate(row, beams, removed);
+        if self.improves(evaluation.timelines) {
+            self.best = Some(RemovalPlan { removed: removed.clone(), timelines: evaluation.timelines });
+            if self.target.is_some() {
+                return true;
+            }
+        }
+        if budget == 0 {
+            return false;
+        }
+
+        let last = removed.last().copied().map(row_major);
+        let bounds = &self.bounds[budget - 1];
+        let mut children: Vec<(u64, (usize, usize))> = evaluation
+            .hits
+            .iter()
+            .filter(|&&splitter| last.is_none_or(|last| row_major(splitter) > last))
+            .map(|&(x, y)| {
+                let bound = evaluation.entering[y - row].iter().fold(0u64, |total, (&col, &timelines)| {
+                    // The removed splitter's beam carries straight on
+                    let each = if col == x { bounds[(x, y + 1)] } else { bounds[(col, y)] };
+                    total.saturating_add(timelines.saturating_mul(each))
+                });
+                (bound, (x, y))
+            })
+            .collect();
+        // Most promising first, so once one isn't worth it neither is the rest
+        children.sort_by_key(|&(bound, splitter)| match self.goal {
+            Goal::Fewest => (bound, row_major(splitter)),
+            Goal::Most => (u64::MAX - bound, row_major(splitter)),
+        });
+
+        for (bound, (x, y)) in children {
+            if !self.worth(bound) {
+                break;
+            }
+            removed.push((x, y));
+            let met = self.search(y, evaluation.entering[y - row].clone(), removed, budget - 1);
+            removed.pop();
+            if met {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Turns up to `max_removed` splitters into empty cells to push the
+/// timeline count towards `goal`. Without a target this is the best count
+/// reachable; with one, a smallest set of removals reaching it (at most
+/// `target` timelines for `Fewest`, at least for `Most`), or `None` if no
+/// set of `max_removed` does. The search space grows quickly with
+/// `max_removed`, so big grids want small budgets or a target to prune with.
+pub fn optimize_removals(input: &str, max_removed: usize, goal: Goal, target: Option<u64>) -> Result<Option<RemovalPlan>> {
+    let grid = parse_input(input)?;
+    let Some(start) = start_column(&grid).filter(|_| grid.height() >= 2) else {
+        return Ok(target.is_none_or(|target| target == 0 || goal == Goal::Fewest).then(|| RemovalPlan { removed: Vec::new(), timelines: 0 }));
+    };
+
+    let mut search = RemovalSearch::new(&grid, goal, target, max_removed);
+    let beams = HashMap::from([(start, 1)]);
+    // With a target, deepen one removal at a time so the first set found is a smallest one
+    let budgets = match target {
+        Some(_) => 0..=max_removed,
+        None => max_removed..=max_removed,
+    };
+    for budget in budgets {
+        if search.search(2, beams.clone(), &mut Vec::new(), budget) {
+            break;
+        }
+    }
+    stats::add("day07.removal_nodes", search.nodes);
+    Ok(search.best)
+}
+
+/// Column of the `S` on the first row
+fn start_column(grid: &Grid<Cell>) -> Option<usize> {
+    grid.row(0).iter().position(|&cell| cell == Cell::Start)
+}
+
+/// Probability a timeline reaching `cell` carries on to the left and to the
+/// right; a plain splitter sends it both ways, anything else straight down
+fn branch_odds(cell: Cell) -> Option<(f64, f64)> {
+    match cell {
+        Cell::Splitter => Some((1.0, 1.0)),
+        Cell::Chance(tenths) => {
+            let left = f64::from(tenths) / 10.0;
+            Some((left, 1.0 - left))
+        }
+        _ => None,
+    }
+}
+
+/// Expected number of timelines reaching the bottom row: the multiplicity DP
+/// of `count_timelines_dp`, with stochastic splitters dividing a beam's
+/// weight between their two sides instead of copying it
+fn expected_timelines(grid: &Grid<Cell>) -> f64 {
+    let Some(start) = start_column(grid) else {
+        return 0.0;
+    };
+    if grid.height() < 2 {
+        return 0.0;
+    }
+
+    let mut weights = vec![0.0; grid.width()];
+    weights[start] = 1.0;
+    for row in 2..grid.height() {
+        let exits = row_exits(grid.row(row));
+        let mut next = vec![0.0; grid.width()];
+        let mut land = |col: Option<usize>, weight: f64| {
+            if let Some(exit) = col.and_then(|col| exits.get(col).copied().flatten()) {
+                next[exit] += weight;
+            }
+        };
+        for (col, &weight) in weights.iter().enumerate().filter(|(_, &w)| w > 0.0) {
+            match branch_odds(grid[(col, row)]) {
+                Some((left, right)) => {
+                    land(col.checked_sub(1), weight * left);
+                    land(Some(col + 1), weight * right);
+                }
+                None => land(Some(col), weight),
+            }
+        }
+        weights = next;
+    }
+    weights.iter().sum()
+}
+
+/// SplitMix64, so sampling runs are reproducible from `--seed`
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // Top 53 bits give a uniform float in [0, 1)
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Monte Carlo estimate of `expected_timelines`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Estimate {
+    mean: f64,
+    std_error: f64,
+}
+
+/// Follows `trials` random beams from the start. At a plain splitter a beam
+/// picks a side uniformly and doubles its weight; at a stochastic one it
+/// picks by the splitter's odds. A beam leaving the grid is worth nothing,
+/// so the mean weight reaching the bottom is an unbiased estimate of the
+/// expected timeline count.
+fn sample_timelines(grid: &Grid<Cell>, trials: usize, seed: u64) -> Estimate {
+    let Some(start) = start_column(grid) else {
+        return Estimate { mean: 0.0, std_error: 0.0 };
+    };
+
+    let exits: Vec<Vec<Option<usize>>> = (0..grid.height()).map(|row| row_exits(grid.row(row))).collect();
+    let mut rng = SplitMix64(seed);
+    let (mut sum, mut sum_squares) = (0.0, 0.0);
+    for _ in 0..trials {
+        let mut col = start;
+        let mut weight = 1.0;
+        for row in 2..grid.height() {
+            let (next, factor) = match branch_odds(grid[(col, row)]) {
+                Some((left, right)) => {
+                    // Pick a side with probability proportional to its odds and
+                    // reweight so the expectation stays the same
+                    let total = left + right;
+                    let go_left = rng.next_f64() * total < left;
+                    (if go_left { col.checked_sub(1) } else { Some(col + 1) }, total)
+                }
+                None => (Some(col), 1.0),
+            };
+            match next.and_then(|next| exits[row].get(next).copied().flatten()) {
+                Some(next) => {
+                    col = next;
+                    weight *= factor;
+                }
+                None => {
+                    weight = 0.0;
+                    break;
+                }
+            }
+        }
+        sum += weight;
+        sum_squares += weight * weight;
+    }
+
+    let n = trials.max(1) as f64;
+    let mean = sum / n;
+    let variance = (sum_squares / n - mean * mean).max(0.0);
+    Estimate { mean, std_error: (variance / n).sqrt() }
+}
+
+/// How `--mode` analyzes stochastic splitters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Expected timeline count from the weighted DP
+    Exact,
+    /// Monte Carlo estimate, printed next to the exact value
+    Sample,
+}
+
+/// Command-line options for day 7 (passed after the day number)
+#[derive(Parser, Debug)]
+#[command(name = "day07", about = "Day 7 beam splitters")]
+struct Options {
+    /// Analyze stochastic splitters (digits 0-9) instead of running the puzzle parts
+    #[arg(long, value_enum)]
+    mode: Option<Mode>,
+    /// Count splits and timelines reading the input a row at a time, without loading the grid
+    #[arg(long, conflicts_with = "mode")]
+    stream: bool,
+    /// Draw the beams traced through the example and the full input
+    #[arg(long, conflicts_with_all = ["mode", "stream"])]
+    render: bool,
+    /// Search for up to K splitters to turn into empty cells to reach --goal
+    #[arg(long, value_name = "K", conflicts_with_all = ["mode", "stream", "render"])]
+    remove: Option<usize>,
+    /// Whether --remove aims for fewer or more timelines
+    #[arg(long, value_enum, default_value_t = Goal::Fewest, requires = "remove")]
+    goal: Goal,
+    /// Stop --remove at the fewest removals giving at most (or with --goal
+    /// most, at least) this many timelines
+    #[arg(long, value_name = "N", requires = "remove")]
+    target: Option<u64>,
+    /// Input file for --mode [default: day07stochastic.txt in the input directory]
+    /// or --stream and --remove [default: day07splitter.txt]
+    #[arg(long)]
+    input: Option<String>,
+    /// Random beams to follow with --mode sample
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    trials: usize,
+    /// Seed for --mode sample
+    #[arg(long, default_value_t = 2025)]
+    seed: u64,
+}
+
+fn run_stochastic(options: &Options, mode: Mode, out: &dyn OutputSink) -> Result<()> {
+    let grid = match &options.input {
+        Some(path) => parse_input(input::read_file(path)?).map_err(|e| e.in_file(path))?,
+        None => Input::for_day(7).parse("stochastic", parse_input)?,
+    };
+
+    let exact = expected_timelines(&grid);
+    out.line(&format!("Expected timelines (exact): {:.6}", exact));
+    if mode == Mode::Sample {
+        if options.trials == 0 {
+            return Err(AocError::usage("--trials must be at least 1"));
+        }
+        let estimate = sample_timelines(&grid, options.trials, options.seed);
+        out.line(&format!(
+            "Expected timelines (sampled, {} trials): {:.6} ± {:.6}",
+            options.trials, estimate.mean, estimate.std_error
+        ));
+        out.line(&format!("Difference: {:.2} standard errors", (estimate.mean - exact).abs() / estimate.std_error.max(f64::MIN_POSITIVE)));
+    }
+    Ok(())
+}
+
+fn run_streaming(options: &Options, out: &dyn OutputSink) -> Result<()> {
+    let path = match &options.input {
+        Some(path) => path.clone(),
+        None => Input::for_day(7).path("splitter").to_string_lossy().into_owned(),
+    };
+    let file = File::open(&path).map_err(|e| AocError::io(&path, e))?;
+
+    let start = std::time::Instant::now();
+    let (split_count, timeline_count) = count_timelines_streaming(BufReader::new(file), &path).map_err(|e| e.in_file(&path))?;
+    out.line(&format!("Streamed {}:", path));
+    out.line(&format!("  Split count: {}", split_count));
+    out.line(&format!("  Unique timelines: {}", timeline_count));
+    out.line(&format!("  Time elapsed: {:?}", start.elapsed()));
+    Ok(())
+}
+
+fn run_removals(options: &Options, max_removed: usize, out: &dyn OutputSink) -> Result<()> {
+    let (grid, origin) = match &options.input {
+        Some(path) => (input::read_file(path)?, Some(path)),
+        None => (Input::for_day(7).read("splitter")?, None),
+    };
+    let in_origin = |e: AocError| match origin {
+        Some(path) => e.in_file(path),
+        None => e,
+    };
+
+    out.line(&format!("Timelines with every splitter: {}", part2(grid).map_err(in_origin)?));
+    let plan = optimize_removals(grid, max_removed, options.goal, options.target).map_err(in_origin)?;
+    let Some(plan) = plan else {
+        let limit = if options.goal == Goal::Fewest { "at most" } else { "at least" };
+        let target = options.target.expect("only a target can go unmet");
+        return Err(AocError::Infeasible(format!("Removing up to {} splitters can't leave {} {} timelines", max_removed, limit, target)));
+    };
+    out.line(&format!("Removing {} splitter(s) leaves {} timelines", plan.removed.len(), plan.timelines));
+    for &(x, y) in &plan.removed {
+        out.line(&format!("  line {}, column {}", y + 1, x + 1));
+    }
+    Ok(())
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Laboratories",
+        input_files: &["test", "splitter", "stochastic", "movers"],
+        parts: [
+            "Number of times the beam is split",
+            "Number of distinct timelines",
+        ],
+        notes: "The stochastic file feeds the probabilistic splitter variant; the movers file adds < and > cells that push beams along a row; --remove searches for splitters to take out",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day07".to_string()).chain(args.iter().cloned()));
+    if let Some(mode) = options.mode {
+        return run_stochastic(&options, mode, out);
+    }
+    if options.stream {
+        return run_streaming(&options, out);
+    }
+    if let Some(max_removed) = options.remove {
+        return run_removals(&options, max_removed, out);
+    }
+
+    let theme = theme::current();
+
     // Test with small example first
-    println!("Testing with small example:");
-    let mut test_grid = parse_input("assets/day07test.txt")?;
+    out.line(&theme.paint(Style::Heading, "Testing with small example:"));
+    let mut test_grid = Input::for_day(7).parse("test", parse_input)?;
     let (test_splits, test_timelines) = count_timelines_dp(&mut test_grid)?;
-    println!("  Split count: {} (expected: 21)", test_splits);
-    println!("  Unique timelines: {} (expected: 40)", test_timelines);
-    println!();
+    out.line(&format!("  Split count: {} (expected: 21)", test_splits));
+    out.line(&format!("  Unique timelines: {} (expected: 40)", test_timelines));
+    if options.render {
+        out.block(&paint_beams(&test_grid, theme));
+    }
+    out.line("");
     
     // Run with full input
-    println!("Running with full input:");
-    let mut grid = parse_input("assets/day07splitter.txt")?;
+    out.line(&theme.paint(Style::Heading, "Running with full input:"));
+    let mut grid = Input::for_day(7).parse("splitter", parse_input)?;
     
     let start = std::time::Instant::now();
     let (split_count, timeline_count) = count_timelines_dp(&mut grid)?;
     let elapsed = start.elapsed();
     
-    println!("  Split count: {}", split_count);
-    println!("  Unique timelines: {}", timeline_count);
-    println!("  Time elapsed: {:?}", elapsed);
+    out.answer(1, "  Split count", &split_count);
+    out.answer(2, "  Unique timelines", &timeline_count);
+    out.line(&format!("  Time elapsed: {:?}", elapsed));
+    if options.render {
+        out.block(&paint_beams(&grid, theme));
+    }
     
     Ok(())
 }
@@ -159,10 +811,11 @@ pub fn run() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_small_example() {
-        let mut test_grid = parse_input("assets/day07test.txt")
+        let mut test_grid = parse_input(Input::for_day(7).example().unwrap())
             .expect("Failed to read test input file");
         
         let (split_count, timeline_count) = count_timelines_dp(&mut test_grid)
@@ -170,17 +823,180 @@ mod tests {
         
         assert_eq!(split_count, 21, "Test split count should be 21");
         assert_eq!(timeline_count, 40, "Test timeline count should be 40");
+
+        // Plain drawing is the grid as-is; colors only add escapes around it
+        assert_eq!(paint_beams(&test_grid, Theme::PLAIN), test_grid.to_string());
+        assert!(paint_beams(&test_grid, Theme::COLOR).contains("\x1b[38;2;86;180;233m|"));
     }
 
     #[test]
     fn test_full_solution() {
-        let mut grid = parse_input("assets/day07splitter.txt")
+        let mut grid = parse_input(Input::for_day(7).read("splitter").unwrap())
             .expect("Failed to read input file");
         
         let (split_count, timeline_count) = count_timelines_dp(&mut grid)
             .expect("Failed to count timelines");
         
-        assert_eq!(split_count, 1651, "Full split count should be 1651");
-        assert_eq!(timeline_count, 108924003331749, "Full timeline count should be 108924003331749");
+        snapshot::check("day07", "split_count", split_count);
+        snapshot::check("day07", "timeline_count", timeline_count);
+    }
+
+    #[test]
+    fn test_streaming_matches_grid() {
+        for input in [Input::for_day(7).example().unwrap(), Input::for_day(7).read("splitter").unwrap()] {
+            let expected = count_timelines_dp(&mut parse_input(input).unwrap()).unwrap();
+            let reader = BufReader::with_capacity(16, input.as_bytes());
+            assert_eq!(count_timelines_streaming(reader, "test").unwrap(), expected);
+        }
+        assert_eq!(count_timelines_streaming("..S..\r\n\n.....\r\n".as_bytes(), "test").unwrap(), (0, 1));
+        assert_eq!(count_timelines_streaming("..S..\n".as_bytes(), "test").unwrap(), (0, 0));
+
+        let err = count_timelines_streaming(".S.\n...\n\n.^..\n".as_bytes(), "test").unwrap_err();
+        assert_eq!(err.to_string(), "Line 4: Row has 4 cells, expected 3");
+        let err = count_timelines_streaming(".S.\n.x.\n".as_bytes(), "test").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2, column 2: Invalid cell character: x");
+    }
+
+    /// Follows every timeline one cell at a time, walking movers step by step
+    /// and giving up on a beam that comes back to a mover it already left
+    fn walk_timelines(grid: &Grid<Cell>) -> u64 {
+        fn settle(grid: &Grid<Cell>, row: usize, col: Option<usize>) -> Option<usize> {
+            let mut col = col.filter(|&c| c < grid.width())?;
+            let mut seen = HashSet::new();
+            loop {
+                let next = match grid[(col, row)] {
+                    Cell::Left => col.checked_sub(1),
+                    Cell::Right => Some(col + 1),
+                    _ => return Some(col),
+                };
+                if !seen.insert(col) {
+                    return None;
+                }
+                col = next.filter(|&c| c < grid.width())?;
+            }
+        }
+        fn follow(grid: &Grid<Cell>, row: usize, col: usize) -> u64 {
+            if row + 1 == grid.height() {
+                return 1;
+            }
+            let next = row + 1;
+            let landed = match grid[(col, next)] {
+                Cell::Splitter => vec![col.checked_sub(1), Some(col + 1)],
+                _ => vec![Some(col)],
+            };
+            landed.into_iter().filter_map(|c| settle(grid, next, c)).map(|c| follow(grid, next, c)).sum()
+        }
+        start_column(grid).filter(|_| grid.height() >= 2).map_or(0, |start| follow(grid, 1, start))
+    }
+
+    #[test]
+    fn test_movers() {
+        let input = Input::for_day(7).read("movers").unwrap();
+        let mut grid = parse_input(input).unwrap();
+        assert_eq!(walk_timelines(&grid), 4);
+        assert_eq!(walk_timelines(&parse_input(Input::for_day(7).example().unwrap()).unwrap()), 40);
+        // Half the timelines are caught in the >< loop or pushed off the right edge
+        assert_eq!(count_timelines_dp(&mut grid).unwrap(), (5, 4));
+        assert_eq!(grid.row(9).iter().map(|c| c.to_char()).collect::<String>(), "...|<^|^|....");
+        assert_eq!(count_timelines_streaming(input.as_bytes(), "test").unwrap(), (5, 4));
+        assert_eq!(expected_timelines(&parse_input(input).unwrap()), 4.0);
+
+        assert_eq!(row_exits(parse_input(".<<.>><>>\n").unwrap().row(0)), [
+            Some(0), Some(0), Some(0), Some(3), None, None, None, None, None
+        ]);
+
+        // The removal search still agrees with trying every set
+        let fewest = brute_force_removals(input, 2).into_iter().map(|(_, t)| t).min().unwrap();
+        assert_eq!(optimize_removals(input, 2, Goal::Fewest, None).unwrap().unwrap().timelines, fewest);
+    }
+
+    /// Every set of up to `max_removed` splitters, tried one by one
+    fn brute_force_removals(input: &str, max_removed: usize) -> Vec<(usize, u64)> {
+        let grid = parse_input(input).unwrap();
+        let splitters: Vec<(usize, usize)> =
+            grid.iter().filter(|(_, &cell)| cell == Cell::Splitter).map(|(pos, _)| pos).collect();
+        let mut outcomes = Vec::new();
+        let mut chosen = Vec::new();
+        fn recurse(grid: &Grid<Cell>, splitters: &[(usize, usize)], chosen: &mut Vec<(usize, usize)>, left: usize, outcomes: &mut Vec<(usize, u64)>) {
+            let mut trial = grid.clone();
+            for &pos in chosen.iter() {
+                trial[pos] = Cell::Empty;
+            }
+            outcomes.push((chosen.len(), count_timelines_dp(&mut trial).unwrap().1));
+            if left == 0 {
+                return;
+            }
+            for (i, &pos) in splitters.iter().enumerate() {
+                chosen.push(pos);
+                recurse(grid, &splitters[i + 1..], chosen, left - 1, outcomes);
+                chosen.pop();
+            }
+        }
+        recurse(&grid, &splitters, &mut chosen, max_removed, &mut outcomes);
+        outcomes
+    }
+
+    #[test]
+    fn test_optimize_removals_matches_brute_force() {
+        let example = Input::for_day(7).example().unwrap();
+        for max_removed in 0..=2 {
+            let outcomes = brute_force_removals(example, max_removed);
+            let fewest = outcomes.iter().map(|&(_, t)| t).min().unwrap();
+            let most = outcomes.iter().map(|&(_, t)| t).max().unwrap();
+
+            let plan = optimize_removals(example, max_removed, Goal::Fewest, None).unwrap().unwrap();
+            assert_eq!(plan.timelines, fewest, "fewest with {}", max_removed);
+            assert!(plan.removed.len() <= max_removed);
+            let plan = optimize_removals(example, max_removed, Goal::Most, None).unwrap().unwrap();
+            assert_eq!(plan.timelines, most, "most with {}", max_removed);
+
+            // The plan's removals give the count it claims
+            let mut grid = parse_input(example).unwrap();
+            for &pos in &plan.removed {
+                grid[pos] = Cell::Empty;
+            }
+            assert_eq!(count_timelines_dp(&mut grid).unwrap().1, plan.timelines);
+        }
+
+        // With a target, the fewest removals that reach it
+        let outcomes = brute_force_removals(example, 2);
+        for target in [40, 30, 20, 12, 5] {
+            let expected = outcomes.iter().filter(|&&(_, t)| t <= target).map(|&(n, _)| n).min();
+            let plan = optimize_removals(example, 2, Goal::Fewest, Some(target)).unwrap();
+            assert_eq!(plan.as_ref().map(|p| p.removed.len()), expected, "target {}", target);
+            assert!(plan.is_none_or(|p| p.timelines <= target));
+        }
+        // Taking a splitter out never adds timelines, so more than the
+        // grid already has is out of reach
+        let plan = optimize_removals(example, 2, Goal::Most, Some(40)).unwrap().unwrap();
+        assert_eq!(plan, RemovalPlan { removed: Vec::new(), timelines: 40 });
+        assert_eq!(optimize_removals(example, 2, Goal::Most, Some(41)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_expected_timelines() {
+        // Without stochastic splitters the expectation is the exact count
+        let example = parse_input(Input::for_day(7).example().unwrap()).unwrap();
+        assert_eq!(expected_timelines(&example), 40.0);
+
+        let even = parse_input(".S.\n...\n.5.\n...\n").unwrap();
+        assert_eq!(expected_timelines(&even), 1.0);
+        // Half the timelines fall off the left edge
+        let edge = parse_input("S..\n...\n5..\n...\n").unwrap();
+        assert_eq!(expected_timelines(&edge), 0.5);
+        let biased = parse_input("..S..\n.....\n..8..\n.^...\n").unwrap();
+        assert!((expected_timelines(&biased) - (0.8 * 2.0 + 0.2)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sampling_agrees_with_exact() {
+        let grid = parse_input(Input::for_day(7).read("stochastic").unwrap()).unwrap();
+        let exact = expected_timelines(&grid);
+        let estimate = sample_timelines(&grid, 200_000, 7);
+        assert!(
+            (estimate.mean - exact).abs() < 4.0 * estimate.std_error,
+            "sampled {:?}, exact {}", estimate, exact
+        );
+        assert_eq!(sample_timelines(&grid, 1000, 7), sample_timelines(&grid, 1000, 7));
     }
 }
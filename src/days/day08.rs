@@ -1,7 +1,18 @@
-use anyhow::{anyhow, Context, Result};
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::{Parser, ValueEnum};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use std::cmp::Ordering;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use crate::input::{self, Input};
+use crate::util::parse;
+use crate::util::progress::{Hidden, Progress};
+use crate::util::output::OutputSink;
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::stats;
+use crate::util::trace::{self, Event};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Coordinate3D {
@@ -10,36 +21,9 @@ pub struct Coordinate3D {
     pub z: i32,
 }
 
-fn parse_input(filename: &str) -> Result<Vec<Coordinate3D>> {
-    let content = fs::read_to_string(filename)
-        .context(format!("Failed to read file: {}", filename))?;
-
-    let coordinates = content
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .enumerate()
-        .map(|(i, line)| {
-            let parts: Vec<&str> = line.trim().split(',').collect();
-            if parts.len() != 3 {
-                return Err(anyhow!(
-                    "Line {} has {} values, expected 3 comma-separated values",
-                    i + 1,
-                    parts.len()
-                ));
-            }
-
-            let x = parts[0].parse::<i32>()
-                .context(format!("Failed to parse x coordinate on line {}", i + 1))?;
-            let y = parts[1].parse::<i32>()
-                .context(format!("Failed to parse y coordinate on line {}", i + 1))?;
-            let z = parts[2].parse::<i32>()
-                .context(format!("Failed to parse z coordinate on line {}", i + 1))?;
-
-            Ok(Coordinate3D { x, y, z })
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    Ok(coordinates)
+pub fn parse_input(content: &str) -> Result<Vec<Coordinate3D>> {
+    let tuples = parse::tuples::<i32, 3>(content)?;
+    Ok(tuples.into_iter().map(|[x, y, z]| Coordinate3D { x, y, z }).collect())
 }
 
 fn euclidean_distance(a: &Coordinate3D, b: &Coordinate3D) -> f64 {
@@ -78,278 +62,904 @@ impl PartialOrd for PairDistance {
     }
 }
 
-fn create_clusters(coordinates: &[Coordinate3D], num_connections: usize) -> (Vec<usize>, usize) {
-    let n = coordinates.len();
-    
-    println!("Clustering {} coordinates...", n);
-    println!("Computing all pairwise distances...");
-    
-    // Min-heap to efficiently get the closest pair
-    let mut heap: BinaryHeap<PairDistance> = BinaryHeap::new();
-    
-    // Compute all pairwise distances and add to heap
-    for i in 0..n {
-        if n >= 100 && i % 100 == 0 {
-            println!("  Processing coordinate {} of {}...", i, n);
-        }
-        for j in (i + 1)..n {
-            let distance = euclidean_distance(&coordinates[i], &coordinates[j]);
-            heap.push(PairDistance { distance, i, j });
+/// Boxes per grid cell `PairSearch::approximate` aims for
+const BOXES_PER_CELL: f64 = 2.0;
+
+/// How `Clusterer` finds the pairs it may connect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairSearch {
+    /// Every pair of boxes: quadratic time and memory, always right
+    Exact,
+    /// Only pairs in the same or adjacent cubes of a grid with sides of
+    /// `cell`. Every pair at most `cell` apart is still found, so connections
+    /// match `Exact` (up to ties) until the first one longer than `cell`; past
+    /// that a longer pair can be connected while a shorter one spanning
+    /// non-adjacent cubes is missed, though never one shorter than `cell`.
+    /// `Clusterer::certified` reports whether that point was reached.
+    Approximate { cell: i64 },
+}
+
+impl PairSearch {
+    /// `Approximate`, with cells sized so an even spread of `coordinates`
+    /// puts about `BOXES_PER_CELL` in each
+    pub fn approximate(coordinates: &[Coordinate3D]) -> Self {
+        let extent = |axis: fn(&Coordinate3D) -> i32| {
+            let values = coordinates.iter().map(axis);
+            let (min, max) = (values.clone().min().unwrap_or(0), values.max().unwrap_or(0));
+            (max as f64 - min as f64).max(1.0)
+        };
+        let volume = extent(|c| c.x) * extent(|c| c.y) * extent(|c| c.z);
+        let cell = (volume * BOXES_PER_CELL / coordinates.len().max(1) as f64).cbrt().ceil();
+        PairSearch::Approximate { cell: (cell as i64).max(1) }
+    }
+}
+
+/// Pairs in the same or adjacent `cell`-sided cubes, each once as (i, j) with i < j
+fn neighbouring_pairs(coordinates: &[Coordinate3D], cell: i64, progress: &dyn Progress) -> Vec<(usize, usize)> {
+    let cube = |c: &Coordinate3D| (i64::from(c.x).div_euclid(cell), i64::from(c.y).div_euclid(cell), i64::from(c.z).div_euclid(cell));
+    let mut cubes: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    for (i, coordinate) in coordinates.iter().enumerate() {
+        cubes.entry(cube(coordinate)).or_default().push(i);
+    }
+
+    let mut pairs = Vec::new();
+    progress.start("Neighbouring pairs", coordinates.len() as u64);
+    for (i, coordinate) in coordinates.iter().enumerate() {
+        let (x, y, z) = cube(coordinate);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(boxes) = cubes.get(&(x + dx, y + dy, z + dz)) {
+                        pairs.extend(boxes.iter().filter(|&&j| j > i).map(|&j| (i, j)));
+                    }
+                }
+            }
         }
+        progress.inc(1);
     }
-    
-    // Track which pairs are directly connected
-    let mut connected_pairs: HashSet<(usize, usize)> = HashSet::new();
-    
-    // Track which cluster each coordinate belongs to
-    let mut coordinate_to_cluster: HashMap<usize, usize> = HashMap::new();
-    
-    // Track clusters as sets of coordinate indices
-    let mut clusters: Vec<HashSet<usize>> = Vec::new();
-    
-    let mut connections_made = 0;
-    
-    println!("Connecting {} closest pairs...", num_connections);
-    
-    // Repeatedly find the closest pair that aren't already directly connected
-    while connections_made < num_connections {
-        // Pop pairs from heap until we find one that's not already connected
-        let closest_pair = loop {
-            if let Some(pair) = heap.pop() {
-                let key = if pair.i < pair.j { (pair.i, pair.j) } else { (pair.j, pair.i) };
-                
-                if !connected_pairs.contains(&key) {
-                    break Some((pair.i, pair.j));
+    progress.finish();
+    pairs
+}
+
+/// One connection made by `Clusterer::connect_next`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Connection {
+    pub i: usize,
+    pub j: usize,
+    pub distance: f64,
+    /// False when both boxes were already in the same circuit
+    pub merged: bool,
+}
+
+/// Incremental closest-first connection state: the pairs not yet connected
+/// and a union-find over the junction boxes, so circuit sizes can be read off
+/// after any number of connections without starting over
+pub struct Clusterer {
+    heap: BinaryHeap<PairDistance>,
+    /// Every pair at most this far apart is in the heap
+    complete_within: f64,
+    /// No connection so far was longer than `complete_within`
+    certified: bool,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    circuits: usize,
+    connections: usize,
+    /// The pair `connect_next` connected last
+    last: Option<(usize, usize)>,
+}
+
+impl Clusterer {
+    /// Queues every pair of boxes, each box starting as its own circuit
+    pub fn new(coordinates: &[Coordinate3D], progress: &dyn Progress) -> Self {
+        Self::with_search(coordinates, PairSearch::Exact, progress)
+    }
+
+    /// Queues the pairs `search` finds, each box starting as its own circuit
+    pub fn with_search(coordinates: &[Coordinate3D], search: PairSearch, progress: &dyn Progress) -> Self {
+        let n = coordinates.len();
+        let pair = |i: usize, j: usize| PairDistance { distance: euclidean_distance(&coordinates[i], &coordinates[j]), i, j };
+        let (heap, complete_within) = match search {
+            PairSearch::Exact => {
+                let mut heap = BinaryHeap::with_capacity(n * n.saturating_sub(1) / 2);
+                progress.start("Pairwise distances", n as u64);
+                for i in 0..n {
+                    for j in (i + 1)..n {
+                        heap.push(pair(i, j));
+                    }
+                    progress.inc(1);
                 }
-                // Otherwise, this pair was already connected, skip it
-            } else {
-                break None; // No more pairs available
+                progress.finish();
+                (heap, f64::INFINITY)
+            }
+            PairSearch::Approximate { cell } => {
+                let pairs = neighbouring_pairs(coordinates, cell, progress);
+                (pairs.into_iter().map(|(i, j)| pair(i, j)).collect(), cell as f64)
             }
         };
-        
-        // If we found a pair, connect them
-        if let Some((i, j)) = closest_pair {
-            let key = if i < j { (i, j) } else { (j, i) };
-            connected_pairs.insert(key);
-            connections_made += 1;
-            
-            if n >= 100 && connections_made % 100 == 0 {
-                println!("  Made {} connections...", connections_made);
+        stats::max("day08.heap_pairs", heap.len() as u64);
+        stats::max("day08.heap_bytes", (heap.capacity() * std::mem::size_of::<PairDistance>()) as u64);
+
+        Clusterer {
+            heap,
+            complete_within,
+            certified: true,
+            parent: (0..n).collect(),
+            size: vec![1; n],
+            circuits: n,
+            connections: 0,
+            last: None,
+        }
+    }
+
+    /// True while every connection made matches `PairSearch::Exact`; see
+    /// `PairSearch::Approximate` for when that stops being guaranteed
+    pub fn certified(&self) -> bool {
+        self.certified
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// Connects the closest pair not connected yet; `None` once every pair is
+    pub fn connect_next(&mut self) -> Option<Connection> {
+        let PairDistance { distance, i, j } = self.heap.pop()?;
+        self.connections += 1;
+        self.certified &= distance <= self.complete_within;
+        self.last = Some((i, j));
+
+        let (root_i, root_j) = (self.find(i), self.find(j));
+        let merged = root_i != root_j;
+        if merged {
+            let (big, small) = if self.size[root_i] >= self.size[root_j] { (root_i, root_j) } else { (root_j, root_i) };
+            self.parent[small] = big;
+            self.size[big] += self.size[small];
+            self.circuits -= 1;
+            trace::emit(|| Event::Merge { a: i, b: j, size: self.size[big] });
+        }
+        Some(Connection { i, j, distance, merged })
+    }
+
+    /// Connections made so far, including redundant ones within a circuit
+    pub fn connections(&self) -> usize {
+        self.connections
+    }
+
+    pub fn circuits(&self) -> usize {
+        self.circuits
+    }
+
+    /// Size of every circuit, largest first
+    pub fn cluster_sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| self.size[i])
+            .collect();
+        sizes.sort_by(|a, b| b.cmp(a));
+        sizes
+    }
+
+    /// Writes everything needed to carry on later: the union-find, the
+    /// counters and the pairs still queued, in the heap's own order so a
+    /// resumed run breaks distance ties exactly like this one would.
+    /// Distances aren't written; `read_checkpoint` recomputes them.
+    pub fn write_checkpoint(&self, coordinates: &[Coordinate3D], out: &mut impl Write) -> std::io::Result<()> {
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            boxes: coordinates.len(),
+            fingerprint: fingerprint(coordinates),
+            connections: self.connections,
+            circuits: self.circuits,
+            complete_within: Some(self.complete_within).filter(|d| d.is_finite()),
+            certified: self.certified,
+            last: self.last,
+            parent: self.parent.clone(),
+            size: self.size.clone(),
+            pairs: self.heap.as_slice().iter().map(|pair| (pair.i, pair.j)).collect(),
+        };
+        serde_json::to_writer(&mut *out, &checkpoint)?;
+        writeln!(out)
+    }
+
+    /// Picks up where `write_checkpoint` left off; `coordinates` must be the
+    /// boxes the checkpoint was written for
+    pub fn read_checkpoint(coordinates: &[Coordinate3D], text: &str) -> Result<Self> {
+        let checkpoint: Checkpoint = serde_json::from_str(text).map_err(|e| {
+            // serde_json appends the position to its message; it goes in the error's own fields instead
+            let message = e.to_string();
+            let message = message.split(" at line ").next().unwrap_or(&message);
+            AocError::parse_at_column(e.line(), e.column(), message)
+        })?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(AocError::parse(format!("Checkpoint version {} isn't supported, expected {}", checkpoint.version, CHECKPOINT_VERSION)));
+        }
+        let n = coordinates.len();
+        if (checkpoint.boxes, checkpoint.fingerprint) != (n, fingerprint(coordinates)) {
+            return Err(AocError::parse("Checkpoint was written for different junction boxes"));
+        }
+        check_union_find(&checkpoint.parent, &checkpoint.size, checkpoint.circuits)?;
+        let in_range = |(i, j): (usize, usize)| i < n && j < n && i != j;
+        if let Some(pair) = checkpoint.last.filter(|&pair| !in_range(pair)) {
+            return Err(AocError::parse(format!("Invalid last pair {:?}", pair)));
+        }
+
+        let mut heap = Vec::with_capacity(checkpoint.pairs.len());
+        for (i, j) in checkpoint.pairs {
+            if !in_range((i, j)) {
+                return Err(AocError::parse(format!("Invalid pair ({}, {})", i, j)));
             }
-            
-            let cluster_i = coordinate_to_cluster.get(&i).copied();
-            let cluster_j = coordinate_to_cluster.get(&j).copied();
-            
-            match (cluster_i, cluster_j) {
-                (Some(ci), Some(cj)) if ci == cj => {
-                    // Both already in same cluster, connection just adds redundancy
-                }
-                (Some(ci), Some(cj)) => {
-                    // Both in different clusters - merge them
-                    let cluster_j_members: Vec<usize> = clusters[cj].iter().copied().collect();
-                    for member in cluster_j_members {
-                        clusters[ci].insert(member);
-                        coordinate_to_cluster.insert(member, ci);
-                    }
-                    clusters[cj].clear();
-                }
-                (Some(ci), None) => {
-                    // i is in a cluster, add j to it
-                    clusters[ci].insert(j);
-                    coordinate_to_cluster.insert(j, ci);
-                }
-                (None, Some(cj)) => {
-                    // j is in a cluster, add i to it
-                    clusters[cj].insert(i);
-                    coordinate_to_cluster.insert(i, cj);
-                }
-                (None, None) => {
-                    // Neither is in a cluster, create a new one
-                    let cluster_id = clusters.len();
-                    let mut new_cluster = HashSet::new();
-                    new_cluster.insert(i);
-                    new_cluster.insert(j);
-                    clusters.push(new_cluster);
-                    coordinate_to_cluster.insert(i, cluster_id);
-                    coordinate_to_cluster.insert(j, cluster_id);
-                }
+            heap.push(PairDistance { distance: euclidean_distance(&coordinates[i], &coordinates[j]), i, j });
+        }
+
+        // Already a valid heap in this order, so this doesn't move anything
+        Ok(Clusterer {
+            heap: BinaryHeap::from(heap),
+            complete_within: checkpoint.complete_within.unwrap_or(f64::INFINITY),
+            certified: checkpoint.certified,
+            parent: checkpoint.parent,
+            size: checkpoint.size,
+            circuits: checkpoint.circuits,
+            connections: checkpoint.connections,
+            last: checkpoint.last,
+        })
+    }
+}
+
+/// What `write_checkpoint` saves, as JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    /// Number of junction boxes and their `fingerprint`
+    boxes: usize,
+    fingerprint: u64,
+    connections: usize,
+    circuits: usize,
+    /// None when every pair was queued; JSON has no infinity
+    complete_within: Option<f64>,
+    certified: bool,
+    last: Option<(usize, usize)>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    /// Queued pairs in the heap's own order
+    pairs: Vec<(usize, usize)>,
+}
+
+const CHECKPOINT_VERSION: u32 = 2;
+
+/// Fails unless `parent` is a forest over its own indices, without cycles,
+/// with `circuits` roots whose sizes add up to every box
+fn check_union_find(parent: &[usize], size: &[usize], circuits: usize) -> Result<()> {
+    let n = parent.len();
+    if size.len() != n {
+        return Err(AocError::parse(format!("Expected {} sizes, got {}", n, size.len())));
+    }
+    if let Some(i) = (0..n).find(|&i| parent[i] >= n) {
+        return Err(AocError::parse(format!("Box {} has parent {}, but there are only {} boxes", i, parent[i], n)));
+    }
+
+    // 0 = not seen, 1 = on the path being followed, 2 = known to reach a root
+    let mut state = vec![0u8; n];
+    let mut path = Vec::new();
+    for start in 0..n {
+        let mut node = start;
+        while state[node] == 0 && parent[node] != node {
+            state[node] = 1;
+            path.push(node);
+            node = parent[node];
+        }
+        if state[node] == 1 {
+            return Err(AocError::parse(format!("Box {}'s parents form a cycle", start)));
+        }
+        state[node] = 2;
+        for visited in path.drain(..) {
+            state[visited] = 2;
+        }
+    }
+
+    let roots: Vec<usize> = (0..n).filter(|&i| parent[i] == i).collect();
+    if roots.len() != circuits {
+        return Err(AocError::parse(format!("Checkpoint says {} circuits, but its parents have {} roots", circuits, roots.len())));
+    }
+    if roots.iter().map(|&root| size[root]).sum::<usize>() != n {
+        return Err(AocError::parse(format!("Circuit sizes don't add up to {} boxes", n)));
+    }
+    Ok(())
+}
+
+/// FNV-1a over the coordinates, to catch a checkpoint resumed against the wrong input
+fn fingerprint(coordinates: &[Coordinate3D]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for c in coordinates {
+        for value in [c.x, c.y, c.z] {
+            for byte in value.to_le_bytes() {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
             }
-        } else {
-            // No more pairs to connect
-            break;
         }
     }
-    
-    // Add singleton clusters for any coordinates that were never connected
-    for i in 0..n {
-        if !coordinate_to_cluster.contains_key(&i) {
-            let mut singleton_cluster = HashSet::new();
-            singleton_cluster.insert(i);
-            clusters.push(singleton_cluster);
+    hash
+}
+
+/// Where and how often `finish_single_cluster` saves its progress
+#[derive(Debug, Clone)]
+pub struct CheckpointPlan {
+    pub path: String,
+    /// Connections between saves
+    pub every: usize,
+}
+
+impl CheckpointPlan {
+    /// Writes to a temporary file first, so an interrupted save never
+    /// leaves a broken checkpoint behind
+    fn save(&self, clusterer: &Clusterer, coordinates: &[Coordinate3D]) -> Result<()> {
+        let temp = format!("{}.tmp", self.path);
+        let file = File::create(&temp).map_err(|e| AocError::io(&temp, e))?;
+        let mut out = BufWriter::new(file);
+        clusterer
+            .write_checkpoint(coordinates, &mut out)
+            .and_then(|()| out.flush())
+            .map_err(|e| AocError::io(&temp, e))?;
+        fs::rename(&temp, &self.path).map_err(|e| AocError::io(&self.path, e))
+    }
+}
+
+/// Product of the three largest circuits, or 0 with fewer than three.
+/// `sizes` must be sorted largest first.
+fn product_of_three_largest(sizes: &[usize]) -> usize {
+    match sizes {
+        [a, b, c, ..] => a * b * c,
+        _ => 0,
+    }
+}
+
+/// (circuit size, number of circuits that size), smallest size first
+fn size_counts(sizes: &[usize]) -> Vec<(usize, usize)> {
+    let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+    for &size in sizes {
+        *counts.entry(size).or_insert(0) += 1;
+    }
+    counts.into_iter().collect()
+}
+
+/// Tells the user when an approximate search may have strayed from the exact answer
+fn warn_if_uncertified(clusterer: &Clusterer, out: &dyn Reporter) {
+    if !clusterer.certified() {
+        out.line("Warning: the approximate search connected pairs longer than its cell size; the result may differ from --search exact");
+    }
+}
+
+/// What part 1 leaves behind after its connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clustering {
+    /// Size of every circuit, largest first
+    pub sizes: Vec<usize>,
+    /// Product of the three largest circuits, or 0 with fewer than three
+    pub product: usize,
+    /// Length of every connection made, shortest first
+    pub distances: Vec<f64>,
+    /// The two boxes of every connection made, in the same order as `distances`
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Clustering {
+    /// Length of the k-th connection made, counting from 1
+    pub fn kth_connection_distance(&self, k: usize) -> Option<f64> {
+        k.checked_sub(1).and_then(|i| self.distances.get(i)).copied()
+    }
+
+    /// Nearest-rank percentile of the connection lengths, for `p` in 0..=100;
+    /// `None` when no connection was made
+    pub fn distance_percentile(&self, p: f64) -> Option<f64> {
+        if self.distances.is_empty() || !(0.0..=100.0).contains(&p) {
+            return None;
         }
+        let rank = ((p / 100.0 * self.distances.len() as f64).ceil() as usize).max(1);
+        self.kth_connection_distance(rank)
     }
-    
-    // Filter out empty clusters and get sizes, then sort for readability
-    let mut cluster_sizes: Vec<usize> = clusters
-        .iter()
-        .filter(|c| !c.is_empty())
-        .map(|c| c.len())
-        .collect();
-    cluster_sizes.sort_by(|a, b| b.cmp(a)); // Sort descending
-    
-    println!("\n{} circuits created:", cluster_sizes.len());
-    let mut size_counts: HashMap<usize, usize> = HashMap::new();
-    for &size in &cluster_sizes {
-        *size_counts.entry(size).or_insert(0) += 1;
+
+    pub fn median_connection_distance(&self) -> Option<f64> {
+        self.distance_percentile(50.0)
     }
-    
-    let mut sizes: Vec<_> = size_counts.keys().copied().collect();
-    sizes.sort_by(|a, b| b.cmp(a));
-    for size in sizes {
-        let count = size_counts[&size];
-        println!("  {} circuit(s) with {} junction box(es)", count, size);
+
+    /// Triangles and density of every circuit, from the connections made
+    pub fn circuit_metrics(&self) -> Vec<CircuitMetrics> {
+        circuit_metrics(self.sizes.iter().sum(), &self.edges)
     }
-    
-    // Show top 10 cluster sizes for debugging
-    println!("\nTop 10 largest circuits:");
-    for (i, &size) in cluster_sizes.iter().take(10).enumerate() {
-        println!("  {}. {} junction boxes", i + 1, size);
+}
+
+/// How tightly one circuit is wired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitMetrics {
+    /// The circuit's boxes, lowest index first
+    pub boxes: Vec<usize>,
+    /// Connections made between its boxes
+    pub connections: usize,
+    /// Sets of three boxes connected to each other
+    pub triangles: usize,
+}
+
+impl CircuitMetrics {
+    /// Connections beyond the `boxes - 1` needed to join the circuit up:
+    /// the ones `Connection::merged` reports as false
+    pub fn redundant(&self) -> usize {
+        self.connections + 1 - self.boxes.len()
+    }
+
+    /// Connections made out of every pair the circuit's boxes could have
+    /// made; 0 for a single box
+    pub fn density(&self) -> f64 {
+        let n = self.boxes.len();
+        if n < 2 {
+            return 0.0;
+        }
+        self.connections as f64 / (n * (n - 1) / 2) as f64
     }
-    
-    // Calculate product of three largest circuits
-    let product = if cluster_sizes.len() >= 3 {
-        let prod = cluster_sizes[0] * cluster_sizes[1] * cluster_sizes[2];
-        println!("\nProduct of three largest circuits: {} * {} * {} = {}", 
-                 cluster_sizes[0], 
-                 cluster_sizes[1], 
-                 cluster_sizes[2],
-                 prod);
-        prod
-    } else {
-        0
-    };
-    
-    (cluster_sizes, product)
 }
 
-fn connect_until_single_cluster(coordinates: &[Coordinate3D]) -> Result<i64> {
-    let n = coordinates.len();
-    
-    println!("Connecting all {} coordinates into a single circuit...", n);
-    println!("Computing all pairwise distances...");
-    
-    // Min-heap to efficiently get the closest pair
-    let mut heap: BinaryHeap<PairDistance> = BinaryHeap::new();
-    
-    // Compute all pairwise distances and add to heap
-    for i in 0..n {
-        if n >= 100 && i % 100 == 0 {
-            println!("  Processing coordinate {} of {}...", i, n);
+/// Splits `boxes` boxes into the circuits `edges` join and counts the
+/// triangles in each, largest circuit first. Each connection is pointed from
+/// the box with fewer connections to the one with more, so every triangle is
+/// found once from its lowest box, and the busiest boxes don't make the
+/// count quadratic.
+pub fn circuit_metrics(boxes: usize, edges: &[(usize, usize)]) -> Vec<CircuitMetrics> {
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); boxes];
+    for &(i, j) in edges {
+        neighbours[i].push(j);
+        neighbours[j].push(i);
+    }
+
+    // Label each box with its circuit
+    let mut circuit = vec![usize::MAX; boxes];
+    let mut metrics = Vec::new();
+    for first in 0..boxes {
+        if circuit[first] != usize::MAX {
+            continue;
         }
-        for j in (i + 1)..n {
-            let distance = euclidean_distance(&coordinates[i], &coordinates[j]);
-            heap.push(PairDistance { distance, i, j });
+        circuit[first] = metrics.len();
+        let mut members = vec![first];
+        let mut next = 0;
+        while let Some(&b) = members.get(next) {
+            next += 1;
+            for &n in &neighbours[b] {
+                if circuit[n] == usize::MAX {
+                    circuit[n] = metrics.len();
+                    members.push(n);
+                }
+            }
         }
+        members.sort_unstable();
+        metrics.push(CircuitMetrics { boxes: members, connections: 0, triangles: 0 });
     }
+
+    let rank = |b: usize| (neighbours[b].len(), b);
+    let forward: Vec<Vec<usize>> = neighbours
+        .iter()
+        .enumerate()
+        .map(|(b, list)| list.iter().copied().filter(|&n| rank(n) > rank(b)).collect())
+        .collect();
+    let mut marked = vec![false; boxes];
+    for (b, out) in forward.iter().enumerate() {
+        metrics[circuit[b]].connections += out.len();
+        for &n in out {
+            marked[n] = true;
+        }
+        for &n in out {
+            metrics[circuit[b]].triangles += forward[n].iter().filter(|&&m| marked[m]).count();
+        }
+        for &n in out {
+            marked[n] = false;
+        }
+    }
+
+    metrics.sort_by(|a, b| b.boxes.len().cmp(&a.boxes.len()).then(a.boxes[0].cmp(&b.boxes[0])));
+    metrics
+}
+
+/// Prints `--triangles`: totals, the densest circuit and the circuits with
+/// the most triangles
+fn report_circuit_metrics(clustering: &Clustering, out: &dyn Reporter) {
+    let metrics = clustering.circuit_metrics();
+    let triangles: usize = metrics.iter().map(|m| m.triangles).sum();
+    let redundant: usize = metrics.iter().map(CircuitMetrics::redundant).sum();
+    out.line(&format!("\nCircuit metrics after {} connections:", clustering.edges.len()));
+    out.line(&format!("  Triangles: {}", triangles));
+    out.line(&format!("  Redundant connections: {}", redundant));
+
+    // Ties go to the bigger circuit, so a lone pair doesn't beat a full triangle
+    let densest = metrics
+        .iter()
+        .filter(|m| m.boxes.len() >= 2)
+        .max_by(|a, b| a.density().total_cmp(&b.density()).then(a.boxes.len().cmp(&b.boxes.len())));
+    if let Some(m) = densest {
+        out.line(&format!(
+            "  Densest circuit: {} boxes, {} connections, density {:.3}, {} triangle(s)",
+            m.boxes.len(),
+            m.connections,
+            m.density(),
+            m.triangles
+        ));
+    }
+
+    let mut by_triangles: Vec<&CircuitMetrics> = metrics.iter().filter(|m| m.triangles > 0).collect();
+    by_triangles.sort_by(|a, b| b.triangles.cmp(&a.triangles).then(b.boxes.len().cmp(&a.boxes.len())));
+    if !by_triangles.is_empty() {
+        out.line("  Most triangles:");
+    }
+    for m in by_triangles.iter().take(10) {
+        out.line(&format!(
+            "    {} triangle(s) in {} boxes, {} connections, density {:.3}",
+            m.triangles,
+            m.boxes.len(),
+            m.connections,
+            m.density()
+        ));
+    }
+}
+
+pub fn create_clusters(coordinates: &[Coordinate3D], num_connections: usize, search: PairSearch, progress: &dyn Progress, out: &dyn OutputSink) -> Clustering {
+    out.line(&format!("Clustering {} coordinates...", coordinates.len()));
+    let mut clusterer = Clusterer::with_search(coordinates, search, progress);
     
-    // Track which pairs are directly connected
-    let mut connected_pairs: HashSet<(usize, usize)> = HashSet::new();
-    
-    // Track which cluster each coordinate belongs to
-    let mut coordinate_to_cluster: HashMap<usize, usize> = HashMap::new();
+    out.line(&format!("Connecting {} closest pairs...", num_connections));
+    progress.start("Connections", num_connections as u64);
+    let mut distances = Vec::with_capacity(num_connections);
+    let mut edges = Vec::with_capacity(num_connections);
+    while clusterer.connections() < num_connections {
+        let Some(connection) = clusterer.connect_next() else { break };
+        distances.push(connection.distance);
+        edges.push((connection.i, connection.j));
+        progress.inc(1);
+    }
+    progress.finish();
+    warn_if_uncertified(&clusterer, out);
     
-    // Track clusters as sets of coordinate indices
-    let mut clusters: Vec<HashSet<usize>> = Vec::new();
+    let cluster_sizes = clusterer.cluster_sizes();
     
-    // Initialize: each coordinate starts in its own cluster
-    for i in 0..n {
-        let mut singleton = HashSet::new();
-        singleton.insert(i);
-        clusters.push(singleton);
-        coordinate_to_cluster.insert(i, i);
+    out.line(&format!("\n{} circuits created:", cluster_sizes.len()));
+    for (size, count) in size_counts(&cluster_sizes).into_iter().rev() {
+        out.line(&format!("  {} circuit(s) with {} junction box(es)", count, size));
     }
     
-    let mut connections_made = 0;
-    let mut last_connected_pair: Option<(usize, usize)> = None;
+    // Show top 10 cluster sizes for debugging
+    out.line("\nTop 10 largest circuits:");
+    for (i, &size) in cluster_sizes.iter().take(10).enumerate() {
+        out.line(&format!("  {}. {} junction boxes", i + 1, size));
+    }
     
-    // Count how many non-empty clusters we have
-    let mut num_clusters = n;
+    let product = product_of_three_largest(&cluster_sizes);
+    if cluster_sizes.len() >= 3 {
+        out.line("");
+        let working = format!("{} * {} * {}", cluster_sizes[0], cluster_sizes[1], cluster_sizes[2]);
+        out.worked_answer(1, "Product of three largest circuits", &working, &product);
+    }
     
-    println!("Starting with {} circuits...", num_clusters);
+    Clustering { sizes: cluster_sizes, product, distances, edges }
+}
+
+pub fn connect_until_single_cluster(coordinates: &[Coordinate3D], search: PairSearch, progress: &dyn Progress, out: &dyn OutputSink) -> Result<i64> {
+    connect_with_checkpoints(coordinates, search, None, progress, out)
+}
+
+/// `connect_until_single_cluster`, saving to `checkpoint` along the way
+fn connect_with_checkpoints(
+    coordinates: &[Coordinate3D],
+    search: PairSearch,
+    checkpoint: Option<&CheckpointPlan>,
+    progress: &dyn Progress,
+    out: &dyn OutputSink,
+) -> Result<i64> {
+    out.line(&format!("Connecting all {} coordinates into a single circuit...", coordinates.len()));
+    let clusterer = Clusterer::with_search(coordinates, search, progress);
+    finish_single_cluster(coordinates, clusterer, search, checkpoint, progress, out)
+}
+
+/// The rest of `connect_until_single_cluster` from wherever `clusterer`
+/// is, saving it to `checkpoint` along the way
+pub fn finish_single_cluster(
+    coordinates: &[Coordinate3D],
+    mut clusterer: Clusterer,
+    search: PairSearch,
+    checkpoint: Option<&CheckpointPlan>,
+    progress: &dyn Progress,
+    out: &dyn OutputSink,
+) -> Result<i64> {
+    out.line(&format!("Starting with {} circuits...", clusterer.circuits()));
+    progress.start("Merging circuits", (clusterer.circuits() as u64).saturating_sub(1));
     
     // Continue until we have only 1 cluster
-    while num_clusters > 1 {
-        // Pop pairs from heap until we find one that's not already connected
-        let closest_pair = loop {
-            if let Some(pair) = heap.pop() {
-                let key = if pair.i < pair.j { (pair.i, pair.j) } else { (pair.j, pair.i) };
-                
-                if !connected_pairs.contains(&key) {
-                    break Some((pair.i, pair.j));
-                }
-                // Otherwise, this pair was already connected, skip it
-            } else {
-                return Err(anyhow!("Ran out of pairs before forming single cluster"));
-            }
-        };
-        
-        // If we found a pair, connect them
-        if let Some((i, j)) = closest_pair {
-            let key = if i < j { (i, j) } else { (j, i) };
-            connected_pairs.insert(key);
-            connections_made += 1;
-            last_connected_pair = Some((i, j));
-            
-            if n >= 100 && connections_made % 100 == 0 {
-                println!("  Made {} connections, {} circuits remaining...", 
-                         connections_made, num_clusters);
-            }
-            
-            let cluster_i = coordinate_to_cluster[&i];
-            let cluster_j = coordinate_to_cluster[&j];
-            
-            if cluster_i != cluster_j {
-                // Merge the two clusters
-                let cluster_j_members: Vec<usize> = clusters[cluster_j].iter().copied().collect();
-                for member in cluster_j_members {
-                    clusters[cluster_i].insert(member);
-                    coordinate_to_cluster.insert(member, cluster_i);
-                }
-                clusters[cluster_j].clear();
-                num_clusters -= 1; // We merged two clusters into one
-            }
-            // else: both already in same cluster, connection just adds redundancy
+    while clusterer.circuits() > 1 {
+        let connection = clusterer
+            .connect_next()
+            .ok_or_else(|| match search {
+                PairSearch::Exact => AocError::solve("Ran out of pairs before forming single cluster"),
+                PairSearch::Approximate { cell } => AocError::solve(format!(
+                    "Ran out of pairs before forming single cluster; try a --cell larger than {}", cell
+                )),
+            })?;
+        if clusterer.connections().is_multiple_of(100) {
+            progress.set_message(&format!("{} connections", clusterer.connections()));
+        }
+        if let Some(plan) = checkpoint.filter(|plan| clusterer.connections().is_multiple_of(plan.every)) {
+            plan.save(&clusterer, coordinates)?;
+        }
+        if connection.merged {
+            progress.inc(1);
         }
     }
     
-    println!("\nAll junction boxes connected into a single circuit!");
-    println!("Total connections made: {}", connections_made);
+    progress.finish();
+    warn_if_uncertified(&clusterer, out);
+    
+    out.line("\nAll junction boxes connected into a single circuit!");
+    out.line(&format!("Total connections made: {}", clusterer.connections()));
     
-    if let Some((i, j)) = last_connected_pair {
+    if let Some((i, j)) = clusterer.last {
         let x_product = (coordinates[i].x as i64) * (coordinates[j].x as i64);
-        println!("\nLast connection: junction box {} (x={}) <-> junction box {} (x={})",
-                 i, coordinates[i].x, j, coordinates[j].x);
-        println!("Product of X coordinates: {} * {} = {}", 
-                 coordinates[i].x, coordinates[j].x, x_product);
+        out.line(&format!("\nLast connection: junction box {} (x={}) <-> junction box {} (x={})",
+                 i, coordinates[i].x, j, coordinates[j].x));
+        let working = format!("{} * {}", coordinates[i].x, coordinates[j].x);
+        out.worked_answer(2, "Product of X coordinates", &working, &x_product);
         Ok(x_product)
     } else {
-        Err(anyhow!("No connections were made"))
+        Err(AocError::solve("No connections were made"))
+    }
+}
+
+/// The circuit picture after some number of connections in a sweep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepPoint {
+    pub connections: usize,
+    pub circuits: usize,
+    pub product: usize,
+}
+
+/// Records the circuits after every `step` connections up to
+/// `max_connections`, all from one run of a single `Clusterer`
+pub fn sweep(coordinates: &[Coordinate3D], step: usize, max_connections: usize, search: PairSearch, progress: &dyn Progress, out: &dyn Reporter) -> Vec<SweepPoint> {
+    let mut clusterer = Clusterer::with_search(coordinates, search, progress);
+    let mut points = Vec::new();
+
+    progress.start("Sweep", max_connections as u64);
+    while clusterer.connections() < max_connections && clusterer.connect_next().is_some() {
+        progress.inc(1);
+        if clusterer.connections().is_multiple_of(step) {
+            points.push(SweepPoint {
+                connections: clusterer.connections(),
+                circuits: clusterer.circuits(),
+                product: product_of_three_largest(&clusterer.cluster_sizes()),
+            });
+        }
+    }
+    progress.finish();
+    warn_if_uncertified(&clusterer, out);
+    points
+}
+
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// One row per circuit size: the size, a bar scaled to the most common
+/// size, and the number of circuits
+fn render_histogram(sizes: &[usize]) -> String {
+    let counts = size_counts(sizes);
+    let max_count = counts.iter().map(|&(_, count)| count).max().unwrap_or(1);
+    let label_width = counts.last().map_or(1, |&(size, _)| size.to_string().len());
+
+    let mut out = String::new();
+    for (size, count) in counts {
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(max_count);
+        out.push_str(&format!("{:>w$} | {} {}\n", size, "#".repeat(bar), count, w = label_width));
+    }
+    out
+}
+
+const SVG_BAR_WIDTH: usize = 24;
+const SVG_CHART_HEIGHT: usize = 200;
+const SVG_MARGIN: usize = 30;
+
+/// Bar chart of the number of circuits of each size, sizes along the bottom
+fn histogram_svg(sizes: &[usize]) -> String {
+    let counts = size_counts(sizes);
+    let max_count = counts.iter().map(|&(_, count)| count).max().unwrap_or(1);
+    let w = counts.len() * SVG_BAR_WIDTH + 2 * SVG_MARGIN;
+    let h = SVG_CHART_HEIGHT + 2 * SVG_MARGIN;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        w, h, w, h
+    );
+
+    let baseline = SVG_MARGIN + SVG_CHART_HEIGHT;
+    for (index, (size, count)) in counts.into_iter().enumerate() {
+        let bar_height = (count * SVG_CHART_HEIGHT).div_ceil(max_count);
+        let x = SVG_MARGIN + index * SVG_BAR_WIDTH;
+        let center = x + SVG_BAR_WIDTH / 2;
+        out.push_str(&format!(
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#4682b4\"/>\n",
+            x + 2, baseline - bar_height, SVG_BAR_WIDTH - 4, bar_height
+        ));
+        out.push_str(&format!(
+            "  <text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n",
+            center, baseline - bar_height - 4, count
+        ));
+        out.push_str(&format!(
+            "  <text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n",
+            center, baseline + 14, size
+        ));
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Product of the three largest circuits after 1000 connections
+pub fn part1(input: &str) -> Result<usize> {
+    Ok(create_clusters(&parse_input(input)?, PUZZLE_CONNECTIONS, PairSearch::Exact, &Hidden, &Silent).product)
+}
+
+/// Product of the X coordinates of the last pair joined into one circuit
+pub fn part2(input: &str) -> Result<i64> {
+    connect_until_single_cluster(&parse_input(input)?, PairSearch::Exact, &Hidden, &Silent)
+}
+
+/// Connections part 1 makes on the full puzzle
+const PUZZLE_CONNECTIONS: usize = 1000;
+
+/// `--search` choices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SearchMode {
+    /// Consider every pair of boxes
+    Exact,
+    /// Consider only boxes in neighbouring grid cells, for very large inputs
+    Approximate,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day08", about = "Day 8: Playground")]
+struct Options {
+    /// Connections to make for part 1, the histogram and the sweep
+    #[arg(long, value_name = "N", default_value_t = PUZZLE_CONNECTIONS)]
+    connections: usize,
+    /// Print an ASCII histogram of circuit sizes after part 1
+    #[arg(long)]
+    histogram: bool,
+    /// Count triangles and redundant connections per circuit after part 1,
+    /// and find the most densely connected circuit
+    #[arg(long)]
+    triangles: bool,
+    /// Write an SVG histogram of circuit sizes after part 1 to this file
+    #[arg(long, value_name = "FILE")]
+    svg: Option<String>,
+    /// Instead of the puzzle parts, report the three-largest product every N
+    /// connections up to --connections
+    #[arg(long, value_name = "N")]
+    sweep: Option<usize>,
+    /// Coordinates file [default: day08coordinates.txt in the input directory]
+    #[arg(long)]
+    input: Option<String>,
+    /// How to find the closest pairs
+    #[arg(long, value_enum, default_value_t = SearchMode::Exact)]
+    search: SearchMode,
+    /// Grid cell size for --search approximate; pairs up to this far apart are
+    /// always considered [default: sized for about two boxes per cell]
+    #[arg(long, value_name = "SIZE", value_parser = clap::value_parser!(i64).range(1..))]
+    cell: Option<i64>,
+    /// Save part 2's progress to this file as it goes
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<String>,
+    /// Connections between part 2 checkpoints
+    #[arg(long, value_name = "N", default_value_t = 10_000, requires = "checkpoint",
+          value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: u64,
+    /// Skip part 1 and carry on part 2 from a saved checkpoint
+    #[arg(long, value_name = "FILE", conflicts_with = "sweep")]
+    resume: Option<String>,
+}
+
+impl Options {
+    fn pair_search(&self, coordinates: &[Coordinate3D]) -> Result<PairSearch> {
+        match (self.search, self.cell) {
+            (SearchMode::Exact, None) => Ok(PairSearch::Exact),
+            (SearchMode::Exact, Some(_)) => Err(AocError::usage("--cell only applies to --search approximate")),
+            (SearchMode::Approximate, Some(cell)) => Ok(PairSearch::Approximate { cell }),
+            (SearchMode::Approximate, None) => Ok(PairSearch::approximate(coordinates)),
+        }
+    }
+}
+
+fn run_sweep(coordinates: &[Coordinate3D], step: usize, max_connections: usize, search: PairSearch, out: &dyn OutputSink) -> Result<()> {
+    if step == 0 {
+        return Err(AocError::usage("--sweep must be at least 1"));
+    }
+
+    out.line(&format!("\n=== Sweep: every {} connections up to {} ===", step, max_connections));
+    let progress = out.progress();
+    let points = stats::measure("Sweep", || sweep(coordinates, step, max_connections, search, progress.as_ref(), out));
+    out.line(&format!("{:>11}  {:>8}  {:>8}", "connections", "circuits", "product"));
+    for point in points {
+        out.line(&format!("{:>11}  {:>8}  {:>8}", point.connections, point.circuits, point.product));
+    }
+    Ok(())
+}
+
+/// Puts part 1's connection lengths in the `--stats` report
+fn record_distances(clustering: &Clustering) {
+    let named = [
+        ("shortest", clustering.kth_connection_distance(1)),
+        ("median", clustering.median_connection_distance()),
+        ("p90", clustering.distance_percentile(90.0)),
+        ("longest", clustering.distances.last().copied()),
+    ];
+    for (name, distance) in named {
+        if let Some(distance) = distance {
+            stats::record(&format!("day08.part1.distance_{}", name), distance);
+        }
+    }
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Playground",
+        input_files: &["coordinates"],
+        parts: [
+            "Product of the three largest circuits after 1000 connections",
+            "Product of the X coordinates of the last pair joined into one circuit",
+        ],
+        notes: "Part 2 can be checkpointed and resumed; traces record merges; --stats reports part 1 connection lengths; --triangles reports per-circuit triangles and density",
     }
 }
 
 /// Day 8: Playground - Junction Box Circuit Analysis
-pub fn run() -> Result<()> {
-    let coordinates = parse_input("assets/day08coordinates.txt")?;
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day08".to_string()).chain(args.iter().cloned()));
+    let coordinates = match &options.input {
+        Some(path) => parse_input(input::read_file(path)?).map_err(|e| e.in_file(path))?,
+        None => Input::for_day(8).parse("coordinates", parse_input)?,
+    };
     
-    println!("Day 8: Loaded {} coordinates", coordinates.len());
+    out.line(&format!("Day 8: Loaded {} coordinates", coordinates.len()));
+    let search = options.pair_search(&coordinates)?;
+    if let PairSearch::Approximate { cell } = search {
+        out.line(&format!("Approximate search with cells of size {}", cell));
+    }
     
-    // Part 1: Connect 1000 closest pairs for the full puzzle
-    println!("\n=== Part 1: Limited Connections ===");
-    create_clusters(&coordinates, 1000);
+    if let Some(step) = options.sweep {
+        return run_sweep(&coordinates, step, options.connections, search, out);
+    }
+    
+    let plan = options.checkpoint.clone().map(|path| CheckpointPlan { path, every: options.checkpoint_every as usize });
+    if let Some(path) = &options.resume {
+        let text = fs::read_to_string(path).map_err(|e| AocError::io(path, e))?;
+        let clusterer = Clusterer::read_checkpoint(&coordinates, &text).map_err(|e| e.in_file(path))?;
+        out.line(&format!("\n=== Part 2: Single Circuit (resumed after {} connections) ===", clusterer.connections()));
+        let progress = out.progress();
+        stats::measure("Part 2", || finish_single_cluster(&coordinates, clusterer, search, plan.as_ref(), progress.as_ref(), out))?;
+        return Ok(());
+    }
+    
+    // Part 1: Connect the closest pairs (1000 for the full puzzle)
+    out.line("\n=== Part 1: Limited Connections ===");
+    let progress = out.progress();
+    let clustering = stats::measure("Part 1", || create_clusters(&coordinates, options.connections, search, progress.as_ref(), out));
+    record_distances(&clustering);
+    if options.triangles {
+        report_circuit_metrics(&clustering, out);
+    }
+    let cluster_sizes = clustering.sizes;
+    
+    if options.histogram {
+        out.line(&format!("\nCircuit sizes after {} connections:", options.connections));
+        out.block(&render_histogram(&cluster_sizes));
+    }
+    if let Some(path) = &options.svg {
+        fs::write(path, histogram_svg(&cluster_sizes)).map_err(|e| AocError::io(path, e))?;
+        out.line(&format!("\nWrote circuit size histogram to {}", path));
+    }
     
     // Part 2: Connect until all are in a single circuit
-    println!("\n=== Part 2: Single Circuit ===");
-    connect_until_single_cluster(&coordinates)?;
+    out.line("\n=== Part 2: Single Circuit ===");
+    stats::measure("Part 2", || connect_with_checkpoints(&coordinates, search, plan.as_ref(), progress.as_ref(), out))?;
     
     Ok(())
 }
@@ -357,18 +967,26 @@ pub fn run() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::reporter::Captured;
+    use std::collections::HashSet;
+    use crate::util::snapshot;
 
     #[test]
     fn test_example() {
         // Load the example data (20 junction boxes)
-        let coordinates = parse_input("assets/day08example.txt")
+        let coordinates = parse_input(Input::for_day(8).example().unwrap())
             .expect("Failed to load example data");
         
         assert_eq!(coordinates.len(), 20, "Example should have 20 junction boxes");
         
         // After making 10 connections, should have 11 circuits
         // Largest: 5, 4, 2 -> product = 40
-        let (cluster_sizes, product) = create_clusters(&coordinates, 10);
+        let captured = Captured::new();
+        let Clustering { sizes: cluster_sizes, product, distances, .. } = create_clusters(&coordinates, 10, PairSearch::Exact, &Hidden, &captured);
+        assert_eq!(distances.len(), 10);
+        assert_eq!(captured.lines()[0], "Clustering 20 coordinates...");
+        assert!(captured.text().ends_with("\nProduct of three largest circuits: 5 * 4 * 2 = 40"), "{}", captured.text());
+        assert!(distances.windows(2).all(|w| w[0] <= w[1]), "distances should be shortest first");
         
         assert_eq!(cluster_sizes.len(), 11, "Should have 11 circuits after 10 connections");
         assert_eq!(cluster_sizes[0], 5, "Largest circuit should have 5 junction boxes");
@@ -380,32 +998,139 @@ mod tests {
     #[test]
     fn test_full_puzzle() {
         // Load the full puzzle data (1000 junction boxes)
-        let coordinates = parse_input("assets/day08coordinates.txt")
+        let coordinates = parse_input(Input::for_day(8).read("coordinates").unwrap())
             .expect("Failed to load full puzzle data");
         
         assert_eq!(coordinates.len(), 1000, "Full puzzle should have 1000 junction boxes");
         
-        // After making 1000 connections, should have 296 circuits
-        // Largest: 57, 37, 32 -> product = 67488
-        let (cluster_sizes, product) = create_clusters(&coordinates, 1000);
+        // Circuits left after 1000 connections, and the three largest
+        let Clustering { sizes: cluster_sizes, product, .. } = create_clusters(&coordinates, 1000, PairSearch::Exact, &Hidden, &Silent);
         
-        assert_eq!(cluster_sizes.len(), 296, "Should have 296 circuits after 1000 connections");
-        assert_eq!(cluster_sizes[0], 57, "Largest circuit should have 57 junction boxes");
-        assert_eq!(cluster_sizes[1], 37, "Second largest circuit should have 37 junction boxes");
-        assert_eq!(cluster_sizes[2], 32, "Third largest circuit should have 32 junction boxes");
-        assert_eq!(product, 67488, "Product of three largest circuits should be 67488");
+        snapshot::check("day08", "circuits", cluster_sizes.len());
+        snapshot::check("day08", "largest_circuits", &cluster_sizes[..3]);
+        snapshot::check("day08", "largest_circuits_product", product);
+    }
+
+    #[test]
+    fn test_sweep_matches_fresh_runs() {
+        let coordinates = parse_input(Input::for_day(8).example().unwrap()).unwrap();
+        let points = sweep(&coordinates, 5, 20, PairSearch::Exact, &Hidden, &Silent);
+        assert_eq!(points.iter().map(|p| p.connections).collect::<Vec<_>>(), vec![5, 10, 15, 20]);
+        for point in points {
+            let clustering = create_clusters(&coordinates, point.connections, PairSearch::Exact, &Hidden, &Silent);
+            assert_eq!((point.circuits, point.product), (clustering.sizes.len(), clustering.product), "after {} connections", point.connections);
+        }
+    }
+
+    #[test]
+    fn test_approximate_search_agrees_on_full_puzzle() {
+        let coordinates = parse_input(Input::for_day(8).read("coordinates").unwrap()).unwrap();
+        let search = PairSearch::approximate(&coordinates);
+
+        let exact = create_clusters(&coordinates, PUZZLE_CONNECTIONS, PairSearch::Exact, &Hidden, &Silent);
+        let approximate = create_clusters(&coordinates, PUZZLE_CONNECTIONS, search, &Hidden, &Silent);
+        assert_eq!(approximate, exact, "part 1 with {:?}", search);
+
+        let exact = connect_until_single_cluster(&coordinates, PairSearch::Exact, &Hidden, &Silent).unwrap();
+        let approximate = connect_until_single_cluster(&coordinates, search, &Hidden, &Silent).unwrap();
+        assert_eq!(approximate, exact, "part 2 with {:?}", search);
+
+        // Part 1 stays within the cell size, so the approximation is certified
+        let mut clusterer = Clusterer::with_search(&coordinates, search, &Hidden);
+        for _ in 0..PUZZLE_CONNECTIONS {
+            clusterer.connect_next();
+        }
+        assert!(clusterer.certified());
+    }
+
+    #[test]
+    fn test_approximate_search_finds_close_pairs() {
+        let coordinates = parse_input("0,0,0\n5,0,0\n100,0,0\n104,3,0\n").unwrap();
+        let mut clusterer = Clusterer::with_search(&coordinates, PairSearch::Approximate { cell: 10 }, &Hidden);
+        assert_eq!(clusterer.connect_next(), Some(Connection { i: 0, j: 1, distance: 5.0, merged: true }));
+        assert_eq!(clusterer.connect_next(), Some(Connection { i: 2, j: 3, distance: 5.0, merged: true }));
+        // The two groups are cells apart, so nothing joins them
+        assert_eq!(clusterer.connect_next(), None);
+        assert!(clusterer.certified());
+        assert!(connect_until_single_cluster(&coordinates, PairSearch::Approximate { cell: 10 }, &Hidden, &Silent).is_err());
+    }
+
+    #[test]
+    fn test_connection_distances() {
+        let coordinates = parse_input("0,0,0\n1,0,0\n3,0,0\n6,0,0\n10,0,0\n").unwrap();
+        let clustering = create_clusters(&coordinates, 4, PairSearch::Exact, &Hidden, &Silent);
+        assert_eq!(clustering.distances, vec![1.0, 2.0, 3.0, 3.0]);
+
+        assert_eq!(clustering.kth_connection_distance(0), None);
+        assert_eq!(clustering.kth_connection_distance(1), Some(1.0));
+        assert_eq!(clustering.kth_connection_distance(4), Some(3.0));
+        assert_eq!(clustering.kth_connection_distance(5), None);
+
+        assert_eq!(clustering.distance_percentile(0.0), Some(1.0));
+        assert_eq!(clustering.distance_percentile(25.0), Some(1.0));
+        assert_eq!(clustering.distance_percentile(26.0), Some(2.0));
+        assert_eq!(clustering.median_connection_distance(), Some(2.0));
+        assert_eq!(clustering.distance_percentile(100.0), Some(3.0));
+        assert_eq!(clustering.distance_percentile(100.5), None);
+        assert_eq!(clustering.distance_percentile(f64::NAN), None);
+
+        let none = create_clusters(&coordinates, 0, PairSearch::Exact, &Hidden, &Silent);
+        assert_eq!(none.median_connection_distance(), None);
+    }
+
+    #[test]
+    fn test_circuit_metrics() {
+        let metrics = circuit_metrics(6, &[(0, 1), (1, 2), (2, 0), (3, 4)]);
+        assert_eq!(metrics[0], CircuitMetrics { boxes: vec![0, 1, 2], connections: 3, triangles: 1 });
+        assert_eq!((metrics[0].redundant(), metrics[0].density()), (1, 1.0));
+        assert_eq!(metrics[1], CircuitMetrics { boxes: vec![3, 4], connections: 1, triangles: 0 });
+        assert_eq!(metrics[2].boxes, [5]);
+        let complete: Vec<(usize, usize)> = (0..5).flat_map(|i| (i + 1..5).map(move |j| (i, j))).collect();
+        assert_eq!(circuit_metrics(5, &complete)[0].triangles, 10);
+
+        // Against checking every triple of boxes joined by the connections made
+        let coordinates = parse_input(Input::for_day(8).read("coordinates").unwrap()).unwrap();
+        let clustering = create_clusters(&coordinates, 3000, PairSearch::Exact, &Hidden, &Silent);
+        let joined: HashSet<(usize, usize)> = clustering.edges.iter().map(|&(i, j)| (i.min(j), i.max(j))).collect();
+        let expected = joined
+            .iter()
+            .map(|&(i, j)| (j + 1..coordinates.len()).filter(|&k| joined.contains(&(i, k)) && joined.contains(&(j, k))).count())
+            .sum::<usize>();
+        let metrics = clustering.circuit_metrics();
+        assert!(expected > 0);
+        assert_eq!(metrics.iter().map(|m| m.triangles).sum::<usize>(), expected);
+        assert_eq!(metrics.iter().map(|m| m.boxes.len()).collect::<Vec<_>>(), clustering.sizes);
+        assert_eq!(metrics.iter().map(|m| m.connections).sum::<usize>(), 3000);
+
+        let captured = Captured::new();
+        report_circuit_metrics(&clustering, &captured);
+        assert_eq!(captured.lines()[1], format!("  Triangles: {}", expected));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let sizes = [5, 4, 2, 2, 1, 1, 1, 1, 1, 1, 1];
+        assert_eq!(render_histogram(&sizes), concat!(
+            "1 | ################################################## 7\n",
+            "2 | ############### 2\n",
+            "4 | ######## 1\n",
+            "5 | ######## 1\n",
+        ));
+        let svg = histogram_svg(&sizes);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 4);
     }
 
     #[test]
     fn test_single_cluster_example() {
         // Load the example data (20 junction boxes)
-        let coordinates = parse_input("assets/day08example.txt")
+        let coordinates = parse_input(Input::for_day(8).example().unwrap())
             .expect("Failed to load example data");
         
         assert_eq!(coordinates.len(), 20, "Example should have 20 junction boxes");
         
         // Connect until all are in a single circuit (requires 19 connections)
-        let x_product = connect_until_single_cluster(&coordinates)
+        let x_product = connect_until_single_cluster(&coordinates, PairSearch::Exact, &Hidden, &Silent)
             .expect("Failed to create single cluster");
         
         // The answer will depend on the data, just verify we got a result
@@ -415,16 +1140,66 @@ mod tests {
     #[test]
     fn test_single_cluster_full_puzzle() {
         // Load the full puzzle data (1000 junction boxes)
-        let coordinates = parse_input("assets/day08coordinates.txt")
+        let coordinates = parse_input(Input::for_day(8).read("coordinates").unwrap())
             .expect("Failed to load full puzzle data");
         
         assert_eq!(coordinates.len(), 1000, "Full puzzle should have 1000 junction boxes");
         
         // Connect until all are in a single circuit (requires 6282 connections)
-        let x_product = connect_until_single_cluster(&coordinates)
+        let x_product = connect_until_single_cluster(&coordinates, PairSearch::Exact, &Hidden, &Silent)
             .expect("Failed to create single cluster");
         
         // The answer is the product of X coordinates of the last two connected junction boxes
-        assert_eq!(x_product, 3767453340, "Product of X coordinates should be 3767453340");
+        snapshot::check("day08", "last_connection_x_product", x_product);
+    }
+
+    #[test]
+    fn test_checkpoint_resumes_part_2() {
+        let coordinates = parse_input(Input::for_day(8).read("coordinates").unwrap()).unwrap();
+        let mut clusterer = Clusterer::with_search(&coordinates, PairSearch::Exact, &Hidden);
+        for _ in 0..3000 {
+            clusterer.connect_next().unwrap();
+        }
+        let mut saved = Vec::new();
+        clusterer.write_checkpoint(&coordinates, &mut saved).unwrap();
+        let text = String::from_utf8(saved).unwrap();
+
+        let resumed = Clusterer::read_checkpoint(&coordinates, &text).unwrap();
+        assert_eq!((resumed.connections(), resumed.circuits()), (clusterer.connections(), clusterer.circuits()));
+        let x_product = finish_single_cluster(&coordinates, resumed, PairSearch::Exact, None, &Hidden, &Silent).unwrap();
+        assert_eq!(x_product, connect_until_single_cluster(&coordinates, PairSearch::Exact, &Hidden, &Silent).unwrap());
+
+        let example = parse_input(Input::for_day(8).example().unwrap()).unwrap();
+        assert!(Clusterer::read_checkpoint(&example, &text).is_err(), "checkpoint for other boxes");
+        assert!(Clusterer::read_checkpoint(&coordinates, "{\"version\": 2,").is_err());
+
+        let corrupted = |change: &dyn Fn(&mut Checkpoint)| {
+            let mut checkpoint: Checkpoint = serde_json::from_str(&text).unwrap();
+            change(&mut checkpoint);
+            Clusterer::read_checkpoint(&coordinates, &serde_json::to_string(&checkpoint).unwrap()).err().unwrap().to_string()
+        };
+        let n = coordinates.len();
+        assert!(corrupted(&|c| c.parent[0] = n).contains("only 1000 boxes"));
+        let roots: Vec<usize> = (0..n).filter(|&i| clusterer.parent[i] == i).take(2).collect();
+        assert!(corrupted(&|c| {
+            c.parent[roots[0]] = roots[1];
+            c.parent[roots[1]] = roots[0];
+        })
+        .contains("cycle"));
+        assert!(corrupted(&|c| {
+            c.size.pop();
+        })
+        .contains("sizes"));
+        assert!(corrupted(&|c| c.circuits += 1).contains("roots"));
+        assert!(corrupted(&|c| c.pairs.push((0, n))).contains("Invalid pair"));
+    }
+
+    #[test]
+    fn test_check_union_find() {
+        assert!(check_union_find(&[0, 0, 1], &[3, 1, 1], 1).is_ok());
+        assert!(check_union_find(&[0, 2, 1], &[1, 2, 1], 1).unwrap_err().to_string().contains("cycle"));
+        assert!(check_union_find(&[1, 0], &[1, 1], 0).unwrap_err().to_string().contains("cycle"));
+        assert!(check_union_find(&[0, 3, 1], &[3, 1, 1], 1).unwrap_err().to_string().contains("parent 3"));
+        assert!(check_union_find(&[0, 1], &[1, 5], 2).unwrap_err().to_string().contains("add up"));
     }
 }
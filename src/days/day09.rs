@@ -1,46 +1,107 @@
-use anyhow::{anyhow, Context, Result};
-use std::fs;
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use crate::input::{self, Input};
+use crate::util::grid::Grid;
+use crate::util::parse;
+use crate::util::output::OutputSink;
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::stats;
+use clap::Parser;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Coordinate {
-    x: usize,
-    y: usize,
-}
-
-fn parse_input(filename: &str) -> Result<Vec<Coordinate>> {
-    let content = fs::read_to_string(filename)
-        .context(format!("Failed to read file: {}", filename))?;
-
-    // Parse all coordinates
-    let coordinates: Vec<Coordinate> = content
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .enumerate()
-        .map(|(i, line)| {
-            let parts: Vec<&str> = line.trim().split(',').collect();
-            if parts.len() != 2 {
-                return Err(anyhow!(
-                    "Line {} has {} values, expected 2 comma-separated values",
-                    i + 1,
-                    parts.len()
-                ));
-            }
+pub struct Coordinate {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
 
-            let x = parts[0].parse::<usize>()
-                .context(format!("Failed to parse x coordinate on line {}", i + 1))?;
-            let y = parts[1].parse::<usize>()
-                .context(format!("Failed to parse y coordinate on line {}", i + 1))?;
+/// A number of tiles. Sides are `usize`, so areas are u128, where the
+/// product of any two sides fits unless both span the whole `u64` range;
+/// that last case is caught by checked arithmetic instead of wrapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Area(u128);
+
+impl Area {
+    pub const ZERO: Area = Area(0);
+
+    /// `width` x `height` tiles
+    pub fn new(width: u128, height: u128) -> Result<Area> {
+        width
+            .checked_mul(height)
+            .map(Area)
+            .ok_or_else(|| AocError::solve(format!("Area of a {} x {} rectangle overflows u128", width, height)))
+    }
 
-            Ok(Coordinate { x, y })
-        })
-        .collect::<Result<Vec<_>>>()?;
+    /// Tiles in the rectangle with opposite corners `a` and `b`, both included
+    pub fn spanning(a: Coordinate, b: Coordinate) -> Result<Area> {
+        Area::new(a.x.abs_diff(b.x) as u128 + 1, a.y.abs_diff(b.y) as u128 + 1)
+    }
+
+    pub fn get(self) -> u128 {
+        self.0
+    }
+}
+
+impl fmt::Display for Area {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Bytes in a typical `x,y` line, for sizing the tile vector up front
+const BYTES_PER_TILE: usize = 12;
 
+pub fn parse_input(content: &str) -> Result<Vec<Coordinate>> {
+    parse_reader(content.as_bytes(), "input", content.len() / BYTES_PER_TILE + 1)
+}
+
+/// The red tiles read from `reader` a line at a time into a vector with
+/// room for `capacity` of them, so a file of millions never has to be held
+/// as text as well. `source` names the reader in I/O errors.
+pub fn parse_reader<R: BufRead>(mut reader: R, source: &str, capacity: usize) -> Result<Vec<Coordinate>> {
+    let mut coordinates = Vec::with_capacity(capacity);
+    let mut line = String::new();
+    let mut line_no = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| AocError::io(source, e))? == 0 {
+            break;
+        }
+        line_no += 1;
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let [x, y] = parse::tuple::<usize, 2>(text, line_no)?;
+        coordinates.push(Coordinate { x, y });
+    }
     Ok(coordinates)
 }
 
-fn find_largest_rectangle(coordinates: &[Coordinate]) -> Option<Square> {
+/// Tiles above which `find_largest_rectangle` stops trying every pair and
+/// only pairs up the tiles on the staircases
+pub const PAIR_SEARCH_LIMIT: usize = 10_000;
+
+/// Largest rectangle with red tiles in opposite corners, or None with fewer
+/// than two tiles; fails only if an area overflows. Past
+/// `PAIR_SEARCH_LIMIT` tiles this is `find_largest_rectangle_pruned`, which
+/// finds the same area but may pick different corners on a tie.
+pub fn find_largest_rectangle(coordinates: &[Coordinate]) -> Result<Option<Square>> {
     if coordinates.len() < 2 {
-        return None;
+        return Ok(None);
+    }
+    if coordinates.len() > PAIR_SEARCH_LIMIT {
+        return find_largest_rectangle_pruned(coordinates);
     }
 
     let mut largest_square: Option<Square> = None;
@@ -51,25 +112,13 @@ fn find_largest_rectangle(coordinates: &[Coordinate]) -> Option<Square> {
             let coord1 = coordinates[i];
             let coord2 = coordinates[j];
 
-            // Calculate distances
-            let dx = if coord1.x > coord2.x {
-                coord1.x - coord2.x
-            } else {
-                coord2.x - coord1.x
-            };
-            let dy = if coord1.y > coord2.y {
-                coord1.y - coord2.y
-            } else {
-                coord2.y - coord1.y
-            };
-
             // Both dimensions must be non-zero to form a rectangle
-            if dx == 0 || dy == 0 {
+            if coord1.x == coord2.x || coord1.y == coord2.y {
                 continue;
             }
 
-            // Calculate area (add 1 to each dimension because coordinates are inclusive)
-            let area = (dx + 1) * (dy + 1);
+            // Coordinates are inclusive, so each side is one more than the distance
+            let area = Area::spanning(coord1, coord2)?;
 
             // Update largest square if this one is bigger
             if largest_square.is_none() || area > largest_square.unwrap().area {
@@ -82,7 +131,271 @@ fn find_largest_rectangle(coordinates: &[Coordinate]) -> Option<Square> {
         }
     }
 
-    largest_square
+    Ok(largest_square)
+}
+
+/// A tile on a staircase, with y negated for the staircases of rectangles
+/// that run from top left to bottom right
+#[derive(Debug, Clone, Copy)]
+struct StairTile {
+    x: i128,
+    y: i128,
+    /// Index into the coordinates
+    index: usize,
+}
+
+/// Tiles in the rectangle from `low` to `high`, negative when `high` is
+/// left of or below `low`. Over `low` on a minimal staircase and `high` on
+/// a maximal one, both by rising x, this is a Monge matrix: the best `high`
+/// for each `low` never moves left as `low` moves right.
+fn stair_area(low: StairTile, high: StairTile) -> Result<i128> {
+    (high.x - low.x + 1)
+        .checked_mul(high.y - low.y + 1)
+        .ok_or_else(|| AocError::solve(format!("Area of the rectangle between tiles {} and {} overflows i128", low.index + 1, high.index + 1)))
+}
+
+/// For rows `lows`, the best column in `highs[first..=last]`, rightmost on a
+/// tie so the columns stay monotone; divide and conquer over the rows keeps
+/// this at O(n log n) evaluations. Returns the best (area, low, high).
+fn best_across(lows: &[StairTile], highs: &[StairTile], first: usize, last: usize) -> Result<Option<(i128, usize, usize)>> {
+    if lows.is_empty() {
+        return Ok(None);
+    }
+    let mid = lows.len() / 2;
+    let mut best = (stair_area(lows[mid], highs[first])?, first);
+    for (col, &high) in highs.iter().enumerate().take(last + 1).skip(first + 1) {
+        let area = stair_area(lows[mid], high)?;
+        if area >= best.0 {
+            best = (area, col);
+        }
+    }
+    let (area, col) = best;
+    let mut result = (area, lows[mid].index, highs[col].index);
+    let left = best_across(&lows[..mid], highs, first, col)?;
+    let right = best_across(&lows[mid + 1..], highs, col, last)?;
+    for candidate in [left, right].into_iter().flatten() {
+        if candidate.0 > result.0 {
+            result = candidate;
+        }
+    }
+    Ok(Some(result))
+}
+
+/// Largest rectangle between a tile on the bottom-left staircase (tiles no
+/// other tile is both left of and below) and one on the top-right
+/// staircase. Moving a corner further out never shrinks a rectangle, so the
+/// largest one has its corners on the staircases.
+fn best_between_staircases(tiles: &mut [StairTile]) -> Result<Option<(i128, usize, usize)>> {
+    tiles.sort_unstable_by_key(|t| (t.x, t.y));
+    let mut lows: Vec<StairTile> = Vec::new();
+    for &tile in tiles.iter() {
+        if lows.last().is_none_or(|low| tile.y < low.y) {
+            lows.push(tile);
+        }
+    }
+    let mut highs: Vec<StairTile> = Vec::new();
+    for &tile in tiles.iter().rev() {
+        if highs.last().is_none_or(|high| tile.y > high.y) {
+            highs.push(tile);
+        }
+    }
+    highs.reverse();
+    stats::add("day09.staircase_tiles", (lows.len() + highs.len()) as u64);
+    best_across(&lows, &highs, 0, highs.len() - 1)
+}
+
+/// `find_largest_rectangle` for inputs too big to try every pair: only
+/// tiles on the four staircases can be corners of the largest rectangle,
+/// and between two staircases the best partners move monotonically, so
+/// this is O(n log n) however the tiles lie
+pub fn find_largest_rectangle_pruned(coordinates: &[Coordinate]) -> Result<Option<Square>> {
+    if coordinates.len() < 2 {
+        return Ok(None);
+    }
+    let mut best: Option<(i128, usize, usize)> = None;
+    // Bottom left to top right, then top left to bottom right with y flipped
+    for flip in [1, -1] {
+        let mut tiles: Vec<StairTile> = coordinates
+            .iter()
+            .enumerate()
+            .map(|(index, c)| StairTile { x: c.x as i128, y: flip * c.y as i128, index })
+            .collect();
+        if let Some(found) = best_between_staircases(&mut tiles)? {
+            if best.is_none_or(|best| found.0 > best.0) {
+                best = Some(found);
+            }
+        }
+    }
+
+    // Any rectangle with a red tile beside the best one's line would be
+    // bigger, so a best with a zero side means every tile is on one line
+    let Some((_, i, j)) = best else { return Ok(None) };
+    let (corner1, corner2) = (coordinates[i], coordinates[j]);
+    if corner1.x == corner2.x || corner1.y == corner2.y {
+        return Ok(None);
+    }
+    Ok(Some(Square { corner1, corner2, area: Area::spanning(corner1, corner2)? }))
+}
+
+/// The red tiles as a closed loop, in the order the boundary visits them.
+/// Validated once by `boundary`, then shared by the part 2 solvers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Boundary {
+    pub vertices: Vec<Coordinate>,
+    /// The tiles weren't given in boundary order and had to be walked
+    pub reordered: bool,
+}
+
+/// Checks that the red tiles, in the order given, outline a simple
+/// rectilinear polygon; when they don't, walks the boundary to order them.
+/// Walking only works when every red tile is a corner: then each one has
+/// exactly one horizontal and one vertical neighbour along the boundary.
+pub fn boundary(coordinates: &[Coordinate]) -> Result<Boundary> {
+    if coordinates.len() < 4 {
+        return Err(AocError::solve(format!("Need at least four red tiles for a polygon, got {}", coordinates.len())));
+    }
+    let mut seen = HashSet::new();
+    if let Some(repeat) = coordinates.iter().find(|&&c| !seen.insert(c)) {
+        return Err(AocError::solve(format!("Red tile {} appears twice", repeat)));
+    }
+
+    let given = match check_simple_loop(coordinates) {
+        Ok(()) => return Ok(Boundary { vertices: coordinates.to_vec(), reordered: false }),
+        Err(message) => message,
+    };
+    match walk_boundary(coordinates).and_then(|vertices| check_simple_loop(&vertices).map(|()| vertices)) {
+        Ok(vertices) => Ok(Boundary { vertices, reordered: true }),
+        Err(walked) => Err(AocError::solve(format!(
+            "Red tiles don't outline a simple rectilinear polygon: {}, and they can't be reordered into one: {}",
+            given, walked
+        ))),
+    }
+}
+
+/// Why the loop through `vertices` isn't a simple rectilinear polygon, if
+/// it isn't. Neighbouring edges may only share their corner and no other
+/// two edges may touch at all; sweeps find any that do in O(n log n).
+fn check_simple_loop(vertices: &[Coordinate]) -> std::result::Result<(), String> {
+    let n = vertices.len();
+    let edges: Vec<(Coordinate, Coordinate)> = (0..n).map(|i| (vertices[i], vertices[(i + 1) % n])).collect();
+    if let Some((from, to)) = edges.iter().find(|(from, to)| from.x != to.x && from.y != to.y) {
+        return Err(format!("edge {} -> {} is not axis-aligned", from, to));
+    }
+    let spans: Vec<Rect> = edges.iter().map(|&(from, to)| Rect::spanning(from, to)).collect();
+    let show = |i: usize| format!("{} -> {}", edges[i].0, edges[i].1);
+
+    // Neighbouring edges first, since one doubling back also touches the
+    // edges around it
+    for i in 0..n {
+        let j = (i + 1) % n;
+        let (a, b) = (spans[i], spans[j]);
+        let shared = Rect {
+            min: Coordinate { x: a.min.x.max(b.min.x), y: a.min.y.max(b.min.y) },
+            max: Coordinate { x: a.max.x.min(b.max.x), y: a.max.y.min(b.max.y) },
+        };
+        if shared.min != shared.max {
+            return Err(format!("edge {} doubles back over edge {}", show(j), show(i)));
+        }
+    }
+
+    let neighbours = |i: usize, j: usize| (i + 1) % n == j || (j + 1) % n == i;
+    let touching = |i: usize, j: usize| Err(format!("edge {} touches edge {}", show(i.max(j)), show(i.min(j))));
+    let horizontal = |i: usize| spans[i].min.y == spans[i].max.y;
+
+    // Edges along the same row or column, by where they start. Every edge
+    // still open when another starts contains that start too, so all open
+    // edges touch each other and only neighbours can be open at once.
+    let mut collinear: Vec<(bool, usize, usize, usize, usize)> = (0..n)
+        .map(|i| {
+            let Rect { min, max } = spans[i];
+            if horizontal(i) { (true, min.y, min.x, max.x, i) } else { (false, min.x, min.y, max.y, i) }
+        })
+        .collect();
+    collinear.sort_unstable();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    for (k, &(is_horizontal, line, start, end, i)) in collinear.iter().enumerate() {
+        if k > 0 && (collinear[k - 1].0, collinear[k - 1].1) != (is_horizontal, line) {
+            open.clear();
+        }
+        open.retain(|&(open_end, _)| open_end >= start);
+        if let Some(&(_, j)) = open.iter().find(|&&(_, j)| !neighbours(i, j)) {
+            return touching(i, j);
+        }
+        open.push((end, i));
+    }
+
+    // Vertical edges against the horizontal ones open at their column,
+    // left to right; edges starting at a column are opened before the
+    // vertical ones there are checked and closed after
+    let mut events: Vec<(usize, u8, usize)> = Vec::with_capacity(2 * n);
+    for (i, span) in spans.iter().enumerate() {
+        if horizontal(i) {
+            events.push((span.min.x, 0, i));
+            events.push((span.max.x, 2, i));
+        } else {
+            events.push((span.min.x, 1, i));
+        }
+    }
+    events.sort_unstable();
+    let mut rows: BTreeSet<(usize, usize)> = BTreeSet::new();
+    for (_, kind, i) in events {
+        match kind {
+            0 => {
+                rows.insert((spans[i].min.y, i));
+            }
+            2 => {
+                rows.remove(&(spans[i].min.y, i));
+            }
+            _ => {
+                // Only this edge's neighbours can touch it without failing, so this
+                // looks at no more than three rows
+                let within = rows.range((spans[i].min.y, 0)..=(spans[i].max.y, usize::MAX));
+                if let Some(&(_, j)) = within.into_iter().find(|&&(_, j)| !neighbours(i, j)) {
+                    return touching(i, j);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Orders the tiles by pairing them up along each row and each column and
+/// following the pairs, alternating horizontal and vertical steps
+fn walk_boundary(coordinates: &[Coordinate]) -> std::result::Result<Vec<Coordinate>, String> {
+    let partners = |key: fn(&Coordinate) -> (usize, usize), axis: &str| {
+        let mut lines: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (i, c) in coordinates.iter().enumerate() {
+            lines.entry(key(c).0).or_default().push(i);
+        }
+        let mut partner = vec![0; coordinates.len()];
+        for (line, mut tiles) in lines {
+            if tiles.len() % 2 == 1 {
+                return Err(format!(
+                    "{} {} has an odd number of red tiles ({}), so some red tile isn't a corner",
+                    axis, line, tiles.len()
+                ));
+            }
+            tiles.sort_by_key(|&i| key(&coordinates[i]).1);
+            for pair in tiles.chunks(2) {
+                partner[pair[0]] = pair[1];
+                partner[pair[1]] = pair[0];
+            }
+        }
+        Ok(partner)
+    };
+    let across = partners(|c| (c.y, c.x), "row")?;
+    let down = partners(|c| (c.x, c.y), "column")?;
+
+    let mut order = vec![0];
+    let mut at = across[0];
+    while at != 0 {
+        order.push(at);
+        at = if order.len() % 2 == 0 { down[at] } else { across[at] };
+    }
+    if order.len() < coordinates.len() {
+        return Err(format!("the boundary through {} closes after {} of {} red tiles", coordinates[0], order.len(), coordinates.len()));
+    }
+    Ok(order.into_iter().map(|i| coordinates[i]).collect())
 }
 
 // Point-in-polygon test using ray casting algorithm
@@ -148,24 +461,23 @@ fn get_polygon_bounds(coordinates: &[Coordinate]) -> (usize, usize, usize, usize
     (min_x, max_x, min_y, max_y)
 }
 
-fn find_largest_rectangle_in_polygon(coordinates: &[Coordinate]) -> Option<Square> {
-    if coordinates.len() < 2 {
-        return None;
-    }
-
-    // Build the polygon from red tiles
-    let polygon: Vec<(i64, i64)> = coordinates
+/// Largest red-cornered rectangle made only of red and green tiles, or None
+/// when there is none; fails only if an area overflows
+pub fn find_largest_rectangle_in_polygon(boundary: &Boundary, out: &dyn Reporter) -> Result<Option<Square>> {
+    let coordinates = &boundary.vertices;
+    let polygon: Vec<(i64, i64)> = boundary
+        .vertices
         .iter()
         .map(|c| (c.x as i64, c.y as i64))
         .collect();
 
     let (poly_min_x, poly_max_x, poly_min_y, poly_max_y) = get_polygon_bounds(coordinates);
 
-    println!("  Polygon bounding box: ({}, {}) to ({}, {})",
-             poly_min_x, poly_min_y, poly_max_x, poly_max_y);
+    out.line(&format!("  Polygon bounding box: ({}, {}) to ({}, {})",
+             poly_min_x, poly_min_y, poly_max_x, poly_max_y));
 
     let mut largest_square: Option<Square> = None;
-    let mut best_area = 0;
+    let mut best_area = Area::ZERO;
 
     // Check every pair of RED tile coordinates as potential opposite corners
     for i in 0..coordinates.len() {
@@ -184,8 +496,7 @@ fn find_largest_rectangle_in_polygon(coordinates: &[Coordinate]) -> Option<Squar
                 continue;
             }
 
-            // Calculate area
-            let area = (max_x - min_x + 1) * (max_y - min_y + 1);
+            let area = Area::spanning(coord1, coord2)?;
 
             // Early termination: if this rectangle can't beat the current best, skip it
             if area <= best_area {
@@ -245,49 +556,487 @@ fn find_largest_rectangle_in_polygon(coordinates: &[Coordinate]) -> Option<Squar
         }
     }
 
-    largest_square
+    Ok(largest_square)
+}
+
+/// A run of tile columns (or rows) that no polygon edge starts or ends
+/// inside, so every tile in it is on the same side of the boundary
+#[derive(Debug, Clone, Copy)]
+struct Band {
+    start: usize,
+    len: usize,
+}
+
+/// One band per distinct red-tile coordinate and one per gap between them,
+/// with an empty band at each end so the outside is connected. Also returns
+/// the band index of each coordinate.
+fn compress(values: impl Iterator<Item = usize>) -> (Vec<Band>, HashMap<usize, usize>) {
+    let mut sorted: Vec<usize> = values.collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut bands = vec![Band { start: sorted[0], len: 0 }];
+    let mut index = HashMap::new();
+    for (k, &value) in sorted.iter().enumerate() {
+        index.insert(value, bands.len());
+        bands.push(Band { start: value, len: 1 });
+        if let Some(&next) = sorted.get(k + 1) {
+            if next > value + 1 {
+                bands.push(Band { start: value + 1, len: next - value - 1 });
+            }
+        }
+    }
+    // Empty, so saturating is harmless for a polygon reaching usize::MAX
+    bands.push(Band { start: sorted[sorted.len() - 1].saturating_add(1), len: 0 });
+    (bands, index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Region {
+    #[default]
+    Unvisited,
+    Edge,
+    Outside,
+}
+
+/// An axis-aligned rectangle of tiles, both corners included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub min: Coordinate,
+    pub max: Coordinate,
+}
+
+impl Rect {
+    /// The rectangle with opposite corners `a` and `b`, in any order
+    pub fn spanning(a: Coordinate, b: Coordinate) -> Rect {
+        Rect {
+            min: Coordinate { x: a.x.min(b.x), y: a.y.min(b.y) },
+            max: Coordinate { x: a.x.max(b.x), y: a.y.max(b.y) },
+        }
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.min, self.max)
+    }
+}
+
+/// The loop of red tiles and the green tiles it encloses, compressed into
+/// bands and flood-filled once so rectangle queries don't touch tiles
+pub struct RectilinearPolygon {
+    columns: Vec<Band>,
+    rows: Vec<Band>,
+    grid: Grid<Region>,
+    /// `outside[y * (width + 1) + x]` counts the outside cells above and
+    /// left of band cell (x, y), for constant-time emptiness checks
+    outside: Vec<usize>,
+}
+
+impl RectilinearPolygon {
+    /// Orders the tiles along the boundary first if need be; fails when
+    /// they can't outline a simple rectilinear polygon
+    pub fn new(coordinates: &[Coordinate]) -> Result<Self> {
+        Ok(Self::from_boundary(&boundary(coordinates)?))
+    }
+
+    /// The polygon outlined by an already validated boundary
+    pub fn from_boundary(boundary: &Boundary) -> Self {
+        let coordinates = &boundary.vertices;
+        let (columns, column_of) = compress(coordinates.iter().map(|c| c.x));
+        let (rows, row_of) = compress(coordinates.iter().map(|c| c.y));
+        let mut grid: Grid<Region> = Grid::new(columns.len(), rows.len());
+
+        for (i, &from) in coordinates.iter().enumerate() {
+            let to = coordinates[(i + 1) % coordinates.len()];
+            let (x1, x2) = (column_of[&from.x], column_of[&to.x]);
+            let (y1, y2) = (row_of[&from.y], row_of[&to.y]);
+            for y in y1.min(y2)..=y1.max(y2) {
+                for x in x1.min(x2)..=x1.max(x2) {
+                    grid[(x, y)] = Region::Edge;
+                }
+            }
+        }
+
+        // The padding bands guarantee (0, 0) is outside and touches all of it
+        let mut stack = vec![(0, 0)];
+        grid[(0, 0)] = Region::Outside;
+        while let Some((x, y)) = stack.pop() {
+            let next: Vec<(usize, usize)> = grid
+                .von_neumann_neighbors(x, y)
+                .filter(|&pos| grid[pos] == Region::Unvisited)
+                .collect();
+            for pos in next {
+                grid[pos] = Region::Outside;
+                stack.push(pos);
+            }
+        }
+
+        let stride = grid.width() + 1;
+        let mut outside = vec![0; stride * (grid.height() + 1)];
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                outside[(y + 1) * stride + x + 1] = usize::from(grid[(x, y)] == Region::Outside)
+                    + outside[y * stride + x + 1]
+                    + outside[(y + 1) * stride + x]
+                    - outside[y * stride + x];
+            }
+        }
+
+        RectilinearPolygon { columns, rows, grid, outside }
+    }
+
+    /// Band holding tile `value`, or None outside the polygon's bounding box
+    fn band_of(bands: &[Band], value: usize) -> Option<usize> {
+        // The first and last bands are empty padding, and the last real band
+        // is a single red-tile coordinate
+        let padded = bands.len() - 1;
+        if value < bands[1].start || value > bands[padded - 1].start {
+            return None;
+        }
+        Some(bands[..padded].partition_point(|band| band.start <= value) - 1)
+    }
+
+    /// Whether band cells `x1..=x2` by `y1..=y2` are all red or green
+    fn bands_inside(&self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> bool {
+        let stride = self.grid.width() + 1;
+        let at = |x: usize, y: usize| self.outside[y * stride + x];
+        at(x2 + 1, y2 + 1) + at(x1, y1) == at(x1, y2 + 1) + at(x2 + 1, y1)
+    }
+
+    /// Whether every tile of `rect` is red or green, in O(log n)
+    pub fn contains_rect(&self, rect: Rect) -> bool {
+        let corner = |c: Coordinate| Some((Self::band_of(&self.columns, c.x)?, Self::band_of(&self.rows, c.y)?));
+        match (corner(rect.min), corner(rect.max)) {
+            (Some(min), Some(max)) => self.bands_inside(min, max),
+            _ => false,
+        }
+    }
+
+    /// Whether tile (x, y) is red or green
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let point = Coordinate { x, y };
+        self.contains_rect(Rect { min: point, max: point })
+    }
+
+    /// Largest red/green rectangle that includes tile (x, y), or None when
+    /// that tile isn't red or green. For each top row band the bottom edge
+    /// moves down while the left and right edges only ever move inwards,
+    /// so this is O(rows x (rows + columns)) band checks.
+    pub fn largest_rect_containing(&self, x: usize, y: usize) -> Result<Option<Square>> {
+        let (Some(px), Some(py)) = (Self::band_of(&self.columns, x), Self::band_of(&self.rows, y)) else {
+            return Ok(None);
+        };
+        if !self.bands_inside((px, py), (px, py)) {
+            return Ok(None);
+        }
+
+        let mut best: Option<Square> = None;
+        for top in (0..=py).rev() {
+            if !self.bands_inside((px, top), (px, py)) {
+                break;
+            }
+            let (mut left, mut right) = (px, px);
+            for bottom in py..self.rows.len() {
+                if !self.bands_inside((px, top), (px, bottom)) {
+                    break;
+                }
+                if bottom == py {
+                    while left > 0 && self.bands_inside((left - 1, top), (left - 1, bottom)) {
+                        left -= 1;
+                    }
+                    while right + 1 < self.columns.len() && self.bands_inside((right + 1, top), (right + 1, bottom)) {
+                        right += 1;
+                    }
+                } else {
+                    while !self.bands_inside((left, top), (px, bottom)) {
+                        left += 1;
+                    }
+                    while !self.bands_inside((px, top), (right, bottom)) {
+                        right -= 1;
+                    }
+                }
+
+                let corner1 = Coordinate { x: self.columns[left].start, y: self.rows[top].start };
+                let corner2 = Coordinate {
+                    x: self.columns[right].start + self.columns[right].len - 1,
+                    y: self.rows[bottom].start + self.rows[bottom].len - 1,
+                };
+                let area = Area::spanning(corner1, corner2)?;
+                if area > best.map_or(Area::ZERO, |square| square.area) {
+                    best = Some(Square { corner1, corner2, area });
+                }
+            }
+        }
+        Ok(best)
+    }
+}
+
+/// Largest axis-aligned rectangle of red and green tiles, whether or not
+/// its corners are red. The polygon is compressed into bands, the outside
+/// flood-filled, and each band row scanned as a histogram of inside heights.
+pub fn find_largest_rectangle_unanchored(boundary: &Boundary) -> Result<Square> {
+    let RectilinearPolygon { columns, rows, grid, .. } = RectilinearPolygon::from_boundary(boundary);
+
+    let too_wide = || AocError::solve("Polygon spans more than usize::MAX tiles");
+    let mut column_offsets = vec![0usize];
+    for band in &columns {
+        column_offsets.push(column_offsets[column_offsets.len() - 1].checked_add(band.len).ok_or_else(too_wide)?);
+    }
+
+    // Tiles of inside rows stacked on each column, ending at the current row
+    let mut heights = vec![0usize; columns.len()];
+    let mut best: Option<Square> = None;
+    for (y, row) in rows.iter().enumerate() {
+        for (x, height) in heights.iter_mut().enumerate() {
+            *height = if grid[(x, y)] == Region::Outside { 0 } else { height.checked_add(row.len).ok_or_else(too_wide)? };
+        }
+
+        // Columns with increasing heights; popping one closes the widest
+        // rectangle of its height
+        let mut stack: Vec<usize> = Vec::new();
+        for x in 0..=columns.len() {
+            let height = heights.get(x).copied().unwrap_or(0);
+            while let Some(&top) = stack.last() {
+                if heights[top] < height {
+                    break;
+                }
+                stack.pop();
+                let left = stack.last().map_or(0, |&s| s + 1);
+                let width = column_offsets[x] - column_offsets[left];
+                let area = Area::new(heights[top] as u128, width as u128)?;
+                if area > best.map_or(Area::ZERO, |square| square.area) {
+                    let (left_x, bottom_y) = (columns[left].start, row.start + row.len - 1);
+                    best = Some(Square {
+                        corner1: Coordinate { x: left_x, y: bottom_y - (heights[top] - 1) },
+                        corner2: Coordinate { x: left_x + (width - 1), y: bottom_y },
+                        area,
+                    });
+                }
+            }
+            stack.push(x);
+        }
+    }
+
+    best.ok_or_else(|| AocError::solve("No tiles inside the polygon"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Square {
+    pub corner1: Coordinate,
+    pub corner2: Coordinate,
+    pub area: Area,
+}
+
+/// Largest rectangle with red tiles in opposite corners
+pub fn part1(input: &str) -> Result<Area> {
+    find_largest_rectangle(&parse_input(input)?)?
+        .map(|square| square.area)
+        .ok_or_else(|| AocError::solve("Need at least two red tiles"))
 }
 
+/// Largest such rectangle made only of red and green tiles
+pub fn part2(input: &str) -> Result<Area> {
+    find_largest_rectangle_in_polygon(&boundary(&parse_input(input)?)?, &Silent)?
+        .map(|square| square.area)
+        .ok_or_else(|| AocError::solve("No valid rectangle found"))
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day09", about = "Day 9: Movie Theater")]
+struct Options {
+    /// Also search rectangles whose corners aren't red tiles, and compare
+    #[arg(long)]
+    unanchored: bool,
+    /// Instead of the puzzle parts, answer queries about the polygon:
+    /// X1,Y1,X2,Y2 asks whether that rectangle is all red and green, X,Y
+    /// asks for the largest such rectangle holding that tile
+    #[arg(long, value_name = "QUERY")]
+    query: Vec<Query>,
+    /// Red tiles to query [default: day09tiles2.txt in the input directory],
+    /// or to solve instead of the bundled files; read a line at a time, so
+    /// it can hold millions
+    #[arg(long)]
+    input: Option<String>,
+}
+
+/// A `--query`
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Square {
-    corner1: Coordinate,
-    corner2: Coordinate,
-    area: usize,
+enum Query {
+    Inside(Rect),
+    Around(Coordinate),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<usize>().map_err(|e| format!("Invalid number '{}': {}", v.trim(), e)))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, y] => Ok(Query::Around(Coordinate { x, y })),
+            [x1, y1, x2, y2] => Ok(Query::Inside(Rect::spanning(Coordinate { x: x1, y: y1 }, Coordinate { x: x2, y: y2 }))),
+            _ => Err(format!("Expected X,Y or X1,Y1,X2,Y2, got {} values", values.len())),
+        }
+    }
 }
 
-pub fn run() -> Result<()> {
+/// The tiles' boundary, saying so when they had to be reordered
+fn report_boundary(coordinates: &[Coordinate], out: &dyn Reporter) -> Result<Boundary> {
+    let boundary = boundary(coordinates)?;
+    if boundary.reordered {
+        out.line("  Red tiles were out of boundary order; walked the boundary to reorder them");
+    }
+    Ok(boundary)
+}
+
+fn run_queries(coordinates: &[Coordinate], queries: &[Query], out: &dyn OutputSink) -> Result<()> {
+    let polygon = RectilinearPolygon::from_boundary(&report_boundary(coordinates, out)?);
+    for &query in queries {
+        match query {
+            Query::Inside(rect) => {
+                let answer = if polygon.contains_rect(rect) { "all red/green" } else { "not all red/green" };
+                out.line(&format!("{}: {}", rect, answer));
+            }
+            Query::Around(point) => match polygon.largest_rect_containing(point.x, point.y)? {
+                Some(square) => out.line(&format!("{}: largest is {} to {}, area {}", point, square.corner1, square.corner2, square.area)),
+                None => out.line(&format!("{}: not a red or green tile", point)),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Both parts on a tile file of any size. Part 2 tries every pair of red
+/// tiles against the whole boundary, so it's skipped past
+/// `PAIR_SEARCH_LIMIT` tiles; `--unanchored` still runs on any size. Tiles
+/// that don't outline a polygon only rule out part 2, not the whole run.
+fn run_file(path: &str, unanchored: bool, out: &dyn OutputSink) -> Result<()> {
+    let file = File::open(path).map_err(|e| AocError::io(path, e))?;
+    let bytes = file.metadata().map_err(|e| AocError::io(path, e))?.len() as usize;
+    let start = std::time::Instant::now();
+    let coordinates = parse_reader(BufReader::new(file), path, bytes / BYTES_PER_TILE + 1).map_err(|e| e.in_file(path))?;
+    out.line(&format!("=== {} ===", path));
+    out.line(&format!("Parsed {} red tile coordinates in {:?}", coordinates.len(), start.elapsed()));
+
+    let start = std::time::Instant::now();
+    match find_largest_rectangle(&coordinates)? {
+        Some(square) => out.line(&format!("\nPart 1 - Any tiles: {} ({} to {}, {:?})", square.area, square.corner1, square.corner2, start.elapsed())),
+        None => out.line("\nPart 1 - No rectangle: the tiles are all on one line"),
+    }
+
+    let too_many = coordinates.len() > PAIR_SEARCH_LIMIT;
+    if too_many && !unanchored {
+        out.line(&format!("\nPart 2 skipped: more than {} red tiles", PAIR_SEARCH_LIMIT));
+        return Ok(());
+    }
+    let boundary = match report_boundary(&coordinates, out) {
+        Ok(boundary) => boundary,
+        Err(e) => {
+            out.line(&format!("\nPart 2 unavailable: {}", e));
+            return Ok(());
+        }
+    };
+    let anchored = if too_many {
+        out.line(&format!("\nPart 2 skipped: more than {} red tiles", PAIR_SEARCH_LIMIT));
+        None
+    } else {
+        let anchored = find_largest_rectangle_in_polygon(&boundary, out)?;
+        match anchored {
+            Some(square) => out.line(&format!("\nPart 2 - Red/green only: {} ({} to {})", square.area, square.corner1, square.corner2)),
+            None => out.line("\nNo valid rectangle found"),
+        }
+        anchored
+    };
+    if unanchored {
+        compare_unanchored(&boundary, anchored, out)?;
+    }
+    Ok(())
+}
+
+/// Part 2's red-cornered rectangle next to the largest one with any corners
+fn compare_unanchored(boundary: &Boundary, anchored: Option<Square>, out: &dyn OutputSink) -> Result<()> {
+    let unanchored = find_largest_rectangle_unanchored(boundary)?;
+    let cell = |square: Option<Square>, show: fn(&Square) -> String| square.as_ref().map_or("-".to_string(), show);
+
+    out.line("\nRed/green rectangles by corner rule:");
+    out.line(&format!("  {:<9} {:>22} {:>22}", "", "red corners", "any corners"));
+    out.line(&format!("  {:<9} {:>22} {:>22}", "area", cell(anchored, |s| s.area.to_string()), unanchored.area));
+    out.line(&format!("  {:<9} {:>22} {:>22}", "corner 1", cell(anchored, |s| s.corner1.to_string()), unanchored.corner1.to_string()));
+    out.line(&format!("  {:<9} {:>22} {:>22}", "corner 2", cell(anchored, |s| s.corner2.to_string()), unanchored.corner2.to_string()));
+    Ok(())
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Movie Theater",
+        input_files: &["tiles1", "tiles2"],
+        parts: [
+            "Largest rectangle with red tiles in opposite corners",
+            "Largest such rectangle made only of red and green tiles",
+        ],
+        notes: "--query answers containment and largest-rectangle questions; red tiles out of boundary order are reordered; --input solves a file of any size, pruning part 1's pairs to the staircases",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day09".to_string()).chain(args.iter().cloned()));
+    if !options.query.is_empty() {
+        let coordinates = match &options.input {
+            Some(path) => parse_input(input::read_file(path)?).map_err(|e| e.in_file(path))?,
+            None => Input::for_day(9).parse("tiles2", parse_input)?,
+        };
+        return run_queries(&coordinates, &options.query, out);
+    }
+    if let Some(path) = &options.input {
+        return run_file(path, options.unanchored, out);
+    }
+
     // Test with small dataset first
-    println!("=== Small dataset (day09tiles1.txt) ===");
-    let coordinates1 = parse_input("assets/day09tiles1.txt")?;
-    println!("Parsed {} red tile coordinates", coordinates1.len());
+    out.line("=== Small dataset (day09tiles1.txt) ===");
+    let coordinates1 = Input::for_day(9).parse("tiles1", parse_input)?;
+    out.line(&format!("Parsed {} red tile coordinates", coordinates1.len()));
 
-    if let Some(square) = find_largest_rectangle(&coordinates1) {
-        println!("\nPart 1 - Any tiles: {}", square.area);
+    if let Some(square) = find_largest_rectangle(&coordinates1)? {
+        out.line(&format!("\nPart 1 - Any tiles: {}", square.area));
     }
 
-    if let Some(square) = find_largest_rectangle_in_polygon(&coordinates1) {
-        println!("\nPart 2 - Red/green only:");
-        println!("  Corner 1: ({}, {})", square.corner1.x, square.corner1.y);
-        println!("  Corner 2: ({}, {})", square.corner2.x, square.corner2.y);
-        println!("  Area: {} (expected: 24)", square.area);
+    let boundary1 = report_boundary(&coordinates1, out)?;
+    let anchored1 = find_largest_rectangle_in_polygon(&boundary1, out)?;
+    if let Some(square) = anchored1 {
+        out.line("\nPart 2 - Red/green only:");
+        out.line(&format!("  Corner 1: ({}, {})", square.corner1.x, square.corner1.y));
+        out.line(&format!("  Corner 2: ({}, {})", square.corner2.x, square.corner2.y));
+        out.line(&format!("  Area: {} (expected: 24)", square.area));
+    }
+    if options.unanchored {
+        compare_unanchored(&boundary1, anchored1, out)?;
     }
 
     // Large dataset
-    println!("\n=== Large dataset (day09tiles2.txt) ===");
-    let coordinates2 = parse_input("assets/day09tiles2.txt")?;
-    println!("Parsed {} red tile coordinates", coordinates2.len());
+    out.line("\n=== Large dataset (day09tiles2.txt) ===");
+    let coordinates2 = Input::for_day(9).parse("tiles2", parse_input)?;
+    out.line(&format!("Parsed {} red tile coordinates", coordinates2.len()));
 
-    if let Some(square) = find_largest_rectangle(&coordinates2) {
-        println!("\nPart 1 - Any tiles: {}", square.area);
+    if let Some(square) = find_largest_rectangle(&coordinates2)? {
+        out.line("");
+        out.answer(1, "Part 1 - Any tiles", &square.area);
     }
 
-    if let Some(square2) = find_largest_rectangle_in_polygon(&coordinates2) {
-        println!("\nPart 2 - Red/green only:");
-        println!("  Corner 1: ({}, {})", square2.corner1.x, square2.corner1.y);
-        println!("  Corner 2: ({}, {})", square2.corner2.x, square2.corner2.y);
-        println!("  Area: {}", square2.area);
+    let boundary2 = report_boundary(&coordinates2, out)?;
+    let anchored2 = find_largest_rectangle_in_polygon(&boundary2, out)?;
+    if let Some(square2) = anchored2 {
+        out.line("\nPart 2 - Red/green only:");
+        out.line(&format!("  Corner 1: ({}, {})", square2.corner1.x, square2.corner1.y));
+        out.line(&format!("  Corner 2: ({}, {})", square2.corner2.x, square2.corner2.y));
+        out.answer(2, "  Area", &square2.area);
     } else {
-        println!("\nNo valid rectangle found");
+        out.line("\nNo valid rectangle found");
+    }
+    if options.unanchored {
+        compare_unanchored(&boundary2, anchored2, out)?;
     }
 
     Ok(())
@@ -296,48 +1045,265 @@ pub fn run() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::reporter::Captured;
+    use crate::util::snapshot;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_run_file_without_polygon() {
+        let path = std::env::temp_dir().join(format!("day09-tiles-{}.txt", std::process::id()));
+        let run = |tiles: &str| {
+            std::fs::write(&path, tiles).unwrap();
+            let captured = Captured::new();
+            run_file(path.to_str().unwrap(), false, &captured).unwrap();
+            captured.lines().join("\n")
+        };
+
+        let lines = run("0,0\n5,0\n5,5\n");
+        assert!(lines.contains("Part 1 - Any tiles: 36"));
+        assert!(lines.contains("Part 2 unavailable: Need at least four red tiles for a polygon, got 3"));
+
+        // Too many tiles for part 2, so the boundary is never walked
+        let scattered: String = (0..=PAIR_SEARCH_LIMIT).map(|i| format!("{},{}\n", i, i * i % 9973)).collect();
+        let lines = run(&scattered);
+        assert!(lines.contains("Part 1 - Any tiles"));
+        assert!(lines.contains("Part 2 skipped"));
+        std::fs::remove_file(&path).unwrap();
+    }
 
     #[test]
     fn test_part1_solution() {
-        let coordinates = parse_input("assets/day09tiles1.txt")
+        let coordinates = parse_input(Input::for_day(9).read("tiles1").unwrap())
             .expect("Failed to load part 1 input");
 
         let square = find_largest_rectangle(&coordinates)
+            .unwrap()
             .expect("Should find a valid rectangle");
 
-        assert_eq!(square.area, 50, "Part 1 solution should be 50");
+        assert_eq!(square.area.get(), 50, "Part 1 solution should be 50");
     }
 
     #[test]
     fn test_part1_with_polygon_constraint() {
-        let coordinates = parse_input("assets/day09tiles1.txt")
+        let coordinates = parse_input(Input::for_day(9).read("tiles1").unwrap())
             .expect("Failed to load part 1 input");
 
-        let square = find_largest_rectangle_in_polygon(&coordinates)
+        let square = find_largest_rectangle_in_polygon(&boundary(&coordinates).unwrap(), &Silent)
+            .unwrap()
             .expect("Should find a valid rectangle");
 
-        assert_eq!(square.area, 24, "Part 1 with polygon constraint should be 24");
+        assert_eq!(square.area.get(), 24, "Part 1 with polygon constraint should be 24");
     }
 
     #[test]
     fn test_part2_solution() {
-        let coordinates = parse_input("assets/day09tiles2.txt")
+        let coordinates = parse_input(Input::for_day(9).read("tiles2").unwrap())
             .expect("Failed to load part 2 input");
 
         let square = find_largest_rectangle(&coordinates)
+            .unwrap()
             .expect("Should find a valid rectangle");
 
-        assert_eq!(square.area, 4740155680, "Part 2 solution should be 4740155680");
+        snapshot::check("day09", "largest_rectangle", square.area.get());
     }
 
     #[test]
     fn test_part2_with_polygon_constraint() {
-        let coordinates = parse_input("assets/day09tiles2.txt")
+        let coordinates = parse_input(Input::for_day(9).read("tiles2").unwrap())
             .expect("Failed to load part 2 input");
 
-        let square = find_largest_rectangle_in_polygon(&coordinates)
+        let square = find_largest_rectangle_in_polygon(&boundary(&coordinates).unwrap(), &Silent)
+            .unwrap()
             .expect("Should find a valid rectangle");
 
-        assert_eq!(square.area, 1543501936, "Part 2 with polygon constraint should be 1543501936");
+        snapshot::check("day09", "largest_rectangle_in_polygon", square.area.get());
+    }
+
+    #[test]
+    fn test_unanchored_rectangle() {
+        // A notch in the top edge: the best rectangle sits under it, and
+        // its top-left corner (0, 3) isn't a red tile
+        let notched = parse_input("0,0\n4,0\n4,3\n6,3\n6,0\n10,0\n10,10\n0,10\n").unwrap();
+        assert_eq!(find_largest_rectangle_in_polygon(&boundary(&notched).unwrap(), &Silent).unwrap().unwrap().area.get(), 56);
+        let square = find_largest_rectangle_unanchored(&boundary(&notched).unwrap()).unwrap();
+        assert_eq!((square.corner1, square.corner2, square.area), (Coordinate { x: 0, y: 3 }, Coordinate { x: 10, y: 10 }, Area(88)));
+
+        let example = parse_input(Input::for_day(9).read("tiles1").unwrap()).unwrap();
+        // (2, 3) to (11, 5): the far corner is green, so part 2 settles for 24
+        let square = find_largest_rectangle_unanchored(&boundary(&example).unwrap()).unwrap();
+        assert_eq!((square.corner1, square.corner2, square.area), (Coordinate { x: 2, y: 3 }, Coordinate { x: 11, y: 5 }, Area(30)));
+
+        let diagonal = parse_input("0,0\n5,5\n0,5\n").unwrap();
+        assert!(boundary(&diagonal).is_err());
+    }
+
+    #[test]
+    fn test_unanchored_never_smaller() {
+        let coordinates = parse_input(Input::for_day(9).read("tiles2").unwrap()).unwrap();
+        let anchored = find_largest_rectangle_in_polygon(&boundary(&coordinates).unwrap(), &Silent).unwrap().unwrap();
+        let unanchored = find_largest_rectangle_unanchored(&boundary(&coordinates).unwrap()).unwrap();
+        assert!(unanchored.area >= anchored.area);
+        snapshot::check("day09", "largest_rectangle_unanchored", unanchored.area.get());
+    }
+
+    #[test]
+    fn test_boundary_order() {
+        let example = parse_input(Input::for_day(9).read("tiles1").unwrap()).unwrap();
+        assert_eq!(boundary(&example).unwrap(), Boundary { vertices: example.clone(), reordered: false });
+
+        // Shuffled, the walk recovers a loop around the same polygon
+        let mut shuffled = example.clone();
+        shuffled.swap(1, 5);
+        shuffled.swap(2, 7);
+        shuffled.reverse();
+        let walked = boundary(&shuffled).unwrap();
+        assert!(walked.reordered);
+        let start = walked.vertices.iter().position(|&c| c == example[0]).unwrap();
+        let mut rotated = walked.vertices.clone();
+        rotated.rotate_left(start);
+        if rotated[1] != example[1] {
+            rotated[1..].reverse();
+        }
+        assert_eq!(rotated, example);
+        let captured = Captured::new();
+        let walked = report_boundary(&shuffled, &captured).unwrap();
+        assert!(captured.lines()[0].contains("walked the boundary to reorder them"));
+        assert_eq!(find_largest_rectangle_in_polygon(&walked, &Silent).unwrap().unwrap().area.get(), 24);
+        assert_eq!(find_largest_rectangle_unanchored(&walked).unwrap().area.get(), 30);
+
+        let error = |input: &str| boundary(&parse_input(input).unwrap()).unwrap_err().to_string();
+        assert_eq!(error("0,0\n5,0\n5,5\n"), "Need at least four red tiles for a polygon, got 3");
+        assert_eq!(error("0,0\n5,0\n5,5\n0,5\n5,0\n"), "Red tile (5, 0) appears twice");
+        // A middle tile on an edge is fine in order but can't be paired up
+        assert_eq!(
+            error("0,0\n5,0\n0,3\n0,5\n5,5\n"),
+            "Red tiles don't outline a simple rectilinear polygon: edge (5, 0) -> (0, 3) is not axis-aligned, \
+             and they can't be reordered into one: row 3 has an odd number of red tiles (1), so some red tile isn't a corner"
+        );
+        assert!(boundary(&parse_input("0,0\n5,0\n5,5\n0,5\n0,3\n").unwrap()).is_ok());
+        // Two separate squares
+        assert!(error("0,0\n1,0\n1,1\n0,1\n5,5\n6,5\n6,6\n5,6\n")
+            .ends_with("the boundary through (0, 0) closes after 4 of 8 red tiles"));
+        // A figure eight: axis-aligned in both orders, but its edges cross
+        assert!(error("0,0\n4,0\n4,2\n2,2\n2,6\n6,6\n6,4\n0,4\n").contains("touches edge"));
+        // Going out and back along the same line
+        assert!(error("0,2\n4,2\n4,0\n4,4\n0,4\n").contains("edge (4, 0) -> (4, 4) doubles back over edge (4, 2) -> (4, 0)"));
+
+        // A staircase well past the pair search limit still checks quickly
+        let steps = PAIR_SEARCH_LIMIT;
+        let mut stairs = vec![Coordinate { x: 0, y: 0 }];
+        for i in 1..=steps {
+            stairs.extend([Coordinate { x: i, y: i - 1 }, Coordinate { x: i, y: i }]);
+        }
+        stairs.push(Coordinate { x: 0, y: steps });
+        assert_eq!(boundary(&stairs).unwrap().vertices.len(), 2 * steps + 2);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_area_near_overflow() {
+        let corner = |x, y| Coordinate { x, y };
+        // Past u64, where a usize product would have wrapped
+        let area = Area::spanning(corner(0, 0), corner(u64::MAX as usize - 1, 2)).unwrap();
+        assert_eq!(area.get(), u64::MAX as u128 * 3);
+        // The largest area there is: one tile short of 2^64 on each side
+        let side = u64::MAX as u128;
+        assert_eq!(Area::spanning(corner(1, 1), corner(usize::MAX, usize::MAX)).unwrap().get(), side * side);
+        // A full 2^64 x 2^64 span is 2^128, one past u128::MAX
+        let err = Area::spanning(corner(0, 0), corner(usize::MAX, usize::MAX)).unwrap_err();
+        assert_eq!(err.to_string(), format!("Area of a {} x {} rectangle overflows u128", side + 1, side + 1));
+        assert_eq!(Area::new(u128::MAX, 1).unwrap().get(), u128::MAX);
+        assert!(Area::new(u128::MAX, 2).is_err());
+
+        let far = parse_input(&format!("0,0\n{},5\n", usize::MAX - 1)).unwrap();
+        assert_eq!(find_largest_rectangle(&far).unwrap().unwrap().area.get(), u64::MAX as u128 * 6);
+        let huge = [corner(0, 0), corner(usize::MAX, usize::MAX)];
+        assert!(find_largest_rectangle(&huge).is_err());
+
+        // Band widths add up to exactly usize::MAX tiles, then one past it
+        let strip = |right: usize| boundary(&[corner(0, 0), corner(right, 0), corner(right, 2), corner(0, 2)]).unwrap();
+        let square = find_largest_rectangle_unanchored(&strip(usize::MAX - 1)).unwrap();
+        assert_eq!((square.corner2, square.area.get()), (corner(usize::MAX - 1, 2), usize::MAX as u128 * 3));
+        let err = find_largest_rectangle_unanchored(&strip(usize::MAX)).unwrap_err();
+        assert_eq!(err.to_string(), "Polygon spans more than usize::MAX tiles");
+    }
+
+    #[test]
+    fn test_pruned_matches_every_pair() {
+        for name in ["tiles1", "tiles2"] {
+            let coordinates = parse_input(Input::for_day(9).read(name).unwrap()).unwrap();
+            let area = |square: Option<Square>| square.map(|s| s.area);
+            assert_eq!(area(find_largest_rectangle_pruned(&coordinates).unwrap()), area(find_largest_rectangle(&coordinates).unwrap()));
+        }
+        let line = parse_input("3,1\n3,9\n3,4\n").unwrap();
+        assert_eq!(find_largest_rectangle_pruned(&line).unwrap(), None);
+
+        // Streamed a line at a time, past the limit: the two far corners win
+        let mut text = String::from("0,0\n\n");
+        let mut state: u64 = 9;
+        for _ in 0..PAIR_SEARCH_LIMIT * 3 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            text.push_str(&format!("{},{}\n", (state >> 33) % 1_000_000, (state >> 13) % 1_000_000));
+        }
+        text.push_str("1000000,1000000\n");
+        let coordinates = parse_reader(BufReader::with_capacity(64, text.as_bytes()), "test", 0).unwrap();
+        assert_eq!(coordinates.len(), PAIR_SEARCH_LIMIT * 3 + 2);
+        assert_eq!(find_largest_rectangle(&coordinates).unwrap().unwrap().area.get(), 1_000_001 * 1_000_001);
+
+        let err = parse_reader("1,2\n\n3,x\n".as_bytes(), "test", 0).unwrap_err();
+        assert_eq!(err.to_string(), "Line 3, column 3: Invalid number 'x': invalid digit found in string");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_pruned_matches_every_pair(points in prop::collection::vec((0usize..25, 0usize..25), 0..60)) {
+            let coordinates: Vec<Coordinate> = points.into_iter().map(|(x, y)| Coordinate { x, y }).collect();
+            let area = |square: Option<Square>| square.map(|s| s.area);
+            prop_assert_eq!(area(find_largest_rectangle_pruned(&coordinates).unwrap()), area(find_largest_rectangle(&coordinates).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_rectangle_queries_match_brute_force() {
+        let notched = parse_input("0,0\n4,0\n4,3\n6,3\n6,0\n10,0\n10,10\n0,10\n").unwrap();
+        let example = parse_input(Input::for_day(9).read("tiles1").unwrap()).unwrap();
+        for coordinates in [notched, example] {
+            let polygon = RectilinearPolygon::new(&coordinates).unwrap();
+            let tiles: Vec<(i64, i64)> = coordinates.iter().map(|c| (c.x as i64, c.y as i64)).collect();
+            let (_, max_x, _, max_y) = get_polygon_bounds(&coordinates);
+            let inside = |rect: Rect| {
+                (rect.min.y..=rect.max.y).all(|y| (rect.min.x..=rect.max.x).all(|x| is_red_or_green(x, y, &tiles)))
+            };
+
+            let mut rects = Vec::new();
+            for (y1, y2) in (0..=max_y + 1).flat_map(|y1| (y1..=max_y + 1).map(move |y2| (y1, y2))) {
+                for (x1, x2) in (0..=max_x + 1).flat_map(|x1| (x1..=max_x + 1).map(move |x2| (x1, x2))) {
+                    let rect = Rect { min: Coordinate { x: x1, y: y1 }, max: Coordinate { x: x2, y: y2 } };
+                    assert_eq!(polygon.contains_rect(rect), inside(rect), "{}", rect);
+                    if inside(rect) {
+                        rects.push(rect);
+                    }
+                }
+            }
+
+            for y in 0..=max_y + 1 {
+                for x in 0..=max_x + 1 {
+                    let largest = rects
+                        .iter()
+                        .filter(|r| (r.min.x..=r.max.x).contains(&x) && (r.min.y..=r.max.y).contains(&y))
+                        .map(|&r| Area::spanning(r.min, r.max).unwrap())
+                        .max();
+                    let found = polygon.largest_rect_containing(x, y).unwrap();
+                    assert_eq!(found.map(|square| square.area), largest, "around ({}, {})", x, y);
+                    if let Some(square) = found {
+                        assert!(polygon.contains_rect(Rect::spanning(square.corner1, square.corner2)));
+                    }
+                }
+            }
+        }
+
+        assert!(matches!("2,3,11,5".parse(), Ok(Query::Inside(_))));
+        assert_eq!("7,4".parse(), Ok(Query::Around(Coordinate { x: 7, y: 4 })));
+        assert!("1,2,3".parse::<Query>().is_err());
     }
 }
@@ -1,14 +1,27 @@
-use anyhow::{anyhow, Context, Result};
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
 use std::fmt;
+use crate::input::Input;
+use crate::util::cancel::CancellationToken;
+use crate::util::graph::Graph;
+use crate::util::output::OutputSink;
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::trace::{self, Event};
+use crate::util::stats;
+use clap::{Parser, ValueEnum};
+use std::collections::HashMap;
 use std::fs;
+use std::time::Duration;
+use web_time::Instant;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Machine {
     pub goal_lights: Vec<bool>,        // Goal state of lights
     pub current_lights: Vec<bool>,          // Current state of lights (initially all false)
     pub goal_joltage: Vec<usize>,    // Goal state of joltage (from curly braces)
     pub current_joltage: Vec<usize>, // Current state of joltage (initially all 0)
     pub buttons: Vec<Vec<usize>>,
+    pub modulus: Option<usize>,      // Counters wrap modulo this value (from a `%M` suffix)
 }
 
 impl Machine {
@@ -17,7 +30,7 @@ impl Machine {
 
 impl fmt::Debug for Machine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "")?;
+        writeln!(f)?;
         writeln!(f, "Joltage:")?;
         write!(f, "- current: {{")?;
         for (i, &jolt) in self.current_joltage.iter().enumerate() {
@@ -63,131 +76,435 @@ impl fmt::Debug for Machine {
             }
             write!(f, ")")?;
         }
-        writeln!(f, "")?;
+        writeln!(f)?;
         
         Ok(())
     }
 }
 
-fn parse_input(filename: &str) -> Result<Vec<Machine>> {
-    let content = fs::read_to_string(filename)
-        .context(format!("Failed to read file: {}", filename))?;
+/// Re-serializes the machine in the input line format, e.g. `[.##.] (3) (1,3) {3,5,4,7}`
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for &light in &self.goal_lights {
+            write!(f, "{}", if light { '#' } else { '.' })?;
+        }
+        write!(f, "]")?;
+        for button in &self.buttons {
+            write!(f, " ({})", join_values(button))?;
+        }
+        write!(f, " {{{}}}", join_values(&self.goal_joltage))?;
+        if let Some(modulus) = self.modulus {
+            write!(f, "%{}", modulus)?;
+        }
+        Ok(())
+    }
+}
 
-    let machines: Vec<Machine> = content
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .enumerate()
-        .map(|(i, line)| {
-            let line = line.trim();
-            
-            // Extract lights part: [.##.]
-            let lights_start = line.find('[')
-                .ok_or_else(|| anyhow!("Line {}: missing '[' for lights", i + 1))?;
-            let lights_end = line.find(']')
-                .ok_or_else(|| anyhow!("Line {}: missing ']' for lights", i + 1))?;
-            
-            let lights_str = &line[lights_start + 1..lights_end];
-            let solution: Vec<bool> = lights_str
-                .chars()
-                .map(|c| match c {
-                    '.' => Ok(false),
-                    '#' => Ok(true),
-                    _ => Err(anyhow!("Line {}: invalid light character '{}'", i + 1, c)),
-                })
-                .collect::<Result<Vec<_>>>()?;
-            
-            // Initialize current state to all off (false)
-            let current = vec![false; solution.len()];
-            
-            // Extract buttons: (3) (1,3) (2) etc.
-            let mut buttons = Vec::new();
-            let mut pos = lights_end + 1;
-            
-            while pos < line.len() {
-                // Skip whitespace
-                while pos < line.len() && line.chars().nth(pos).unwrap().is_whitespace() {
-                    pos += 1;
+fn join_values(values: &[usize]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// One bracketed section of a machine line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Section {
+    Lights(Vec<bool>),
+    Button(Vec<usize>),
+    Joltage(Vec<usize>, Option<usize>),
+}
+
+/// Single-pass tokenizer over one machine line.
+/// Columns are 1-based character positions so errors point at the offending character.
+struct LineTokenizer {
+    line_no: usize,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LineTokenizer {
+    fn new(line: &str, line_no: usize) -> Self {
+        LineTokenizer {
+            line_no,
+            chars: line.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, column: usize, message: String) -> AocError {
+        AocError::parse_at_column(self.line_no, column, message)
+    }
+
+    /// Return the next section with its starting column, or None once the end of the line is reached
+    fn next_section(&mut self) -> Result<Option<(usize, Section)>> {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+
+        let Some(&open) = self.chars.get(self.pos) else {
+            return Ok(None);
+        };
+        let open_column = self.pos + 1;
+        self.pos += 1;
+
+        match open {
+            '[' => {
+                let mut lights = Vec::new();
+                loop {
+                    match self.chars.get(self.pos) {
+                        Some('.') => lights.push(false),
+                        Some('#') => lights.push(true),
+                        Some(']') => break,
+                        Some(&c) => {
+                            return Err(self.error(self.pos + 1, format!("invalid light character '{}'", c)));
+                        }
+                        None => {
+                            return Err(self.error(open_column, "missing ']' for lights".to_string()));
+                        }
+                    }
+                    self.pos += 1;
                 }
-                
-                // Check if we've reached the curly braces part
-                if pos < line.len() && line.chars().nth(pos).unwrap() == '{' {
-                    break;
+                self.pos += 1;
+                Ok(Some((open_column, Section::Lights(lights))))
+            }
+            '(' => {
+                let indices = self.parse_values(open_column, ')', "button index")?;
+                Ok(Some((open_column, Section::Button(indices))))
+            }
+            '{' => {
+                let joltage = self.parse_values(open_column, '}', "joltage value")?;
+                let modulus = self.parse_modulus()?;
+                Ok(Some((open_column, Section::Joltage(joltage, modulus))))
+            }
+            c => Err(self.error(open_column, format!("unexpected character '{}'", c))),
+        }
+    }
+
+    /// Parse an optional `%M` suffix directly after a joltage section
+    fn parse_modulus(&mut self) -> Result<Option<usize>> {
+        if self.chars.get(self.pos) != Some(&'%') {
+            return Ok(None);
+        }
+        self.pos += 1;
+
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.parse::<usize>() {
+            Ok(modulus) if modulus > 0 => Ok(Some(modulus)),
+            _ => Err(self.error(start + 1, format!("invalid modulus '{}'", digits))),
+        }
+    }
+
+    /// Parse a comma-separated list of integers up to (and consuming) the closing delimiter
+    fn parse_values(&mut self, open_column: usize, close: char, what: &str) -> Result<Vec<usize>> {
+        let mut values = Vec::new();
+        let mut item = String::new();
+        let mut item_column = self.pos + 1;
+
+        loop {
+            let Some(&c) = self.chars.get(self.pos) else {
+                return Err(self.error(open_column, format!("missing '{}' for {}", close, what)));
+            };
+            self.pos += 1;
+
+            if c == ',' || c == close {
+                let trimmed = item.trim();
+                // An empty list like "()" is allowed, but not empty items like "(1,,2)"
+                if !(trimmed.is_empty() && c == close && values.is_empty()) {
+                    let value = trimmed
+                        .parse::<usize>()
+                        .map_err(|_| self.error(item_column, format!("invalid {} '{}'", what, trimmed)))?;
+                    values.push(value);
                 }
-                
-                // Find next opening parenthesis
-                if let Some(open_paren) = line[pos..].find('(') {
-                    let button_start = pos + open_paren;
-                    let button_end = line[button_start..]
-                        .find(')')
-                        .ok_or_else(|| anyhow!("Line {}: missing ')' for button", i + 1))?;
-                    
-                    let button_str = &line[button_start + 1..button_start + button_end];
-                    let button_indices: Vec<usize> = if button_str.is_empty() {
-                        Vec::new()
-                    } else {
-                        button_str
-                            .split(',')
-                            .map(|s| {
-                                s.trim()
-                                    .parse::<usize>()
-                                    .context(format!("Line {}: invalid button index '{}'", i + 1, s))
-                            })
-                            .collect::<Result<Vec<_>>>()?
-                    };
-                    
-                    buttons.push(button_indices);
-                    pos = button_start + button_end + 1;
-                } else {
-                    break;
+                if c == close {
+                    return Ok(values);
                 }
+                item.clear();
+                item_column = self.pos + 1;
+            } else {
+                item.push(c);
             }
-            
-            // Extract joltage goal from curly braces: {3,5,4,7}
-            let goal_joltage = if let Some(brace_start) = line.find('{') {
-                let brace_end = line[brace_start..]
-                    .find('}')
-                    .ok_or_else(|| anyhow!("Line {}: missing '}}' for joltage", i + 1))?;
-                
-                let joltage_str = &line[brace_start + 1..brace_start + brace_end];
-                if joltage_str.is_empty() {
-                    Vec::new()
-                } else {
-                    joltage_str
-                        .split(',')
-                        .map(|s| {
-                            s.trim()
-                                .parse::<usize>()
-                                .context(format!("Line {}: invalid joltage value '{}'", i + 1, s))
-                        })
-                        .collect::<Result<Vec<_>>>()?
+        }
+    }
+}
+
+/// Parse one machine line; sections may appear in any order
+fn parse_machine(line: &str, line_no: usize) -> Result<Machine> {
+    let mut tokenizer = LineTokenizer::new(line, line_no);
+    let mut goal_lights = None;
+    let mut goal_joltage = None;
+    let mut modulus = None;
+    let mut buttons = Vec::new();
+
+    while let Some((column, section)) = tokenizer.next_section()? {
+        match section {
+            Section::Lights(lights) => {
+                if goal_lights.replace(lights).is_some() {
+                    return Err(tokenizer.error(column, "duplicate lights section".to_string()));
                 }
-            } else {
-                Vec::new()
-            };
-            
-            // Initialize current joltage to all 0
-            let current_joltage = vec![0; goal_joltage.len()];
-            
-            Ok(Machine { 
-                goal_lights: solution, 
-                current_lights: current, 
-                goal_joltage,
-                current_joltage,
-                buttons 
-            })
-        })
-        .collect::<Result<Vec<_>>>()?;
+            }
+            Section::Button(indices) => buttons.push(indices),
+            Section::Joltage(joltage, wrap) => {
+                if goal_joltage.replace(joltage).is_some() {
+                    return Err(tokenizer.error(column, "duplicate joltage section".to_string()));
+                }
+                modulus = wrap;
+            }
+        }
+    }
+
+    let goal_lights = goal_lights
+        .ok_or_else(|| AocError::parse_at(line_no, "missing '[' for lights"))?;
+    let goal_joltage = goal_joltage.unwrap_or_default();
+
+    Ok(Machine {
+        current_lights: vec![false; goal_lights.len()],
+        current_joltage: vec![0; goal_joltage.len()],
+        goal_lights,
+        goal_joltage,
+        buttons,
+        modulus,
+    })
+}
 
-    Ok(machines)
+pub fn parse_input(content: &str) -> Result<Vec<Machine>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_machine(line, i + 1))
+        .collect()
 }
 
 // Old brute-force methods removed - using Gaussian elimination now
 
+/// Why a machine's joltage goal cannot be reached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Infeasibility {
+    /// A counter with a non-zero goal that no button increments
+    UntouchedCounter(usize),
+    /// The counters whose equations combine into `0 = non-zero` (no rational solution)
+    Inconsistent(Vec<usize>),
+    /// Rational solutions exist, but these counters' equations rule out integer press counts
+    NonIntegral(Vec<usize>),
+}
+
+impl fmt::Display for Infeasibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Infeasibility::UntouchedCounter(counter) => {
+                write!(f, "counter {} has a non-zero goal but no button affects it", counter)
+            }
+            Infeasibility::Inconsistent(counters) => {
+                write!(f, "counters {:?} have contradictory goals", counters)
+            }
+            Infeasibility::NonIntegral(counters) => {
+                write!(f, "counters {:?} can only be met with fractional presses", counters)
+            }
+        }
+    }
+}
+
+/// Result of the pre-solve analysis of a machine's joltage system `A x = b`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeasibilityReport {
+    pub rank: usize,           // Rank of the button matrix A
+    pub augmented_rank: usize, // Rank of [A | b]
+    pub problems: Vec<Infeasibility>,
+}
+
+impl FeasibilityReport {
+    pub fn is_feasible(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Check whether a machine's joltage goal is reachable at all before searching for presses.
+/// Uses fraction-free integer elimination, so the ranks are exact (no floating point tolerance).
+/// Each row also carries which original counters it was built from, for diagnostics.
+pub fn analyze_feasibility(machine: &Machine) -> FeasibilityReport {
+    let num_counters = machine.goal_joltage.len();
+    let num_buttons = machine.buttons.len();
+
+    // Row layout: [A (num_buttons) | b | provenance (num_counters)]
+    let width = num_buttons + 1 + num_counters;
+    let mut rows: Vec<Vec<i128>> = (0..num_counters)
+        .map(|counter| {
+            let mut row = vec![0i128; width];
+            for (button_idx, button) in machine.buttons.iter().enumerate() {
+                if button.contains(&counter) {
+                    row[button_idx] = 1;
+                }
+            }
+            row[num_buttons] = machine.goal_joltage[counter] as i128;
+            row[num_buttons + 1 + counter] = 1;
+            row
+        })
+        .collect();
+
+    let mut rank = 0;
+    for col in 0..num_buttons {
+        let Some(pivot) = (rank..num_counters).find(|&r| rows[r][col] != 0) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+
+        let pivot_row = rows[rank].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r == rank || row[col] == 0 {
+                continue;
+            }
+            let factor = row[col];
+            for (value, &pivot_value) in row.iter_mut().zip(&pivot_row) {
+                *value = *value * pivot_row[col] - factor * pivot_value;
+            }
+            // Keep the integers small by dividing out the row's common factor
+            let divisor = row.iter().fold(0, |acc, &v| gcd(acc, v));
+            if divisor > 1 {
+                row.iter_mut().for_each(|v| *v /= divisor);
+            }
+        }
+
+        rank += 1;
+        if rank == num_counters {
+            break;
+        }
+    }
+
+    let involved_counters = |row: &[i128]| -> Vec<usize> {
+        (0..num_counters)
+            .filter(|&counter| row[num_buttons + 1 + counter] != 0)
+            .collect()
+    };
+
+    let mut augmented_rank = rank;
+    let mut problems = Vec::new();
+    for row in &rows {
+        let coefficient_gcd = row[..num_buttons].iter().fold(0, |acc, &v| gcd(acc, v));
+        let rhs = row[num_buttons];
+
+        if coefficient_gcd == 0 {
+            if rhs == 0 {
+                continue;
+            }
+            augmented_rank += 1;
+            let counters = involved_counters(row);
+            let untouched = counters.len() == 1
+                && !machine.buttons.iter().any(|button| button.contains(&counters[0]));
+            problems.push(if untouched {
+                Infeasibility::UntouchedCounter(counters[0])
+            } else {
+                Infeasibility::Inconsistent(counters)
+            });
+        } else if rhs % coefficient_gcd != 0 {
+            // Fully reduced rows can repeat the same certificate; report it once
+            let problem = Infeasibility::NonIntegral(involved_counters(row));
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+
+    FeasibilityReport {
+        rank,
+        augmented_rank,
+        problems,
+    }
+}
+
+
+/// Splits a machine into independent subsystems: the connected components
+/// of the graph linking each button to the counters it wires. Counters in
+/// different subsystems share no button, so their fewest presses add up,
+/// and each piece enumerates only its own free variables. Counters keep
+/// their relative order; buttons that wire no counter are dropped, since
+/// pressing them never helps.
+pub fn subsystems(machine: &Machine) -> Vec<Machine> {
+    let counters = machine.goal_joltage.len();
+    let mut graph = Graph::new();
+    for counter in 0..counters {
+        graph.add_node(&format!("counter {}", counter));
+    }
+    for (i, button) in machine.buttons.iter().enumerate() {
+        let node = graph.add_node(&format!("button {}", i));
+        for &counter in button.iter().filter(|&&c| c < counters) {
+            graph.add_edge(node, graph.node_id(&format!("counter {}", counter)).expect("counter nodes come first"));
+        }
+    }
+
+    // Counters are the first nodes, so every component holding one starts with it
+    graph
+        .weakly_connected_components()
+        .into_iter()
+        .filter(|component| component[0].index() < counters)
+        .map(|component| {
+            let (owned, pressed): (Vec<usize>, Vec<usize>) =
+                component.iter().map(|node| node.index()).partition(|&index| index < counters);
+            let label: HashMap<usize, usize> = owned.iter().enumerate().map(|(new, &old)| (old, new)).collect();
+            let goal_lights: Vec<bool> = owned.iter().filter_map(|&c| machine.goal_lights.get(c).copied()).collect();
+            Machine {
+                current_lights: vec![false; goal_lights.len()],
+                goal_lights,
+                goal_joltage: owned.iter().map(|&c| machine.goal_joltage[c]).collect(),
+                current_joltage: vec![0; owned.len()],
+                buttons: pressed
+                    .iter()
+                    .map(|&node| machine.buttons[node - counters].iter().filter_map(|c| label.get(c).copied()).collect())
+                    .collect(),
+                modulus: machine.modulus,
+            }
+        })
+        .collect()
+}
+
+/// Sum of every subsystem's fewest presses, or None when one has no solution
+fn sum_over_subsystems(
+    machine: &Machine,
+    mut solve: impl FnMut(&Machine) -> Result<Option<usize>>,
+) -> Result<Option<usize>> {
+    let pieces = subsystems(machine);
+    stats::add("day10.subsystems", pieces.len() as u64);
+    let mut total = 0;
+    for piece in &pieces {
+        match solve(piece)? {
+            Some(presses) => total += presses,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(total))
+}
+
 /// Solve a machine's joltage using Gaussian elimination with free variable optimization
-/// Returns the minimum number of button presses needed
-fn solve_joltage(machine: &Machine) -> usize {
+/// Returns the minimum number of button presses needed, or None if no solution was found
+pub fn solve_joltage(machine: &Machine) -> Option<usize> {
+    solve_joltage_cancellable(machine, &CancellationToken::new()).expect("an unfired token never cancels")
+}
+
+/// `solve_joltage` that polls `cancel` while enumerating free variables
+pub fn solve_joltage_cancellable(machine: &Machine, cancel: &CancellationToken) -> Result<Option<usize>> {
+    search_joltage(machine, cancel, &mut 0)
+}
+
+/// The free-variable search behind `solve_joltage`, run on each subsystem
+/// separately and adding the number of search nodes it visits to `nodes`
+fn search_joltage(machine: &Machine, cancel: &CancellationToken, nodes: &mut usize) -> Result<Option<usize>> {
+    sum_over_subsystems(machine, |piece| search_subsystem(piece, cancel, nodes))
+}
+
+/// `search_joltage` on the machine as a whole
+fn search_subsystem(machine: &Machine, cancel: &CancellationToken, nodes: &mut usize) -> Result<Option<usize>> {
     if machine.goal_joltage.is_empty() {
-        return 0;
+        return Ok(Some(0));
     }
     
     let num_counters = machine.goal_joltage.len();
@@ -228,16 +545,17 @@ fn solve_joltage(machine: &Machine) -> usize {
             
             // Normalize pivot row
             let pivot_val = matrix[current_row][col];
-            for j in 0..=num_buttons {
-                matrix[current_row][j] /= pivot_val;
+            for value in matrix[current_row].iter_mut() {
+                *value /= pivot_val;
             }
             
             // Eliminate below and above the pivot
-            for row in 0..num_counters {
-                if row != current_row && matrix[row][col].abs() > 1e-10 {
-                    let factor = matrix[row][col];
-                    for j in 0..=num_buttons {
-                        matrix[row][j] -= factor * matrix[current_row][j];
+            let pivot_row_values = matrix[current_row].clone();
+            for (row, values) in matrix.iter_mut().enumerate() {
+                if row != current_row && values[col].abs() > 1e-10 {
+                    let factor = values[col];
+                    for (value, &pivot_value) in values.iter_mut().zip(&pivot_row_values) {
+                        *value -= factor * pivot_value;
                     }
                 }
             }
@@ -282,7 +600,7 @@ fn solve_joltage(machine: &Machine) -> usize {
         let total: usize = solution.iter()
             .map(|&x| x.round().max(0.0) as usize)
             .sum();
-        return total;
+        return Ok(Some(total));
     }
     
     // Search over small values of free variables to find minimum
@@ -352,9 +670,17 @@ fn solve_joltage(machine: &Machine) -> usize {
         current: &mut Vec<usize>,
         try_fn: &impl Fn(&[usize]) -> Option<usize>,
         best: &mut usize,
+        cancel: &CancellationToken,
+        nodes: &mut usize,
     ) {
+        *nodes += 1;
+        if cancel.is_cancelled() {
+            return;
+        }
         if current.len() == free_vars_count {
-            if let Some(sum) = try_fn(current) {
+            let presses = try_fn(current);
+            trace::emit(|| Event::Assign { values: current.clone(), presses });
+            if let Some(sum) = presses {
                 *best = (*best).min(sum);
             }
             return;
@@ -370,71 +696,836 @@ fn solve_joltage(machine: &Machine) -> usize {
             }
             
             current.push(val);
-            enumerate_combinations(free_vars_count, search_limit, current, try_fn, best);
+            enumerate_combinations(free_vars_count, search_limit, current, try_fn, best, cancel, nodes);
             current.pop();
         }
     }
     
     let mut current = Vec::new();
-    enumerate_combinations(free_vars.len(), search_limit, &mut current, &try_free_assignment, &mut best_sum);
+    enumerate_combinations(free_vars.len(), search_limit, &mut current, &try_free_assignment, &mut best_sum, cancel, nodes);
+    cancel.check()?;
     
-    // If no solution found, return 0 (should not happen with correct input)
+    // No solution within the search limit
     if best_sum == usize::MAX {
-        eprintln!("WARNING: No solution found for machine!");
-        return 0;
+        return Ok(None);
     }
     
-    best_sum
+    Ok(Some(best_sum))
+}
+
+/// Smith normal form `D = U * A * V` of an integer matrix, with `U` and `V` unimodular
+#[derive(Debug, Clone)]
+struct SmithNormalForm {
+    d: Vec<Vec<i128>>,
+    u: Vec<Vec<i128>>,
+    v: Vec<Vec<i128>>,
+}
+
+fn identity(n: usize) -> Vec<Vec<i128>> {
+    (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1 } else { 0 }).collect())
+        .collect()
+}
+
+fn smith_normal_form(a: &[Vec<i128>], num_cols: usize) -> SmithNormalForm {
+    let num_rows = a.len();
+    let mut d = a.to_vec();
+    let mut u = identity(num_rows);
+    let mut v = identity(num_cols);
+
+    // Row and column operations, mirrored into U and V respectively
+    fn add_row(m: &mut [Vec<i128>], target: usize, source: usize, factor: i128) {
+        for k in 0..m[target].len() {
+            m[target][k] += factor * m[source][k];
+        }
+    }
+    fn add_col(m: &mut [Vec<i128>], target: usize, source: usize, factor: i128) {
+        for row in m.iter_mut() {
+            row[target] += factor * row[source];
+        }
+    }
+    fn swap_cols(m: &mut [Vec<i128>], a: usize, b: usize) {
+        for row in m.iter_mut() {
+            row.swap(a, b);
+        }
+    }
+
+    for t in 0..num_rows.min(num_cols) {
+        loop {
+            // Move the smallest non-zero entry of the remaining block to (t, t)
+            let smallest = (t..num_rows)
+                .flat_map(|i| (t..num_cols).map(move |j| (i, j)))
+                .filter(|&(i, j)| d[i][j] != 0)
+                .min_by_key(|&(i, j)| d[i][j].abs());
+            let Some((pi, pj)) = smallest else {
+                return SmithNormalForm { d, u, v };
+            };
+            d.swap(t, pi);
+            u.swap(t, pi);
+            swap_cols(&mut d, t, pj);
+            swap_cols(&mut v, t, pj);
+
+            // Reduce the pivot's row and column; any remainder becomes a smaller pivot
+            let mut clean = true;
+            for i in t + 1..num_rows {
+                let q = d[i][t] / d[t][t];
+                add_row(&mut d, i, t, -q);
+                add_row(&mut u, i, t, -q);
+                clean &= d[i][t] == 0;
+            }
+            for j in t + 1..num_cols {
+                let q = d[t][j] / d[t][t];
+                add_col(&mut d, j, t, -q);
+                add_col(&mut v, j, t, -q);
+                clean &= d[t][j] == 0;
+            }
+            if !clean {
+                continue;
+            }
+
+            // The pivot must divide every remaining entry; if not, fold that row in and retry
+            let offending_row = (t + 1..num_rows)
+                .find(|&i| (t + 1..num_cols).any(|j| d[i][j] % d[t][t] != 0));
+            match offending_row {
+                Some(i) => {
+                    add_row(&mut d, t, i, 1);
+                    add_row(&mut u, t, i, 1);
+                }
+                None => break,
+            }
+        }
+
+        if d[t][t] < 0 {
+            d[t].iter_mut().for_each(|x| *x = -*x);
+            u[t].iter_mut().for_each(|x| *x = -*x);
+        }
+    }
+
+    SmithNormalForm { d, u, v }
+}
+
+/// Extended Euclid: returns (g, x, y) with a*x + b*y = g
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a.rem_euclid(b));
+        (g, y, x - (a.div_euclid(b)) * y)
+    }
+}
+
+/// Cap on the number of candidate solutions enumerated for a modular machine
+const MAX_MODULAR_CANDIDATES: usize = 1_000_000;
+
+/// Solve a machine whose counters wrap modulo M: find presses x with `A x ≡ b (mod M)`
+/// minimizing the total presses. Pressing a button M times is a no-op, so each x_i is in 0..M.
+/// Uses the Smith normal form to diagonalize the system, solves each diagonal congruence,
+/// then enumerates the (usually small) solution set. Returns None if no solution exists
+/// or the solution set exceeds `MAX_MODULAR_CANDIDATES`. Adds the number of candidates
+/// enumerated to `nodes`. Each subsystem is solved on its own, so the cap applies per piece.
+fn solve_joltage_modular(machine: &Machine, modulus: usize, nodes: &mut usize) -> Option<usize> {
+    sum_over_subsystems(machine, |piece| Ok(solve_subsystem_modular(piece, modulus, nodes)))
+        .expect("the modular solver never fails")
+}
+
+/// `solve_joltage_modular` on the machine as a whole
+fn solve_subsystem_modular(machine: &Machine, modulus: usize, nodes: &mut usize) -> Option<usize> {
+    let m = modulus as i128;
+    let num_counters = machine.goal_joltage.len();
+    let num_buttons = machine.buttons.len();
+
+    let matrix: Vec<Vec<i128>> = (0..num_counters)
+        .map(|counter| {
+            machine
+                .buttons
+                .iter()
+                .map(|button| button.iter().filter(|&&c| c == counter).count() as i128)
+                .collect()
+        })
+        .collect();
+    let snf = smith_normal_form(&matrix, num_buttons);
+
+    // c = U b (mod M), so the system becomes D y ≡ c with x = V y
+    let c: Vec<i128> = snf
+        .u
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&machine.goal_joltage)
+                .map(|(&coef, &goal)| coef * goal as i128)
+                .sum::<i128>()
+                .rem_euclid(m)
+        })
+        .collect();
+
+    let diagonal = |i: usize| if i < num_counters { snf.d[i][i] } else { 0 };
+
+    // Rows without a matching button column must already be satisfied
+    if (num_buttons..num_counters).any(|i| c[i] != 0) {
+        return None;
+    }
+
+    // Candidate values for each y_i
+    let mut choices: Vec<Vec<i128>> = Vec::with_capacity(num_buttons);
+    for (i, &target) in c.iter().chain(std::iter::repeat(&0)).take(num_buttons).enumerate() {
+        let d = diagonal(i).rem_euclid(m);
+        if d == 0 {
+            if target != 0 {
+                return None;
+            }
+            choices.push((0..m).collect());
+            continue;
+        }
+        let (g, inverse, _) = extended_gcd(d, m);
+        if target % g != 0 {
+            return None;
+        }
+        let step = m / g;
+        let base = ((target / g) * inverse).rem_euclid(step);
+        choices.push((0..g).map(|k| base + k * step).collect());
+    }
+
+    let total_candidates = choices
+        .iter()
+        .try_fold(1usize, |acc, options| acc.checked_mul(options.len()))?;
+    if total_candidates > MAX_MODULAR_CANDIDATES {
+        return None;
+    }
+    *nodes += total_candidates;
+
+    let mut best: Option<usize> = None;
+    let mut indices = vec![0usize; num_buttons];
+    for _ in 0..total_candidates {
+        let y: Vec<i128> = indices.iter().zip(&choices).map(|(&k, options)| options[k]).collect();
+        let presses: usize = snf
+            .v
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&y)
+                    .map(|(&coef, &value)| coef * value)
+                    .sum::<i128>()
+                    .rem_euclid(m) as usize
+            })
+            .sum();
+        best = Some(best.map_or(presses, |b| b.min(presses)));
+
+        // Advance the mixed-radix counter over all choices
+        for (index, options) in indices.iter_mut().zip(&choices) {
+            *index += 1;
+            if *index < options.len() {
+                break;
+            }
+            *index = 0;
+        }
+    }
+
+    best
+}
+
+/// Which algorithm solves machines without a modulus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Solver {
+    /// Branch and bound over the free variables of the row-reduced system
+    Search,
+    /// Local search around a particular solution along an LLL-reduced
+    /// basis of the integer nullspace, for machines with many free variables
+    Lattice,
+}
+
+/// Lovász condition constant for `lll_reduce`
+const LLL_DELTA: f64 = 0.75;
+
+/// Most lattice points the neighbourhood of one centre may hold
+const LATTICE_NEIGHBOURHOOD: usize = 20_000;
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn to_f64(vector: &[i128]) -> Vec<f64> {
+    vector.iter().map(|&x| x as f64).collect()
+}
+
+/// Gram-Schmidt orthogonalization of `basis`: the orthogonal vectors and the
+/// coefficients `mu[i][j]` of each vector along the earlier orthogonal ones
+fn gram_schmidt(basis: &[Vec<i128>]) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let mut orthogonal: Vec<Vec<f64>> = Vec::with_capacity(basis.len());
+    let mut mu = vec![vec![0.0; basis.len()]; basis.len()];
+    for (i, vector) in basis.iter().enumerate() {
+        let float = to_f64(vector);
+        let mut v = float.clone();
+        for j in 0..i {
+            let norm = dot(&orthogonal[j], &orthogonal[j]);
+            mu[i][j] = if norm == 0.0 { 0.0 } else { dot(&float, &orthogonal[j]) / norm };
+            for (value, &o) in v.iter_mut().zip(&orthogonal[j]) {
+                *value -= mu[i][j] * o;
+            }
+        }
+        orthogonal.push(v);
+    }
+    (orthogonal, mu)
+}
+
+/// LLL-reduces a lattice basis in place, so its vectors come out short and
+/// close to orthogonal. The bases here have a handful of vectors with small
+/// entries, so the Gram-Schmidt data is simply recomputed after each step.
+fn lll_reduce(basis: &mut [Vec<i128>]) {
+    let mut i = 1;
+    while i < basis.len() {
+        for j in (0..i).rev() {
+            let q = gram_schmidt(basis).1[i][j].round() as i128;
+            if q != 0 {
+                let reducer = basis[j].clone();
+                for (value, r) in basis[i].iter_mut().zip(reducer) {
+                    *value -= q * r;
+                }
+            }
+        }
+        let (orthogonal, mu) = gram_schmidt(basis);
+        let lovasz = (LLL_DELTA - mu[i][i - 1] * mu[i][i - 1]) * dot(&orthogonal[i - 1], &orthogonal[i - 1]);
+        if dot(&orthogonal[i], &orthogonal[i]) >= lovasz {
+            i += 1;
+        } else {
+            basis.swap(i, i - 1);
+            i = (i - 1).max(1);
+        }
+    }
+}
+
+/// How far `x` is from being a valid press vector, then, once it is one,
+/// how many presses it takes; lower is better. Presses don't count before
+/// then, or the search could slide off forever along a direction that
+/// leaves the shortfall alone.
+fn lattice_score(x: &[i128]) -> (i128, i128) {
+    let shortfall = x.iter().filter(|&&v| v < 0).map(|&v| -v).sum();
+    (shortfall, if shortfall == 0 { x.iter().sum() } else { 0 })
+}
+
+/// Fewest presses found by the lattice method on the machine as a whole.
+/// The Smith form `D = U A V` gives an integer solution `x0` of `A x = b`,
+/// and the last columns of `V`, past the rank, are an integer basis of the
+/// nullspace: the unimodular column reduction behind the Hermite form. LLL
+/// makes that basis short, `x0` is reduced against it Babai-style, and then
+/// a local search moves to the best point of the box of basis combinations
+/// around it until nothing in the box does better. Every answer is a real
+/// press vector, but a better one outside every box visited can be missed,
+/// so this is a heuristic to check against `Solver::Search`. Adds the lattice
+/// points tried to `nodes`.
+fn solve_subsystem_lattice(machine: &Machine, nodes: &mut usize) -> Option<usize> {
+    let num_counters = machine.goal_joltage.len();
+    let num_buttons = machine.buttons.len();
+    let matrix: Vec<Vec<i128>> = (0..num_counters)
+        .map(|counter| {
+            machine
+                .buttons
+                .iter()
+                .map(|button| button.iter().filter(|&&c| c == counter).count() as i128)
+                .collect()
+        })
+        .collect();
+    let snf = smith_normal_form(&matrix, num_buttons);
+    let c: Vec<i128> = snf
+        .u
+        .iter()
+        .map(|row| row.iter().zip(&machine.goal_joltage).map(|(&coef, &goal)| coef * goal as i128).sum())
+        .collect();
+
+    // D y = c, with y free past the rank
+    let rank = (0..num_counters.min(num_buttons)).take_while(|&i| snf.d[i][i] != 0).count();
+    if (rank..num_counters).any(|i| c[i] != 0) || (0..rank).any(|i| c[i] % snf.d[i][i] != 0) {
+        return None;
+    }
+    let y: Vec<i128> = (0..num_buttons).map(|i| if i < rank { c[i] / snf.d[i][i] } else { 0 }).collect();
+    let mut x: Vec<i128> = snf.v.iter().map(|row| row.iter().zip(&y).map(|(&v, &y)| v * y).sum()).collect();
+    let mut basis: Vec<Vec<i128>> = (rank..num_buttons).map(|j| snf.v.iter().map(|row| row[j]).collect()).collect();
+    stats::add("day10.lattice_dimensions", basis.len() as u64);
+
+    // A unique solution has nothing to search around
+    if basis.is_empty() {
+        return x.iter().all(|&v| v >= 0).then(|| x.iter().sum::<i128>() as usize);
+    }
+    lll_reduce(&mut basis);
+    let (orthogonal, _) = gram_schmidt(&basis);
+    for (vector, o) in basis.iter().zip(&orthogonal).rev() {
+        let q = (dot(&to_f64(&x), o) / dot(o, o)).round() as i128;
+        for (value, &b) in x.iter_mut().zip(vector) {
+            *value -= q * b;
+        }
+    }
+
+    // The widest box of combinations with coefficients in -radius..=radius
+    // that fits the budget; past nine dimensions even -1..=1 doesn't, and
+    // only the budget's worth of it is tried
+    let dims = basis.len() as u32;
+    let side = |radius: usize| (2 * radius + 1).checked_pow(dims).unwrap_or(usize::MAX);
+    let mut radius = 1;
+    while side(radius + 1) <= LATTICE_NEIGHBOURHOOD {
+        radius += 1;
+    }
+    let points = side(radius).min(LATTICE_NEIGHBOURHOOD);
+
+    let mut best = lattice_score(&x);
+    let mut coefficients = vec![-(radius as i128); basis.len()];
+    loop {
+        let mut moved: Option<Vec<i128>> = None;
+        coefficients.iter_mut().for_each(|t| *t = -(radius as i128));
+        for _ in 0..points {
+            let candidate: Vec<i128> = (0..num_buttons)
+                .map(|i| x[i] + basis.iter().zip(&coefficients).map(|(b, &t)| t * b[i]).sum::<i128>())
+                .collect();
+            *nodes += 1;
+            let score = lattice_score(&candidate);
+            if score < best {
+                best = score;
+                moved = Some(candidate);
+            }
+            // Advance the mixed-radix counter over the box
+            for t in coefficients.iter_mut() {
+                *t += 1;
+                if *t <= radius as i128 {
+                    break;
+                }
+                *t = -(radius as i128);
+            }
+        }
+        match moved {
+            Some(candidate) => x = candidate,
+            None => break,
+        }
+    }
+
+    (best.0 == 0).then_some(best.1 as usize)
+}
+
+/// `solve_subsystem_lattice` on each subsystem
+fn search_lattice(machine: &Machine, nodes: &mut usize) -> Option<usize> {
+    sum_over_subsystems(machine, |piece| Ok(solve_subsystem_lattice(piece, nodes))).expect("the lattice solver never fails")
+}
+
+/// Check feasibility, then solve; prints diagnostics for machines that can't be solved
+fn check_and_solve(machine_number: usize, machine: &Machine, cancel: &CancellationToken, out: &dyn Reporter) -> Result<Option<usize>> {
+    check_and_solve_counted(machine_number, machine, Solver::Search, cancel, &mut 0, out)
+}
+
+/// `check_and_solve`, adding the search nodes the solver visits to `nodes`
+/// and to the `--stats` report
+fn check_and_solve_counted(
+    machine_number: usize,
+    machine: &Machine,
+    solver: Solver,
+    cancel: &CancellationToken,
+    nodes: &mut usize,
+    out: &dyn Reporter,
+) -> Result<Option<usize>> {
+    let before = *nodes;
+    let presses = solve_machine(machine_number, machine, solver, cancel, nodes, out);
+    stats::add("day10.machines_solved", 1);
+    stats::add("day10.search_nodes", (*nodes - before) as u64);
+    presses
+}
+
+fn solve_machine(
+    machine_number: usize,
+    machine: &Machine,
+    solver: Solver,
+    cancel: &CancellationToken,
+    nodes: &mut usize,
+    out: &dyn Reporter,
+) -> Result<Option<usize>> {
+    trace::emit(|| Event::Machine { number: machine_number });
+    if let Some(modulus) = machine.modulus {
+        let presses = solve_joltage_modular(machine, modulus, nodes);
+        if presses.is_none() {
+            out.line(&format!("Machine {}: no solution modulo {}", machine_number, modulus));
+        }
+        return Ok(presses);
+    }
+
+    let report = analyze_feasibility(machine);
+    if !report.is_feasible() {
+        out.line(&format!(
+            "Machine {}: infeasible (rank {} vs augmented rank {})",
+            machine_number, report.rank, report.augmented_rank
+        ));
+        for problem in &report.problems {
+            out.line(&format!("  - {}", problem));
+        }
+        return Ok(None);
+    }
+
+    let presses = match solver {
+        Solver::Search => search_joltage(machine, cancel, nodes)?,
+        Solver::Lattice => search_lattice(machine, nodes),
+    };
+    if presses.is_none() {
+        let limit = match solver {
+            Solver::Search => "within the search limit",
+            Solver::Lattice => "near the lattice's particular solution",
+        };
+        out.line(&format!("Machine {}: feasible, but no solution found {}", machine_number, limit));
+    }
+    Ok(presses)
+}
+
+/// What the fewest-presses answer depends on, with counters relabelled and
+/// buttons sorted so relabelled copies of a machine usually compare equal.
+/// Counters are ordered by goal and by the sizes of the buttons wiring them,
+/// with ties left in input order, so equal forms always share an answer but
+/// a few copies that only differ in how tied counters are numbered are missed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanonicalMachine {
+    goal_joltage: Vec<usize>,
+    buttons: Vec<Vec<usize>>,
+    modulus: Option<usize>,
+}
+
+impl CanonicalMachine {
+    pub fn of(machine: &Machine) -> Self {
+        let counters = machine.goal_joltage.len();
+        let mut wiring: Vec<Vec<usize>> = vec![Vec::new(); counters];
+        for button in &machine.buttons {
+            for &counter in button.iter().filter(|&&c| c < counters) {
+                wiring[counter].push(button.len());
+            }
+        }
+        for sizes in &mut wiring {
+            sizes.sort_unstable();
+        }
+
+        let mut order: Vec<usize> = (0..counters).collect();
+        order.sort_by_key(|&c| (machine.goal_joltage[c], &wiring[c]));
+        let mut label = vec![0; counters];
+        for (new, &old) in order.iter().enumerate() {
+            label[old] = new;
+        }
+
+        // Indices past the last counter can't collide with a new label, so keep them
+        let mut buttons: Vec<Vec<usize>> = machine
+            .buttons
+            .iter()
+            .map(|button| {
+                let mut relabelled: Vec<usize> = button.iter().map(|&c| label.get(c).copied().unwrap_or(c)).collect();
+                relabelled.sort_unstable();
+                relabelled
+            })
+            .collect();
+        buttons.sort();
+
+        CanonicalMachine {
+            goal_joltage: order.iter().map(|&c| machine.goal_joltage[c]).collect(),
+            buttons,
+            modulus: machine.modulus,
+        }
+    }
+}
+
+/// Answers by canonical form, so structurally identical machines are solved once
+#[derive(Debug, Default)]
+pub struct SolutionCache {
+    solutions: HashMap<CanonicalMachine, Option<usize>>,
+    lookups: usize,
+    hits: usize,
+}
+
+impl SolutionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached answer for `machine`, or `solve`'s, which is then cached;
+    /// errors such as cancellation aren't
+    pub fn get_or_solve(&mut self, machine: &Machine, solve: impl FnOnce() -> Result<Option<usize>>) -> Result<Option<usize>> {
+        self.lookups += 1;
+        let key = CanonicalMachine::of(machine);
+        if let Some(&presses) = self.solutions.get(&key) {
+            self.hits += 1;
+            return Ok(presses);
+        }
+        let presses = solve()?;
+        self.solutions.insert(key, presses);
+        Ok(presses)
+    }
+
+    pub fn lookups(&self) -> usize {
+        self.lookups
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Fraction of lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+}
+
+fn print_cache_stats(cache: &SolutionCache, out: &dyn OutputSink) {
+    out.line(&format!(
+        "Solution cache: {} of {} machines answered from the cache ({:.1}%)",
+        cache.hits(),
+        cache.lookups(),
+        cache.hit_rate() * 100.0
+    ));
+}
+
+/// Fewest button presses summed over every machine in `input`, with
+/// repeated machines answered from a cache; fails on the first machine
+/// that has no solution
+pub fn total_presses(input: &str) -> Result<usize> {
+    let cancel = CancellationToken::new();
+    let mut cache = SolutionCache::new();
+    let mut total = 0;
+    for (i, machine) in parse_input(input)?.iter().enumerate() {
+        total += cache.get_or_solve(machine, || check_and_solve(i + 1, machine, &cancel, &Silent))?
+            .ok_or_else(|| AocError::solve(format!("Machine {} has no solution", i + 1)))?;
+    }
+    Ok(total)
+}
+
+pub fn part1(input: &str) -> Result<usize> {
+    total_presses(input)
 }
 
-/// Day 10: Exercise description
-pub fn run() -> Result<()> {
-    // Part 1
-    println!("=== Part 1 ===");
-    let machines1 = parse_input("assets/day10machines1.txt")?;
-    println!("Parsed {} machines", machines1.len());
+pub fn part2(input: &str) -> Result<usize> {
+    total_presses(input)
+}
+
+/// How hard one machine was to solve, for the `--metrics` report
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineMetrics {
+    pub machine: usize,
+    pub buttons: usize,
+    pub counters: usize,
+    pub rank: usize,
+    /// Buttons left over once the rank is accounted for; the search enumerates these
+    pub free_variables: usize,
+    /// Independent pieces the machine splits into, see `subsystems`
+    pub subsystems: usize,
+    pub search_nodes: usize,
+    pub presses: Option<usize>,
+    pub solve_time: Duration,
+    /// Answered from the `SolutionCache` without searching
+    pub cached: bool,
+}
+
+/// Solves one machine through `cache`, recording its size, rank and search effort
+pub fn measure_machine(
+    machine_number: usize,
+    machine: &Machine,
+    solver: Solver,
+    cancel: &CancellationToken,
+    cache: &mut SolutionCache,
+    out: &dyn Reporter,
+) -> Result<MachineMetrics> {
+    let rank = analyze_feasibility(machine).rank;
+    let mut search_nodes = 0;
+    let hits = cache.hits();
+    let start = Instant::now();
+    let presses = cache.get_or_solve(machine, || check_and_solve_counted(machine_number, machine, solver, cancel, &mut search_nodes, out))?;
+    Ok(MachineMetrics {
+        machine: machine_number,
+        buttons: machine.buttons.len(),
+        counters: machine.goal_joltage.len(),
+        rank,
+        free_variables: machine.buttons.len() - rank,
+        subsystems: subsystems(machine).len(),
+        search_nodes,
+        presses,
+        solve_time: start.elapsed(),
+        cached: cache.hits() > hits,
+    })
+}
+
+const METRICS_CSV_HEADER: &str = "part,machine,buttons,counters,rank,free_variables,subsystems,search_nodes,presses,solve_ms,cached";
+
+/// One CSV row per machine, `presses` left empty for unsolved machines
+fn metrics_csv(parts: &[(usize, Vec<MachineMetrics>)]) -> String {
+    let mut out = format!("{}\n", METRICS_CSV_HEADER);
+    for (part, metrics) in parts {
+        for m in metrics {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{:.3},{}\n",
+                part,
+                m.machine,
+                m.buttons,
+                m.counters,
+                m.rank,
+                m.free_variables,
+                m.subsystems,
+                m.search_nodes,
+                m.presses.map_or(String::new(), |p| p.to_string()),
+                m.solve_time.as_secs_f64() * 1000.0,
+                m.cached
+            ));
+        }
+    }
+    out
+}
+
+/// Machines shown in the `--metrics` table, slowest first
+const METRICS_SHOWN: usize = 10;
+
+fn print_metrics(part: usize, metrics: &[MachineMetrics], out: &dyn OutputSink) {
+    let mut slowest: Vec<&MachineMetrics> = metrics.iter().collect();
+    slowest.sort_by_key(|m| std::cmp::Reverse(m.solve_time));
+    let total: Duration = metrics.iter().map(|m| m.solve_time).sum();
+
+    out.line(&format!("\nPart {}: slowest machines ({:.3} ms total)", part, total.as_secs_f64() * 1000.0));
+    out.line(&format!(
+        "  {:>7} {:>7} {:>8} {:>4} {:>4} {:>6} {:>10} {:>7} {:>10}",
+        "machine", "buttons", "counters", "rank", "free", "pieces", "nodes", "presses", "ms"
+    ));
+    for m in slowest.into_iter().take(METRICS_SHOWN) {
+        out.line(&format!(
+            "  {:>7} {:>7} {:>8} {:>4} {:>4} {:>6} {:>10} {:>7} {:>10.3}",
+            m.machine,
+            m.buttons,
+            m.counters,
+            m.rank,
+            m.free_variables,
+            m.subsystems,
+            m.search_nodes,
+            m.presses.map_or("-".to_string(), |p| p.to_string()),
+            m.solve_time.as_secs_f64() * 1000.0
+        ));
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "day10", about = "Day 10: Factory")]
+struct Options {
+    /// Print the slowest machines of each part with their rank, free variables, subsystems and search nodes
+    #[arg(long)]
+    metrics: bool,
+    /// Write every machine's metrics to this CSV file
+    #[arg(long, value_name = "FILE")]
+    csv: Option<String>,
+    /// How to solve machines without a modulus; lattice also checks its part
+    /// 1 answers against search
+    #[arg(long, value_enum, default_value_t = Solver::Search)]
+    solver: Solver,
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Factory",
+        input_files: &["machines1", "machines2"],
+        parts: [
+            "Fewest presses to configure every machine in the small input",
+            "Fewest presses to configure every machine in the full input",
+        ],
+        notes: "Honours --timeout and --trace; machines may wrap with a %M suffix; independent subsystems are solved separately; --solver lattice searches an LLL-reduced nullspace basis",
+    }
+}
+
+pub fn run(args: &[String], cancel: &CancellationToken, out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day10".to_string()).chain(args.iter().cloned()));
+    let metrics1 = stats::measure("Part 1", || run_part1(options.solver, cancel, out))?;
+    let metrics2 = stats::measure("Part 2", || run_part2(options.solver, cancel, out))?;
+
+    if options.metrics {
+        print_metrics(1, &metrics1, out);
+        print_metrics(2, &metrics2, out);
+    }
+    if let Some(path) = &options.csv {
+        fs::write(path, metrics_csv(&[(1, metrics1), (2, metrics2)])).map_err(|e| AocError::io(path, e))?;
+        out.line(&format!("\nWrote machine metrics to {}", path));
+    }
+    Ok(())
+}
+
+fn run_part1(solver: Solver, cancel: &CancellationToken, out: &dyn OutputSink) -> Result<Vec<MachineMetrics>> {
+    out.line("=== Part 1 ===");
+    let machines1 = Input::for_day(10).parse("machines1", parse_input)?;
+    out.line(&format!("Parsed {} machines", machines1.len()));
     
     let mut total1 = 0;
+    let mut unsolved1 = 0;
+    let mut metrics = Vec::with_capacity(machines1.len());
+    let mut cache = SolutionCache::new();
+    let mut agreed = 0;
     for (i, machine) in machines1.into_iter().enumerate() {
-        let presses = solve_joltage(&machine);
-        println!("Machine {}: {} presses", i + 1, presses);
-        total1 += presses;
+        let machine_metrics = measure_machine(i + 1, &machine, solver, cancel, &mut cache, out)?;
+        match machine_metrics.presses {
+            Some(presses) => {
+                out.line(&format!("Machine {}: {} presses", i + 1, presses));
+                total1 += presses;
+            }
+            None => unsolved1 += 1,
+        }
+        if solver == Solver::Lattice {
+            let searched = check_and_solve(i + 1, &machine, cancel, &Silent)?;
+            if searched == machine_metrics.presses {
+                agreed += 1;
+            } else {
+                out.line(&format!("  Lattice disagrees with search: {:?} vs {:?}", machine_metrics.presses, searched));
+            }
+        }
+        metrics.push(machine_metrics);
     }
     
-    println!("\nPart 1 Total: {}", total1);
-    
-    // Part 2
-    println!("\n=== Part 2 ===");
-    let machines2 = parse_input("assets/day10machines2.txt")?;
+    out.line("");
+    out.answer(1, "Part 1 Total", &total1);
+    out.line(&format!("{} machines unsolved", unsolved1));
+    if solver == Solver::Lattice {
+        out.line(&format!("Lattice agreed with search on {} of {} machines", agreed, metrics.len()));
+    }
+    print_cache_stats(&cache, out);
+    Ok(metrics)
+}
+
+fn run_part2(solver: Solver, cancel: &CancellationToken, out: &dyn OutputSink) -> Result<Vec<MachineMetrics>> {
+    out.line("\n=== Part 2 ===");
+    let machines2 = Input::for_day(10).parse("machines2", parse_input)?;
     let num_machines2 = machines2.len();
-    println!("Parsed {} machines", num_machines2);
+    out.line(&format!("Parsed {} machines", num_machines2));
     
+    let progress = out.progress();
+    progress.start("Machines", num_machines2 as u64);
     let mut total2 = 0;
+    let mut unsolved2 = 0;
+    let mut metrics = Vec::with_capacity(num_machines2);
+    let mut cache = SolutionCache::new();
     for (i, machine) in machines2.into_iter().enumerate() {
-        let presses = solve_joltage(&machine);
-        if (i + 1) % 10 == 0 || i == num_machines2 - 1 {
-            println!("Machine {}: {} presses", i + 1, presses);
+        let machine_metrics = measure_machine(i + 1, &machine, solver, cancel, &mut cache, out)?;
+        match machine_metrics.presses {
+            Some(presses) => total2 += presses,
+            None => unsolved2 += 1,
         }
-        total2 += presses;
+        metrics.push(machine_metrics);
+        progress.set_message(&format!("total {}", total2));
+        progress.inc(1);
     }
+    progress.finish();
     
-    println!("\nPart 2 Total: {}", total2);
+    out.line("");
+    out.answer(2, "Part 2 Total", &total2);
+    out.line(&format!("{} machines unsolved", unsolved2));
+    print_cache_stats(&cache, out);
     
-    Ok(())
+    Ok(metrics)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::reporter::Captured;
+    use crate::util::snapshot;
 
     #[test]
     fn test_part1_joltage_solution() {
-        let machines = parse_input("assets/day10machines1.txt")
+        let machines = parse_input(Input::for_day(10).read("machines1").unwrap())
             .expect("Failed to load part 1 input");
 
         let mut total = 0;
         for (i, machine) in machines.iter().enumerate() {
-            let presses = solve_joltage(&machine);
+            let presses = solve_joltage(machine).expect("Machine should be solvable");
             println!("Machine {}: {} presses", i + 1, presses);
             total += presses;
         }
@@ -444,16 +1535,380 @@ mod tests {
 
     #[test]
     fn test_part2_joltage_solution() {
-        let machines = parse_input("assets/day10machines2.txt")
+        let machines = parse_input(Input::for_day(10).read("machines2").unwrap())
             .expect("Failed to load part 2 input");
 
         let mut total = 0;
         for machine in machines.iter() {
-            let presses = solve_joltage(&machine);
+            let presses = solve_joltage(machine).expect("Machine should be solvable");
             total += presses;
         }
 
-        assert_eq!(total, 17133, "Part 2 joltage solution should be 17133");
+        snapshot::check("day10", "joltage_total", total);
+    }
+
+    #[test]
+    fn test_parse_machine_sections_in_any_order() {
+        let expected = parse_machine("[.##.] (3) (1,3) (2) {3,5,4,7}", 1).unwrap();
+        let shuffled = parse_machine("{3,5,4,7} (3) [.##.] (1,3) (2)", 1).unwrap();
+        assert_eq!(shuffled, expected);
+        assert_eq!(expected.buttons, vec![vec![3], vec![1, 3], vec![2]]);
+        assert_eq!(expected.current_joltage, vec![0; 4]);
+    }
+
+    #[test]
+    fn test_parse_machine_error_columns() {
+        let err = parse_machine("[.#x.] (1)", 3).unwrap_err();
+        assert_eq!(err.to_string(), "Line 3, column 4: invalid light character 'x'");
+
+        let err = parse_machine("[.#] (1,a) {1}", 1).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 9: invalid button index 'a'");
+
+        let err = parse_machine("[.#] (1) {1,2", 1).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 10: missing '}' for joltage value");
+
+        let err = parse_machine("[.#] (1) [#.]", 1).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 10: duplicate lights section");
+
+        let err = parse_machine("[.#] x", 1).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 6: unexpected character 'x'");
+    }
+
+    #[test]
+    fn test_display_roundtrip_fixtures() {
+        for name in ["machines1", "machines2"] {
+            let machines = parse_input(Input::for_day(10).read(name).unwrap()).expect("Failed to load input");
+            for machine in machines {
+                let reparsed = parse_machine(&machine.to_string(), 1).unwrap();
+                assert_eq!(reparsed, machine);
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_roundtrip_random_machines() {
+        // Small xorshift generator so the fuzz cases are reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        for _ in 0..500 {
+            let num_lights = next(10);
+            let goal_lights: Vec<bool> = (0..num_lights).map(|_| next(2) == 1).collect();
+            let buttons: Vec<Vec<usize>> = (0..next(8))
+                .map(|_| (0..next(5)).map(|_| next(1000)).collect())
+                .collect();
+            let goal_joltage: Vec<usize> = (0..next(8)).map(|_| next(100_000)).collect();
+            let machine = Machine {
+                current_lights: vec![false; goal_lights.len()],
+                current_joltage: vec![0; goal_joltage.len()],
+                goal_lights,
+                goal_joltage,
+                buttons,
+                modulus: if next(3) == 0 { Some(1 + next(50)) } else { None },
+            };
+
+            let reparsed = parse_machine(&machine.to_string(), 1).unwrap();
+            assert_eq!(reparsed, machine, "roundtrip failed for '{}'", machine);
+        }
+    }
+
+    #[test]
+    fn test_fixture_machines_are_feasible() {
+        for name in ["machines1", "machines2"] {
+            let machines = parse_input(Input::for_day(10).read(name).unwrap()).expect("Failed to load input");
+            for machine in &machines {
+                let report = analyze_feasibility(machine);
+                assert!(report.is_feasible(), "{} should be feasible: {:?}", machine, report);
+                assert_eq!(report.rank, report.augmented_rank);
+            }
+        }
+    }
+
+    #[test]
+    fn test_feasibility_untouched_counter() {
+        let machine = parse_machine("[...] (0) (0,1) {3,2,5}", 1).unwrap();
+        let report = analyze_feasibility(&machine);
+        assert_eq!(report.problems, vec![Infeasibility::UntouchedCounter(2)]);
+        assert_eq!(report.rank, 2);
+        assert_eq!(report.augmented_rank, 3);
+        let captured = Captured::new();
+        assert_eq!(check_and_solve(1, &machine, &CancellationToken::new(), &captured).unwrap(), None);
+        assert_eq!(captured.lines()[0], "Machine 1: infeasible (rank 2 vs augmented rank 3)");
+    }
+
+    #[test]
+    fn test_feasibility_inconsistent_counters() {
+        // Both counters are only ever pressed together, so they must share a goal
+        let machine = parse_machine("[..] (0,1) {1,2}", 1).unwrap();
+        let report = analyze_feasibility(&machine);
+        assert_eq!(report.problems, vec![Infeasibility::Inconsistent(vec![0, 1])]);
+        assert_eq!((report.rank, report.augmented_rank), (1, 2));
+    }
+
+    #[test]
+    fn test_feasibility_non_integral() {
+        // Every counter needs 1, but each button bumps two of them: x = 0.5 each
+        let machine = parse_machine("[...] (0,1) (1,2) (0,2) {1,1,1}", 1).unwrap();
+        let report = analyze_feasibility(&machine);
+        assert_eq!(report.rank, 3);
+        assert_eq!(report.augmented_rank, 3);
+        assert_eq!(report.problems, vec![Infeasibility::NonIntegral(vec![0, 1, 2])]);
+    }
+
+    #[test]
+    fn test_parse_modulus_suffix() {
+        let machine = parse_machine("[..] (0) (1) {3,4}%5", 1).unwrap();
+        assert_eq!(machine.modulus, Some(5));
+        assert_eq!(machine.to_string(), "[..] (0) (1) {3,4}%5");
+
+        let err = parse_machine("[..] (0) {3}%0", 1).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1, column 14: invalid modulus '0'");
+    }
+
+    #[test]
+    fn test_smith_normal_form_diagonalizes() {
+        let a = vec![vec![2, 4, 4], vec![-6, 6, 12], vec![10, -4, -16]];
+        let snf = smith_normal_form(&a, 3);
+
+        let multiply = |x: &[Vec<i128>], y: &[Vec<i128>]| -> Vec<Vec<i128>> {
+            (0..x.len())
+                .map(|i| (0..y[0].len()).map(|j| (0..y.len()).map(|k| x[i][k] * y[k][j]).sum()).collect())
+                .collect()
+        };
+        assert_eq!(multiply(&multiply(&snf.u, &a), &snf.v), snf.d);
+        assert_eq!((snf.d[0][0], snf.d[1][1], snf.d[2][2]), (2, 6, 12));
+    }
+
+    #[test]
+    fn test_machine_metrics() {
+        let machine = parse_machine("[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}", 1).unwrap();
+        let mut cache = SolutionCache::new();
+        let metrics = measure_machine(1, &machine, Solver::Search, &CancellationToken::new(), &mut cache, &Silent).unwrap();
+        assert_eq!(
+            (metrics.buttons, metrics.counters, metrics.rank, metrics.free_variables, metrics.presses),
+            (6, 4, 4, 2, Some(10))
+        );
+        assert!(metrics.search_nodes > 0 && !metrics.cached);
+        let again = measure_machine(2, &machine, Solver::Search, &CancellationToken::new(), &mut cache, &Silent).unwrap();
+        assert_eq!((again.presses, again.search_nodes, again.cached), (Some(10), 0, true));
+
+        let csv = metrics_csv(&[(2, vec![MachineMetrics { presses: None, solve_time: Duration::from_micros(1500), ..metrics }])]);
+        let expected_row = format!("2,1,6,4,4,2,1,{},,1.500,false\n", metrics.search_nodes);
+        assert_eq!(csv, format!("{}\n{}", METRICS_CSV_HEADER, expected_row));
+    }
+
+    #[test]
+    fn test_subsystems_split_sparse_machines() {
+        // Counters 0 and 2 share button (0,2); counter 1 and 3 share (1,3);
+        // button (5) wires no counter at all
+        let machine = parse_machine("[#.#.] (0,2) (0) (1,3) (3) (5) {3,2,1,4}", 1).unwrap();
+        let pieces = subsystems(&machine);
+        let shape = |m: &Machine| (m.goal_lights.clone(), m.goal_joltage.clone(), m.buttons.clone());
+        assert_eq!(
+            pieces.iter().map(shape).collect::<Vec<_>>(),
+            vec![
+                (vec![true, true], vec![3, 1], vec![vec![0, 1], vec![0]]),
+                (vec![false, false], vec![2, 4], vec![vec![0, 1], vec![1]]),
+            ]
+        );
+        assert_eq!(solve_joltage(&machine), Some(7));
+
+        // Two copies of the example side by side solve to twice its answer,
+        // enumerating far fewer free-variable combinations than as one system
+        let doubled = parse_machine(
+            "[.##..##.] (3) (1,3) (2) (2,3) (0,2) (0,1) (7) (5,7) (6) (6,7) (4,6) (4,5) {3,5,4,7,3,5,4,7}",
+            1,
+        )
+        .unwrap();
+        assert_eq!(subsystems(&doubled).len(), 2);
+        let cancel = CancellationToken::new();
+        let (mut split, mut whole) = (0, 0);
+        assert_eq!(search_joltage(&doubled, &cancel, &mut split).unwrap(), Some(20));
+        assert_eq!(search_subsystem(&doubled, &cancel, &mut whole).unwrap(), Some(20));
+        assert!(split < whole, "{} nodes split vs {} whole", split, whole);
+
+        let wrapped = parse_machine("[....] (0,1) (1) (2,3) (3) (2) {2,1,1,2}%3", 1).unwrap();
+        assert_eq!(subsystems(&wrapped).len(), 2);
+        assert_eq!(solve_joltage_modular(&wrapped, 3, &mut 0), solve_subsystem_modular(&wrapped, 3, &mut 0));
+        assert_eq!(solve_joltage_modular(&wrapped, 3, &mut 0), brute_force_presses(&wrapped, 12));
+    }
+
+    #[test]
+    fn test_canonical_form_ignores_labels() {
+        let machine = parse_machine("[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}", 1).unwrap();
+        // Counters 0 and 3 swapped, buttons reordered and listed backwards
+        let relabelled = parse_machine("[.##.] (3,1) (0,2) (0) (2,3) (1,0) (2) {7,5,4,3}", 1).unwrap();
+        assert_eq!(CanonicalMachine::of(&machine), CanonicalMachine::of(&relabelled));
+        let other_goal = parse_machine("[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,8}", 1).unwrap();
+        assert_ne!(CanonicalMachine::of(&machine), CanonicalMachine::of(&other_goal));
+        let wrapped = parse_machine("[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}%9", 1).unwrap();
+        assert_ne!(CanonicalMachine::of(&machine), CanonicalMachine::of(&wrapped));
+
+        let mut cache = SolutionCache::new();
+        let cancel = CancellationToken::new();
+        for m in [&machine, &relabelled, &other_goal] {
+            let presses = cache.get_or_solve(m, || check_and_solve(1, m, &cancel, &Silent)).unwrap();
+            assert_eq!(presses, solve_joltage(m));
+        }
+        assert_eq!((cache.hits(), cache.lookups()), (1, 3));
+        assert!((cache.hit_rate() - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_modular_solver_wraps() {
+        // Goal 1 mod 5 on a single counter: one press of button 0
+        let machine = parse_machine("[.] (0) {6}%5", 1).unwrap();
+        assert_eq!(solve_joltage_modular(&machine, 5, &mut 0), Some(1));
+
+        // Counter 1 can only be reached through (0,1); its two presses leave
+        // counter 0 at 2, which needs 3 more to wrap to 0 mod 5
+        let machine = parse_machine("[..] (0) (0,1) {0,2}%5", 1).unwrap();
+        assert_eq!(check_and_solve(1, &machine, &CancellationToken::new(), &Silent).unwrap(), Some(5));
+
+        // 2x ≡ 1 (mod 4) has no solution
+        let machine = parse_machine("[.] (0) {1}%4", 1).unwrap();
+        let doubled = Machine { buttons: vec![vec![0, 0]], ..machine };
+        assert_eq!(solve_joltage_modular(&doubled, 4, &mut 0), None);
+    }
+
+    #[test]
+    fn test_modular_matches_brute_force() {
+        let machine = parse_machine("[...] (0,1) (1,2) (0,2) (2) {4,1,3}%6", 1).unwrap();
+        let mut best = usize::MAX;
+        for x0 in 0..6 {
+            for x1 in 0..6 {
+                for x2 in 0..6 {
+                    for x3 in 0..6 {
+                        let counters = [(x0 + x2) % 6, (x0 + x1) % 6, (x1 + x2 + x3) % 6];
+                        if counters == [4, 1, 3] {
+                            best = best.min(x0 + x1 + x2 + x3);
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(solve_joltage_modular(&machine, 6, &mut 0), Some(best));
     }
-}
 
+    /// Fewest presses by breadth-first search over counter states, one
+    /// press per level, giving up after `budget` presses. Slow but simple
+    /// enough to trust as an oracle for the algebraic solvers.
+    fn brute_force_presses(machine: &Machine, budget: usize) -> Option<usize> {
+        let wrap = |value: usize| machine.modulus.map_or(value, |m| value % m);
+        let goal: Vec<usize> = machine.goal_joltage.iter().map(|&g| wrap(g)).collect();
+        let mut seen = std::collections::HashSet::from([vec![0; goal.len()]]);
+        let mut frontier = vec![vec![0; goal.len()]];
+        for presses in 0..=budget {
+            if frontier.contains(&goal) {
+                return Some(presses);
+            }
+            let mut next = Vec::new();
+            for state in &frontier {
+                for button in &machine.buttons {
+                    let mut pressed = state.clone();
+                    for &counter in button {
+                        pressed[counter] = wrap(pressed[counter] + 1);
+                    }
+                    // Without wrapping, counters never come back down
+                    let overshot = machine.modulus.is_none() && pressed.iter().zip(&goal).any(|(p, g)| p > g);
+                    if !overshot && seen.insert(pressed.clone()) {
+                        next.push(pressed);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        None
+    }
+
+    #[test]
+    fn test_brute_force_agrees_on_part1_machines() {
+        let machines = parse_input(Input::for_day(10).read("machines1").unwrap()).unwrap();
+        for machine in &machines {
+            assert_eq!(brute_force_presses(machine, 40), solve_joltage(machine), "{}", machine);
+        }
+    }
+
+    #[test]
+    fn test_brute_force_agrees_on_random_machines() {
+        // Same reproducible xorshift as the roundtrip fuzz test
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        let cancel = CancellationToken::new();
+        for _ in 0..300 {
+            let counters = 1 + next(4);
+            let buttons: Vec<Vec<usize>> = (0..1 + next(5))
+                .map(|_| (0..counters).filter(|_| next(2) == 1).collect())
+                .collect();
+            let modulus = if next(4) == 0 { Some(2 + next(5)) } else { None };
+            // Goals reached by some press vector, so every machine is solvable
+            // and its answer is at most the presses used here
+            let mut goal_joltage = vec![0; counters];
+            let mut used = 0;
+            for button in &buttons {
+                let presses = next(4);
+                used += presses;
+                for &counter in button {
+                    goal_joltage[counter] += presses;
+                }
+            }
+            let machine = Machine {
+                goal_lights: vec![false; counters],
+                current_lights: vec![false; counters],
+                goal_joltage,
+                current_joltage: vec![0; counters],
+                buttons,
+                modulus,
+            };
+
+            let expected = brute_force_presses(&machine, used);
+            assert!(expected.is_some(), "{} is solvable in {} presses", machine, used);
+            assert_eq!(check_and_solve(1, &machine, &cancel, &Silent).unwrap(), expected, "{}", machine);
+        }
+    }
+
+    #[test]
+    fn test_lll_shortens_basis() {
+        let mut basis = vec![vec![1, 1, 1], vec![-1, 0, 2], vec![3, 5, 6]];
+        lll_reduce(&mut basis);
+        // Same lattice: the determinant keeps its size
+        let det = |m: &[Vec<i128>]| {
+            m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+        };
+        assert_eq!(det(&basis).abs(), 3);
+        assert!(basis.iter().all(|v| v.iter().map(|x| x * x).sum::<i128>() <= 5), "{:?}", basis);
+    }
+
+    #[test]
+    fn test_lattice_agrees_with_search() {
+        for name in ["machines1", "machines2"] {
+            let machines = parse_input(Input::for_day(10).read(name).unwrap()).unwrap();
+            for machine in &machines {
+                assert_eq!(search_lattice(machine, &mut 0), solve_joltage(machine), "{}", machine);
+            }
+        }
+        // One free variable: (0,1) against (0) and (1)
+        let machine = parse_machine("[..] (0) (1) (0,1) {3,5}", 1).unwrap();
+        let mut nodes = 0;
+        assert_eq!(search_lattice(&machine, &mut nodes), Some(5));
+        assert!(nodes > 0);
+        let captured = Captured::new();
+        let presses = check_and_solve_counted(1, &machine, Solver::Lattice, &CancellationToken::new(), &mut 0, &captured).unwrap();
+        assert_eq!((presses, captured.lines()), (Some(5), vec![]));
+        // Unique, but needs a negative press
+        let machine = parse_machine("[..] (0) (0,1) {1,2}", 1).unwrap();
+        assert_eq!(search_lattice(&machine, &mut 0), None);
+    }
+}
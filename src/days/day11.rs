@@ -1,249 +1,1211 @@
-use anyhow::{anyhow, Context, Result};
-use std::cell::RefCell;
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::Parser;
+use num_bigint::BigUint;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::rc::Rc;
 
-/// Node in the graph
-#[derive(Debug, Clone)]
-pub struct Node {
-    pub id: String,
-    pub children: Vec<Rc<RefCell<Node>>>,
+use crate::input::{self, Input};
+use crate::util::graph::{iter_paths, Graph, NodeId};
+use crate::util::output::OutputSink;
+use crate::util::{parse, stats};
+
+fn parse_input(content: &str, root_id: &str) -> Result<(Graph, NodeId)> {
+    let graph = parse_graph(content)?;
+    let root = graph
+        .node_id(root_id)
+        .ok_or_else(|| AocError::parse(format!("Root node '{}' not found in input", root_id)))?;
+    Ok((graph, root))
+}
+
+/// One child of a `parent: child child*3 ...` line; `*N` gives the edge weight N
+fn parse_child(item: &str, line_no: usize) -> Result<(&str, u64)> {
+    let Some((name, weight)) = item.split_once('*') else {
+        return Ok((item, 1));
+    };
+    let weight: u64 = weight
+        .parse()
+        .map_err(|e| AocError::parse_at(line_no, format!("Invalid weight in '{}': {}", item, e)))?;
+    if name.is_empty() {
+        return Err(AocError::parse_at(line_no, format!("Missing node name before '*' in '{}'", item)));
+    }
+    if weight == 0 {
+        return Err(AocError::parse_at(line_no, format!("Edge weight in '{}' must be at least 1", item)));
+    }
+    Ok((name, weight))
 }
 
-impl Node {
-    fn new(id: String) -> Self {
-        Node {
-            id,
-            children: Vec::new(),
+fn parse_graph(content: &str) -> Result<Graph> {
+    let mut graph = Graph::new();
+
+    for (line_no, line) in parse::lines(content) {
+        let (parent, children) = parse::labeled(line, line_no)?;
+        let parent = graph.add_node(parent);
+        for item in children {
+            let (child_id, weight) = parse_child(item, line_no)?;
+            let child = graph.add_node(child_id);
+            graph.add_weighted_edge(parent, child, weight);
         }
     }
+
+    Ok(graph)
 }
 
-fn parse_input(filename: &str, root_id: &str) -> Result<Rc<RefCell<Node>>> {
-    let content = fs::read_to_string(filename)
-        .context(format!("Failed to read file: {}", filename))?;
+/// Count the paths from `src` that end at any of `sinks`, one count per sink.
+/// A path stops at the first sink it reaches, so each path is counted once.
+/// Uses an explicit-stack post-order DFS with a per-node cache, so deep graphs can't
+/// overflow the call stack and shared subgraphs are only counted once.
+/// Fails on a cycle reachable from `src` before a sink, where a per-node count
+/// would depend on which edge the search happened to enter the cycle by.
+/// Counts are promoted to u128 and then BigUint only when they overflow u64.
+pub fn count_paths_by_sink(graph: &Graph, src: NodeId, sinks: &[NodeId]) -> Result<Vec<PathTotal>> {
+    if let Some(counts) = count_paths_by_sink_generic::<u64>(graph, src, sinks)? {
+        return Ok(counts.into_iter().map(PathTotal::Small).collect());
+    }
+    if let Some(counts) = count_paths_by_sink_generic::<u128>(graph, src, sinks)? {
+        return Ok(counts.into_iter().map(PathTotal::Wide).collect());
+    }
+    let counts = count_paths_by_sink_generic::<BigUint>(graph, src, sinks)?
+        .ok_or_else(|| AocError::solve("BigUint path count cannot overflow"))?;
+    Ok(counts.into_iter().map(PathTotal::Big).collect())
+}
 
-    // First pass: create all nodes
-    let mut nodes: HashMap<String, Rc<RefCell<Node>>> = HashMap::new();
-    let mut edges: Vec<(String, Vec<String>)> = Vec::new();
+/// `count_paths_by_sink` with accumulator `C`; Ok(None) if a count overflows it
+pub fn count_paths_by_sink_generic<C: PathCount>(graph: &Graph, src: NodeId, sinks: &[NodeId]) -> Result<Option<Vec<C>>> {
+    let mut memo: Vec<Option<Vec<C>>> = vec![None; graph.len()];
+    let mut on_path = vec![false; graph.len()];
+    // (node, children already pushed)
+    let mut stack = vec![(src, false)];
 
-    for (i, line) in content.lines().enumerate() {
-        let line = line.trim();
-        if line.is_empty() {
+    while let Some((node, expanded)) = stack.pop() {
+        if memo[node.index()].is_some() {
             continue;
         }
 
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!(
-                "Line {} has invalid format, expected 'id: child1 child2 ...'",
-                i + 1
-            ));
+        // Base case: a sink ends exactly one path, its own
+        if let Some(k) = sinks.iter().position(|&sink| sink == node) {
+            let mut counts = vec![C::zero(); sinks.len()];
+            counts[k] = C::one();
+            memo[node.index()] = Some(counts);
+            continue;
         }
 
-        let node_id = parts[0].trim().to_string();
-        let children_ids: Vec<String> = parts[1]
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
-
-        // Create node if it doesn't exist
-        if !nodes.contains_key(&node_id) {
-            nodes.insert(node_id.clone(), Rc::new(RefCell::new(Node::new(node_id.clone()))));
+        if expanded {
+            // All children are resolved
+            let mut totals = vec![C::zero(); sinks.len()];
+            for child in graph.children(node) {
+                for (total, count) in totals.iter_mut().zip(memo[child.index()].iter().flatten()) {
+                    match total.checked_add(count) {
+                        Some(sum) => *total = sum,
+                        None => return Ok(None),
+                    }
+                }
+            }
+            on_path[node.index()] = false;
+            memo[node.index()] = Some(totals);
+        } else if !on_path[node.index()] {
+            on_path[node.index()] = true;
+            stack.push((node, true));
+            for &child in graph.children(node) {
+                // Only the nodes above this one are on the path
+                if on_path[child.index()] {
+                    return Err(graph.cycle_error(node, child));
+                }
+                if memo[child.index()].is_none() {
+                    stack.push((child, false));
+                }
+            }
         }
+    }
+
+    Ok(Some(memo[src.index()].take().unwrap_or_else(|| vec![C::zero(); sinks.len()])))
+}
+
+/// Count the paths from `src` to any of `sinks`
+pub fn count_paths(graph: &Graph, src: NodeId, sinks: &[NodeId]) -> Result<PathTotal> {
+    Ok(count_paths_by_sink(graph, src, sinks)?.into_iter().sum())
+}
+
+/// Accumulator for path counts. `checked_add` and `checked_scale` return None on overflow
+/// so callers can retry with a wider type.
+pub trait PathCount: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    /// The count times an edge weight
+    fn checked_scale(&self, factor: u64) -> Option<Self>;
+}
 
-        // Create child nodes if they don't exist
-        for child_id in &children_ids {
-            if !nodes.contains_key(child_id) {
-                nodes.insert(
-                    child_id.clone(),
-                    Rc::new(RefCell::new(Node::new(child_id.clone()))),
-                );
+macro_rules! impl_path_count {
+    ($($t:ty),*) => {$(
+        impl PathCount for $t {
+            fn zero() -> Self { 0 }
+            fn one() -> Self { 1 }
+            fn checked_add(&self, other: &Self) -> Option<Self> { <$t>::checked_add(*self, *other) }
+            fn checked_scale(&self, factor: u64) -> Option<Self> {
+                <$t>::checked_mul(*self, <$t>::try_from(factor).ok()?)
             }
         }
+    )*};
+}
+impl_path_count!(usize, u64, u128);
 
-        edges.push((node_id, children_ids));
+impl PathCount for BigUint {
+    fn zero() -> Self {
+        BigUint::ZERO
+    }
+    fn one() -> Self {
+        BigUint::from(1u8)
     }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+    fn checked_scale(&self, factor: u64) -> Option<Self> {
+        Some(self * factor)
+    }
+}
 
-    // Second pass: connect nodes
-    for (parent_id, children_ids) in edges {
-        let parent = nodes
-            .get(&parent_id)
-            .ok_or_else(|| anyhow!("Parent node '{}' not found", parent_id))?;
+/// A path count stored in the narrowest type that holds it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathTotal {
+    Small(u64),
+    Wide(u128),
+    Big(BigUint),
+}
 
-        for child_id in children_ids {
-            let child = nodes
-                .get(&child_id)
-                .ok_or_else(|| anyhow!("Child node '{}' not found", child_id))?;
-            parent.borrow_mut().children.push(Rc::clone(child));
+impl From<BigUint> for PathTotal {
+    /// The narrowest variant that holds `count`
+    fn from(count: BigUint) -> Self {
+        if let Ok(small) = u64::try_from(&count) {
+            PathTotal::Small(small)
+        } else if let Ok(wide) = u128::try_from(&count) {
+            PathTotal::Wide(wide)
+        } else {
+            PathTotal::Big(count)
         }
     }
+}
 
-    // Find and return the specified root node
-    nodes
-        .get(root_id)
-        .cloned()
-        .ok_or_else(|| anyhow!("Root node '{}' not found in input", root_id))
+impl From<PathTotal> for BigUint {
+    fn from(total: PathTotal) -> Self {
+        match total {
+            PathTotal::Small(count) => BigUint::from(count),
+            PathTotal::Wide(count) => BigUint::from(count),
+            PathTotal::Big(count) => count,
+        }
+    }
 }
 
-/// Count the number of unique paths from a given node to 'out' nodes
-fn count_paths_to_out(node: &Rc<RefCell<Node>>) -> usize {
-    let node_ref = node.borrow();
-    
-    // Base case: if this is an 'out' node, we found one path
-    if node_ref.id == "out" {
-        return 1;
+/// Adds in BigUint, so sums of per-sink counts can't overflow either
+impl std::iter::Sum for PathTotal {
+    fn sum<I: Iterator<Item = PathTotal>>(iter: I) -> Self {
+        PathTotal::from(iter.map(BigUint::from).sum::<BigUint>())
     }
-    
-    // Recursive case: sum up paths from all children
-    node_ref
-        .children
-        .iter()
-        .map(|child| count_paths_to_out(child))
-        .sum()
-}
-
-/// Count paths from current node to 'out', but only paths that include all required nodes
-/// Uses memoization to avoid recomputing the same subproblems
-fn count_paths_with_required_memo(
-    node: &Rc<RefCell<Node>>,
-    visited_required: HashSet<String>,
-    visited_in_path: HashSet<String>,
-    required_nodes: &HashSet<String>,
-    memo: &mut HashMap<(String, Vec<String>), usize>,
-) -> usize {
-    let node_ref = node.borrow();
-    let node_id = node_ref.id.clone();
-    
-    // Cycle detection: if we've already visited this node in the current path, return 0
-    if visited_in_path.contains(&node_id) {
-        return 0;
-    }
-    
-    // Create a cache key: (node_id, sorted list of required nodes we've visited)
-    let mut visited_req_sorted: Vec<String> = visited_required.iter().cloned().collect();
-    visited_req_sorted.sort();
-    let cache_key = (node_id.clone(), visited_req_sorted);
-    
-    // Check memo cache
-    if let Some(&cached_result) = memo.get(&cache_key) {
-        return cached_result;
-    }
-    
-    // Mark this node as visited in the current path
-    let mut new_visited_in_path = visited_in_path.clone();
-    new_visited_in_path.insert(node_id.clone());
-    
-    // Track if this node is one of the required ones
-    let mut new_visited_required = visited_required.clone();
-    if required_nodes.contains(&node_id) {
-        new_visited_required.insert(node_id.clone());
-    }
-    
-    // Base case: if this is an 'out' node
-    let result = if node_id == "out" {
-        // Only count this path if we've visited all required nodes
-        if new_visited_required.len() == required_nodes.len() {
-            1
-        } else {
-            0
+}
+
+impl fmt::Display for PathTotal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathTotal::Small(count) => write!(f, "{}", count),
+            PathTotal::Wide(count) => write!(f, "{}", count),
+            PathTotal::Big(count) => write!(f, "{}", count),
         }
-    } else {
-        // Recursive case: sum up valid paths from all children
-        node_ref
-            .children
+    }
+}
+
+/// Count paths from `src` to `dst` that pass through every node in `required`.
+/// The memo is keyed by (node, bitmask of required nodes seen so far), so at most 64
+/// required nodes are supported. Like `count_paths`, this is an explicit-stack DFS
+/// and fails on a cycle reachable from `src` before `dst`.
+/// Returns Ok(None) if the count overflows the accumulator type `C`.
+pub fn count_paths_generic<C: PathCount>(
+    graph: &Graph,
+    src: NodeId,
+    dst: NodeId,
+    required: &[NodeId],
+) -> Result<Option<C>> {
+    count_paths_core(graph, src, dst, required, false)
+}
+
+/// `count_paths_generic` with edge weights as multiplicities: an edge of weight w stands
+/// for w parallel edges, so each path counts as the product of its weights
+pub fn count_weighted_paths_generic<C: PathCount>(
+    graph: &Graph,
+    src: NodeId,
+    dst: NodeId,
+    required: &[NodeId],
+) -> Result<Option<C>> {
+    count_paths_core(graph, src, dst, required, true)
+}
+
+/// Path counts by (node, required nodes visited including it)
+type Memo<C> = HashMap<(NodeId, u64), C>;
+
+fn count_paths_core<C: PathCount>(
+    graph: &Graph,
+    src: NodeId,
+    dst: NodeId,
+    required: &[NodeId],
+    weighted: bool,
+) -> Result<Option<C>> {
+    count_paths_memoized(graph, src, dst, required, weighted, &mut HashMap::new())
+}
+
+/// `count_paths_core` filling in `memo`, which may hold states from earlier
+/// counts towards the same `dst` with the same `required` nodes
+fn count_paths_memoized<C: PathCount>(
+    graph: &Graph,
+    src: NodeId,
+    dst: NodeId,
+    required: &[NodeId],
+    weighted: bool,
+    memo: &mut Memo<C>,
+) -> Result<Option<C>> {
+    if required.len() > 64 {
+        return Err(AocError::usage(format!("At most 64 required nodes are supported, got {}", required.len())));
+    }
+
+    let bit_of = |node: NodeId| -> u64 {
+        required
             .iter()
-            .map(|child| {
-                count_paths_with_required_memo(
-                    child,
-                    new_visited_required.clone(),
-                    new_visited_in_path.clone(),
-                    required_nodes,
-                    memo,
-                )
-            })
-            .sum()
+            .enumerate()
+            .filter(|(_, &r)| r == node)
+            .fold(0, |mask, (i, _)| mask | (1u64 << i))
     };
-    
-    // Cache the result
-    memo.insert(cache_key, result);
-    result
+    let full_mask = if required.len() == 64 { u64::MAX } else { (1u64 << required.len()) - 1 };
+
+    // State = (node, required nodes visited including this one)
+    let mut on_path: HashSet<(NodeId, u64)> = HashSet::new();
+    let start = (src, bit_of(src));
+    let mut stack = vec![(start, false)];
+
+    while let Some((state, expanded)) = stack.pop() {
+        if memo.contains_key(&state) {
+            continue;
+        }
+        let (node, mask) = state;
+
+        if node == dst {
+            memo.insert(state, if mask == full_mask { C::one() } else { C::zero() });
+            continue;
+        }
+
+        let child_states = graph.weighted_children(node).map(|(child, weight)| ((child, mask | bit_of(child)), weight));
+        if expanded {
+            let mut total = C::zero();
+            for (child_state, weight) in child_states {
+                if let Some(count) = memo.get(&child_state) {
+                    let paths = if weighted { count.checked_scale(weight) } else { Some(count.clone()) };
+                    match paths.and_then(|paths| total.checked_add(&paths)) {
+                        Some(sum) => total = sum,
+                        None => return Ok(None),
+                    }
+                }
+            }
+            on_path.remove(&state);
+            memo.insert(state, total);
+        } else if on_path.insert(state) {
+            stack.push((state, true));
+            for (child_state, _) in child_states {
+                // The mask only grows, so a cycle in the graph comes back to a
+                // state on the path once it has picked up its required nodes
+                if on_path.contains(&child_state) {
+                    return Err(graph.cycle_error(node, child_state.0));
+                }
+                if !memo.contains_key(&child_state) {
+                    stack.push((child_state, false));
+                }
+            }
+        }
+    }
+
+    Ok(Some(memo.get(&start).cloned().unwrap_or_else(C::zero)))
+}
+
+/// `count_paths_generic` with a `usize` accumulator; overflow is an error
+pub fn count_paths_with_required(graph: &Graph, src: NodeId, dst: NodeId, required: &[NodeId]) -> Result<usize> {
+    count_paths_generic::<usize>(graph, src, dst, required)?
+        .ok_or_else(|| AocError::solve("Path count overflows usize; a plain --source/--target query counts paths of any size"))
+}
+
+/// Count paths with u64, promoting to u128 and then BigUint only when the count overflows
+pub fn count_paths_auto(graph: &Graph, src: NodeId, dst: NodeId, required: &[NodeId]) -> Result<PathTotal> {
+    count_auto(graph, src, dst, required, false)
+}
+
+/// `count_paths_auto` for `count_weighted_paths_generic`
+pub fn count_weighted_paths_auto(graph: &Graph, src: NodeId, dst: NodeId, required: &[NodeId]) -> Result<PathTotal> {
+    count_auto(graph, src, dst, required, true)
+}
+
+fn count_auto(graph: &Graph, src: NodeId, dst: NodeId, required: &[NodeId], weighted: bool) -> Result<PathTotal> {
+    if let Some(count) = count_paths_core::<u64>(graph, src, dst, required, weighted)? {
+        return Ok(PathTotal::Small(count));
+    }
+    if let Some(count) = count_paths_core::<u128>(graph, src, dst, required, weighted)? {
+        return Ok(PathTotal::Wide(count));
+    }
+    let count = count_paths_core::<BigUint>(graph, src, dst, required, weighted)?
+        .ok_or_else(|| AocError::solve("BigUint path count cannot overflow"))?;
+    Ok(PathTotal::Big(count))
+}
+
+/// A graph parsed once and the path-count memos of every query run on it.
+/// Queries towards the same target through the same required nodes share a
+/// memo keyed by (node, required nodes visited), so each later query only
+/// walks the states earlier ones never reached. A query whose source reaches
+/// a cycle fails just as a fresh count does, and only states below no cycle
+/// are ever stored, so the memos never change an answer.
+pub struct PathCounter {
+    graph: Graph,
+    /// Keyed by target and required nodes
+    memos: HashMap<(NodeId, Vec<NodeId>), Memo<usize>>,
+}
+
+impl PathCounter {
+    pub fn new(graph: Graph) -> Self {
+        PathCounter { graph, memos: HashMap::new() }
+    }
+
+    pub fn graph(&self) -> &Graph {
+        &self.graph
+    }
+
+    pub fn node(&self, name: &str) -> Result<NodeId> {
+        Ok(resolve_nodes(&self.graph, &[name])?[0])
+    }
+
+    /// Like `count_paths_with_required`, reusing and extending the memo of
+    /// earlier queries towards `dst` through `required`
+    pub fn count(&mut self, src: NodeId, dst: NodeId, required: &[NodeId]) -> Result<usize> {
+        let memo = self.memos.entry((dst, required.to_vec())).or_default();
+        let known = memo.len();
+        let count = count_paths_memoized::<usize>(&self.graph, src, dst, required, false, memo)?;
+        stats::add("day11.states_counted", (memo.len() - known) as u64);
+        count.ok_or_else(|| AocError::solve("Path count overflows usize; a plain --source/--target query counts paths of any size"))
+    }
+
+    /// (node, required nodes visited) states remembered across all queries
+    pub fn cached_states(&self) -> usize {
+        self.memos.values().map(HashMap::len).sum()
+    }
+
+    /// A node's count only depends on what lies below it, so after edits
+    /// at `touched` only the states of the nodes that reach one of them are
+    /// stale; the rest of every memo is kept
+    fn invalidate_reaching(&mut self, touched: &[NodeId]) {
+        let stale = self.graph.reaching_any(touched);
+        let before = self.cached_states();
+        for memo in self.memos.values_mut() {
+            memo.retain(|&(node, _), _| !stale[node.index()]);
+        }
+        stats::add("day11.states_invalidated", (before - self.cached_states()) as u64);
+    }
+
+    /// Adds a node with no edges, or finds the one already called `name`;
+    /// nothing counted so far can change
+    pub fn add_node(&mut self, name: &str) -> NodeId {
+        self.graph.add_node(name)
+    }
+
+    /// Adds an edge from `from` to `to` and forgets the counts it changes
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        self.graph.add_edge(from, to);
+        // After adding, so nodes the new edge puts on a cycle through `from` count too
+        self.invalidate_reaching(&[from]);
+    }
+
+    /// Removes every edge from `from` to `to`, forgetting the counts that
+    /// depended on them; returns how many there were
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) -> usize {
+        let removed = self.graph.remove_edge(from, to).len();
+        if removed > 0 {
+            self.invalidate_reaching(&[from]);
+        }
+        removed
+    }
+
+    /// Takes `id` out of every path by removing its edges, which are
+    /// returned for `restore`; "what if this device failed" without parsing
+    /// the graph again or recounting the parts of it the device can't reach
+    pub fn remove_node(&mut self, id: NodeId) -> Vec<(NodeId, NodeId, u64)> {
+        // Before removing, while the nodes above it still reach it
+        self.invalidate_reaching(&[id]);
+        self.graph.disconnect(id)
+    }
+
+    /// Adds back edges `remove_node` removed, with one search for the
+    /// counts they change however many there are
+    pub fn restore(&mut self, edges: Vec<(NodeId, NodeId, u64)>) {
+        for &(from, to, weight) in &edges {
+            self.graph.add_weighted_edge(from, to, weight);
+        }
+        let sources: Vec<NodeId> = edges.iter().map(|&(from, _, _)| from).collect();
+        self.invalidate_reaching(&sources);
+    }
 }
 
 /// Count the number of unique paths from 'svr' to 'out' that include both 'dac' and 'fft'
-fn count_paths_from_svr(root: &Rc<RefCell<Node>>) -> usize {
-    let mut required_nodes = HashSet::new();
-    required_nodes.insert("dac".to_string());
-    required_nodes.insert("fft".to_string());
-    
-    let mut memo = HashMap::new();
-    count_paths_with_required_memo(
-        root,
-        HashSet::new(),
-        HashSet::new(),
-        &required_nodes,
-        &mut memo,
-    )
-}
-
-/// Day 11: Exercise description
-pub fn run() -> Result<()> {
+fn count_paths_from_svr(counter: &mut PathCounter) -> Result<usize> {
+    let required = resolve_nodes(counter.graph(), &["dac", "fft"])?;
+    let (svr, out) = (counter.node("svr")?, counter.node("out")?);
+    counter.count(svr, out, &required)
+}
+
+fn resolve_nodes<S: AsRef<str>>(graph: &Graph, names: &[S]) -> Result<Vec<NodeId>> {
+    names
+        .iter()
+        .map(|name| {
+            graph
+                .node_id(name.as_ref())
+                .ok_or_else(|| AocError::parse(format!("Node '{}' not found in input", name.as_ref())))
+        })
+        .collect()
+}
+
+/// Command-line options for day 11 (passed after the day number)
+#[derive(Parser, Debug)]
+#[command(name = "day11", about = "Day 11 path queries")]
+struct Options {
+    /// Input file for a custom query [default: day11io2.txt in the input directory]
+    #[arg(long)]
+    input: Option<String>,
+    /// Source node; when set, runs a custom query instead of the puzzle parts
+    #[arg(long)]
+    source: Option<String>,
+    /// Target node for a custom query
+    #[arg(long, default_value = "out")]
+    target: String,
+    /// Comma-separated nodes every counted path must visit
+    #[arg(long, value_delimiter = ',')]
+    required: Vec<String>,
+    /// Print the shortest and longest path between two nodes
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"])]
+    path_query: Option<Vec<String>>,
+    /// Write the graph as Graphviz DOT; with --source, paths to --target are highlighted
+    /// and --required nodes are drawn bold
+    #[arg(long, value_name = "FILE")]
+    export_dot: Option<String>,
+    /// Report cycles and count --source to --target paths on the condensed (acyclic) graph
+    #[arg(long, requires = "source")]
+    condense: bool,
+    /// Print the first N paths from --source to --target
+    #[arg(long, value_name = "N", requires = "source")]
+    dump_paths: Option<usize>,
+    /// Report unreachable nodes, dead ends and the nodes every path from --source
+    /// (default svr) to --target must pass through
+    #[arg(long)]
+    analyze: bool,
+    /// Count paths with edge weights (`a: b*3`) as multiplicities
+    #[arg(long, requires = "source")]
+    weighted: bool,
+    /// Print the lowest-cost path from --source to --target, edge weights being costs
+    #[arg(long, requires = "source")]
+    cheapest: bool,
+    /// Comma-separated sinks: count paths from --source (default you) ending at
+    /// any of them, with a count per sink
+    #[arg(long, value_delimiter = ',',
+          conflicts_with_all = ["required", "weighted", "condense", "dump_paths", "cheapest"])]
+    sink: Vec<String>,
+    /// Comma-separated devices: recount --source to --target paths with each one
+    /// removed in turn, editing the graph in place
+    #[arg(long, value_delimiter = ',', requires = "source",
+          conflicts_with_all = ["weighted", "condense", "dump_paths", "cheapest"])]
+    without: Vec<String>,
+}
+
+impl Options {
+    fn read_input(&self) -> Result<&'static str> {
+        match &self.input {
+            Some(path) => input::read_file(path),
+            None => Input::for_day(11).read("io2"),
+        }
+    }
+}
+
+fn run_query(options: &Options, source: &str, out: &dyn OutputSink) -> Result<()> {
+    let (graph, src) = parse_input(options.read_input()?, source)?;
+    let dst = resolve_nodes(&graph, &[&options.target])?[0];
+    let required = resolve_nodes(&graph, &options.required)?;
+
+    if options.weighted {
+        let count = count_weighted_paths_auto(&graph, src, dst, &required)?;
+        out.line(&format!(
+            "Weighted paths from '{}' to '{}' through {:?}: {}",
+            source, options.target, options.required, count
+        ));
+        return Ok(());
+    }
+    let count = count_paths_auto(&graph, src, dst, &required)?;
+    out.line(&format!(
+        "Paths from '{}' to '{}' through {:?}: {}",
+        source, options.target, options.required, count
+    ));
+    Ok(())
+}
+
+fn run_without(options: &Options, source: &str, out: &dyn OutputSink) -> Result<()> {
+    let mut counter = PathCounter::new(parse_graph(options.read_input()?)?);
+    let (src, dst) = (counter.node(source)?, counter.node(&options.target)?);
+    let required = resolve_nodes(counter.graph(), &options.required)?;
+    let devices = resolve_nodes(counter.graph(), &options.without)?;
+
+    let baseline = counter.count(src, dst, &required)?;
+    out.line(&format!(
+        "Paths from '{}' to '{}' through {:?}: {}",
+        source, options.target, options.required, baseline
+    ));
+    for (name, device) in options.without.iter().zip(devices) {
+        let removed = counter.remove_node(device);
+        let count = counter.count(src, dst, &required)?;
+        out.line(&format!(
+            "  without {:<12} {:>16} ({:+})",
+            format!("'{}':", name),
+            count,
+            count as i128 - baseline as i128
+        ));
+        counter.restore(removed);
+    }
+    Ok(())
+}
+
+fn run_sink_query(options: &Options, out: &dyn OutputSink) -> Result<()> {
+    let source = options.source.as_deref().unwrap_or("you");
+    let (graph, src) = parse_input(options.read_input()?, source)?;
+    let sinks = resolve_nodes(&graph, &options.sink)?;
+
+    let counts = count_paths_by_sink(&graph, src, &sinks)?;
+    out.line(&format!("Paths from '{}':", source));
+    for (name, count) in options.sink.iter().zip(&counts) {
+        out.line(&format!("  {:<16} {}", format!("to '{}'", name), count));
+    }
+    out.line(&format!("  {:<16} {}", "total", counts.into_iter().sum::<PathTotal>()));
+    Ok(())
+}
+
+fn run_cheapest(options: &Options, source: &str, out: &dyn OutputSink) -> Result<()> {
+    let (graph, src) = parse_input(options.read_input()?, source)?;
+    let dst = resolve_nodes(&graph, &[&options.target])?[0];
+
+    match graph.cheapest_path(src, dst) {
+        Some((cost, path)) => out.line(&format!(
+            "Cheapest path (cost {}, {} edges): {}",
+            cost,
+            path.len() - 1,
+            graph.path_names(&path).join(" -> ")
+        )),
+        None => out.line(&format!("No path from '{}' to '{}'", source, options.target)),
+    }
+    Ok(())
+}
+
+/// Count paths on the condensation of the graph, where every cycle is collapsed to one node.
+/// Fails if a required node sits inside a cycle, since "passing through" it is then ambiguous.
+pub fn count_paths_condensed(graph: &Graph, src: NodeId, dst: NodeId, required: &[NodeId]) -> Result<usize> {
+    let condensation = graph.condense();
+    let cyclic = graph.cyclic_components();
+
+    for &node in required {
+        if let Some(component) = cyclic.iter().find(|component| component.contains(&node)) {
+            return Err(AocError::solve(format!(
+                "Required node '{}' is inside a cycle with [{}]",
+                graph.name(node),
+                graph.path_names(component).join(", ")
+            )));
+        }
+    }
+
+    let component = |node: NodeId| condensation.component_of[node.index()];
+    let condensed_required: Vec<NodeId> = required.iter().map(|&node| component(node)).collect();
+    count_paths_with_required(&condensation.graph, component(src), component(dst), &condensed_required)
+}
+
+fn run_condensed_query(options: &Options, source: &str, out: &dyn OutputSink) -> Result<()> {
+    let (graph, src) = parse_input(options.read_input()?, source)?;
+    let dst = resolve_nodes(&graph, &[&options.target])?[0];
+    let required = resolve_nodes(&graph, &options.required)?;
+
+    let cyclic = graph.cyclic_components();
+    if cyclic.is_empty() {
+        out.line("No cycles found");
+    } else {
+        out.line(&format!("Found {} cycle(s):", cyclic.len()));
+        for component in &cyclic {
+            out.line(&format!("  [{}]", graph.path_names(component).join(", ")));
+        }
+    }
+
+    let count = count_paths_condensed(&graph, src, dst, &required)?;
+    out.line(&format!(
+        "Paths on the condensed graph from '{}' to '{}' through {:?}: {}",
+        source, options.target, options.required, count
+    ));
+    Ok(())
+}
+
+fn dump_paths(options: &Options, source: &str, limit: usize, out: &dyn OutputSink) -> Result<()> {
+    let (graph, src) = parse_input(options.read_input()?, source)?;
+    let dst = resolve_nodes(&graph, &[&options.target])?[0];
+
+    let mut printed = 0;
+    for (i, path) in iter_paths(&graph, src, dst).take(limit).enumerate() {
+        out.line(&format!("{:>4}: {}", i + 1, graph.path_names(&path).join(" -> ")));
+        printed += 1;
+    }
+    out.line(&format!("Printed {} path(s) from '{}' to '{}'", printed, source, options.target));
+    Ok(())
+}
+
+fn export_dot(options: &Options, dot_file: &str, out: &dyn OutputSink) -> Result<()> {
+    let graph = parse_graph(options.read_input()?)?;
+    let required = resolve_nodes(&graph, &options.required)?;
+
+    let path_between = match &options.source {
+        Some(source) => {
+            let ends = resolve_nodes(&graph, &[source, &options.target])?;
+            Some((ends[0], ends[1]))
+        }
+        None => None,
+    };
+
+    fs::write(dot_file, graph.to_dot(path_between, &required))
+        .map_err(|e| AocError::io(dot_file, e))?;
+    out.line(&format!("Wrote {} nodes to {}", graph.len(), dot_file));
+    Ok(())
+}
+
+/// Reachability and dominator summary of the graph between a root and a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Nodes the root can't reach
+    pub unreachable: Vec<NodeId>,
+    /// Nodes that can't reach the target
+    pub dead_ends: Vec<NodeId>,
+    /// Nodes every root -> target path passes through, in path order, excluding both ends
+    pub dominators: Vec<NodeId>,
+}
+
+pub fn analyze(graph: &Graph, root: NodeId, target: NodeId) -> Analysis {
+    let reachable = graph.reachable_from(root);
+    let reaching = graph.reaching(target);
+    let mut dominators = graph.dominators_of(root, target);
+    if dominators.len() >= 2 {
+        dominators.pop();
+        dominators.remove(0);
+    }
+    Analysis {
+        unreachable: graph.node_ids().filter(|n| !reachable[n.index()]).collect(),
+        dead_ends: graph.node_ids().filter(|n| !reaching[n.index()]).collect(),
+        dominators,
+    }
+}
+
+/// Node names listed per `--analyze` category before eliding the rest
+const ANALYZE_LISTED: usize = 20;
+
+fn list_nodes(graph: &Graph, nodes: &[NodeId]) -> String {
+    if nodes.is_empty() {
+        return "none".to_string();
+    }
+    let mut names = graph.path_names(&nodes[..nodes.len().min(ANALYZE_LISTED)]).join(", ");
+    if nodes.len() > ANALYZE_LISTED {
+        names.push_str(&format!(", ... ({} more)", nodes.len() - ANALYZE_LISTED));
+    }
+    names
+}
+
+fn run_analysis(options: &Options, out: &dyn OutputSink) -> Result<()> {
+    let source = options.source.as_deref().unwrap_or("svr");
+    let (graph, root) = parse_input(options.read_input()?, source)?;
+    let target = resolve_nodes(&graph, &[&options.target])?[0];
+    let analysis = analyze(&graph, root, target);
+
+    out.line(&format!("{} nodes, analyzed from '{}' to '{}'", graph.len(), source, options.target));
+    out.line(&format!(
+        "Unreachable from '{}' ({}): {}",
+        source,
+        analysis.unreachable.len(),
+        list_nodes(&graph, &analysis.unreachable)
+    ));
+    out.line(&format!(
+        "Can't reach '{}' ({}): {}",
+        options.target,
+        analysis.dead_ends.len(),
+        list_nodes(&graph, &analysis.dead_ends)
+    ));
+    if !graph.reachable_from(root)[target.index()] {
+        out.line(&format!("No path from '{}' to '{}'", source, options.target));
+    } else if analysis.dominators.is_empty() {
+        out.line(&format!("No node lies on every path from '{}' to '{}'", source, options.target));
+    } else {
+        out.line(&format!(
+            "On every path from '{}' to '{}': {}",
+            source,
+            options.target,
+            graph.path_names(&analysis.dominators).join(" -> ")
+        ));
+    }
+    Ok(())
+}
+
+fn run_path_query(input: &str, from: &str, to: &str, out: &dyn OutputSink) -> Result<()> {
+    let (graph, src) = parse_input(input, from)?;
+    let dst = resolve_nodes(&graph, &[to])?[0];
+
+    match graph.shortest_path(src, dst) {
+        Some(path) => {
+            out.line(&format!("Shortest path ({} edges): {}", path.len() - 1, graph.path_names(&path).join(" -> ")));
+        }
+        None => {
+            out.line(&format!("No path from '{}' to '{}'", from, to));
+            return Ok(());
+        }
+    }
+    if let Some(path) = graph.longest_path(src, dst)? {
+        out.line(&format!("Longest path ({} edges): {}", path.len() - 1, graph.path_names(&path).join(" -> ")));
+    }
+    Ok(())
+}
+
+/// Paths from `you` to `out`
+pub fn part1(input: &str) -> Result<PathTotal> {
+    let (graph, root) = parse_input(input, "you")?;
+    let out = resolve_nodes(&graph, &["out"])?;
+    count_paths(&graph, root, &out)
+}
+
+/// Paths from `svr` to `out` through both `dac` and `fft`
+pub fn part2(input: &str) -> Result<usize> {
+    count_paths_from_svr(&mut PathCounter::new(parse_graph(input)?))
+}
+
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Reactor",
+        input_files: &["io1", "io2"],
+        parts: [
+            "Paths from `you` to `out`",
+            "Paths from `svr` to `out` through both `dac` and `fft`",
+        ],
+        notes: "Many custom path queries; see --help. --without recounts with devices removed, \
+                invalidating only the memo states above them",
+    }
+}
+
+pub fn run(args: &[String], out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day11".to_string()).chain(args.iter().cloned()));
+    if let Some(dot_file) = &options.export_dot {
+        return export_dot(&options, dot_file, out);
+    }
+    if options.analyze {
+        return run_analysis(&options, out);
+    }
+    if let Some(endpoints) = &options.path_query {
+        return run_path_query(options.read_input()?, &endpoints[0], &endpoints[1], out);
+    }
+    if !options.sink.is_empty() {
+        return run_sink_query(&options, out);
+    }
+    if let Some(source) = &options.source {
+        if options.condense {
+            return run_condensed_query(&options, source, out);
+        }
+        if let Some(limit) = options.dump_paths {
+            return dump_paths(&options, source, limit, out);
+        }
+        if options.cheapest {
+            return run_cheapest(&options, source, out);
+        }
+        if !options.without.is_empty() {
+            return run_without(&options, source, out);
+        }
+        return run_query(&options, source, out);
+    }
+
     // Part 1
-    println!("Part 1:");
-    let root1 = parse_input("assets/day11io1.txt", "you")?;
-    let num_paths1 = count_paths_to_out(&root1);
-    println!("  Number of unique paths from 'you' to 'out': {}", num_paths1);
-    
+    out.line("Part 1:");
+    let (graph1, root1) = parse_input(Input::for_day(11).read("io1")?, "you")?;
+    let num_paths1 = count_paths(&graph1, root1, &resolve_nodes(&graph1, &["out"])?)?;
+    out.answer(1, "  Number of unique paths from 'you' to 'out'", &num_paths1);
+
+    // Parts 2 and 2b query the same graph, so parse it once and share the memos
+    let mut counter = PathCounter::new(parse_graph(Input::for_day(11).read("io2")?)?);
+
     // Part 2
-    println!("\nPart 2:");
-    let root2 = parse_input("assets/day11io2.txt", "you")?;
-    let num_paths2 = count_paths_to_out(&root2);
-    println!("  Number of unique paths from 'you' to 'out': {}", num_paths2);
-    
+    out.line("\nPart 2:");
+    let (you, out_node) = (counter.node("you")?, counter.node("out")?);
+    let num_paths2 = counter.count(you, out_node, &[])?;
+    out.line(&format!("  Number of unique paths from 'you' to 'out': {}", num_paths2));
+
     // Part 2b - from 'svr' with constraints
-    println!("\nPart 2b:");
-    let root2b = parse_input("assets/day11io2.txt", "svr")?;
-    let num_paths2b = count_paths_from_svr(&root2b);
-    println!("  Number of paths from 'svr' to 'out' including both 'dac' and 'fft': {}", num_paths2b);
-    
+    out.line("\nPart 2b:");
+    let num_paths2b = count_paths_from_svr(&mut counter)?;
+    out.answer(2, "  Number of paths from 'svr' to 'out' including both 'dac' and 'fft'", &num_paths2b);
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::snapshot;
 
     #[test]
     fn test_part1_path_count() {
-        let root = parse_input("assets/day11io1.txt", "you")
+        let (graph, root) = parse_input(Input::for_day(11).read("io1").unwrap(), "you")
             .expect("Failed to load part 1 input");
-        
-        let num_paths = count_paths_to_out(&root);
-        
-        assert_eq!(num_paths, 5, "Part 1 should have 5 unique paths");
+
+        let out = graph.node_id("out").unwrap();
+        let num_paths = count_paths(&graph, root, &[out]).unwrap();
+
+        assert_eq!(num_paths, PathTotal::Small(5), "Part 1 should have 5 unique paths");
     }
 
     #[test]
     fn test_part2_path_count() {
-        let root = parse_input("assets/day11io2.txt", "you")
+        let (graph, root) = parse_input(Input::for_day(11).read("io2").unwrap(), "you")
             .expect("Failed to load part 2 input");
-        
-        let num_paths = count_paths_to_out(&root);
-        
-        assert_eq!(num_paths, 701, "Part 2 should have 701 unique paths");
+
+        let out = graph.node_id("out").unwrap();
+        let PathTotal::Small(num_paths) = count_paths(&graph, root, &[out]).unwrap() else {
+            panic!("Part 2 path count should fit in u64");
+        };
+
+        snapshot::check("day11", "part2_paths", num_paths);
     }
 
     #[test]
     fn test_part2b_svr_with_constraints() {
-        let root = parse_input("assets/day11io2.txt", "svr")
-            .expect("Failed to load part 2 input");
-        
-        let num_paths = count_paths_from_svr(&root);
-        
+        let graph = parse_graph(Input::for_day(11).read("io2").unwrap()).expect("Failed to load part 2 input");
+
+        let num_paths = count_paths_from_svr(&mut PathCounter::new(graph)).unwrap();
+
+        snapshot::check("day11", "part2b_svr_paths", num_paths);
+    }
+
+    #[test]
+    fn test_path_counter_reuses_memos() {
+        let graph = parse_graph("a: b c\nb: d\nc: d\nd: e out\ne: out\nx: b\n").unwrap();
+        let mut counter = PathCounter::new(graph);
+        let id = |name| counter.node(name).unwrap();
+        let (a, b, d, x, out) = (id("a"), id("b"), id("d"), id("x"), id("out"));
+
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 4);
+        let states = counter.cached_states();
+        // Everything below b is already known, so only x is new
+        assert_eq!(counter.count(x, out, &[]).unwrap(), 2);
+        assert_eq!(counter.cached_states(), states + 1);
+        assert_eq!(counter.count(b, out, &[]).unwrap(), 2);
+        assert_eq!(counter.cached_states(), states + 1);
+
+        // Other required nodes get their own memo
+        assert_eq!(counter.count(a, out, &[b]).unwrap(), 2);
+        assert_eq!(counter.count(x, out, &[d]).unwrap(), 2);
+        assert!(counter.cached_states() > states + 1);
+        assert_eq!(PathTotal::Small(counter.count(a, out, &[]).unwrap() as u64), count_paths(counter.graph(), a, &[out]).unwrap());
+    }
+
+    #[test]
+    fn test_path_counter_edits() {
+        let graph = parse_graph("a: b c\nb: d\nc: d\nd: e out\ne: out\nx: b\n").unwrap();
+        let mut counter = PathCounter::new(graph);
+        let id = |name| counter.node(name).unwrap();
+        let (a, b, c, d, e, x, out) = (id("a"), id("b"), id("c"), id("d"), id("e"), id("x"), id("out"));
+        let fresh = |counter: &PathCounter, src, required: &[NodeId]| {
+            count_paths_with_required(counter.graph(), src, out, required).unwrap()
+        };
+
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 4);
+        assert_eq!(counter.count(a, out, &[d]).unwrap(), 4);
+        let states = counter.cached_states();
+
+        // Only b and a (x was never counted) are forgotten, in both memos; c, d, e and out stay
+        let removed = counter.remove_node(b);
+        assert_eq!(counter.cached_states(), states - 4);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 2);
+        assert_eq!(counter.count(x, out, &[]).unwrap(), 0);
+        assert_eq!(counter.count(a, out, &[d]).unwrap(), fresh(&counter, a, &[d]));
+        counter.restore(removed);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 4);
+        assert_eq!(counter.count(x, out, &[]).unwrap(), 2);
+
+        assert_eq!(counter.remove_edge(d, out), 1);
+        assert_eq!(counter.remove_edge(d, out), 0);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 2);
+        let f = counter.add_node("f");
+        counter.add_edge(c, f);
+        counter.add_edge(f, out);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), fresh(&counter, a, &[]));
+        assert_eq!(counter.count(c, out, &[e]).unwrap(), fresh(&counter, c, &[e]));
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 2 + 1);
+
+        // An edge closing a cycle after the memos are filled: every start
+        // that reaches the cycle fails, as a fresh count does
+        assert_eq!(counter.count(c, out, &[]).unwrap(), 2);
+        counter.add_edge(f, c);
+        for src in [f, c, a] {
+            assert!(counter.count(src, out, &[]).is_err());
+            assert!(count_paths_with_required(counter.graph(), src, out, &[]).is_err());
+        }
+        assert_eq!(counter.remove_edge(f, c), 1);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_path_counter_on_cycles() {
+        // Which edge a memo would drop from the b -> c -> b cycle depends on
+        // where the walk entered it, so every query reaching it fails instead
+        let graph = parse_graph("a: b\nb: c out\nc: b out\n").unwrap();
+        let mut counter = PathCounter::new(graph);
+        let id = |name| counter.node(name).unwrap();
+        let (a, b, c, out) = (id("a"), id("b"), id("c"), id("out"));
+        for src in [c, b, a] {
+            let err = counter.count(src, out, &[]).unwrap_err().to_string();
+            assert!(err.starts_with("Cycle detected: edge"), "{}", err);
+        }
+        assert_eq!(counter.remove_edge(c, b), 1);
+        assert_eq!(counter.count(a, out, &[]).unwrap(), 2);
+        assert_eq!(counter.count(c, out, &[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_deep_chain_does_not_overflow() {
+        // A 200k-node chain would blow the stack with plain recursion
+        let depth = 200_000;
+        let mut input = String::new();
+        for i in 0..depth {
+            input.push_str(&format!("n{}: n{}\n", i, i + 1));
+        }
+        input.push_str(&format!("n{}: out\n", depth));
+
+        let graph = parse_graph(&input).unwrap();
+        let out = graph.node_id("out").unwrap();
+        assert_eq!(count_paths(&graph, graph.node_id("n0").unwrap(), &[out]).unwrap(), PathTotal::Small(1));
+    }
+
+    #[test]
+    fn test_shared_subgraphs_counted_once() {
+        // A ladder of 60 diamonds has 2^60 paths; without memoization this never finishes
+        let mut input = String::new();
+        for i in 0..60 {
+            input.push_str(&format!("d{i}: a{i} b{i}\na{i}: d{next}\nb{i}: d{next}\n", i = i, next = i + 1));
+        }
+        input.push_str("d60: out\n");
+
+        let graph = parse_graph(&input).unwrap();
+        let out = graph.node_id("out").unwrap();
+        assert_eq!(count_paths(&graph, graph.node_id("d0").unwrap(), &[out]).unwrap(), PathTotal::Small(1 << 60));
+    }
+
+    #[test]
+    fn test_reachable_cycles_rejected() {
+        // Four simple paths, but a memo would count 3: whichever of b -> c and
+        // c -> b the search meets first would be dropped for both entries
+        let graph = parse_graph("you: b c\nb: c out\nc: b out\n").unwrap();
+        let ids = resolve_nodes(&graph, &["you", "out"]).unwrap();
+        let (you, out) = (ids[0], ids[1]);
+        assert_eq!(iter_paths(&graph, you, out).count(), 4);
+        let err = count_paths(&graph, you, &[out]).unwrap_err().to_string();
+        assert!(err.starts_with("Cycle detected: edge"), "{}", err);
+        assert!(count_paths_by_sink(&graph, you, &[out]).is_err());
+        assert!(count_paths_auto(&graph, you, out, &[]).is_err());
+
+        // A cycle only through the sink, or out of reach, changes nothing
+        let graph = parse_graph("a: out\nout: a\nx: y\ny: x out\n").unwrap();
+        let ids = resolve_nodes(&graph, &["a", "out"]).unwrap();
+        assert_eq!(count_paths(&graph, ids[0], &[ids[1]]).unwrap(), PathTotal::Small(1));
+        assert_eq!(count_paths_with_required(&graph, ids[0], ids[1], &[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_several_sinks() {
+        // b is a sink, so the path s -> b -> t stops at b
+        let graph = parse_graph("s: a b\na: t u\nb: t\n").unwrap();
+        let ids = resolve_nodes(&graph, &["s", "t", "u", "b"]).unwrap();
+        let (s, t, u, b) = (ids[0], ids[1], ids[2], ids[3]);
+
+        let small = |counts: &[u64]| counts.iter().copied().map(PathTotal::Small).collect::<Vec<_>>();
+        assert_eq!(count_paths_by_sink(&graph, s, &[t, u]).unwrap(), small(&[2, 1]));
+        assert_eq!(count_paths_by_sink(&graph, s, &[t, u, b]).unwrap(), small(&[1, 1, 1]));
+        assert_eq!(count_paths(&graph, s, &[t, u, b]).unwrap(), PathTotal::Small(3));
+        assert_eq!(count_paths_by_sink(&graph, s, &[]).unwrap(), Vec::new());
+        assert_eq!(count_paths(&graph, s, &[s]).unwrap(), PathTotal::Small(1));
+
+        let options = Options::try_parse_from(["day11", "--sink", "out,dac"]).unwrap();
+        assert_eq!(options.sink, vec!["out", "dac"]);
+        assert!(Options::try_parse_from(["day11", "--sink", "out", "--required", "dac"]).is_err());
+    }
+
+    #[test]
+    fn test_graph_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Graph>();
+    }
+
+    #[test]
+    fn test_required_nodes_filter_paths() {
+        let graph = parse_graph("s: a b\na: c\nb: c\nc: t\n").unwrap();
+        let ids = resolve_nodes(&graph, &["s", "t", "a", "b", "c"]).unwrap();
+        let (s, t) = (ids[0], ids[1]);
+
+        assert_eq!(count_paths_with_required(&graph, s, t, &[]).unwrap(), 2);
+        assert_eq!(count_paths_with_required(&graph, s, t, &[ids[2]]).unwrap(), 1);
+        assert_eq!(count_paths_with_required(&graph, s, t, &[ids[4]]).unwrap(), 2);
+        assert_eq!(count_paths_with_required(&graph, s, t, &[ids[2], ids[3]]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_required_nodes_limit() {
+        let mut input = String::new();
+        for i in 0..65 {
+            input.push_str(&format!("n{}: n{}\n", i, i + 1));
+        }
+        let graph = parse_graph(&input).unwrap();
+        let names: Vec<String> = (0..=65).map(|i| format!("n{}", i)).collect();
+        let ids = resolve_nodes(&graph, &names).unwrap();
+
+        // 64 required nodes fit in the mask
+        assert_eq!(count_paths_with_required(&graph, ids[0], ids[65], &ids[1..65]).unwrap(), 1);
+        assert!(count_paths_with_required(&graph, ids[0], ids[65], &ids[0..65]).is_err());
+    }
+
+    #[test]
+    fn test_options_parse_required_list() {
+        let options = Options::try_parse_from(["day11", "--source", "svr", "--required", "dac,fft"]).unwrap();
+        assert_eq!(options.source.as_deref(), Some("svr"));
+        assert_eq!(options.target, "out");
+        assert_eq!(options.required, vec!["dac", "fft"]);
+
+        let options = Options::try_parse_from(["day11", "--path-query", "you", "out"]).unwrap();
+        assert_eq!(options.path_query, Some(vec!["you".to_string(), "out".to_string()]));
+    }
+
+    #[test]
+    fn test_path_queries_on_part1() {
+        let (graph, root) = parse_input(Input::for_day(11).read("io1").unwrap(), "you").unwrap();
+        let out = graph.node_id("out").unwrap();
+
+        let shortest = graph.shortest_path(root, out).unwrap();
+        let longest = graph.longest_path(root, out).unwrap().unwrap();
+        assert_eq!(shortest.len() - 1, 3);
+        assert_eq!(longest.len() - 1, 4);
+    }
+
+    #[test]
+    fn test_condensed_counting() {
+        // a <-> b form a cycle; the condensation has paths s-{a,b}-t and s-c-t
+        let graph = parse_graph("s: a c\na: b\nb: a t\nc: t\n").unwrap();
+        let ids = resolve_nodes(&graph, &["s", "t", "a", "c"]).unwrap();
+
+        assert_eq!(count_paths_condensed(&graph, ids[0], ids[1], &[]).unwrap(), 2);
+        assert_eq!(count_paths_condensed(&graph, ids[0], ids[1], &[ids[3]]).unwrap(), 1);
+
+        let err = count_paths_condensed(&graph, ids[0], ids[1], &[ids[2]]).unwrap_err();
+        assert_eq!(err.to_string(), "Required node 'a' is inside a cycle with [a, b]");
+    }
+
+    #[test]
+    fn test_puzzle_input_is_acyclic() {
+        let (graph, _) = parse_input(Input::for_day(11).read("io2").unwrap(), "svr").unwrap();
+        assert!(graph.cyclic_components().is_empty());
+        let (svr, out) = (graph.node_id("svr").unwrap(), graph.node_id("out").unwrap());
+        assert_eq!(
+            count_paths_condensed(&graph, svr, out, &[]).unwrap(),
+            count_paths_with_required(&graph, svr, out, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_iter_paths_agrees_with_memoized_count() {
+        let (graph, root) = parse_input(Input::for_day(11).read("io2").unwrap(), "you").unwrap();
+        let out = graph.node_id("out").unwrap();
+
+        let paths: Vec<Vec<NodeId>> = iter_paths(&graph, root, out).collect();
+        assert_eq!(PathTotal::Small(paths.len() as u64), count_paths(&graph, root, &[out]).unwrap());
+        assert!(paths.iter().all(|path| path[0] == root && path[path.len() - 1] == out));
+    }
+
+    #[test]
+    fn test_analyze_reports_unreachable_dead_ends_and_dominators() {
+        // Every s -> out path passes through m; z hangs off the side, u is never reached
+        let graph = parse_graph("s: a b z\na: m\nb: m\nm: c\nc: out\nu: out\n").unwrap();
+        let ids = resolve_nodes(&graph, &["s", "out"]).unwrap();
+
+        let analysis = analyze(&graph, ids[0], ids[1]);
+        assert_eq!(graph.path_names(&analysis.unreachable), vec!["u"]);
+        assert_eq!(graph.path_names(&analysis.dead_ends), vec!["z"]);
+        assert_eq!(graph.path_names(&analysis.dominators), vec!["m", "c"]);
+    }
+
+    #[test]
+    fn test_weighted_edges() {
+        // s -> a -> t three times over, s -> b -> t twice, s -> t directly once
+        let graph = parse_graph("s: a*3 b t*7\na: t\nb: t*2\n").unwrap();
+        let ids = resolve_nodes(&graph, &["s", "t", "a", "b"]).unwrap();
+        let (s, t) = (ids[0], ids[1]);
+
+        assert_eq!(count_paths_with_required(&graph, s, t, &[]).unwrap(), 3);
+        assert_eq!(count_weighted_paths_generic::<u64>(&graph, s, t, &[]).unwrap(), Some(3 + 2 + 7));
+        assert_eq!(count_weighted_paths_auto(&graph, s, t, &[ids[3]]).unwrap(), PathTotal::Small(2));
+        let (cost, path) = graph.cheapest_path(s, t).unwrap();
+        assert_eq!((cost, graph.path_names(&path)), (3, vec!["s", "b", "t"]));
+
+        // Weights compound along a path, so huge ones promote the accumulator
+        let graph = parse_graph(&format!("s: a*{max}\na: t*{max}\n", max = u64::MAX)).unwrap();
+        let ids = resolve_nodes(&graph, &["s", "t"]).unwrap();
+        let expected = u128::from(u64::MAX) * u128::from(u64::MAX);
+        assert_eq!(count_weighted_paths_auto(&graph, ids[0], ids[1], &[]).unwrap(), PathTotal::Wide(expected));
+
+        assert_eq!(parse_graph("a: b*x\n").unwrap_err().to_string(), "Line 1: Invalid weight in 'b*x': invalid digit found in string");
+        assert_eq!(parse_graph("a: b\nb: c*0\n").unwrap_err().to_string(), "Line 2: Edge weight in 'c*0' must be at least 1");
+        assert!(parse_graph("a: *2\n").is_err());
+    }
+
+    fn diamond_ladder(rungs: usize) -> (Graph, NodeId, NodeId) {
+        let mut input = String::new();
+        for i in 0..rungs {
+            input.push_str(&format!("d{i}: a{i} b{i}\na{i}: d{next}\nb{i}: d{next}\n", i = i, next = i + 1));
+        }
+        let graph = parse_graph(&input).unwrap();
+        let ends = resolve_nodes(&graph, &["d0".to_string(), format!("d{}", rungs)]).unwrap();
+        (graph, ends[0], ends[1])
+    }
+
+    #[test]
+    fn test_part1_past_usize() {
+        let mut input = String::from("you: d0\nd70: out\n");
+        for i in 0..70 {
+            input.push_str(&format!("d{i}: a{i} b{i}\na{i}: d{next}\nb{i}: d{next}\n", i = i, next = i + 1));
+        }
+        assert_eq!(part1(&input).unwrap(), PathTotal::Wide(1 << 70));
+    }
+
+    #[test]
+    fn test_auto_promotes_past_u64() {
+        let (graph, src, dst) = diamond_ladder(63);
+        assert_eq!(count_paths_auto(&graph, src, dst, &[]).unwrap(), PathTotal::Small(1 << 63));
+
+        let (graph, src, dst) = diamond_ladder(70);
+        assert_eq!(count_paths_generic::<u64>(&graph, src, dst, &[]).unwrap(), None);
+        assert_eq!(count_paths_auto(&graph, src, dst, &[]).unwrap(), PathTotal::Wide(1 << 70));
+        assert!(count_paths_with_required(&graph, src, dst, &[]).is_err());
+
+        // Per sink too, and past usize: --sink on a 70-rung ladder
+        let side = graph.node_id("a69").unwrap();
+        let counts = count_paths_by_sink(&graph, src, &[dst, side]).unwrap();
+        assert_eq!(counts, vec![PathTotal::Wide(1 << 69), PathTotal::Wide(1 << 69)]);
+        assert_eq!(counts.into_iter().sum::<PathTotal>(), PathTotal::Wide(1 << 70));
+        assert_eq!(count_paths_by_sink_generic::<usize>(&graph, src, &[dst]).unwrap(), None);
+        assert_eq!(count_paths(&graph, src, &[dst]).unwrap(), PathTotal::Wide(1 << 70));
+        assert_eq!([PathTotal::Small(u64::MAX), PathTotal::Small(1)].into_iter().sum::<PathTotal>(), PathTotal::Wide(1 << 64));
+
+        let (graph, src, dst) = diamond_ladder(130);
+        let expected = BigUint::from(1u8) << 130usize;
+        assert_eq!(count_paths_auto(&graph, src, dst, &[]).unwrap(), PathTotal::Big(expected.clone()));
         assert_eq!(
-            num_paths, 390108778818526,
-            "Part 2b should have 390108778818526 paths from 'svr' to 'out' including both 'dac' and 'fft'"
+            count_paths_auto(&graph, src, dst, &[]).unwrap().to_string(),
+            expected.to_string()
         );
     }
 }
@@ -1,40 +1,29 @@
-use anyhow::{anyhow, Context, Result};
+// Day 12 input format, CLI and reporting; the packing itself lives in util::polyomino
+
+use crate::days::DayMetadata;
+use crate::error::{AocError, Result};
+use clap::{Parser, ValueEnum};
+use crate::input::{self, Input};
+use crate::util::cancel::CancellationToken;
+use crate::util::grid::Grid;
+use crate::util::json::{self, Value};
+use crate::util::polyomino::{self, Attempt, Coords, Objective, Packer, Placement, ProblemSpace, Shape, ShapeCatalog, Strategy};
+use crate::util::output::OutputSink;
+use crate::util::parse;
+use crate::util::progress::{Hidden, Progress};
+use crate::util::render::{self, RegionGrid};
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::stats;
+use crate::util::theme::{self, Style};
+use crate::util::trace;
 use std::fs;
-use std::collections::{HashMap, HashSet};
-use varisat::{ExtendFormula, CnfFormula, Var, Lit, Solver};
-
-#[derive(Debug, Clone)]
-pub struct Shape {
-    pub id: usize,
-    pub grid: Vec<Vec<char>>, // 3x3 grid
-}
-
-#[derive(Debug, Clone)]
-pub struct ProblemSpace {
-    pub width: usize,
-    pub height: usize, // "long" dimension
-    pub shape_counts: Vec<usize>, // Count for each shape ID (index = shape ID)
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Coords {
-    pub x: i32,
-    pub y: i32,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Placement {
-    pub shape_id: usize,
-    pub instance: usize,
-    pub x: i32,
-    pub y: i32,
-    pub cells: Vec<Coords>, // Actual grid cells occupied by this placement
-}
-
-fn parse_input(filename: &str) -> Result<(Vec<Shape>, Vec<ProblemSpace>)> {
-    let content = fs::read_to_string(filename)
-        .context(format!("Failed to read file: {}", filename))?;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use rayon::prelude::*;
 
+pub fn parse_input(content: &str) -> Result<(Vec<Shape>, Vec<ProblemSpace>)> {
     // Collect all lines but trim trailing empty lines
     let all_lines: Vec<&str> = content.lines().collect();
     let lines: Vec<&str> = all_lines.iter()
@@ -58,24 +47,28 @@ fn parse_input(filename: &str) -> Result<(Vec<Shape>, Vec<ProblemSpace>)> {
             // Extract shape ID
             let id_str = &line[..line.len() - 1];
             let id = id_str.parse::<usize>()
-                .context(format!("Line {}: invalid shape ID '{}'", i + 1, id_str))?;
+                .map_err(|e| AocError::parse_at(i + 1, format!("invalid shape ID '{}': {}", id_str, e)))?;
             
             // Read the next 3 lines as the 3x3 grid
             if i + 3 >= lines.len() {
-                return Err(anyhow!("Line {}: shape {} incomplete, expected 3 grid lines", i + 1, id));
+                return Err(AocError::parse_at(i + 1, format!("shape {} incomplete, expected 3 grid lines", id)));
             }
             
             let mut grid = Vec::new();
             for j in 1..=3 {
                 let grid_line = lines[i + j].trim();
                 if grid_line.len() != 3 {
-                    return Err(anyhow!(
-                        "Line {}: shape {} grid line {} should be 3 characters, got '{}'",
-                        i + j + 1, id, j, grid_line
-                    ));
+                    return Err(AocError::parse_at(i + j + 1, format!(
+                        "shape {} grid line {} should be 3 characters, got '{}'",
+                        id, j, grid_line
+                    )));
                 }
                 grid.push(grid_line.chars().collect());
             }
+            // The solvers anchor every orientation on its first cell
+            if !grid.iter().flatten().any(|&ch| ch == '#') {
+                return Err(AocError::parse_at(i + 1, format!("shape {} has no cells", id)));
+            }
             
             shapes.push(Shape { id, grid });
             i += 4; // Skip ID line and 3 grid lines, plus empty line if present
@@ -83,19 +76,19 @@ fn parse_input(filename: &str) -> Result<(Vec<Shape>, Vec<ProblemSpace>)> {
             // This is a problem space definition
             let parts: Vec<&str> = line.split(':').collect();
             if parts.len() != 2 {
-                return Err(anyhow!("Line {}: invalid problem space format", i + 1));
+                return Err(AocError::parse_at(i + 1, "invalid problem space format"));
             }
             
             // Parse dimensions (e.g., "12x5")
             let dims: Vec<&str> = parts[0].trim().split('x').collect();
             if dims.len() != 2 {
-                return Err(anyhow!("Line {}: invalid dimensions format, expected 'WxH'", i + 1));
+                return Err(AocError::parse_at(i + 1, "invalid dimensions format, expected 'WxH'"));
             }
             
             let width = dims[0].parse::<usize>()
-                .context(format!("Line {}: invalid width '{}'", i + 1, dims[0]))?;
+                .map_err(|e| AocError::parse_at(i + 1, format!("invalid width '{}': {}", dims[0], e)))?;
             let height = dims[1].parse::<usize>()
-                .context(format!("Line {}: invalid height '{}'", i + 1, dims[1]))?;
+                .map_err(|e| AocError::parse_at(i + 1, format!("invalid height '{}': {}", dims[1], e)))?;
             
             // Parse shape counts
             let counts_str = parts[1].trim();
@@ -103,585 +96,1151 @@ fn parse_input(filename: &str) -> Result<(Vec<Shape>, Vec<ProblemSpace>)> {
                 .split_whitespace()
                 .map(|s| {
                     s.parse::<usize>()
-                        .context(format!("Line {}: invalid shape count '{}'", i + 1, s))
+                        .map_err(|e| AocError::parse_at(i + 1, format!("invalid shape count '{}': {}", s, e)))
                 })
                 .collect::<Result<Vec<_>>>()?;
             
+            // An optional board mask follows: `height` rows of `.` (open) and `#` (blocked)
+            let is_mask_line = |l: &str| !l.trim().is_empty() && l.trim().chars().all(|c| c == '.' || c == '#');
+            let mut blocked = HashSet::new();
+            let mut next = i + 1;
+            if lines.get(next).is_some_and(|l| is_mask_line(l)) {
+                for y in 0..height {
+                    let row = lines.get(next + y).map(|l| l.trim()).unwrap_or("");
+                    if !is_mask_line(row) || row.len() != width {
+                        return Err(AocError::parse_at(next + y + 1, format!(
+                            "board mask row {} should be {} characters of '.' or '#', got '{}'",
+                            y + 1, width, row
+                        )));
+                    }
+                    for (x, ch) in row.chars().enumerate() {
+                        if ch == '#' {
+                            blocked.insert(Coords { x: x as i32, y: y as i32 });
+                        }
+                    }
+                }
+                next += height;
+            }
+
             spaces.push(ProblemSpace {
                 width,
                 height,
                 shape_counts,
+                blocked,
             });
-            i += 1;
+            i = next;
         } else if line.is_empty() {
             // Skip empty lines
             i += 1;
         } else {
             // Unexpected line format
-            return Err(anyhow!("Line {}: unexpected format '{}'", i + 1, line));
+            return Err(AocError::parse_at(i + 1, format!("unexpected format '{}'", line)));
         }
     }
 
     Ok((shapes, spaces))
 }
 
-impl Shape {
-    fn get_cells(&self) -> Vec<Coords> {
-        let mut cells = Vec::new();
-        for (y, row) in self.grid.iter().enumerate() {
-            for (x, &ch) in row.iter().enumerate() {
-                if ch == '#' {
-                    cells.push(Coords { x: x as i32, y: y as i32 });
-                }
+
+/// Output format for drawn solutions, picked with `--render`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RenderMode {
+    /// Shape IDs as digits
+    Text,
+    /// Colored terminal output, one color per piece
+    Ansi,
+    /// One SVG file per solution in --render-dir
+    Svg,
+    /// One PNG file per solution in --render-dir
+    Png,
+}
+
+#[derive(Debug, Clone)]
+struct Render {
+    mode: RenderMode,
+    dir: String,
+}
+
+/// Pixels per board cell in SVG/PNG output
+const RENDER_CELL_SIZE: usize = 24;
+
+impl Render {
+    /// Draws a solution; `name` becomes the file stem for file formats
+    fn show(&self, solution: &[Placement], space: &ProblemSpace, name: &str, out: &dyn OutputSink) -> Result<()> {
+        let extension = match self.mode {
+            RenderMode::Text => {
+                visualize_solution(solution, space, out);
+                return Ok(());
+            }
+            RenderMode::Ansi => {
+                let grid = solution_grid(solution, space);
+                let label = |piece: usize| char::from_digit(solution[piece].shape_id as u32 % 36, 36).unwrap_or('?');
+                out.block(&render::to_ansi(&grid, theme::current(), label));
+                return Ok(());
             }
+            RenderMode::Svg => "svg",
+            RenderMode::Png => "png",
+        };
+
+        fs::create_dir_all(&self.dir).map_err(|e| AocError::io(&self.dir, e))?;
+        let path = format!("{}/{}.{}", self.dir, name, extension);
+        let grid = solution_grid(solution, space);
+        if self.mode == RenderMode::Svg {
+            fs::write(&path, render::to_svg(&grid, RENDER_CELL_SIZE)).map_err(|e| AocError::io(&path, e))?;
+        } else {
+            render::write_png(&grid, RENDER_CELL_SIZE, &path)?;
         }
-        cells
+        out.line(&format!("Wrote {}", path));
+        Ok(())
     }
+}
 
-    fn rotate_90(cells: &[Coords]) -> Vec<Coords> {
-        cells.iter().map(|c| Coords { x: -c.y, y: c.x }).collect()
+/// The board with every piece of `solution` as its own region
+fn solution_grid(solution: &[Placement], space: &ProblemSpace) -> RegionGrid {
+    let mut grid = RegionGrid::new(space.width, space.height);
+    for cell in &space.blocked {
+        grid.set(cell.x as usize, cell.y as usize, render::Cell::Blocked);
     }
+    for (piece, placement) in solution.iter().enumerate() {
+        for cell in &placement.cells {
+            grid.set(cell.x as usize, cell.y as usize, render::Cell::Region(piece));
+        }
+    }
+    grid
+}
 
-    fn flip_horizontal(cells: &[Coords]) -> Vec<Coords> {
-        cells.iter().map(|c| Coords { x: -c.x, y: c.y }).collect()
+fn visualize_solution(solution: &[Placement], space: &ProblemSpace, out: &dyn OutputSink) {
+    let mut grid = Grid::filled(space.width, space.height, '.');
+    for cell in &space.blocked {
+        grid.set(cell.x as usize, cell.y as usize, '#');
     }
 
-    fn normalize(cells: &[Coords]) -> Vec<Coords> {
-        if cells.is_empty() {
-            return Vec::new();
+    for placement in solution {
+        let symbol = (b'0' + placement.shape_id as u8) as char;
+        for cell in &placement.cells {
+            grid.set(cell.x as usize, cell.y as usize, symbol);
         }
-        let min_x = cells.iter().map(|c| c.x).min().unwrap();
-        let min_y = cells.iter().map(|c| c.y).min().unwrap();
-        let mut normalized: Vec<Coords> = cells
-            .iter()
-            .map(|c| Coords { x: c.x - min_x, y: c.y - min_y })
-            .collect();
-        normalized.sort_by_key(|c| (c.y, c.x));
-        normalized
     }
 
-    fn get_unique_transformations(&self) -> Vec<Vec<Coords>> {
-        let base_cells = self.get_cells();
-        let mut transformations = HashSet::new();
+    out.block(&theme::current().paint_grid(&grid, |&symbol| {
+        let style = match symbol {
+            '.' | '#' => Style::Muted,
+            shape => Style::Category((shape as u8 - b'0') as usize),
+        };
+        (symbol, style)
+    }));
+}
 
-        // Try all 4 rotations
-        let mut current = base_cells.clone();
-        for _ in 0..4 {
-            transformations.insert(Self::normalize(&current));
-            current = Self::rotate_90(&current);
-        }
 
-        // Try flipped + 4 rotations
-        let flipped = Self::flip_horizontal(&base_cells);
-        let mut current = flipped;
-        for _ in 0..4 {
-            transformations.insert(Self::normalize(&current));
-            current = Self::rotate_90(&current);
+/// Solves one part as `part` says: sequentially, optionally drawing each
+/// packing and the best partial ones, or on the rayon pool. With `export`,
+/// every packing found is added to it as `solution_json` describes.
+fn solve_part(
+    number: u8,
+    part: &PartConfig,
+    render: &Render,
+    cancel: &CancellationToken,
+    export: Option<&mut Vec<Value>>,
+    out: &dyn OutputSink,
+) -> Result<usize> {
+    let part_name = &format!("Part {}", number);
+    let (shapes, mut spaces) = parse_input(part.input.read()?)?;
+    if let Some(limit) = part.limit {
+        spaces.truncate(limit);
+    }
+    let mut packer = Packer::with_shapes(&shapes);
+    packer
+        .set_strategy(part.solver, part.timeout)
+        .set_cancellation(cancel.clone());
+
+    if part.parallel {
+        out.line(&format!("\n\nSolving ALL {} problems ({})...", part_name, part.solver.label()));
+        let total_start = Instant::now();
+        let found = export.is_some().then(|| Mutex::new(Vec::new()));
+        let SolveTally { solved, failed, infeasible, timed_out } =
+            solve_spaces_parallel(&packer, &spaces, part_name, out.progress().as_ref(), found.as_ref())?;
+        if let (Some(export), Some(found)) = (export, found) {
+            let mut found = found.into_inner().unwrap();
+            found.sort_by_key(|&(i, _)| i);
+            export.extend(found.iter().map(|(i, solution)| solution_json(packer.catalog(), i + 1, &spaces[*i], solution)));
         }
 
-        // HashSet automatically deduplicates, so symmetric shapes
-        // will have fewer transformations
-        transformations.into_iter().collect()
+        let theme = theme::current();
+        out.line(&format!("\n{}", theme.paint(Style::Heading, format!("========== {} Results ==========", part_name))));
+        out.line(&format!("Total problems: {}", spaces.len()));
+        out.answer(number, "Solved", &solved);
+        out.line(&format!("Failed: {} ({} ruled out before solving)", theme.paint(Style::Bad, failed + infeasible), infeasible));
+        if timed_out > 0 {
+            out.line(&format!("Timed out: {}", theme.paint(Style::Warning, timed_out)));
+        }
+        out.line(&format!("Total time: {:.2}s", total_start.elapsed().as_secs_f64()));
+        if solved > 0 {
+            out.line(&format!("Average per solved problem: {:.4}s", total_start.elapsed().as_secs_f64() / solved as f64));
+        }
+        return Ok(solved);
     }
 
-    fn count_cells(&self) -> usize {
-        self.grid.iter()
-            .flat_map(|row| row.iter())
-            .filter(|&&ch| ch == '#')
-            .count()
-    }
-}
+    let show_visualizations = part.visualize;
+    let narration: &dyn Reporter = if show_visualizations { out } else { &Silent };
+    out.line(&format!("\n========== {} ({}) ==========", part_name, part.solver.label()));
+    out.line(&format!("Parsed {} shapes", shapes.len()));
+    out.line(&format!("Parsed {} problem spaces", spaces.len()));
 
-fn generate_placements(
-    shape: &Shape,
-    instance: usize,
-    width: usize,
-    height: usize,
-) -> Vec<Placement> {
-    let mut placements = Vec::new();
-    let transformations = shape.get_unique_transformations();
-
-    for transform in &transformations {
-        for y in 0..height as i32 {
-            for x in 0..width as i32 {
-                let cells: Vec<Coords> = transform
-                    .iter()
-                    .map(|c| Coords { x: x + c.x, y: y + c.y })
-                    .collect();
-
-                if cells.iter().all(|c| c.x >= 0 && c.x < width as i32 && c.y >= 0 && c.y < height as i32) {
-                    placements.push(Placement {
-                        shape_id: shape.id,
-                        instance,
-                        x,
-                        y,
-                        cells,
-                    });
+    let mut solution_count = 0;
+    let mut export = export;
+    let progress: Box<dyn Progress> = if show_visualizations { Box::new(Hidden) } else { out.progress() };
+    progress.start(part_name, spaces.len() as u64);
+
+    for (i, space) in spaces.iter().enumerate() {
+        if show_visualizations {
+            out.line(&format!("\n----- Problem Space {} -----", i + 1));
+            out.line(&format!("Dimensions: {}x{}", space.width, space.height));
+            out.line(&format!("Shape counts: {:?}", space.shape_counts));
+        }
+
+        packer.set_board(space.clone());
+        let solved = match packer.solve_narrated(narration)? {
+            Attempt::Solved(solution) => {
+                solution_count += 1;
+                if let Some(export) = export.as_deref_mut() {
+                    export.push(solution_json(packer.catalog(), i + 1, space, &solution));
+                }
+                if show_visualizations {
+                    out.line("\nSolution visualization:");
+                    render.show(&solution, space, &format!("space-{}", i + 1), out)?;
+                }
+                true
+            }
+            Attempt::NoSolution => {
+                if show_visualizations {
+                    out.line("No solution found");
+                }
+                if part.explain {
+                    explain_space(&packer, i + 1, out)?;
+                }
+                false
+            }
+            Attempt::TimedOut => {
+                if show_visualizations {
+                    out.line("Gave up: every backend timed out");
+                }
+                false
+            }
+            Attempt::Infeasible(reason) => {
+                if show_visualizations {
+                    out.line(&format!("Infeasible ({})", reason));
+                }
+                if part.explain {
+                    explain_space(&packer, i + 1, out)?;
+                }
+                false
+            }
+        };
+
+        if let Some(objective) = part.maximize {
+            let coverage = packer.maximize(objective)?;
+            if show_visualizations {
+                out.line(&format!("Best coverage: {} / {} {}{}", coverage.score, coverage.upper_bound, objective.label(),
+                    if coverage.optimal { "" } else { " (timed out, may not be optimal)" }));
+                if !solved && !coverage.placements.is_empty() {
+                    render.show(&coverage.placements, space, &format!("space-{}-best", i + 1), out)?;
                 }
             }
         }
+        progress.set_message(&format!("{} solved", solution_count));
+        progress.inc(1);
     }
+    progress.finish();
 
-    placements
-}
+    let theme = theme::current();
+    out.line(&format!("\n{}", theme.paint(Style::Heading, format!("{} Summary", part_name))));
+    out.answer(number, &format!("Problem spaces solved, of {}", spaces.len()), &solution_count);
 
-fn solve_with_sat(
-    shapes: &[Shape],
-    space: &ProblemSpace,
-) -> Result<Option<Vec<Placement>>> {
-    solve_with_sat_verbose(shapes, space, false)
+    Ok(solution_count)
 }
 
-fn solve_with_sat_verbose(
-    shapes: &[Shape],
-    space: &ProblemSpace,
-    verbose: bool,
-) -> Result<Option<Vec<Placement>>> {
-    let mut all_placements = Vec::new();
-    let mut placement_to_var = HashMap::new();
-    let mut var_to_placement = HashMap::new();
-    let mut next_var = 1usize;
-
-    let total_pieces: usize = space.shape_counts.iter().sum();
-    if verbose {
-        println!("Generating placements for {} total pieces...", total_pieces);
-    }
-
-    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
-        if count == 0 {
-            continue;
-        }
+/// Prints the unsatisfiable core of the packer's board and whether Dancing
+/// Links agrees, to tell a genuinely impossible space from an encoding bug
+fn explain_space(packer: &Packer, number: usize, out: &dyn OutputSink) -> Result<()> {
+    let theme = theme::current();
+    let Some(core) = packer.explain()? else {
+        out.line(&theme.paint(Style::Warning, format!("Space {}: SAT packs it after all; the solver that gave up disagrees", number)));
+        return Ok(());
+    };
+    let verdict = match core.confirmed {
+        Some(true) => theme.paint(Style::Good, "Dancing Links agrees"),
+        Some(false) => theme.paint(Style::Bad, "but Dancing Links packs them, so the SAT encoding is wrong"),
+        None => theme.paint(Style::Warning, "Dancing Links timed out checking"),
+    };
+    out.line(&format!(
+        "Space {}: {} ({} of {} pieces, {} SAT calls); {}",
+        number,
+        core,
+        core.pieces(),
+        packer.board().shape_counts.iter().sum::<usize>(),
+        core.solves,
+        verdict
+    ));
+    Ok(())
+}
 
-        let shape = shapes.iter().find(|s| s.id == shape_idx)
-            .ok_or_else(|| anyhow!("Shape {} not found", shape_idx))?;
+/// Per-outcome space counts from `solve_spaces_parallel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SolveTally {
+    pub solved: usize,
+    /// A solver found no packing, or errored
+    pub failed: usize,
+    /// Ruled out by the feasibility checks without running a solver
+    pub infeasible: usize,
+    pub timed_out: usize,
+}
 
-        for instance in 0..count {
-            let placements = generate_placements(shape, instance, space.width, space.height);
-            if verbose {
-                println!("  Shape {} instance {}: {} possible placements", shape_idx, instance, placements.len());
+/// Packings found by parallel workers, each with the index of its space
+type FoundPackings = Mutex<Vec<(usize, Vec<Placement>)>>;
+
+/// Solves every space on the rayon pool, with workers reporting each
+/// finished space to `progress`; one space at a time while tracing. Fails with `AocError::Timeout` if the
+/// packer's cancellation token fires. Packings found go into `solutions`,
+/// if given, with the index of their space, in no particular order.
+fn solve_spaces_parallel(
+    packer: &Packer,
+    spaces: &[ProblemSpace],
+    part_name: &str,
+    progress: &dyn Progress,
+    solutions: Option<&FoundPackings>,
+) -> Result<SolveTally> {
+    let solved = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let infeasible = AtomicUsize::new(0);
+    let timed_out = AtomicUsize::new(0);
+    let cancelled = Mutex::new(None);
+
+    progress.start(part_name, spaces.len() as u64);
+    let solve_one = |(i, space): (usize, &ProblemSpace)| {
+        let mut packer = packer.clone();
+        packer.set_board(space.clone());
+        match packer.solve() {
+            Ok(Attempt::Solved(solution)) => {
+                if let Some(solutions) = solutions {
+                    solutions.lock().unwrap().push((i, solution));
+                }
+                solved.fetch_add(1, Ordering::Relaxed)
             }
-
-            for placement in placements {
-                let var = Var::from_index(next_var);
-                next_var += 1;
-                placement_to_var.insert(placement.clone(), var);
-                var_to_placement.insert(var, placement.clone());
-                all_placements.push(placement);
+            Ok(Attempt::NoSolution) => failed.fetch_add(1, Ordering::Relaxed),
+            Ok(Attempt::TimedOut) => timed_out.fetch_add(1, Ordering::Relaxed),
+            Ok(Attempt::Infeasible(_)) => infeasible.fetch_add(1, Ordering::Relaxed),
+            Err(err @ AocError::Timeout(_)) => {
+                cancelled.lock().unwrap().get_or_insert(err);
+                return;
             }
-        }
+            Err(_) => failed.fetch_add(1, Ordering::Relaxed),
+        };
+        let unsolved = failed.load(Ordering::Relaxed) + infeasible.load(Ordering::Relaxed);
+        progress.set_message(&format!("{} solved, {} failed", solved.load(Ordering::Relaxed), unsolved));
+        progress.inc(1);
+    };
+    // Interleaved events from several workers couldn't be replayed
+    if trace::enabled() {
+        spaces.iter().enumerate().for_each(solve_one);
+    } else {
+        spaces.par_iter().enumerate().for_each(solve_one);
     }
+    progress.finish();
 
-    if verbose {
-        println!("Total placements (variables): {}", all_placements.len());
+    if let Some(err) = cancelled.into_inner().unwrap() {
+        return Err(err);
     }
+    Ok(SolveTally {
+        solved: solved.into_inner(),
+        failed: failed.into_inner(),
+        infeasible: infeasible.into_inner(),
+        timed_out: timed_out.into_inner(),
+    })
+}
 
-    let mut formula = CnfFormula::new();
+/// Command-line options for day 12 (passed after the day number)
+#[derive(Parser, Debug)]
+#[command(name = "day12", about = "Day 12 present packing")]
+struct Options {
+    /// Input file for --enumerate [default: day12trees1.txt in the input directory]
+    #[arg(long)]
+    input: Option<String>,
+    /// Print up to N distinct packings for every problem space in --input
+    #[arg(long, value_name = "N")]
+    enumerate: Option<usize>,
+    /// With --enumerate, treat packings that are rotations or reflections of
+    /// each other (on the board) as one
+    #[arg(long, requires = "enumerate")]
+    distinct: bool,
+    /// Backend for every space: auto, sat, backtracking or dlx
+    #[arg(long, default_value = "auto")]
+    solver: Strategy,
+    /// Seconds before auto falls back to the next backend. The global
+    /// --timeout, before the day number, limits the whole run instead.
+    #[arg(long, value_name = "SECS", default_value_t = 10.0)]
+    backend_timeout: f64,
+    /// How solutions are drawn
+    #[arg(long, value_enum, default_value_t = RenderMode::Text)]
+    render: RenderMode,
+    /// Directory for --render svg/png files
+    #[arg(long, value_name = "DIR", default_value = "day12-render")]
+    render_dir: String,
+    /// Also report the best partial packing of each Part 1 space, maximizing
+    /// covered cells or placed pieces (uses --backend-timeout per space)
+    #[arg(long, value_name = "OBJECTIVE")]
+    maximize: Option<Objective>,
+    /// Name the pieces that make each unpackable Part 1 space impossible,
+    /// double-checked with Dancing Links (uses --backend-timeout per space)
+    #[arg(long)]
+    explain: bool,
+    /// Per-part settings (input, solver, timeout, drawing, limits) on top of
+    /// the options above; see `DayConfig::apply` for the format
+    #[arg(long, value_name = "FILE", conflicts_with = "enumerate")]
+    config: Option<String>,
+    /// Write every packing found, per part and space, as JSON to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "enumerate")]
+    export_solutions: Option<String>,
+    /// Instead of solving, check the packings in a file --export-solutions
+    /// wrote against the spaces they claim to pack
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["enumerate", "export_solutions"])]
+    check_solutions: Option<String>,
+}
 
-    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
-        if count == 0 {
-            continue;
-        }
+impl Options {
+    fn backend_timeout(&self) -> Result<Duration> {
+        Duration::try_from_secs_f64(self.backend_timeout)
+            .map_err(|_| AocError::usage(format!("Invalid --backend-timeout {}", self.backend_timeout)))
+    }
+}
 
-        for instance in 0..count {
-            let instance_placements: Vec<&Placement> = all_placements
-                .iter()
-                .filter(|p| p.shape_id == shape_idx && p.instance == instance)
-                .collect();
+/// Where a part reads its problem spaces from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartInput {
+    /// A file in the input directory, e.g. `trees1`
+    Asset(&'static str),
+    File(String),
+}
 
-            let vars: Vec<Lit> = instance_placements
-                .iter()
-                .map(|p| placement_to_var[p].positive())
-                .collect();
+impl PartInput {
+    fn read(&self) -> Result<&'static str> {
+        match self {
+            PartInput::Asset(name) => Input::for_day(12).read(name),
+            PartInput::File(path) => input::read_file(path),
+        }
+    }
+}
 
-            formula.add_clause(&vars);
+/// How one part is solved: what it reads, which backend runs, what gets
+/// drawn and how much work it may do
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartConfig {
+    pub input: PartInput,
+    pub solver: Strategy,
+    /// Per space, before auto falls back to the next backend
+    pub timeout: Duration,
+    /// Print every space and draw its packing
+    pub visualize: bool,
+    /// Solve the spaces on the rayon pool, without drawing or maximizing
+    pub parallel: bool,
+    pub maximize: Option<Objective>,
+    /// Report which pieces make each unpackable space impossible
+    pub explain: bool,
+    /// Solve only the first N spaces
+    pub limit: Option<usize>,
+}
 
-            for i in 0..vars.len() {
-                for j in i + 1..vars.len() {
-                    formula.add_clause(&[!vars[i], !vars[j]]);
-                }
-            }
+impl PartConfig {
+    pub fn new(input: PartInput) -> Self {
+        PartConfig {
+            input,
+            solver: Strategy::Auto,
+            timeout: Duration::from_secs(10),
+            visualize: false,
+            parallel: false,
+            maximize: None,
+            explain: false,
+            limit: None,
         }
     }
 
-    let mut cell_to_placements: HashMap<Coords, Vec<Var>> = HashMap::new();
-    for (placement, &var) in &placement_to_var {
-        for &cell in &placement.cells {
-            cell_to_placements.entry(cell).or_insert_with(Vec::new).push(var);
-        }
+    pub fn set_solver(&mut self, solver: Strategy, timeout: Duration) -> &mut Self {
+        self.solver = solver;
+        self.timeout = timeout;
+        self
     }
 
-    if verbose {
-        println!("Encoding grid cell constraints...");
+    pub fn set_visualize(&mut self, visualize: bool) -> &mut Self {
+        self.visualize = visualize;
+        self
     }
-    for (_cell, vars) in &cell_to_placements {
-        for i in 0..vars.len() {
-            for j in i + 1..vars.len() {
-                formula.add_clause(&[!vars[i].positive(), !vars[j].positive()]);
-            }
-        }
+
+    pub fn set_parallel(&mut self, parallel: bool) -> &mut Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn set_maximize(&mut self, maximize: Option<Objective>) -> &mut Self {
+        self.maximize = maximize;
+        self
     }
 
-    if verbose {
-        println!("Solving SAT problem with {} variables and {} clauses...", next_var - 1, formula.len());
+    pub fn set_explain(&mut self, explain: bool) -> &mut Self {
+        self.explain = explain;
+        self
     }
 
-    let mut solver = Solver::new();
-    solver.add_formula(&formula);
+    pub fn set_limit(&mut self, limit: Option<usize>) -> &mut Self {
+        self.limit = limit;
+        self
+    }
 
-    if solver.solve().unwrap() {
-        if verbose {
-            println!("Solution found!");
+    fn validate(&self, part_name: &str) -> Result<()> {
+        if self.parallel && (self.visualize || self.maximize.is_some() || self.explain) {
+            return Err(AocError::usage(format!("{}: parallel solving can't visualize, maximize or explain", part_name)));
         }
-        let model = solver.model().unwrap();
-        let solution: Vec<Placement> = model
-            .iter()
-            .filter_map(|&lit| {
-                if lit.is_positive() {
-                    var_to_placement.get(&lit.var()).cloned()
-                } else {
-                    None
-                }
-            })
-            .collect();
+        Ok(())
+    }
 
-        Ok(Some(solution))
-    } else {
-        if verbose {
-            println!("No solution exists");
+    /// Sets `key` from a config file value
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let flag = |value: &str| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(AocError::parse(format!("Expected true or false for '{}', got '{}'", key, value))),
+        };
+        match key {
+            "input" => self.input = PartInput::File(value.to_string()),
+            "solver" => self.solver = value.parse()?,
+            "timeout" => {
+                self.timeout = value
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| AocError::parse(format!("Invalid timeout '{}'", value)))?;
+            }
+            "visualize" => self.visualize = flag(value)?,
+            "parallel" => self.parallel = flag(value)?,
+            "maximize" => self.maximize = if value == "none" { None } else { Some(value.parse()?) },
+            "explain" => self.explain = flag(value)?,
+            "limit" => {
+                self.limit = Some(value.parse().map_err(|e| AocError::parse(format!("Invalid limit '{}': {}", value, e)))?);
+            }
+            _ => return Err(AocError::parse(format!("Unknown setting '{}'", key))),
         }
-        Ok(None)
+        Ok(())
     }
 }
 
-fn visualize_solution(solution: &[Placement], width: usize, height: usize) {
-    let mut grid = vec![vec!['.'; width]; height];
+/// Both parts' settings. The defaults are the puzzle's: Part 1 drawn space
+/// by space, Part 2 solved in parallel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayConfig {
+    pub part1: PartConfig,
+    pub part2: PartConfig,
+}
 
-    for placement in solution {
-        let symbol = (b'0' + placement.shape_id as u8) as char;
-        for cell in &placement.cells {
-            grid[cell.y as usize][cell.x as usize] = symbol;
-        }
+impl DayConfig {
+    pub fn new(solver: Strategy, timeout: Duration, maximize: Option<Objective>) -> Self {
+        let mut part1 = PartConfig::new(PartInput::Asset("trees1"));
+        part1.set_solver(solver, timeout).set_visualize(true).set_maximize(maximize);
+        let mut part2 = PartConfig::new(PartInput::Asset("trees2"));
+        part2.set_solver(solver, timeout).set_parallel(true);
+        DayConfig { part1, part2 }
     }
 
-    for row in grid {
-        println!("{}", row.iter().collect::<String>());
+    /// Applies a config file: `key = value` lines under `[part1]` or
+    /// `[part2]`, or before either to set both. Keys are `input` (a file
+    /// path), `solver`, `timeout` (seconds), `visualize`, `parallel`,
+    /// `maximize` (an objective or `none`), `explain` and `limit` (spaces
+    /// to solve).
+    /// Values may be quoted and `#` starts a comment.
+    pub fn apply(&mut self, text: &str) -> Result<()> {
+        let mut section: Option<usize> = None;
+        for (line_no, line) in parse::lines(text) {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                section = match name.trim() {
+                    "part1" => Some(1),
+                    "part2" => Some(2),
+                    other => return Err(AocError::parse_at(line_no, format!("Unknown section '[{}]'", other))),
+                };
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| AocError::parse_at(line_no, format!("Expected 'key = value', got '{}'", line)))?;
+            let value = value.trim().trim_matches('"');
+            let parts = match section {
+                Some(1) => vec![&mut self.part1],
+                Some(_) => vec![&mut self.part2],
+                None => vec![&mut self.part1, &mut self.part2],
+            };
+            for part in parts {
+                part.set(key.trim(), value).map_err(|e| e.on_line(line_no))?;
+            }
+        }
+        Ok(())
     }
 }
 
-fn solve_with_backtracking(
-    shapes: &[Shape],
-    space: &ProblemSpace,
-) -> Result<Option<Vec<Placement>>> {
-    let width = space.width;
-    let height = space.height;
-    let mut grid = vec![vec![None; width]; height];
-
-    let mut pieces_to_place = Vec::new();
-    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
-        for instance in 0..count {
-            let shape = shapes.iter().find(|s| s.id == shape_idx)
-                .ok_or_else(|| anyhow!("Shape {} not found", shape_idx))?;
-
-            pieces_to_place.push((shape_idx, instance, shape.clone()));
+fn run_enumerate(input: &str, limit: usize, distinct: bool, render: &Render, out: &dyn OutputSink) -> Result<()> {
+    let (shapes, spaces) = parse_input(input)?;
+    let mut packer = Packer::with_shapes(&shapes);
+
+    for (i, space) in spaces.iter().enumerate() {
+        out.line(&format!("\n----- Problem Space {} ({}x{}, counts {:?}) -----",
+            i + 1, space.width, space.height, space.shape_counts));
+
+        packer.set_board(space.clone());
+        let solutions = if distinct { packer.enumerate_distinct(limit)? } else { packer.enumerate(limit)? };
+        for (n, solution) in solutions.iter().enumerate() {
+            out.line(&format!("\nPacking {}:", n + 1));
+            render.show(solution, space, &format!("space-{}-packing-{}", i + 1, n + 1), out)?;
+        }
+
+        let kind = if distinct { "packings distinct up to symmetry" } else { "packings" };
+        if solutions.len() == limit {
+            out.line(&format!("\nShowing the first {} {} (there may be more)", limit, kind));
+        } else {
+            out.line(&format!("\n{} {} in total", solutions.len(), kind));
         }
     }
 
-    // Sort by most constrained first (fewest unique transformations, then largest size)
-    pieces_to_place.sort_by_key(|(_, _, shape)| {
-        let num_transforms = shape.get_unique_transformations().len();
-        let num_cells = shape.count_cells();
-        // Prioritize: fewest transformations first, then most cells
-        (num_transforms, -(num_cells as i32))
+    Ok(())
+}
+
+/// One solved space for `--export-solutions`: its 1-based number and size,
+/// then every piece with its shape, instance, orientation (an index into
+/// the shape's sorted unique orientations), offset and covered cells
+fn solution_json(catalog: &ShapeCatalog, number: usize, space: &ProblemSpace, solution: &[Placement]) -> Value {
+    let placements = solution.iter().map(|placement| {
+        let transform = polyomino::orientation_index(catalog, placement).map_or(Value::Null, Value::from);
+        let cells = placement.cells.iter().map(|c| Value::from(vec![c.x, c.y])).collect();
+        Value::object([
+            ("shape", Value::from(placement.shape_id)),
+            ("instance", Value::from(placement.instance)),
+            ("transform", transform),
+            ("x", Value::from(placement.x)),
+            ("y", Value::from(placement.y)),
+            ("cells", Value::Array(cells)),
+        ])
     });
+    Value::object([
+        ("space", Value::from(number)),
+        ("width", Value::from(space.width)),
+        ("height", Value::from(space.height)),
+        ("placements", Value::Array(placements.collect())),
+    ])
+}
 
-    let mut solution = Vec::new();
-
-    if backtrack_optimized(
-        &pieces_to_place,
-        0,
-        &mut grid,
-        width,
-        height,
-        &mut solution,
-        shapes,
-    ) {
-        Ok(Some(solution))
-    } else {
-        Ok(None)
+/// A packing read back from an `--export-solutions` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedSolution {
+    /// 1-based, as in the file
+    pub space: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Each piece with the orientation index the file gave it
+    pub placements: Vec<(usize, Placement)>,
+}
+
+impl ExportedSolution {
+    fn from_json(value: &Value) -> Result<Self> {
+        let space = value.field("space")?.as_usize()?;
+        let (width, height) = (value.field("width")?.as_usize()?, value.field("height")?.as_usize()?);
+        let placements = value
+            .field("placements")?
+            .as_array()?
+            .iter()
+            .map(|piece| {
+                let cells = piece
+                    .field("cells")?
+                    .as_array()?
+                    .iter()
+                    .map(|cell| match cell.as_array()? {
+                        [x, y] => Ok(Coords { x: x.as_i32()?, y: y.as_i32()? }),
+                        _ => Err(AocError::parse(format!("Expected an [x, y] cell, got {}", cell))),
+                    })
+                    .collect::<Result<_>>()?;
+                let placement = Placement {
+                    shape_id: piece.field("shape")?.as_usize()?,
+                    instance: piece.field("instance")?.as_usize()?,
+                    x: piece.field("x")?.as_i32()?,
+                    y: piece.field("y")?.as_i32()?,
+                    cells,
+                };
+                Ok((piece.field("transform")?.as_usize()?, placement))
+            })
+            .collect::<Result<_>>()?;
+        Ok(ExportedSolution { space, width, height, placements })
+    }
+
+    /// Re-validates the packing against `space`, which must be the one it
+    /// was exported for: the sizes match, each piece's orientation index
+    /// names the orientation its cells have, and `polyomino::check_solution`
+    /// accepts it
+    pub fn check(&self, catalog: &ShapeCatalog, space: &ProblemSpace) -> Result<()> {
+        if (self.width, self.height) != (space.width, space.height) {
+            return Err(AocError::solve(format!(
+                "Exported for a {}x{} space, but space {} is {}x{}",
+                self.width, self.height, self.space, space.width, space.height
+            )));
+        }
+        for (transform, placement) in &self.placements {
+            if polyomino::orientation_index(catalog, placement) != Some(*transform) {
+                return Err(AocError::solve(format!(
+                    "Shape {} instance {} isn't in orientation {}",
+                    placement.shape_id, placement.instance, transform
+                )));
+            }
+        }
+        let solution: Vec<Placement> = self.placements.iter().map(|(_, placement)| placement.clone()).collect();
+        polyomino::check_solution(catalog, space, &solution)
     }
 }
 
-fn can_place_cells(cells: &[Coords], grid: &[Vec<Option<usize>>]) -> bool {
-    cells.iter().all(|c| grid[c.y as usize][c.x as usize].is_none())
+/// Reads an `--export-solutions` file: `{"part1": [...], "part2": [...]}`,
+/// each list holding one object per solved space as `solution_json` writes
+pub fn parse_solutions(text: &str) -> Result<[Vec<ExportedSolution>; 2]> {
+    let document = json::parse(text)?;
+    let part = |key| -> Result<Vec<ExportedSolution>> {
+        match document.get(key) {
+            Some(spaces) => spaces.as_array()?.iter().map(ExportedSolution::from_json).collect(),
+            None => Ok(Vec::new()),
+        }
+    };
+    Ok([part("part1")?, part("part2")?])
 }
 
-fn place_cells(cells: &[Coords], grid: &mut [Vec<Option<usize>>], piece_id: usize) {
-    for cell in cells {
-        grid[cell.y as usize][cell.x as usize] = Some(piece_id);
+fn run_check_solutions(path: &str, config: &DayConfig, out: &dyn OutputSink) -> Result<()> {
+    let exported = parse_solutions(input::read_file(path)?).map_err(|e| e.in_file(path))?;
+    let theme = theme::current();
+    let (mut checked, mut failed) = (0, 0);
+    for ((part_name, part), solutions) in [("Part 1", &config.part1), ("Part 2", &config.part2)].into_iter().zip(&exported) {
+        let (shapes, spaces) = parse_input(part.input.read()?)?;
+        let catalog = ShapeCatalog::new(&shapes);
+        for solution in solutions {
+            let result = match solution.space.checked_sub(1).and_then(|i| spaces.get(i)) {
+                Some(space) => solution.check(&catalog, space),
+                None => Err(AocError::solve(format!("There are only {} spaces", spaces.len()))),
+            };
+            checked += 1;
+            if let Err(err) = result {
+                failed += 1;
+                out.line(&format!("{} space {}: {}", part_name, solution.space, theme.paint(Style::Bad, err)));
+            }
+        }
     }
-}
 
-fn remove_cells(cells: &[Coords], grid: &mut [Vec<Option<usize>>]) {
-    for cell in cells {
-        grid[cell.y as usize][cell.x as usize] = None;
+    if failed > 0 {
+        return Err(AocError::solve(format!("{} of {} exported packings don't hold", failed, checked)));
     }
+    out.line(&format!("{} {} exported packings hold", theme.paint(Style::Good, "OK:"), checked));
+    Ok(())
 }
 
-fn find_first_empty(grid: &[Vec<Option<usize>>], width: usize, height: usize) -> Option<(usize, usize)> {
-    for y in 0..height {
-        for x in 0..width {
-            if grid[y][x].is_none() {
-                return Some((x, y));
-            }
+/// Number of regions in `input` that can fit all of their presents, each
+/// tried with the default `Packer` strategy and time budget
+pub fn count_solvable(input: &str) -> Result<usize> {
+    let (shapes, spaces) = parse_input(input)?;
+    let mut packer = Packer::with_shapes(&shapes);
+    let mut solved = 0;
+    for space in spaces {
+        packer.set_board(space);
+        if matches!(packer.solve()?, Attempt::Solved(_)) {
+            solved += 1;
         }
     }
-    None
+    Ok(solved)
 }
 
-fn count_empty_cells(grid: &[Vec<Option<usize>>]) -> usize {
-    grid.iter()
-        .flat_map(|row| row.iter())
-        .filter(|cell| cell.is_none())
-        .count()
+pub fn part1(input: &str) -> Result<usize> {
+    count_solvable(input)
 }
 
-fn count_remaining_cells(pieces: &[(usize, usize, Shape)], start_idx: usize) -> usize {
-    pieces[start_idx..]
-        .iter()
-        .map(|(_, _, shape)| shape.count_cells())
-        .sum()
+pub fn part2(input: &str) -> Result<usize> {
+    count_solvable(input)
 }
 
-fn backtrack_optimized(
-    pieces: &[(usize, usize, Shape)],
-    piece_idx: usize,
-    grid: &mut [Vec<Option<usize>>],
-    width: usize,
-    height: usize,
-    solution: &mut Vec<Placement>,
-    _shapes: &[Shape],
-) -> bool {
-    if piece_idx == pieces.len() {
-        return true;
+pub fn metadata() -> DayMetadata {
+    DayMetadata {
+        title: "Christmas Tree Farm",
+        input_files: &["trees1", "trees2"],
+        parts: [
+            "Regions of the small input that fit all of their presents",
+            "Regions of the full input that fit all of their presents",
+        ],
+        notes: "Honours --timeout and --trace; --backend-timeout is the budget per solver backend; --config sets each part's solver and limits; --explain names why a space can't be packed; \
+                --export-solutions writes the packings as JSON and --check-solutions re-validates them",
     }
+}
 
-    // Early failure detection: check if we have enough space for remaining pieces
-    let empty_cells = count_empty_cells(grid);
-    let remaining_cells = count_remaining_cells(pieces, piece_idx);
-
-    if empty_cells < remaining_cells {
-        // Not enough space - prune this branch
-        return false;
+pub fn run(args: &[String], cancel: &CancellationToken, out: &dyn OutputSink) -> Result<()> {
+    let options = Options::parse_from(std::iter::once("day12".to_string()).chain(args.iter().cloned()));
+    let render = Render { mode: options.render, dir: options.render_dir.clone() };
+    if let Some(limit) = options.enumerate {
+        let input = match &options.input {
+            Some(path) => input::read_file(path)?,
+            None => Input::for_day(12).read("trees1")?,
+        };
+        return run_enumerate(input, limit, options.distinct, &render, out);
     }
 
-    let (shape_id, instance, shape) = &pieces[piece_idx];
+    let mut config = DayConfig::new(options.solver, options.backend_timeout()?, options.maximize);
+    config.part1.set_explain(options.explain);
+    if let Some(path) = &options.config {
+        config.apply(input::read_file(path)?).map_err(|e| e.in_file(path))?;
+    }
+    config.part1.validate("Part 1")?;
+    config.part2.validate("Part 2")?;
+    if let Some(path) = &options.check_solutions {
+        return run_check_solutions(path, &config, out);
+    }
 
-    let transformations = shape.get_unique_transformations();
+    // Analyze shape symmetries
+    let (shapes, _) = parse_input(config.part2.input.read()?)?;
+    let packer = Packer::with_shapes(&shapes);
+    out.line("Analyzing shape symmetries for Part 2:");
+    for shape in &shapes {
+        out.line(&format!("  Shape {}: {} cells, {} unique transformations (out of 8 possible)",
+            shape.id, shape.count_cells(), packer.catalog().transforms[shape.id].len()));
+    }
 
-    for transform in &transformations {
-        for y in 0..height as i32 {
-            for x in 0..width as i32 {
-                let cells: Vec<Coords> = transform
-                    .iter()
-                    .map(|c| Coords { x: x + c.x, y: y + c.y })
-                    .collect();
-
-                if cells.iter().all(|c| {
-                    c.x >= 0 && c.x < width as i32 &&
-                    c.y >= 0 && c.y < height as i32
-                }) && can_place_cells(&cells, grid) {
-                    let placement = Placement {
-                        shape_id: *shape_id,
-                        instance: *instance,
-                        x,
-                        y,
-                        cells: cells.clone(),
-                    };
-
-                    place_cells(&cells, grid, piece_idx);
-                    solution.push(placement);
-
-                    if backtrack_optimized(pieces, piece_idx + 1, grid, width, height, solution, _shapes) {
-                        return true;
-                    }
+    let export = options.export_solutions.is_some();
+    let (mut solutions1, mut solutions2) = (Vec::new(), Vec::new());
+    stats::measure("Part 1", || solve_part(1, &config.part1, &render, cancel, export.then_some(&mut solutions1), out))?;
+    stats::measure("Part 2", || solve_part(2, &config.part2, &render, cancel, export.then_some(&mut solutions2), out))?;
 
-                    solution.pop();
-                    remove_cells(&cells, grid);
-                }
-            }
-        }
+    if let Some(path) = &options.export_solutions {
+        let count = solutions1.len() + solutions2.len();
+        let document = Value::object([("part1", Value::Array(solutions1)), ("part2", Value::Array(solutions2))]);
+        fs::write(path, format!("{}\n", document)).map_err(|e| AocError::io(path, e))?;
+        out.line(&format!("\nWrote {} packings to {}", count, path));
     }
-
-    false
+    Ok(())
 }
 
-fn solve_part(filename: &str, part_name: &str, show_visualizations: bool) -> Result<usize> {
-    let (shapes, spaces) = parse_input(filename)?;
-
-    println!("\n========== {} ==========", part_name);
-    println!("Parsed {} shapes", shapes.len());
-    println!("Parsed {} problem spaces", spaces.len());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::snapshot;
+    use crate::util::polyomino::*;
+    use std::collections::HashMap;
 
-    let mut solution_count = 0;
+    #[test]
+    fn test_part1_has_two_solutions() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
 
-    for (i, space) in spaces.iter().enumerate() {
-        if show_visualizations {
-            println!("\n----- Problem Space {} -----", i + 1);
-            println!("Dimensions: {}x{}", space.width, space.height);
-            println!("Shape counts: {:?}", space.shape_counts);
-        } else {
-            print!("\rSolving space {}/{} ({} solved so far)...", i + 1, spaces.len(), solution_count);
-            use std::io::Write;
-            std::io::stdout().flush().ok();
-        }
+        let mut solution_count = 0;
 
-        match solve_with_sat_verbose(&shapes, space, show_visualizations)? {
-            Some(solution) => {
+        for space in &spaces {
+            if let Some(_solution) = solve_with_sat(&catalog, space).unwrap() {
                 solution_count += 1;
-                if show_visualizations {
-                    println!("\nSolution visualization:");
-                    visualize_solution(&solution, space.width, space.height);
-                }
-            }
-            None => {
-                if show_visualizations {
-                    println!("No solution found");
-                }
             }
         }
-    }
 
-    if !show_visualizations {
-        println!();
+        assert_eq!(solution_count, 2, "Part 1 should have exactly 2 solutions");
     }
 
-    println!("\n{} Summary: {} / {} problem spaces solved", part_name, solution_count, spaces.len());
+    #[test]
+    fn test_dlx_part1_has_two_solutions() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
 
-    Ok(solution_count)
-}
+        let solution_count = spaces
+            .iter()
+            .filter(|space| solve_with_dlx(&catalog, space).unwrap().is_some())
+            .count();
 
-fn solve_part_backtracking(filename: &str, part_name: &str, show_visualizations: bool) -> Result<usize> {
-    let (shapes, spaces) = parse_input(filename)?;
+        assert_eq!(solution_count, 2, "DLX should agree with SAT on Part 1");
+    }
 
-    println!("\n========== {} (Backtracking) ==========", part_name);
-    println!("Parsed {} shapes", shapes.len());
-    println!("Parsed {} problem spaces", spaces.len());
+    #[test]
+    fn test_dlx_part2_has_481_solutions() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees2").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
 
-    let mut solution_count = 0;
+        let solution_count = spaces
+            .iter()
+            .filter(|space| solve_with_dlx(&catalog, space).unwrap().is_some())
+            .count();
 
-    for (i, space) in spaces.iter().enumerate() {
-        if show_visualizations {
-            println!("\n----- Problem Space {} -----", i + 1);
-            println!("Dimensions: {}x{}", space.width, space.height);
-            println!("Shape counts: {:?}", space.shape_counts);
-        } else {
-            print!("\rSolving space {}/{} ({} solved so far)...", i + 1, spaces.len(), solution_count);
-            use std::io::Write;
-            std::io::stdout().flush().ok();
-        }
+        snapshot::check("day12", "part2_solved", solution_count);
+    }
 
-        match solve_with_backtracking(&shapes, space)? {
-            Some(solution) => {
-                solution_count += 1;
-                if show_visualizations {
-                    println!("\nSolution visualization:");
-                    visualize_solution(&solution, space.width, space.height);
-                }
-            }
-            None => {
-                if show_visualizations {
-                    println!("No solution found");
+    #[test]
+    fn test_dlx_solution_is_non_overlapping() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        for space in &spaces {
+            if let Some(solution) = solve_with_dlx(&catalog, space).unwrap() {
+                let pieces: usize = space.shape_counts.iter().sum();
+                assert_eq!(solution.len(), pieces);
+
+                let mut seen = HashSet::new();
+                for placement in &solution {
+                    for cell in &placement.cells {
+                        assert!(seen.insert(*cell), "cell {:?} covered twice", cell);
+                    }
                 }
             }
         }
     }
 
-    if !show_visualizations {
-        println!();
+    #[test]
+    fn test_parallel_solving_matches_sequential() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+
+        let mut packer = Packer::with_shapes(&shapes);
+        packer.set_strategy(Strategy::Fixed(SolverKind::Dlx), Duration::MAX);
+        let tally = solve_spaces_parallel(&packer, &spaces, "Part 1", &Hidden, None).unwrap();
+
+        assert_eq!(tally, SolveTally { solved: 2, failed: spaces.len() - 2, infeasible: 0, timed_out: 0 });
     }
 
-    println!("\n{} Summary: {} / {} problem spaces solved", part_name, solution_count, spaces.len());
+    #[test]
+    fn test_exported_solutions_round_trip() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let mut packer = Packer::with_shapes(&shapes);
+        packer.set_strategy(Strategy::Fixed(SolverKind::Dlx), Duration::MAX);
+        let found = Mutex::new(Vec::new());
+        solve_spaces_parallel(&packer, &spaces, "Part 1", &Hidden, Some(&found)).unwrap();
+        let found = found.into_inner().unwrap();
+        assert_eq!(found.len(), 2);
+
+        let exported: Vec<Value> = found
+            .iter()
+            .map(|(i, solution)| solution_json(packer.catalog(), i + 1, &spaces[*i], solution))
+            .collect();
+        let text = Value::object([("part1", Value::Array(exported))]).to_string();
+        let [part1, part2] = parse_solutions(&text).unwrap();
+        assert!(part2.is_empty());
+        for (solution, (i, placements)) in part1.iter().zip(&found) {
+            assert_eq!(solution.space, i + 1);
+            assert_eq!(solution.placements.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>(), *placements);
+            solution.check(packer.catalog(), &spaces[*i]).unwrap();
+        }
 
-    Ok(solution_count)
-}
+        let mut turned = part1[0].clone();
+        turned.placements[0].0 += 1;
+        assert!(turned.check(packer.catalog(), &spaces[found[0].0]).unwrap_err().to_string().contains("orientation"));
+        let other = spaces.iter().find(|space| space.width != spaces[found[0].0].width).unwrap();
+        assert!(part1[0].check(packer.catalog(), other).unwrap_err().to_string().contains("Exported for"));
+        assert!(parse_solutions(r#"{"part1": [{"space": 1}]}"#).unwrap_err().to_string().contains("'width'"));
+    }
 
-/// Day 12: Exercise description
-pub fn run() -> Result<()> {
-    // Analyze shape symmetries
-    let (shapes, spaces) = parse_input("assets/day12trees2.txt")?;
-    println!("Analyzing shape symmetries for Part 2:");
-    for shape in &shapes {
-        let transformations = shape.get_unique_transformations();
-        println!("  Shape {}: {} cells, {} unique transformations (out of 8 possible)",
-            shape.id, shape.count_cells(), transformations.len());
+    #[test]
+    fn test_enumerated_solutions_are_distinct() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let space = &spaces[0];
+
+        let solutions = enumerate_solutions(&catalog, space, 5).unwrap();
+        assert!(!solutions.is_empty());
+
+        let as_sets: HashSet<Vec<Vec<Coords>>> = solutions
+            .iter()
+            .map(|solution| {
+                let mut cells: Vec<Vec<Coords>> = solution.iter().map(|p| p.cells.clone()).collect();
+                cells.sort_by_key(|c| c.iter().map(|c| (c.y, c.x)).collect::<Vec<_>>());
+                cells
+            })
+            .collect();
+        assert_eq!(as_sets.len(), solutions.len());
     }
 
-    println!("\n\nUsing SAT solver for Part 1 (small problems)...");
-    solve_part("assets/day12trees1.txt", "Part 1", true)?;
+    #[test]
+    fn test_options_enumerate() {
+        let parse = |args: &[&str]| Options::try_parse_from(std::iter::once("day12").chain(args.iter().copied()));
 
-    println!("\n\nSolving ALL Part 2 problems with backtracking + early pruning...");
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.enumerate, None);
 
-    use std::time::Instant;
-    let total_start = Instant::now();
-    let mut solved = 0;
-    let mut failed = 0;
+        let options = parse(&["--enumerate", "3", "--input", "x.txt"]).unwrap();
+        assert_eq!(options.enumerate, Some(3));
+        assert_eq!(options.input.as_deref(), Some("x.txt"));
 
-    for (i, space) in spaces.iter().enumerate() {
-        if (i + 1) % 100 == 0 || i < 10 {
-            print!("\rProgress: {}/{} ({} solved, {} failed)", i + 1, spaces.len(), solved, failed);
-            std::io::Write::flush(&mut std::io::stdout()).ok();
-        }
+        assert!(parse(&["--enumerate", "many"]).is_err());
+        assert!(parse(&["--enumerate", "3", "--distinct"]).unwrap().distinct);
+        assert!(parse(&["--distinct"]).is_err());
+    }
 
-        match solve_with_backtracking(&shapes, space) {
-            Ok(Some(_)) => solved += 1,
-            Ok(None) => failed += 1,
-            Err(_) => failed += 1,
-        }
+    #[test]
+    fn test_backtracking_part1_has_two_solutions() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        let solution_count = spaces
+            .iter()
+            .filter(|space| solve_with_backtracking(&catalog, space).unwrap().is_some())
+            .count();
+
+        assert_eq!(solution_count, 2, "Backtracking should agree with SAT on Part 1");
     }
 
-    println!("\n\n========== Part 2 Results ==========");
-    println!("Total problems: {}", spaces.len());
-    println!("Solved: {}", solved);
-    println!("Failed: {}", failed);
-    println!("Total time: {:.2}s", total_start.elapsed().as_secs_f64());
-    if solved > 0 {
-        println!("Average per solved problem: {:.4}s", total_start.elapsed().as_secs_f64() / solved as f64);
+    #[test]
+    fn test_auto_strategy_part1() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        let solved = spaces
+            .iter()
+            .filter(|space| {
+                let attempt = solve_with_strategy(Strategy::Auto, &catalog, space, Duration::from_secs(60), &Silent, &CancellationToken::new());
+                matches!(attempt.unwrap(), Attempt::Solved(_))
+            })
+            .count();
+
+        assert_eq!(solved, 2);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_options_solver() {
+        let parse = |args: &[&str]| Options::try_parse_from(std::iter::once("day12").chain(args.iter().copied()));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.solver, Strategy::Auto);
+        assert_eq!(options.backend_timeout, 10.0);
+
+        let options = parse(&["--solver", "dlx", "--backend-timeout", "0.5"]).unwrap();
+        assert_eq!(options.solver, Strategy::Fixed(SolverKind::Dlx));
+        assert_eq!(options.backend_timeout, 0.5);
+        // --timeout is the global limit, parsed before the day's options
+        assert!(parse(&["--timeout", "0.5"]).is_err());
+
+        assert!(parse(&["--solver", "magic"]).is_err());
+    }
 
     #[test]
-    fn test_part1_has_two_solutions() {
-        let (shapes, spaces) = parse_input("assets/day12trees1.txt").unwrap();
+    fn test_options_render() {
+        let parse = |args: &[&str]| Options::try_parse_from(std::iter::once("day12").chain(args.iter().copied()));
+
+        assert_eq!(parse(&[]).unwrap().render, RenderMode::Text);
+        let options = parse(&["--render", "png", "--render-dir", "out"]).unwrap();
+        assert_eq!(options.render, RenderMode::Png);
+        assert_eq!(options.render_dir, "out");
+        assert!(parse(&["--render", "gif"]).is_err());
+    }
 
-        let mut solution_count = 0;
+    #[test]
+    fn test_options_maximize() {
+        let parse = |args: &[&str]| Options::try_parse_from(std::iter::once("day12").chain(args.iter().copied()));
 
-        for space in &spaces {
-            if let Some(_solution) = solve_with_sat(&shapes, space).unwrap() {
-                solution_count += 1;
+        assert_eq!(parse(&[]).unwrap().maximize, None);
+        assert_eq!(parse(&["--maximize", "pieces"]).unwrap().maximize, Some(Objective::Pieces));
+        let err = parse(&["--maximize", "area"]).unwrap_err().to_string();
+        assert!(err.contains("[possible values: cells, pieces]"), "{}", err);
+    }
+
+    #[test]
+    fn test_day_config() {
+        let mut config = DayConfig::new(Strategy::Auto, Duration::from_secs(10), None);
+        assert_eq!((config.part1.visualize, config.part1.parallel), (true, false));
+        assert_eq!((config.part2.visualize, config.part2.parallel), (false, true));
+        assert_eq!(config.part2.input, PartInput::Asset("trees2"));
+
+        let text = "timeout = 2.5  # both parts\n\n[part1]\nsolver = \"backtracking\"\nmaximize = cells\n\n[part2]\ninput = big.txt\nlimit = 3\n";
+        config.apply(text).unwrap();
+        assert_eq!(config.part1.solver, Strategy::Fixed(SolverKind::Backtracking));
+        assert_eq!(config.part1.maximize, Some(Objective::Cells));
+        assert_eq!((config.part1.timeout, config.part2.timeout), (Duration::from_secs_f64(2.5), Duration::from_secs_f64(2.5)));
+        assert_eq!(config.part2.input, PartInput::File("big.txt".to_string()));
+        assert_eq!((config.part1.limit, config.part2.limit), (None, Some(3)));
+        assert!(config.part1.validate("Part 1").is_ok());
+
+        config.apply("[part2]\nvisualize = true\n").unwrap();
+        assert!(config.part2.validate("Part 2").is_err());
+        config.apply("[part2]\nvisualize = false\nexplain = true\n").unwrap();
+        assert!(config.part2.explain);
+        assert!(config.part2.validate("Part 2").is_err());
+        config.apply("[part2]\nparallel = false\n").unwrap();
+        assert!(config.part2.validate("Part 2").is_ok());
+
+        let err = config.apply("[part1]\nspeed = 11\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: Unknown setting 'speed'");
+        assert_eq!(config.apply("[part3]\n").unwrap_err().to_string(), "Line 1: Unknown section '[part3]'");
+        assert!(config.apply("parallel = yes\n").is_err());
+        assert!(config.apply("timeout = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_solution_grid() {
+        let input = "0:\n###\n...\n...\n\n4x2: 2\n#...\n...#\n";
+        let (shapes, spaces) = parse_input(input).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let solution = solve_with_dlx(&catalog, &spaces[0]).unwrap().expect("expected a packing");
+
+        let grid = solution_grid(&solution, &spaces[0]);
+        assert_eq!(grid[(0, 0)], render::Cell::Blocked);
+        let mut region_sizes = HashMap::new();
+        for (_, &cell) in grid.iter() {
+            if let render::Cell::Region(piece) = cell {
+                *region_sizes.entry(piece).or_insert(0) += 1;
             }
         }
+        assert_eq!(region_sizes, HashMap::from([(0, 3), (1, 3)]));
+    }
 
-        assert_eq!(solution_count, 2, "Part 1 should have exactly 2 solutions");
+    #[test]
+    fn test_board_mask() {
+        let input = "0:\n###\n###\n###\n\n3x3: 1\n\n4x3: 1\n#...\n....\n#...\n\n4x3: 1\n#...\n....\n.#..\n";
+        let (shapes, spaces) = parse_input(input).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        assert_eq!(spaces.len(), 3);
+        assert!(spaces[0].blocked.is_empty());
+        assert_eq!(spaces[1].blocked, HashSet::from([Coords { x: 0, y: 0 }, Coords { x: 0, y: 2 }]));
+        assert_eq!(spaces[2].open_cells(), 10);
+
+        type Backend = fn(&ShapeCatalog, &ProblemSpace) -> Result<Option<Vec<Placement>>>;
+        let backends: [(&str, Backend); 3] = [
+            ("sat", solve_with_sat),
+            ("backtracking", solve_with_backtracking),
+            ("dlx", solve_with_dlx),
+        ];
+        for (name, solve) in backends {
+            let solved: Vec<bool> = spaces.iter().map(|space| solve(&catalog, space).unwrap().is_some()).collect();
+            assert_eq!(solved, vec![true, true, false], "{}", name);
+        }
+
+        let solution = solve_with_dlx(&catalog, &spaces[1]).unwrap().expect("expected a packing");
+        assert_eq!(solution[0].x, 1);
+    }
+
+    #[test]
+    fn test_board_mask_errors() {
+        let short_row = "0:\n###\n###\n###\n\n4x2: 1\n....\n...\n";
+        let err = parse_input(short_row).unwrap_err().to_string();
+        assert!(err.contains("Line 8"), "{}", err);
+
+        let missing_row = "0:\n###\n###\n###\n\n4x2: 1\n....\n";
+        assert!(parse_input(missing_row).is_err());
+    }
+
+    #[test]
+    fn test_empty_shape_rejected() {
+        let err = parse_input("0:\n###\n#..\n...\n\n1:\n...\n...\n...\n\n4x4: 1 1\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 6: shape 1 has no cells");
     }
 
     #[test]
     fn test_part2_has_481_solutions() {
-        let (shapes, spaces) = parse_input("assets/day12trees2.txt").unwrap();
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees2").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
 
         let mut solution_count = 0;
 
         for space in &spaces {
-            if let Some(_solution) = solve_with_backtracking(&shapes, space).unwrap() {
+            if let Some(_solution) = solve_with_backtracking(&catalog, space).unwrap() {
                 solution_count += 1;
             }
         }
 
-        assert_eq!(solution_count, 481, "Part 2 should have exactly 481 solutions");
+        snapshot::check("day12", "part2_solved", solution_count);
     }
 }
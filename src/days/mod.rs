@@ -12,3 +12,100 @@ pub mod day10;
 pub mod day11;
 pub mod day12;
 
+
+use crate::error::{AocError, Result};
+use std::ops::RangeInclusive;
+
+/// Every day with a solution
+pub const DAYS: RangeInclusive<u8> = 1..=12;
+
+/// What a day solves and what it reads, for `list` and other tooling that
+/// shouldn't have to read the README
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayMetadata {
+    /// The puzzle's title, without the "Day N: " prefix
+    pub title: &'static str,
+    /// Names for `Input::for_day`, e.g. `turns` for `day01turns.txt`
+    pub input_files: &'static [&'static str],
+    /// What each part's answer is
+    pub parts: [&'static str; 2],
+    pub notes: &'static str,
+}
+
+/// Metadata of `day`, which must be in `DAYS`
+pub fn metadata(day: u8) -> Result<DayMetadata> {
+    let metadata = match day {
+        1 => day01::metadata(),
+        2 => day02::metadata(),
+        3 => day03::metadata(),
+        4 => day04::metadata(),
+        5 => day05::metadata(),
+        6 => day06::metadata(),
+        7 => day07::metadata(),
+        8 => day08::metadata(),
+        9 => day09::metadata(),
+        10 => day10::metadata(),
+        11 => day11::metadata(),
+        12 => day12::metadata(),
+        _ => return Err(AocError::usage(format!("Day {} not implemented yet", day))),
+    };
+    Ok(metadata)
+}
+
+/// Solves `part` (1 or 2) of `day` from the puzzle input text, returning the
+/// answer as a string. Doesn't touch the filesystem, so it also works from WASM.
+pub fn solve(day: u8, part: u8, input: &str) -> Result<String> {
+    macro_rules! answer {
+        ($day:ident) => {
+            match part {
+                1 => $day::part1(input).map(|answer| answer.to_string()),
+                2 => $day::part2(input).map(|answer| answer.to_string()),
+                _ => Err(AocError::usage(format!("Part must be 1 or 2, got {}", part))),
+            }
+        };
+    }
+
+    match day {
+        1 => answer!(day01),
+        2 => answer!(day02),
+        3 => answer!(day03),
+        4 => answer!(day04),
+        5 => answer!(day05),
+        6 => answer!(day06),
+        7 => answer!(day07),
+        8 => answer!(day08),
+        9 => answer!(day09),
+        10 => answer!(day10),
+        11 => answer!(day11),
+        12 => answer!(day12),
+        _ => Err(AocError::usage(format!("Day {} not implemented yet", day))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn test_solve_from_string() {
+        let example = Input::for_day(7).example().unwrap();
+        assert_eq!(solve(7, 1, example).unwrap(), "21");
+        assert_eq!(solve(7, 2, example).unwrap(), "40");
+        assert!(matches!(solve(7, 3, example), Err(AocError::Usage(_))));
+        assert!(matches!(solve(13, 1, example), Err(AocError::Usage(_))));
+    }
+
+    #[test]
+    fn test_metadata_names_existing_inputs() {
+        for day in DAYS {
+            let metadata = metadata(day).unwrap();
+            assert!(!metadata.title.is_empty() && !metadata.input_files.is_empty(), "day {}", day);
+            for name in metadata.input_files {
+                let path = Input::for_day(day).path(name);
+                assert!(path.exists(), "day {} lists {}", day, path.display());
+            }
+        }
+        assert!(metadata(13).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+// Error type shared by every day and utility, so library users can match on
+// what went wrong and the CLI can turn it into an exit code
+
+use std::fmt;
+use std::time::Duration;
+
+pub type Result<T, E = AocError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum AocError {
+    /// Malformed puzzle input. `line` and `column` are 1-based; `file` is
+    /// filled in by `Input::parse` once the input's origin is known.
+    Parse {
+        file: Option<String>,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// The input parsed, but a solver couldn't produce an answer from it
+    Solve(String),
+    /// The puzzle provably has no solution
+    Infeasible(String),
+    Timeout(Duration),
+    /// Bad command-line option value
+    Usage(String),
+}
+
+impl AocError {
+    /// Parse error tied to no particular position, e.g. an empty input
+    pub fn parse(message: impl Into<String>) -> Self {
+        AocError::Parse { file: None, line: None, column: None, message: message.into() }
+    }
+
+    /// Parse error on a 1-based line
+    pub fn parse_at(line: usize, message: impl Into<String>) -> Self {
+        AocError::Parse { file: None, line: Some(line), column: None, message: message.into() }
+    }
+
+    /// Parse error at a 1-based line and column
+    pub fn parse_at_column(line: usize, column: usize, message: impl Into<String>) -> Self {
+        AocError::Parse { file: None, line: Some(line), column: Some(column), message: message.into() }
+    }
+
+    pub fn io(path: impl Into<String>, source: std::io::Error) -> Self {
+        AocError::Io { path: path.into(), source }
+    }
+
+    pub fn solve(message: impl Into<String>) -> Self {
+        AocError::Solve(message.into())
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        AocError::Usage(message.into())
+    }
+
+    /// Records which file a parse error came from; other kinds pass through
+    pub fn in_file(self, path: impl Into<String>) -> Self {
+        match self {
+            AocError::Parse { file: None, line, column, message } => {
+                AocError::Parse { file: Some(path.into()), line, column, message }
+            }
+            other => other,
+        }
+    }
+
+    /// Records the line a parse error came from, for errors raised by helpers
+    /// that only see part of a line; other kinds pass through
+    pub fn on_line(self, line: usize) -> Self {
+        match self {
+            AocError::Parse { file, line: None, column, message } => AocError::Parse { file, line: Some(line), column, message },
+            other => other,
+        }
+    }
+
+    /// Process exit status for the CLI, following sysexits.h where it has
+    /// a matching code
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AocError::Parse { .. } => 65, // EX_DATAERR
+            AocError::Io { .. } => 74,    // EX_IOERR
+            AocError::Solve(_) => 1,
+            AocError::Infeasible(_) => 3,
+            AocError::Timeout(_) => 124, // what timeout(1) uses
+            AocError::Usage(_) => 2,     // same as clap's own usage errors
+        }
+    }
+}
+
+impl fmt::Display for AocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AocError::Parse { file, line, column, message } => {
+                if let Some(file) = file {
+                    write!(f, "{}: ", file)?;
+                }
+                match (line, column) {
+                    (Some(line), Some(column)) => write!(f, "Line {}, column {}: ", line, column)?,
+                    (Some(line), None) => write!(f, "Line {}: ", line)?,
+                    _ => {}
+                }
+                write!(f, "{}", message)
+            }
+            AocError::Io { path, source } => write!(f, "{}: {}", path, source),
+            AocError::Solve(message) => write!(f, "{}", message),
+            AocError::Infeasible(reason) => write!(f, "No solution exists: {}", reason),
+            AocError::Timeout(limit) => write!(f, "Timed out after {:.1}s", limit.as_secs_f64()),
+            AocError::Usage(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AocError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_display() {
+        let err = AocError::parse_at_column(3, 7, "invalid digit 'x'").in_file("assets/day10machines1.txt");
+        assert_eq!(err.to_string(), "assets/day10machines1.txt: Line 3, column 7: invalid digit 'x'");
+        assert_eq!(err.exit_code(), 65);
+
+        assert_eq!(AocError::parse_at(2, "bad range").to_string(), "Line 2: bad range");
+        assert_eq!(AocError::parse("empty input").in_file("a.txt").to_string(), "a.txt: empty input");
+        // Only parse errors take a file
+        assert!(matches!(AocError::solve("stuck").in_file("a.txt"), AocError::Solve(_)));
+    }
+}
@@ -0,0 +1,112 @@
+// Puzzle input lookup shared by all days: where the files live, read-once
+// caching, and the small examples embedded in the binary
+
+use crate::error::{AocError, Result};
+use crate::util::stats;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// Overrides the `assets/` directory when set
+pub const INPUT_DIR_VAR: &str = "AOC_INPUT_DIR";
+
+const DEFAULT_DIR: &str = "assets";
+
+/// Input files of one day, named `dayNN<name>.txt` inside the input directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Input {
+    day: u8,
+}
+
+impl Input {
+    pub fn for_day(day: u8) -> Self {
+        Input { day }
+    }
+
+    /// `$AOC_INPUT_DIR` if set, `assets/` otherwise
+    pub fn dir() -> PathBuf {
+        std::env::var_os(INPUT_DIR_VAR).map_or_else(|| PathBuf::from(DEFAULT_DIR), PathBuf::from)
+    }
+
+    /// Path of the file `name` belongs to, e.g. `assets/day05ids.txt` for
+    /// `Input::for_day(5).path("ids")`
+    pub fn path(&self, name: &str) -> PathBuf {
+        Self::dir().join(format!("day{:02}{}.txt", self.day, name))
+    }
+
+    /// Contents of the day's `name` file, read from disk at most once
+    pub fn read(&self, name: &str) -> Result<&'static str> {
+        read_file(&self.path(name).to_string_lossy())
+    }
+
+    /// Reads the `name` file and runs `parser` on it, naming the file in
+    /// any parse error
+    pub fn parse<T>(&self, name: &str, parser: impl FnOnce(&'static str) -> Result<T>) -> Result<T> {
+        let path = self.path(name);
+        parser(self.read(name)?).map_err(|e| e.in_file(path.to_string_lossy()))
+    }
+
+    /// The worked example from the puzzle text, for the days whose assets
+    /// include one
+    pub fn example(&self) -> Option<&'static str> {
+        match self.day {
+            7 => Some(include_str!("../assets/day07test.txt")),
+            8 => Some(include_str!("../assets/day08example.txt")),
+            _ => None,
+        }
+    }
+}
+
+/// Contents of any file, cached for the rest of the process so repeated
+/// parts and tests don't hit the disk again
+pub fn read_file(path: &str) -> Result<&'static str> {
+    static CACHE: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+
+    if let Some(&content) = cache.lock().unwrap().get(path) {
+        return Ok(content);
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| AocError::io(path, e))?;
+    // Inputs are small and live until exit, so leaking them is what lets
+    // every caller borrow from the cache
+    let content: &'static str = Box::leak(content.into_boxed_str());
+    stats::add("input.files", 1);
+    stats::add("input.bytes", content.len() as u64);
+    stats::add("input.lines", content.lines().count() as u64);
+    Ok(*cache.lock().unwrap().entry(path.to_string()).or_insert(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        // The default only applies when the environment doesn't override it
+        if std::env::var_os(INPUT_DIR_VAR).is_none() {
+            assert_eq!(Input::for_day(5).path("ids"), PathBuf::from("assets/day05ids.txt"));
+        }
+        assert!(Input::for_day(12).path("trees2").ends_with("day12trees2.txt"));
+    }
+
+    #[test]
+    fn test_read_is_cached() {
+        let first = Input::for_day(5).read("ids").unwrap();
+        let second = Input::for_day(5).read("ids").unwrap();
+        assert!(std::ptr::eq(first, second));
+        let err = Input::for_day(5).read("missing").unwrap_err();
+        assert!(matches!(&err, AocError::Io { path, .. } if path.ends_with("day05missing.txt")));
+    }
+
+    #[test]
+    fn test_parse_names_the_file() {
+        let err = Input::for_day(5).parse("ids", |_| -> Result<()> { Err(AocError::parse_at(4, "bad")) }).unwrap_err();
+        assert!(err.to_string().ends_with("day05ids.txt: Line 4: bad"), "{}", err);
+    }
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(Input::for_day(8).example(), Some(include_str!("../assets/day08example.txt")));
+        assert_eq!(Input::for_day(1).example(), None);
+    }
+}
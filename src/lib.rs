@@ -1,4 +1,8 @@
 // Shared utilities and common code for Advent of Code 2025
 
 pub mod days;
-
+pub mod error;
+pub mod input;
+pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,34 +1,240 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use advent_of_code_2025::days;
+use advent_of_code_2025::error::{AocError, Result};
+use advent_of_code_2025::input::{self, Input};
+use advent_of_code_2025::util::cancel::CancellationToken;
+use advent_of_code_2025::util::output::{self, OutputSink};
+use advent_of_code_2025::util::reporter::Stdout;
+use advent_of_code_2025::util::trace::{self, Replay, Trace};
+use advent_of_code_2025::util::theme::{self, Style, Theme};
+use advent_of_code_2025::util::{progress, stats};
+use std::fs;
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "Advent of Code 2025")]
 #[command(about = "Solutions for Advent of Code 2025", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    #[arg(value_parser = clap::value_parser!(u8).range(1..=12))]
-    day: u8,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=12), required = true)]
+    day: Option<u8>,
+
+    /// Don't show progress bars or status lines on stderr
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Print a run report to stderr: wall time (and peak heap with the
+    /// mem-stats feature) per day and part, plus solver counters
+    #[arg(long)]
+    stats: bool,
+
+    /// Write the run report as JSON to FILE
+    #[arg(long, value_name = "FILE")]
+    stats_json: Option<String>,
+
+    /// Give up with a timeout error after this many seconds (days 2, 10 and 12)
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<f64>,
+
+    /// Record solver decisions to FILE for `replay` (days 8, 10 and 12)
+    #[arg(long, value_name = "FILE")]
+    trace: Option<String>,
+
+    /// Send the day's output to FILE instead of the terminal: one JSON event
+    /// per line (lines, answers, progress, stats) for a .jsonl file, plain
+    /// text otherwise
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Day-specific options, passed through to the selected day (e.g. `11 --source svr`)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print every day's title, parts and input files
+    List,
+    /// Step through a log written with --trace
+    Replay {
+        file: String,
+        /// Stop after this many steps
+        #[arg(long, value_name = "N")]
+        steps: Option<usize>,
+        /// Pause between steps
+        #[arg(long, value_name = "MS", default_value_t = 0)]
+        delay: u64,
+    },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[cfg(feature = "mem-stats")]
+#[global_allocator]
+static ALLOCATOR: stats::TrackingAllocator = stats::TrackingAllocator;
+
+/// Days whose solvers poll the cancellation token
+const DAYS_WITH_TIMEOUT: &[u8] = &[2, 10, 12];
+
+/// Days whose solvers emit trace events
+const DAYS_WITH_TRACE: &[u8] = &[8, 10, 12];
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    progress::set_enabled(!cli.no_progress);
+    // A file gets no escape codes, even when stdout is a terminal
+    if cli.output.is_some() {
+        theme::set(Theme::PLAIN);
+    }
+    // JSON lines output carries the run report as an event
+    stats::set_enabled(cli.stats || cli.stats_json.is_some() || cli.output.as_deref().is_some_and(output::is_json_lines));
+
+    let result = match (&cli.command, cli.day) {
+        (Some(Command::List), _) => list(),
+        (Some(Command::Replay { file, steps, delay }), _) => replay(file, *steps, Duration::from_millis(*delay)),
+        (None, Some(day)) => run_with_output(&cli, day),
+        (None, None) => unreachable!("clap requires a day without a subcommand"),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Runs `day`, sending everything it reports to the terminal or --output
+fn run_with_output(cli: &Cli, day: u8) -> Result<()> {
+    let out: Box<dyn OutputSink> = match &cli.output {
+        Some(path) => output::to_file(path, day)?,
+        None => Box::new(Stdout),
+    };
+    let result = stats::measure(&format!("Day {}", day), || run_day(cli, day, out.as_ref()));
+    result.and(report_stats(cli, out.as_ref())).and(out.finish())
+}
+
+fn run_day(cli: &Cli, day: u8, out: &dyn OutputSink) -> Result<()> {
+    if cli.trace.is_some() && !DAYS_WITH_TRACE.contains(&day) {
+        return Err(AocError::usage(format!("Day {} doesn't support --trace", day)));
+    }
+    let cancel = match cli.timeout {
+        Some(_) if !DAYS_WITH_TIMEOUT.contains(&day) => {
+            return Err(AocError::usage(format!("Day {} doesn't support --timeout", day)));
+        }
+        Some(secs) => CancellationToken::with_timeout(
+            Duration::try_from_secs_f64(secs).map_err(|_| AocError::usage(format!("Invalid --timeout {}", secs)))?,
+        ),
+        None => CancellationToken::new(),
+    };
     
-    println!("🎄 Advent of Code 2025 - Day {} 🎄\n", cli.day);
+    let title = days::metadata(day)?.title;
+    out.line(&theme::current().paint(Style::Heading, format!("🎄 Advent of Code 2025 - Day {}: {} 🎄", day, title)));
+    out.line("");
+    if cli.trace.is_some() {
+        trace::start();
+    }
     
-    match cli.day {
-        1 => days::day01::run()?,
-        2 => days::day02::run()?,
-        3 => days::day03::run()?,
-        4 => days::day04::run()?,
-        5 => days::day05::run()?,
-        6 => days::day06::run()?,
-        7 => days::day07::run()?,
-        8 => days::day08::run()?,
-        9 => days::day09::run()?,
-        10 => days::day10::run()?,
-        11 => days::day11::run()?,
-        12 => days::day12::run()?,
+    match day {
+        1 => days::day01::run(&cli.args, out)?,
+        2 => days::day02::run(&cli.args, &cancel, out)?,
+        3 => days::day03::run(&cli.args, out)?,
+        4 => days::day04::run(&cli.args, out)?,
+        5 => days::day05::run(&cli.args, out)?,
+        6 => days::day06::run(&cli.args, out)?,
+        7 => days::day07::run(&cli.args, out)?,
+        8 => days::day08::run(&cli.args, out)?,
+        9 => days::day09::run(&cli.args, out)?,
+        10 => days::day10::run(&cli.args, &cancel, out)?,
+        11 => days::day11::run(&cli.args, out)?,
+        12 => days::day12::run(&cli.args, &cancel, out)?,
         _ => unreachable!("clap should prevent this"),
     }
+
+    if let Some(path) = &cli.trace {
+        let log = trace::finish(day);
+        fs::write(path, log.to_string()).map_err(|e| AocError::io(path, e))?;
+        out.line(&format!("\nWrote {} trace events to {}{}", log.events.len(), path,
+            if log.truncated { " (truncated)" } else { "" }));
+    }
     
     Ok(())
 }
+
+/// Prints and/or writes out the run report once everything has finished,
+/// and hands it to the sink
+fn report_stats(cli: &Cli, out: &dyn OutputSink) -> Result<()> {
+    let report = stats::take_report();
+    if !report.is_empty() {
+        out.stats(&report);
+    }
+    if cli.stats && !report.is_empty() {
+        eprint!("{}", report);
+    }
+    if let Some(path) = &cli.stats_json {
+        fs::write(path, report.to_json()).map_err(|e| AocError::io(path, e))?;
+    }
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    let theme = theme::current();
+    for day in days::DAYS {
+        let metadata = days::metadata(day)?;
+        println!("{}", theme.paint(Style::Heading, format!("Day {}: {}", day, metadata.title)));
+        for (part, answer) in metadata.parts.iter().enumerate() {
+            println!("  Part {}: {}", part + 1, answer);
+        }
+        let inputs: Vec<String> = metadata
+            .input_files
+            .iter()
+            .map(|name| {
+                let path = Input::for_day(day).path(name);
+                if path.exists() {
+                    path.display().to_string()
+                } else {
+                    theme.paint(Style::Bad, format!("{} (missing)", path.display()))
+                }
+            })
+            .collect();
+        println!("  Inputs: {}", inputs.join(", "));
+        println!("  {}", theme.paint(Style::Muted, metadata.notes));
+    }
+    Ok(())
+}
+
+fn replay(path: &str, steps: Option<usize>, delay: Duration) -> Result<()> {
+    let log: Trace = input::read_file(path)?.parse().map_err(|e: AocError| e.in_file(path))?;
+    println!("Replaying {} events from day {}\n", log.events.len(), log.day);
+
+    let mut replay = Replay::new();
+    for (i, event) in log.events.iter().take(steps.unwrap_or(usize::MAX)).enumerate() {
+        if i > 0 && !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        print!("[{}] {}", i + 1, replay.step(event));
+    }
+    if log.truncated {
+        println!("\nThe log was truncated at {} events", log.events.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_and_day_timeouts() {
+        let cli = Cli::try_parse_from(["aoc", "--timeout", "60", "12", "--backend-timeout", "5"]).unwrap();
+        assert_eq!((cli.day, cli.timeout), (Some(12), Some(60.0)));
+        assert_eq!(cli.args, ["--backend-timeout", "5"]);
+
+        // After the day number --timeout is still the global limit, not a day option
+        let cli = Cli::try_parse_from(["aoc", "12", "--timeout", "0.5"]).unwrap();
+        assert_eq!(cli.timeout, Some(0.5));
+        assert!(cli.args.is_empty());
+    }
+}
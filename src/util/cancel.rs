@@ -0,0 +1,87 @@
+// Cooperative cancellation for long solves. Solvers poll the token inside
+// their big loops and give up with `AocError::Timeout` once it fires, either
+// because its time limit passed or because someone called `cancel`.
+
+use crate::error::{AocError, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use web_time::Instant;
+
+/// Shared flag plus an optional deadline; clones observe the same state
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    started: Instant,
+    deadline: Option<Instant>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    /// Token that only fires when `cancel` is called
+    pub fn new() -> Self {
+        CancellationToken {
+            inner: Arc::new(Inner { cancelled: AtomicBool::new(false), started: Instant::now(), deadline: None }),
+        }
+    }
+
+    /// Token that also fires once `timeout` has passed
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let started = Instant::now();
+        CancellationToken {
+            inner: Arc::new(Inner { cancelled: AtomicBool::new(false), started, deadline: started.checked_add(timeout) }),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        if self.inner.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.inner.deadline.is_some_and(|at| Instant::now() >= at) {
+            // Latch, so later polls skip the clock
+            self.cancel();
+            return true;
+        }
+        false
+    }
+
+    /// `Err(AocError::Timeout)` once the token has fired
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(AocError::Timeout(self.inner.started.elapsed()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        clone.cancel();
+        assert!(matches!(token.check(), Err(AocError::Timeout(_))));
+
+        assert!(CancellationToken::with_timeout(Duration::ZERO).is_cancelled());
+        assert!(!CancellationToken::with_timeout(Duration::from_secs(3600)).is_cancelled());
+    }
+}
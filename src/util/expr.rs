@@ -0,0 +1,142 @@
+// Tiny infix arithmetic: non-negative integers, `+`, `*` and parentheses,
+// with `*` binding tighter than `+`. Parsed by recursive descent over
+//
+//     sum     = product ("+" product)*
+//     product = atom ("*" atom)*
+//     atom    = number | "(" sum ")"
+
+use crate::error::{AocError, Result};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(i64),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self) -> i64 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Add(a, b) => a.eval() + b.eval(),
+            Expr::Mul(a, b) => a.eval() * b.eval(),
+        }
+    }
+}
+
+/// Fully parenthesised, so the tree shape is visible
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> AocError {
+        AocError::parse(format!("{} at position {} of '{}'", message, self.pos + 1, self.text))
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `token` if it comes next
+    fn eat(&mut self, token: u8) -> bool {
+        self.skip_spaces();
+        if self.bytes.get(self.pos) == Some(&token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut expr = self.product()?;
+        while self.eat(b'+') {
+            expr = Expr::Add(Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut expr = self.atom()?;
+        while self.eat(b'*') {
+            expr = Expr::Mul(Box::new(expr), Box::new(self.atom()?));
+        }
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        if self.eat(b'(') {
+            let expr = self.sum()?;
+            if !self.eat(b')') {
+                return Err(self.error("Expected ')'"));
+            }
+            return Ok(expr);
+        }
+
+        self.skip_spaces();
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("Expected a number or '('"));
+        }
+        self.text[start..self.pos].parse().map(Expr::Num).map_err(|e| {
+            self.pos = start;
+            self.error(&format!("Invalid number: {}", e))
+        })
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expr> {
+    let mut parser = Parser { text, bytes: text.as_bytes(), pos: 0 };
+    let expr = parser.sum()?;
+    parser.skip_spaces();
+    if parser.pos < text.len() {
+        return Err(parser.error("Unexpected character"));
+    }
+    Ok(expr)
+}
+
+/// Parses and evaluates `text` in one go
+pub fn eval(text: &str) -> Result<i64> {
+    parse(text).map(|expr| expr.eval())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence_and_parentheses() {
+        assert_eq!(eval("42").unwrap(), 42);
+        assert_eq!(eval("2+3*4").unwrap(), 14);
+        assert_eq!(eval("(2+3)*4").unwrap(), 20);
+        assert_eq!(eval(" ( (1 + 2) * (3+4) ) + 5 ").unwrap(), 26);
+        assert_eq!(parse("1+2*3+4").unwrap().to_string(), "((1 + (2 * 3)) + 4)");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(eval("(1+2").unwrap_err().to_string(), "Expected ')' at position 5 of '(1+2'");
+        assert_eq!(eval("1+*2").unwrap_err().to_string(), "Expected a number or '(' at position 3 of '1+*2'");
+        assert_eq!(eval("1 2").unwrap_err().to_string(), "Unexpected character at position 3 of '1 2'");
+        assert!(eval("").is_err());
+    }
+}
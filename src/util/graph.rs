@@ -0,0 +1,849 @@
+// Index-based directed graph shared by the graph-shaped days
+
+use crate::error::{AocError, Result};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+/// Index of a node inside a `Graph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Per-node payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeData {
+    pub name: String,
+}
+
+/// A graph with each strongly connected component collapsed into a single node
+#[derive(Debug, Clone)]
+pub struct Condensation {
+    /// The condensed DAG; node i represents `components[i]`
+    pub graph: Graph,
+    pub components: Vec<Vec<NodeId>>,
+    /// For every original node, the condensed node containing it
+    pub component_of: Vec<NodeId>,
+}
+
+/// Lazily enumerates simple paths between two nodes, see `iter_paths`
+pub struct PathIter<'a> {
+    graph: &'a Graph,
+    dst: NodeId,
+    // (node, index of the next child to visit); usize::MAX marks an already-emitted target
+    stack: Vec<(NodeId, usize)>,
+    on_path: Vec<bool>,
+}
+
+impl Iterator for PathIter<'_> {
+    type Item = Vec<NodeId>;
+
+    fn next(&mut self) -> Option<Vec<NodeId>> {
+        while let Some(&mut (node, ref mut next_child)) = self.stack.last_mut() {
+            if node == self.dst {
+                if *next_child == 0 {
+                    *next_child = usize::MAX;
+                    return Some(self.stack.iter().map(|&(n, _)| n).collect());
+                }
+                self.on_path[node.index()] = false;
+                self.stack.pop();
+                continue;
+            }
+
+            match self.graph.children(node).get(*next_child) {
+                Some(&child) => {
+                    *next_child += 1;
+                    // Only simple paths, so cycles can't make the iterator run forever
+                    if !self.on_path[child.index()] {
+                        self.on_path[child.index()] = true;
+                        self.stack.push((child, 0));
+                    }
+                }
+                None => {
+                    self.on_path[node.index()] = false;
+                    self.stack.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterate over the simple paths from `src` to `dst` in DFS order, one node sequence at a time.
+/// Uses an explicit stack, so `iter_paths(graph, a, b).take(n)` only does the work for n paths.
+pub fn iter_paths(graph: &Graph, src: NodeId, dst: NodeId) -> PathIter<'_> {
+    let mut on_path = vec![false; graph.len()];
+    on_path[src.index()] = true;
+    PathIter {
+        graph,
+        dst,
+        stack: vec![(src, 0)],
+        on_path,
+    }
+}
+
+/// Directed graph stored as an arena: nodes and adjacency lists are plain vectors indexed
+/// by `NodeId`, so traversals are cheap copies of indices and the graph is `Send + Sync`.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<NodeData>,
+    pub edges: Vec<Vec<NodeId>>,
+    /// Weight of every edge, parallel to `edges`; 1 unless added with a weight
+    pub weights: Vec<Vec<u64>>,
+    pub name_index: HashMap<String, NodeId>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the node with this name, creating it if it doesn't exist yet
+    pub fn add_node(&mut self, name: &str) -> NodeId {
+        if let Some(&id) = self.name_index.get(name) {
+            return id;
+        }
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(NodeData { name: name.to_string() });
+        self.edges.push(Vec::new());
+        self.weights.push(Vec::new());
+        self.name_index.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        self.add_weighted_edge(from, to, 1);
+    }
+
+    /// Adds an edge carrying `weight`, read as a multiplicity by path counts
+    /// and as a cost by `cheapest_path`
+    pub fn add_weighted_edge(&mut self, from: NodeId, to: NodeId, weight: u64) {
+        self.edges[from.index()].push(to);
+        self.weights[from.index()].push(weight);
+    }
+
+    /// Removes every edge from `from` to `to`, returning their weights
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) -> Vec<u64> {
+        let (edges, weights) = (&mut self.edges[from.index()], &mut self.weights[from.index()]);
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < edges.len() {
+            if edges[i] == to {
+                edges.remove(i);
+                removed.push(weights.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        removed
+    }
+
+    /// Removes every edge into or out of `id`, returning them as (from, to,
+    /// weight) so they can be added back. The node itself stays, so every
+    /// `NodeId` stays valid; with no edges it lies on no path between others.
+    pub fn disconnect(&mut self, id: NodeId) -> Vec<(NodeId, NodeId, u64)> {
+        let mut removed: Vec<(NodeId, NodeId, u64)> = self.weighted_children(id).map(|(to, weight)| (id, to, weight)).collect();
+        self.edges[id.index()].clear();
+        self.weights[id.index()].clear();
+        for from in self.node_ids() {
+            for weight in self.remove_edge(from, id) {
+                removed.push((from, id, weight));
+            }
+        }
+        removed
+    }
+
+    pub fn node_id(&self, name: &str) -> Option<NodeId> {
+        self.name_index.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.nodes[id.index()].name
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.edges[id.index()]
+    }
+
+    /// Children of `id` with the weight of the edge to each
+    pub fn weighted_children(&self, id: NodeId) -> impl Iterator<Item = (NodeId, u64)> + '_ {
+        self.edges[id.index()].iter().copied().zip(self.weights[id.index()].iter().copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// Topological order of every node reachable from `src` (iterative DFS).
+    /// Fails with the offending edge if a cycle is reachable.
+    pub fn topological_order(&self, src: NodeId) -> Result<Vec<NodeId>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Unvisited,
+            InProgress,
+            Done,
+        }
+
+        let mut marks = vec![Mark::Unvisited; self.len()];
+        let mut post_order = Vec::new();
+        // (node, index of the next child to visit)
+        let mut stack = vec![(src, 0)];
+        marks[src.index()] = Mark::InProgress;
+
+        while let Some(&mut (node, ref mut next_child)) = stack.last_mut() {
+            if let Some(&child) = self.children(node).get(*next_child) {
+                *next_child += 1;
+                match marks[child.index()] {
+                    Mark::Unvisited => {
+                        marks[child.index()] = Mark::InProgress;
+                        stack.push((child, 0));
+                    }
+                    Mark::InProgress => return Err(self.cycle_error(node, child)),
+                    Mark::Done => {}
+                }
+            } else {
+                marks[node.index()] = Mark::Done;
+                post_order.push(node);
+                stack.pop();
+            }
+        }
+
+        post_order.reverse();
+        Ok(post_order)
+    }
+
+    /// The error for a search that got back to `to`, still on its path, along
+    /// the edge `from` -> `to`
+    pub fn cycle_error(&self, from: NodeId, to: NodeId) -> AocError {
+        AocError::solve(format!("Cycle detected: edge '{}' -> '{}' closes a loop", self.name(from), self.name(to)))
+    }
+
+    /// Fewest-edges path from `src` to `dst` (BFS), or None if `dst` is unreachable
+    pub fn shortest_path(&self, src: NodeId, dst: NodeId) -> Option<Vec<NodeId>> {
+        let mut previous: Vec<Option<NodeId>> = vec![None; self.len()];
+        let mut seen = vec![false; self.len()];
+        let mut queue = VecDeque::from([src]);
+        seen[src.index()] = true;
+
+        while let Some(node) = queue.pop_front() {
+            if node == dst {
+                return Some(Self::walk_back(&previous, dst));
+            }
+            for &child in self.children(node) {
+                if !seen[child.index()] {
+                    seen[child.index()] = true;
+                    previous[child.index()] = Some(node);
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Most-edges path from `src` to `dst`, computed by DP over a topological order.
+    /// Longest simple paths are only tractable on DAGs, so a reachable cycle is an error.
+    pub fn longest_path(&self, src: NodeId, dst: NodeId) -> Result<Option<Vec<NodeId>>> {
+        let order = self.topological_order(src)?;
+
+        let mut length: Vec<Option<usize>> = vec![None; self.len()];
+        let mut previous: Vec<Option<NodeId>> = vec![None; self.len()];
+        length[src.index()] = Some(0);
+
+        for node in order {
+            let Some(node_length) = length[node.index()] else {
+                continue;
+            };
+            for &child in self.children(node) {
+                if length[child.index()].is_none_or(|current| node_length + 1 > current) {
+                    length[child.index()] = Some(node_length + 1);
+                    previous[child.index()] = Some(node);
+                }
+            }
+        }
+
+        Ok(length[dst.index()].map(|_| Self::walk_back(&previous, dst)))
+    }
+
+    /// Lowest total-weight path from `src` to `dst` with its cost (Dijkstra), or None if
+    /// `dst` is unreachable. Weights are never negative, so cycles are fine. Costs are
+    /// summed as u128, which no path of u64 weights can overflow.
+    pub fn cheapest_path(&self, src: NodeId, dst: NodeId) -> Option<(u128, Vec<NodeId>)> {
+        let mut cost: Vec<Option<u128>> = vec![None; self.len()];
+        let mut previous: Vec<Option<NodeId>> = vec![None; self.len()];
+        let mut queue = BinaryHeap::from([Reverse((0u128, src))]);
+        cost[src.index()] = Some(0);
+
+        while let Some(Reverse((node_cost, node))) = queue.pop() {
+            if node == dst {
+                return Some((node_cost, Self::walk_back(&previous, dst)));
+            }
+            // Stale entry for a node already reached more cheaply
+            if cost[node.index()].is_some_and(|best| best < node_cost) {
+                continue;
+            }
+            for (child, weight) in self.weighted_children(node) {
+                let child_cost = node_cost + u128::from(weight);
+                if cost[child.index()].is_none_or(|current| child_cost < current) {
+                    cost[child.index()] = Some(child_cost);
+                    previous[child.index()] = Some(node);
+                    queue.push(Reverse((child_cost, child)));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn walk_back(previous: &[Option<NodeId>], dst: NodeId) -> Vec<NodeId> {
+        let mut path = vec![dst];
+        while let Some(prev) = previous[path[path.len() - 1].index()] {
+            path.push(prev);
+        }
+        path.reverse();
+        path
+    }
+
+    /// Nodes reachable from `src` (including `src`)
+    pub fn reachable_from(&self, src: NodeId) -> Vec<bool> {
+        let mut seen = vec![false; self.len()];
+        let mut stack = vec![src];
+        seen[src.index()] = true;
+        while let Some(node) = stack.pop() {
+            for &child in self.children(node) {
+                if !seen[child.index()] {
+                    seen[child.index()] = true;
+                    stack.push(child);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Incoming edges of every node, indexed by `NodeId`
+    pub fn parents(&self) -> Vec<Vec<NodeId>> {
+        let mut parents: Vec<Vec<NodeId>> = vec![Vec::new(); self.len()];
+        for node in self.node_ids() {
+            for &child in self.children(node) {
+                parents[child.index()].push(node);
+            }
+        }
+        parents
+    }
+
+    /// Nodes that can reach `dst` (including `dst`), via a search over reversed edges
+    pub fn reaching(&self, dst: NodeId) -> Vec<bool> {
+        self.reaching_any(&[dst])
+    }
+
+    /// Nodes that can reach any of `targets` (including them), in one search
+    pub fn reaching_any(&self, targets: &[NodeId]) -> Vec<bool> {
+        let parents = self.parents();
+        let mut seen = vec![false; self.len()];
+        let mut stack = Vec::new();
+        for &target in targets {
+            if !seen[target.index()] {
+                seen[target.index()] = true;
+                stack.push(target);
+            }
+        }
+        while let Some(node) = stack.pop() {
+            for &parent in &parents[node.index()] {
+                if !seen[parent.index()] {
+                    seen[parent.index()] = true;
+                    stack.push(parent);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Nodes reachable from `src` in DFS post-order (iterative, each node once)
+    fn post_order(&self, src: NodeId) -> Vec<NodeId> {
+        let mut seen = vec![false; self.len()];
+        let mut order = Vec::new();
+        // (node, index of the next child to visit)
+        let mut stack = vec![(src, 0)];
+        seen[src.index()] = true;
+
+        while let Some(&mut (node, ref mut next_child)) = stack.last_mut() {
+            if let Some(&child) = self.children(node).get(*next_child) {
+                *next_child += 1;
+                if !seen[child.index()] {
+                    seen[child.index()] = true;
+                    stack.push((child, 0));
+                }
+            } else {
+                order.push(node);
+                stack.pop();
+            }
+        }
+        order
+    }
+
+    /// Immediate dominator of every node reachable from `src`: the last node that every
+    /// `src` -> node path must pass through. `src` is its own dominator; unreachable nodes
+    /// get None. Uses the iterative Cooper-Harvey-Kennedy algorithm, so cycles are fine.
+    pub fn immediate_dominators(&self, src: NodeId) -> Vec<Option<NodeId>> {
+        let post_order = self.post_order(src);
+        let mut rank = vec![usize::MAX; self.len()];
+        for (i, &node) in post_order.iter().enumerate() {
+            rank[node.index()] = i;
+        }
+        let parents = self.parents();
+
+        let mut idom: Vec<Option<NodeId>> = vec![None; self.len()];
+        idom[src.index()] = Some(src);
+
+        let intersect = |idom: &[Option<NodeId>], mut a: NodeId, mut b: NodeId| {
+            while a != b {
+                while rank[a.index()] < rank[b.index()] {
+                    a = idom[a.index()].expect("processed nodes have a dominator");
+                }
+                while rank[b.index()] < rank[a.index()] {
+                    b = idom[b.index()].expect("processed nodes have a dominator");
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            // Reverse post-order, skipping `src` itself
+            for &node in post_order.iter().rev().skip(1) {
+                let mut new_idom: Option<NodeId> = None;
+                for &parent in &parents[node.index()] {
+                    if idom[parent.index()].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        Some(current) => intersect(&idom, parent, current),
+                        None => parent,
+                    });
+                }
+                if new_idom.is_some() && idom[node.index()] != new_idom {
+                    idom[node.index()] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idom
+    }
+
+    /// Nodes every `src` -> `dst` path passes through, in path order and including both ends.
+    /// Empty if `dst` is unreachable.
+    pub fn dominators_of(&self, src: NodeId, dst: NodeId) -> Vec<NodeId> {
+        let idom = self.immediate_dominators(src);
+        if idom[dst.index()].is_none() {
+            return Vec::new();
+        }
+        let mut chain = vec![dst];
+        let mut node = dst;
+        while node != src {
+            node = idom[node.index()].expect("dominator chain stays reachable");
+            chain.push(node);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Mark the nodes lying on at least one `src` -> `dst` path.
+    /// An edge u -> v is on such a path exactly when both u and v are marked.
+    pub fn nodes_on_paths(&self, src: NodeId, dst: NodeId) -> Vec<bool> {
+        let forward = self.reachable_from(src);
+        let backward = self.reaching(dst);
+        forward.iter().zip(&backward).map(|(&f, &b)| f && b).collect()
+    }
+
+    /// Render the graph in Graphviz DOT format. When `path_between` is given, nodes and edges
+    /// on any path between the pair are colored; `emphasized` nodes are drawn bold. Edges
+    /// with a weight other than 1 are labeled with it.
+    pub fn to_dot(&self, path_between: Option<(NodeId, NodeId)>, emphasized: &[NodeId]) -> String {
+        let on_path = path_between.map(|(src, dst)| self.nodes_on_paths(src, dst));
+        let marked = |id: NodeId| on_path.as_ref().is_some_and(|marks| marks[id.index()]);
+
+        let mut dot = String::from("digraph G {\n");
+        for id in self.node_ids() {
+            let mut attributes = Vec::new();
+            if marked(id) {
+                attributes.push("color=red".to_string());
+                attributes.push("fontcolor=red".to_string());
+            }
+            if emphasized.contains(&id) {
+                attributes.push("style=bold".to_string());
+                attributes.push("penwidth=3".to_string());
+            }
+            dot.push_str(&format!("    {:?}", self.name(id)));
+            if !attributes.is_empty() {
+                dot.push_str(&format!(" [{}]", attributes.join(", ")));
+            }
+            dot.push_str(";\n");
+        }
+        for id in self.node_ids() {
+            for (child, weight) in self.weighted_children(id) {
+                dot.push_str(&format!("    {:?} -> {:?}", self.name(id), self.name(child)));
+                let mut attributes = Vec::new();
+                if marked(id) && marked(child) {
+                    attributes.push("color=red".to_string());
+                }
+                if weight != 1 {
+                    attributes.push(format!("label=\"{}\"", weight));
+                }
+                if !attributes.is_empty() {
+                    dot.push_str(&format!(" [{}]", attributes.join(", ")));
+                }
+                dot.push_str(";\n");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Strongly connected components (iterative Tarjan), in reverse topological order
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        let n = self.len();
+        let mut index = vec![usize::MAX; n];
+        let mut low_link = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut component_stack = Vec::new();
+        let mut components = Vec::new();
+        let mut next_index = 0;
+
+        for root in self.node_ids() {
+            if index[root.index()] != usize::MAX {
+                continue;
+            }
+            // (node, index of the next child to visit)
+            let mut call_stack = vec![(root, 0)];
+            index[root.index()] = next_index;
+            low_link[root.index()] = next_index;
+            next_index += 1;
+            component_stack.push(root);
+            on_stack[root.index()] = true;
+
+            while let Some(&mut (node, ref mut next_child)) = call_stack.last_mut() {
+                if let Some(&child) = self.children(node).get(*next_child) {
+                    *next_child += 1;
+                    if index[child.index()] == usize::MAX {
+                        index[child.index()] = next_index;
+                        low_link[child.index()] = next_index;
+                        next_index += 1;
+                        component_stack.push(child);
+                        on_stack[child.index()] = true;
+                        call_stack.push((child, 0));
+                    } else if on_stack[child.index()] {
+                        low_link[node.index()] = low_link[node.index()].min(index[child.index()]);
+                    }
+                    continue;
+                }
+
+                call_stack.pop();
+                if let Some(&(parent, _)) = call_stack.last() {
+                    low_link[parent.index()] = low_link[parent.index()].min(low_link[node.index()]);
+                }
+                if low_link[node.index()] == index[node.index()] {
+                    let mut component = Vec::new();
+                    while let Some(member) = component_stack.pop() {
+                        on_stack[member.index()] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.reverse();
+                    components.push(component);
+                }
+            }
+        }
+
+        components
+    }
+
+    /// Connected components ignoring edge direction, each listed in node
+    /// order and ordered by their first node
+    pub fn weakly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        let parents = self.parents();
+        let mut seen = vec![false; self.len()];
+        let mut components = Vec::new();
+        for root in self.node_ids() {
+            if seen[root.index()] {
+                continue;
+            }
+            seen[root.index()] = true;
+            let mut component = vec![root];
+            let mut stack = vec![root];
+            while let Some(node) = stack.pop() {
+                for &next in self.children(node).iter().chain(&parents[node.index()]) {
+                    if !seen[next.index()] {
+                        seen[next.index()] = true;
+                        component.push(next);
+                        stack.push(next);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// Components that contain a cycle: more than one node, or a node with a self-loop
+    pub fn cyclic_components(&self) -> Vec<Vec<NodeId>> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.children(component[0]).contains(&component[0])
+            })
+            .collect()
+    }
+
+    /// Collapse every strongly connected component into one node. Singleton components keep
+    /// their name; larger ones are named after their members, e.g. `{a,b,c}`. Parallel edges
+    /// merge into one, so the condensed edges all have weight 1.
+    pub fn condense(&self) -> Condensation {
+        let mut components = self.strongly_connected_components();
+        // Tarjan emits components in reverse topological order
+        components.reverse();
+
+        let mut component_of = vec![NodeId(0); self.len()];
+        let mut graph = Graph::new();
+        for component in &components {
+            let name = if component.len() == 1 {
+                self.name(component[0]).to_string()
+            } else {
+                format!("{{{}}}", self.path_names(component).join(","))
+            };
+            let condensed = graph.add_node(&name);
+            for &member in component {
+                component_of[member.index()] = condensed;
+            }
+        }
+
+        for node in self.node_ids() {
+            let from = component_of[node.index()];
+            for &child in self.children(node) {
+                let to = component_of[child.index()];
+                if from != to && !graph.children(from).contains(&to) {
+                    graph.add_edge(from, to);
+                }
+            }
+        }
+
+        Condensation {
+            graph,
+            components,
+            component_of,
+        }
+    }
+
+    /// Node names along a path, for display
+    pub fn path_names(&self, path: &[NodeId]) -> Vec<&str> {
+        path.iter().map(|&id| self.name(id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_node_is_idempotent() {
+        let mut graph = Graph::new();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        assert_eq!(graph.add_node("a"), a);
+        graph.add_edge(a, b);
+
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.children(a), &[b]);
+        assert_eq!(graph.name(b), "b");
+        assert_eq!(graph.node_id("missing"), None);
+    }
+
+    #[test]
+    fn test_remove_edges_and_disconnect() {
+        let mut graph = build(&[("a", "b"), ("a", "c"), ("a", "b"), ("c", "b"), ("b", "d")]);
+        let id = |graph: &Graph, name| graph.node_id(name).unwrap();
+        let (a, b, c, d) = (id(&graph, "a"), id(&graph, "b"), id(&graph, "c"), id(&graph, "d"));
+
+        assert_eq!(graph.remove_edge(a, b), vec![1, 1]);
+        assert_eq!(graph.children(a), &[c]);
+        assert!(graph.remove_edge(a, d).is_empty());
+
+        let removed = graph.disconnect(b);
+        assert_eq!(removed, vec![(b, d, 1), (c, b, 1)]);
+        assert!(graph.children(b).is_empty() && graph.children(c).is_empty());
+        assert_eq!(graph.node_id("b"), Some(b));
+        for (from, to, weight) in removed {
+            graph.add_weighted_edge(from, to, weight);
+        }
+        assert_eq!(graph.shortest_path(a, d).map(|path| path.len()), Some(4));
+
+        let reaching = graph.reaching_any(&[c, b, c]);
+        assert_eq!(reaching, vec![true, true, true, false]);
+        assert_eq!(reaching, graph.reaching(b));
+        assert_eq!(graph.reaching_any(&[d]), graph.reaching(d));
+    }
+
+    fn build(edges: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph::new();
+        for (from, to) in edges {
+            let from = graph.add_node(from);
+            let to = graph.add_node(to);
+            graph.add_edge(from, to);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_shortest_and_longest_paths() {
+        let graph = build(&[("a", "b"), ("b", "c"), ("c", "d"), ("a", "d"), ("b", "d")]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let shortest = graph.shortest_path(id("a"), id("d")).unwrap();
+        assert_eq!(graph.path_names(&shortest), vec!["a", "d"]);
+
+        let longest = graph.longest_path(id("a"), id("d")).unwrap().unwrap();
+        assert_eq!(graph.path_names(&longest), vec!["a", "b", "c", "d"]);
+
+        assert_eq!(graph.shortest_path(id("d"), id("a")), None);
+        assert_eq!(graph.longest_path(id("d"), id("a")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_longest_path_rejects_cycles() {
+        let graph = build(&[("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let err = graph.longest_path(id("a"), id("d")).unwrap_err();
+        assert_eq!(err.to_string(), "Cycle detected: edge 'c' -> 'a' closes a loop");
+        // BFS is fine with cycles
+        assert_eq!(graph.shortest_path(id("a"), id("d")).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_nodes_on_paths_and_dot_export() {
+        let graph = build(&[("a", "b"), ("b", "d"), ("a", "c"), ("c", "x"), ("e", "d")]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let marks = graph.nodes_on_paths(id("a"), id("d"));
+        let marked: Vec<&str> = graph.node_ids().filter(|n| marks[n.index()]).map(|n| graph.name(n)).collect();
+        assert_eq!(marked, vec!["a", "b", "d"]);
+
+        let dot = graph.to_dot(Some((id("a"), id("d"))), &[id("b")]);
+        assert!(dot.starts_with("digraph G {"));
+        assert!(dot.contains("\"b\" [color=red, fontcolor=red, style=bold, penwidth=3];"));
+        assert!(dot.contains("\"c\";"));
+        assert!(dot.contains("\"a\" -> \"b\" [color=red];"));
+        assert!(dot.contains("\"a\" -> \"c\";"));
+        assert!(dot.contains("\"e\" -> \"d\";"));
+    }
+
+    #[test]
+    fn test_cheapest_path_uses_weights() {
+        let mut graph = Graph::new();
+        let (a, b, c, d) = (graph.add_node("a"), graph.add_node("b"), graph.add_node("c"), graph.add_node("d"));
+        graph.add_weighted_edge(a, d, 10);
+        graph.add_weighted_edge(a, b, 2);
+        graph.add_weighted_edge(b, c, 3);
+        graph.add_weighted_edge(c, d, 4);
+        graph.add_weighted_edge(c, a, 1);
+        graph.add_weighted_edge(b, d, u64::MAX);
+
+        let (cost, path) = graph.cheapest_path(a, d).unwrap();
+        assert_eq!((cost, graph.path_names(&path)), (9, vec!["a", "b", "c", "d"]));
+        assert_eq!(graph.cheapest_path(d, a), None);
+        assert_eq!(graph.cheapest_path(a, a), Some((0, vec![a])));
+        // BFS still counts edges, not weight
+        assert_eq!(graph.shortest_path(a, d).unwrap(), vec![a, d]);
+
+        assert!(graph.to_dot(None, &[]).contains("\"a\" -> \"d\" [label=\"10\"];"));
+        assert_eq!(graph.weighted_children(b).collect::<Vec<_>>(), vec![(c, 3), (d, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_strongly_connected_components_and_condensation() {
+        let graph = build(&[
+            ("s", "a"), ("a", "b"), ("b", "a"), ("b", "c"), ("c", "c"), ("c", "t"), ("s", "t"),
+        ]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let cyclic: Vec<Vec<&str>> = graph
+            .cyclic_components()
+            .iter()
+            .map(|component| graph.path_names(component))
+            .collect();
+        assert_eq!(cyclic, vec![vec!["c"], vec!["a", "b"]]);
+
+        let condensation = graph.condense();
+        assert_eq!(condensation.graph.len(), 4);
+        let ab = condensation.component_of[id("a").index()];
+        assert_eq!(condensation.component_of[id("b").index()], ab);
+        assert_eq!(condensation.graph.name(ab), "{a,b}");
+        // The self-loop on c disappears, so the condensation is a DAG
+        let s = condensation.component_of[id("s").index()];
+        assert!(condensation.graph.topological_order(s).is_ok());
+    }
+
+    #[test]
+    fn test_weakly_connected_components() {
+        let mut graph = build(&[("a", "b"), ("c", "b"), ("d", "e"), ("e", "d")]);
+        graph.add_node("lonely");
+        let names = |component: &Vec<NodeId>| graph.path_names(component);
+
+        let components = graph.weakly_connected_components();
+        assert_eq!(components.iter().map(names).collect::<Vec<_>>(), vec![vec!["a", "b", "c"], vec!["d", "e"], vec!["lonely"]]);
+        assert!(Graph::new().weakly_connected_components().is_empty());
+    }
+
+    #[test]
+    fn test_dominators() {
+        // Every a -> f path goes through c and e; b and d are bypassed, x is unreachable
+        let graph = build(&[
+            ("a", "b"), ("a", "c"), ("b", "c"), ("c", "d"), ("c", "e"), ("d", "e"), ("e", "c"),
+            ("e", "f"), ("x", "f"),
+        ]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let idom = graph.immediate_dominators(id("a"));
+        assert_eq!(idom[id("a").index()], Some(id("a")));
+        assert_eq!(idom[id("c").index()], Some(id("a")));
+        assert_eq!(idom[id("d").index()], Some(id("c")));
+        assert_eq!(idom[id("e").index()], Some(id("c")));
+        assert_eq!(idom[id("x").index()], None);
+
+        let chain = graph.dominators_of(id("a"), id("f"));
+        assert_eq!(graph.path_names(&chain), vec!["a", "c", "e", "f"]);
+        assert!(graph.dominators_of(id("f"), id("a")).is_empty());
+    }
+
+    #[test]
+    fn test_iter_paths_lazily() {
+        let graph = build(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "a"), ("b", "c")]);
+        let id = |name| graph.node_id(name).unwrap();
+
+        let paths: Vec<Vec<&str>> = iter_paths(&graph, id("a"), id("d"))
+            .map(|path| graph.path_names(&path))
+            .collect();
+        assert_eq!(paths, vec![vec!["a", "b", "d"], vec!["a", "b", "c", "d"], vec!["a", "c", "d"]]);
+
+        assert_eq!(iter_paths(&graph, id("a"), id("d")).take(1).count(), 1);
+        assert_eq!(iter_paths(&graph, id("a"), id("a")).collect::<Vec<_>>(), vec![vec![id("a")]]);
+        let graph = build(&[("a", "b"), ("c", "a")]);
+        assert_eq!(iter_paths(&graph, graph.node_id("a").unwrap(), graph.node_id("c").unwrap()).count(), 0);
+    }
+}
@@ -0,0 +1,192 @@
+// Dense 2D grids addressed by (x, y), with x the column and y the row
+
+use crate::error::{AocError, Result};
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// The 8 surrounding offsets (Moore neighbourhood)
+pub const MOORE: [(isize, isize); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0),           (1, 0),
+    (-1, 1),  (0, 1),  (1, 1),
+];
+
+/// The 4 orthogonal offsets (Von Neumann neighbourhood)
+pub const VON_NEUMANN: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// A width x height grid stored row-major
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone + Default> Grid<T> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::filled(width, height, T::default())
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn filled(width: usize, height: usize, value: T) -> Self {
+        Grid { width, height, cells: vec![value; width * height] }
+    }
+}
+
+impl<T> Grid<T> {
+    /// Parses a character map, one row per non-blank line. `cell` returns
+    /// `None` for characters it doesn't accept; rows must all be equally long.
+    pub fn parse(input: &str, mut cell: impl FnMut(char) -> Option<T>) -> Result<Self> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = Vec::new();
+        for (line_no, line) in input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let before = cells.len();
+            for (col, c) in line.chars().enumerate() {
+                let value = cell(c).ok_or_else(|| {
+                    AocError::parse_at_column(line_no + 1, col + 1, format!("Invalid cell character: {}", c))
+                })?;
+                cells.push(value);
+            }
+            let row_width = cells.len() - before;
+            if *width.get_or_insert(row_width) != row_width {
+                return Err(AocError::parse_at(line_no + 1, format!(
+                    "Row has {} cells, expected {}", row_width, width.unwrap_or_default()
+                )));
+            }
+            height += 1;
+        }
+        Ok(Grid { width: width.unwrap_or(0), height, cells })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        (x < self.width && y < self.height).then(|| &self.cells[y * self.width + x])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        (x < self.width && y < self.height).then(|| &mut self.cells[y * self.width + x])
+    }
+
+    /// Stores `value` at (x, y); returns false, leaving the grid alone, when
+    /// that is off the grid
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> bool {
+        match self.get_mut(x, y) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// (x + dx, y + dy) if it is on the grid
+    pub fn offset(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let nx = x.checked_add_signed(dx).filter(|&nx| nx < self.width)?;
+        let ny = y.checked_add_signed(dy).filter(|&ny| ny < self.height)?;
+        Some((nx, ny))
+    }
+
+    /// On-grid positions among the 8 cells around (x, y)
+    pub fn moore_neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        MOORE.iter().filter_map(move |&(dx, dy)| self.offset(x, y, dx, dy))
+    }
+
+    /// On-grid positions among the 4 cells orthogonally next to (x, y)
+    pub fn von_neumann_neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        VON_NEUMANN.iter().filter_map(move |&(dx, dy)| self.offset(x, y, dx, dy))
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(x).step_by(self.width.max(1)).take(self.height)
+    }
+
+    /// Every cell with its (x, y), row by row
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.cells.iter().enumerate().map(move |(i, cell)| ((i % self.width, i / self.width), cell))
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        self.get(x, y).unwrap_or_else(|| panic!("({}, {}) is outside the {}x{} grid", x, y, self.width, self.height))
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        let (width, height) = (self.width, self.height);
+        self.get_mut(x, y).unwrap_or_else(|| panic!("({}, {}) is outside the {}x{} grid", x, y, width, height))
+    }
+}
+
+/// One line per row, each cell written with its own `Display`
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_access() {
+        let mut grid = Grid::parse("#..\n\n.#.\n", |c| Some(c == '#')).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(1, 1), Some(&true));
+        assert_eq!(grid.get(3, 0), None);
+        assert!(grid.set(2, 1, true));
+        assert!(!grid.set(0, 2, true));
+        assert_eq!(grid.row(1), &[false, true, true]);
+        assert_eq!(grid.column(0).copied().collect::<Vec<_>>(), vec![true, false]);
+        assert_eq!(grid.iter().filter(|(_, &cell)| cell).count(), 3);
+
+        let err = Grid::parse("..\n.x\n", |c| (c == '.').then_some(c)).unwrap_err();
+        assert_eq!(err.to_string(), "Line 2, column 2: Invalid cell character: x");
+        assert!(Grid::parse("..\n...\n", Some).is_err());
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let grid: Grid<u8> = Grid::new(3, 3);
+        assert_eq!(grid.moore_neighbors(1, 1).count(), 8);
+        assert_eq!(grid.moore_neighbors(0, 0).collect::<Vec<_>>(), vec![(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(grid.von_neumann_neighbors(2, 2).collect::<Vec<_>>(), vec![(2, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_display() {
+        let grid = Grid::parse("ab\ncd", Some).unwrap();
+        assert_eq!(grid.to_string(), "ab\ncd\n");
+    }
+}
@@ -0,0 +1,556 @@
+// Sets of integers stored as sorted, merged inclusive ranges, and piecewise
+// offset maps over them (like the almanac maps of AoC 2023 day 5)
+
+use std::collections::BTreeSet;
+use std::ops::{Add, Sub};
+
+/// Integer types an `IntervalSet` can hold. `MIN` and `MAX` let intervals
+/// reach the ends of the type without stepping past them.
+pub trait Bound: Copy + Ord + From<u8> + Add<Output = Self> + Sub<Output = Self> {
+    const MIN: Self;
+    const MAX: Self;
+
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_bound {
+    ($($t:ty),*) => {
+        $(
+            impl Bound for $t {
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+
+                fn checked_sub(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_bound!(u8, u16, u32, u64, u128, usize, i16, i32, i64, i128, isize);
+
+/// The values `start..=end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval<T> {
+    pub start: T,
+    pub end: T,
+}
+
+impl<T: Bound> Interval<T> {
+    pub fn new(start: T, end: T) -> Self {
+        Interval { start, end }
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        value >= self.start && value <= self.end
+    }
+
+    /// Number of values in the interval; overflows when it spans the whole type
+    pub fn count(&self) -> T {
+        self.end - self.start + T::from(1)
+    }
+
+    /// `count`, `None` when that doesn't fit in `T`
+    pub fn checked_count(&self) -> Option<T> {
+        self.end.checked_sub(self.start)?.checked_add(T::from(1))
+    }
+
+    pub fn intersection(&self, other: &Interval<T>) -> Option<Interval<T>> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (start <= end).then(|| Interval::new(start, end))
+    }
+
+    /// The parts of `self` below and above `other`
+    fn minus(&self, other: &Interval<T>) -> [Option<Interval<T>>; 2] {
+        let below = (self.start < other.start).then(|| Interval::new(self.start, self.end.min(other.start - T::from(1))));
+        let above = (self.end > other.end).then(|| Interval::new(self.start.max(other.end + T::from(1)), self.end));
+        [below, above]
+    }
+
+    /// Whether `next`, which starts no earlier than `self`, can be folded into it
+    fn overlaps_or_adjacent(&self, next: &Interval<T>) -> bool {
+        // Nothing lies past `MAX`, so don't step over it
+        self.end == T::MAX || next.start <= self.end + T::from(1)
+    }
+}
+
+/// The values complement operations are taken within: every value of the
+/// type, or only those of one window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Universe<T> {
+    /// `None` for a window with nothing in it
+    bounds: Option<Interval<T>>,
+}
+
+impl<T: Bound> Universe<T> {
+    /// Every value from `T::MIN` to `T::MAX`
+    pub fn full() -> Self {
+        Universe { bounds: Some(Interval::new(T::MIN, T::MAX)) }
+    }
+
+    /// The values `lo..=hi`; empty when `lo > hi`
+    pub fn new(lo: T, hi: T) -> Self {
+        Universe { bounds: (lo <= hi).then(|| Interval::new(lo, hi)) }
+    }
+
+    pub fn bounds(&self) -> Option<Interval<T>> {
+        self.bounds
+    }
+}
+
+impl<T: Bound> Default for Universe<T> {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+/// Union of intervals, kept sorted with overlapping and adjacent ones merged
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Bound> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut ranges: Vec<Interval<T>> = iter.into_iter().collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut intervals: Vec<Interval<T>> = Vec::with_capacity(ranges.len());
+        for next in ranges {
+            match intervals.last_mut() {
+                Some(current) if current.overlaps_or_adjacent(&next) => current.end = current.end.max(next.end),
+                _ => intervals.push(next),
+            }
+        }
+        IntervalSet { intervals }
+    }
+}
+
+impl<T: Bound> IntervalSet<T> {
+    /// The merged intervals, in ascending order
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        // The last interval starting at or before `value` is the only candidate
+        let idx = self.intervals.partition_point(|range| range.start <= value);
+        idx > 0 && self.intervals[idx - 1].contains(value)
+    }
+
+    /// Number of distinct values covered; overflows when that's more than
+    /// `T` can hold
+    pub fn count(&self) -> T {
+        self.intervals.iter().fold(T::from(0), |total, range| total + range.count())
+    }
+
+    /// `count`, `None` when that doesn't fit in `T`
+    pub fn checked_count(&self) -> Option<T> {
+        self.intervals
+            .iter()
+            .try_fold(T::from(0), |total, range| total.checked_add(range.checked_count()?))
+    }
+
+    /// Number of values in `range` the set covers, worked out from the
+    /// intervals overlapping it rather than value by value; overflows when
+    /// that's more than `T` can hold
+    pub fn intersect_range(&self, range: Interval<T>) -> T {
+        self.overlapping(range).fold(T::from(0), |total, part| total + part.count())
+    }
+
+    /// `intersect_range`, `None` when that doesn't fit in `T`
+    pub fn checked_intersect_range(&self, range: Interval<T>) -> Option<T> {
+        self.overlapping(range).try_fold(T::from(0), |total, part| total.checked_add(part.checked_count()?))
+    }
+
+    /// The parts of the set inside `range`, in ascending order
+    fn overlapping(&self, range: Interval<T>) -> impl Iterator<Item = Interval<T>> + '_ {
+        let first = self.intervals.partition_point(|interval| interval.end < range.start);
+        self.intervals[first..].iter().map_while(move |interval| interval.intersection(&range))
+    }
+
+    /// The values in `lo..=hi` the set doesn't cover; empty when `lo > hi`
+    pub fn complement_within(&self, lo: T, hi: T) -> IntervalSet<T> {
+        self.complement(Universe::new(lo, hi))
+    }
+
+    /// The values of `universe` the set doesn't cover
+    pub fn complement(&self, universe: Universe<T>) -> IntervalSet<T> {
+        let mut gaps = Vec::new();
+        let Some(Interval { start: lo, end: hi }) = universe.bounds else {
+            return IntervalSet { intervals: gaps };
+        };
+        // Skip intervals that end before the window
+        let first = self.intervals.partition_point(|range| range.end < lo);
+        let mut cursor = lo;
+        for range in &self.intervals[first..] {
+            if range.start > hi {
+                break;
+            }
+            if range.start > cursor {
+                gaps.push(Interval::new(cursor, range.start - T::from(1)));
+            }
+            // Stopping here also keeps `range.end + 1` from overflowing
+            if range.end >= hi {
+                return IntervalSet { intervals: gaps };
+            }
+            cursor = cursor.max(range.end + T::from(1));
+        }
+        gaps.push(Interval::new(cursor, hi));
+        IntervalSet { intervals: gaps }
+    }
+}
+
+/// `IntervalSet` that remembers where its values came from: each merged
+/// interval keeps the tags of every input interval folded into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedIntervalSet<T, S> {
+    set: IntervalSet<T>,
+    /// Parallel to `set.intervals`
+    tags: Vec<BTreeSet<S>>,
+}
+
+impl<T, S> Default for TaggedIntervalSet<T, S> {
+    fn default() -> Self {
+        TaggedIntervalSet { set: IntervalSet { intervals: Vec::new() }, tags: Vec::new() }
+    }
+}
+
+impl<T: Bound, S: Ord> FromIterator<(Interval<T>, S)> for TaggedIntervalSet<T, S> {
+    fn from_iter<I: IntoIterator<Item = (Interval<T>, S)>>(iter: I) -> Self {
+        let mut ranges: Vec<(Interval<T>, S)> = iter.into_iter().collect();
+        ranges.sort_by_key(|(r, _)| r.start);
+
+        let mut merged = TaggedIntervalSet::default();
+        for (next, tag) in ranges {
+            match merged.set.intervals.last_mut() {
+                Some(current) if current.overlaps_or_adjacent(&next) => {
+                    current.end = current.end.max(next.end);
+                    merged.tags.last_mut().expect("one tag set per interval").insert(tag);
+                }
+                _ => {
+                    merged.set.intervals.push(next);
+                    merged.tags.push(BTreeSet::from([tag]));
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl<T: Bound, S: Ord> TaggedIntervalSet<T, S> {
+    /// The merged intervals without their tags
+    pub fn set(&self) -> &IntervalSet<T> {
+        &self.set
+    }
+
+    /// Each merged interval, in ascending order, with the tags it came from
+    pub fn iter(&self) -> impl Iterator<Item = (Interval<T>, &BTreeSet<S>)> {
+        self.set.intervals.iter().copied().zip(&self.tags)
+    }
+
+    /// Tags of the merged interval holding `value`, `None` if no interval does
+    pub fn tags_at(&self, value: T) -> Option<&BTreeSet<S>> {
+        let idx = self.set.intervals.partition_point(|range| range.start <= value);
+        (idx > 0 && self.set.intervals[idx - 1].contains(value)).then(|| &self.tags[idx - 1])
+    }
+}
+
+/// Sends `source` to the interval of the same length starting at `dest_start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapSegment<T> {
+    pub source: Interval<T>,
+    pub dest_start: T,
+}
+
+impl<T: Bound> MapSegment<T> {
+    pub fn new(source: Interval<T>, dest_start: T) -> Self {
+        MapSegment { source, dest_start }
+    }
+
+    /// Where `value`, which must lie in `source`, ends up
+    fn map(&self, value: T) -> T {
+        value - self.source.start + self.dest_start
+    }
+
+    /// The segment restricted to `part` of its source
+    fn restrict(&self, part: Interval<T>) -> Self {
+        MapSegment { source: part, dest_start: self.map(part.start) }
+    }
+}
+
+/// Shifts values by segment; values no segment covers map to themselves.
+/// Where segments overlap, the one listed first wins.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PiecewiseMap<T> {
+    segments: Vec<MapSegment<T>>,
+}
+
+impl<T: Bound> FromIterator<MapSegment<T>> for PiecewiseMap<T> {
+    fn from_iter<I: IntoIterator<Item = MapSegment<T>>>(iter: I) -> Self {
+        PiecewiseMap { segments: iter.into_iter().collect() }
+    }
+}
+
+impl<T: Bound> PiecewiseMap<T> {
+    pub fn new(segments: Vec<MapSegment<T>>) -> Self {
+        PiecewiseMap { segments }
+    }
+
+    pub fn map_value(&self, value: T) -> T {
+        self.segments
+            .iter()
+            .find(|segment| segment.source.contains(value))
+            .map_or(value, |segment| segment.map(value))
+    }
+
+    /// Splits `interval` at segment boundaries; each piece comes with the
+    /// start of its image
+    fn split(&self, interval: Interval<T>) -> Vec<(Interval<T>, T)> {
+        let mut pending = vec![interval];
+        let mut pieces = Vec::new();
+        for segment in &self.segments {
+            let mut rest = Vec::new();
+            for piece in pending {
+                match piece.intersection(&segment.source) {
+                    Some(hit) => {
+                        pieces.push((hit, segment.map(hit.start)));
+                        rest.extend(piece.minus(&segment.source).into_iter().flatten());
+                    }
+                    None => rest.push(piece),
+                }
+            }
+            pending = rest;
+        }
+        pieces.extend(pending.into_iter().map(|piece| (piece, piece.start)));
+        pieces
+    }
+
+    /// Image of every value in `set`
+    pub fn apply(&self, set: &IntervalSet<T>) -> IntervalSet<T> {
+        set.intervals()
+            .iter()
+            .flat_map(|&interval| self.split(interval))
+            .map(|(piece, start)| Interval::new(start, start + (piece.end - piece.start)))
+            .collect()
+    }
+
+    /// The segments with overlaps resolved, so no two sources intersect
+    fn disjoint_segments(&self) -> Vec<MapSegment<T>> {
+        let mut disjoint = Vec::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            let mut parts = vec![segment.source];
+            for earlier in &self.segments[..i] {
+                parts = parts.iter().flat_map(|part| part.minus(&earlier.source)).flatten().collect();
+            }
+            disjoint.extend(parts.into_iter().map(|part| segment.restrict(part)));
+        }
+        disjoint
+    }
+
+    /// The map that applies `self` and then `next`
+    pub fn then(&self, next: &PiecewiseMap<T>) -> PiecewiseMap<T> {
+        let first = self.disjoint_segments();
+        let mut segments = Vec::new();
+
+        // Values `self` moves: follow their image through `next`
+        for segment in &first {
+            let image = Interval::new(segment.dest_start, segment.map(segment.source.end));
+            for (piece, dest_start) in next.split(image) {
+                let source_start = segment.source.start + (piece.start - segment.dest_start);
+                let source = Interval::new(source_start, source_start + (piece.end - piece.start));
+                segments.push(MapSegment::new(source, dest_start));
+            }
+        }
+
+        // Values `self` leaves alone: only `next` applies
+        for segment in next.disjoint_segments() {
+            let mut parts = vec![segment.source];
+            for moved in &first {
+                parts = parts.iter().flat_map(|part| part.minus(&moved.source)).flatten().collect();
+            }
+            segments.extend(parts.into_iter().map(|part| segment.restrict(part)));
+        }
+
+        PiecewiseMap { segments }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_merges_overlapping_and_adjacent() {
+        let set: IntervalSet<u64> = [Interval::new(10, 14), Interval::new(3, 5), Interval::new(12, 18), Interval::new(6, 7)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.intervals(), &[Interval::new(3, 7), Interval::new(10, 18)]);
+        assert_eq!(set.count(), 14);
+        assert!(set.contains(3) && set.contains(7) && set.contains(18));
+        assert!(!set.contains(2) && !set.contains(8) && !set.contains(19));
+    }
+
+    #[test]
+    fn test_tagged_merge_keeps_sources() {
+        let set: TaggedIntervalSet<u64, &str> = [
+            (Interval::new(10, 14), "b"),
+            (Interval::new(3, 5), "a"),
+            (Interval::new(12, 18), "a"),
+            (Interval::new(30, 31), "c"),
+            (Interval::new(6, 7), "a"),
+        ]
+        .into_iter()
+        .collect();
+        let merged: Vec<(Interval<u64>, Vec<&str>)> = set.iter().map(|(r, tags)| (r, tags.iter().copied().collect())).collect();
+        assert_eq!(
+            merged,
+            vec![(Interval::new(3, 7), vec!["a"]), (Interval::new(10, 18), vec!["a", "b"]), (Interval::new(30, 31), vec!["c"])]
+        );
+        assert_eq!(set.set().count(), 16);
+        assert_eq!(set.tags_at(11).map(|tags| tags.len()), Some(2));
+        assert_eq!(set.tags_at(20), None);
+    }
+
+    #[test]
+    fn test_complement_within() {
+        let set: IntervalSet<u64> = [Interval::new(3, 7), Interval::new(10, 18)].into_iter().collect();
+        assert_eq!(set.complement_within(0, 20).intervals(), &[Interval::new(0, 2), Interval::new(8, 9), Interval::new(19, 20)]);
+        assert_eq!(set.complement_within(5, 12).intervals(), &[Interval::new(8, 9)]);
+        assert_eq!(set.complement_within(8, 9).intervals(), &[Interval::new(8, 9)]);
+        assert!(set.complement_within(11, 15).intervals().is_empty());
+        assert!(set.complement_within(9, 8).intervals().is_empty());
+
+        let full: IntervalSet<u8> = [Interval::new(250, 255)].into_iter().collect();
+        assert_eq!(full.complement_within(0, 255).intervals(), &[Interval::new(0, 249)]);
+        assert_eq!(full.complement(Universe::full()), full.complement_within(0, 255));
+        assert!(full.complement(Universe::new(1, 0)).intervals().is_empty());
+    }
+
+    #[test]
+    fn test_type_boundaries() {
+        // Merging into an interval that already ends at MAX
+        let set: IntervalSet<u64> = [Interval::new(10, u64::MAX), Interval::new(20, 30), Interval::new(u64::MAX, u64::MAX)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.intervals(), &[Interval::new(10, u64::MAX)]);
+        assert_eq!(set.count(), u64::MAX - 9);
+        assert_eq!(set.complement(Universe::full()).intervals(), &[Interval::new(0, 9)]);
+
+        let everything: IntervalSet<u64> = [Interval::new(0, 5), Interval::new(6, u64::MAX)].into_iter().collect();
+        assert_eq!(everything.intervals(), &[Interval::new(0, u64::MAX)]);
+        assert_eq!(everything.checked_count(), None);
+        assert!(everything.complement(Universe::full()).intervals().is_empty());
+        assert_eq!(IntervalSet::<u64>::default().complement(Universe::full()), everything);
+        assert_eq!(Interval::new(i64::MIN, i64::MAX).checked_count(), None);
+        assert_eq!(Interval::new(i64::MIN, -2).checked_count(), Some(i64::MAX));
+        assert_eq!(everything.checked_intersect_range(Interval::new(0, u64::MAX)), None);
+        assert_eq!(everything.checked_intersect_range(Interval::new(1, u64::MAX)), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_intersect_range() {
+        let set: IntervalSet<u64> = [Interval::new(3, 7), Interval::new(10, 18), Interval::new(30, 30)].into_iter().collect();
+        assert_eq!(set.intersect_range(Interval::new(0, 100)), set.count());
+        assert_eq!(set.intersect_range(Interval::new(5, 12)), 3 + 3);
+        assert_eq!(set.intersect_range(Interval::new(8, 9)), 0);
+        assert_eq!(set.intersect_range(Interval::new(30, 30)), 1);
+        assert_eq!(set.intersect_range(Interval::new(31, 40)), 0);
+    }
+
+    /// Intervals of u8 that often touch 0 or 255
+    fn u8_interval() -> impl Strategy<Value = Interval<u8>> {
+        let end = prop_oneof![Just(0u8), Just(u8::MAX), any::<u8>()];
+        (end.clone(), end).prop_map(|(a, b)| Interval::new(a.min(b), a.max(b)))
+    }
+
+    /// The values of `set` one by one
+    fn members(set: &IntervalSet<u8>) -> Vec<bool> {
+        (0..=u8::MAX).map(|value| set.contains(value)).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_merge_matches_membership(intervals in prop::collection::vec(u8_interval(), 0..8)) {
+            let set: IntervalSet<u8> = intervals.iter().copied().collect();
+            let expected: Vec<bool> = (0..=u8::MAX).map(|value| intervals.iter().any(|r| r.contains(value))).collect();
+            prop_assert_eq!(members(&set), expected.clone());
+            let covered = expected.iter().filter(|&&member| member).count();
+            prop_assert_eq!(set.checked_count().map(usize::from), (covered <= 255).then_some(covered));
+            // Merged intervals are sorted, disjoint and not adjacent
+            for pair in set.intervals().windows(2) {
+                prop_assert!(pair[0].end < u8::MAX && pair[0].end + 1 < pair[1].start);
+            }
+        }
+
+        #[test]
+        fn prop_intersect_range_matches_membership(intervals in prop::collection::vec(u8_interval(), 0..8), range in u8_interval()) {
+            let set: IntervalSet<u8> = intervals.into_iter().collect();
+            let covered = (range.start..=range.end).filter(|&value| set.contains(value)).count();
+            prop_assert_eq!(set.checked_intersect_range(range).map(usize::from), (covered <= 255).then_some(covered));
+        }
+
+        #[test]
+        fn prop_complement_partitions_universe(intervals in prop::collection::vec(u8_interval(), 0..8), lo: u8, hi: u8) {
+            let set: IntervalSet<u8> = intervals.into_iter().collect();
+            let gaps = set.complement(Universe::new(lo, hi));
+            for value in 0..=u8::MAX {
+                let in_universe = lo <= value && value <= hi;
+                prop_assert_eq!(gaps.contains(value), in_universe && !set.contains(value), "value {}", value);
+            }
+            let full = set.complement(Universe::full());
+            prop_assert_eq!(members(&full), members(&set).iter().map(|member| !member).collect::<Vec<_>>());
+        }
+    }
+
+    /// 2023 day 5's seed-to-soil and soil-to-fertilizer maps, the second
+    /// with a segment overlapping an earlier one
+    fn almanac() -> (PiecewiseMap<u64>, PiecewiseMap<u64>) {
+        let seed_to_soil = PiecewiseMap::new(vec![
+            MapSegment::new(Interval::new(98, 99), 50),
+            MapSegment::new(Interval::new(50, 97), 52),
+        ]);
+        let soil_to_fertilizer = PiecewiseMap::new(vec![
+            MapSegment::new(Interval::new(15, 51), 0),
+            MapSegment::new(Interval::new(52, 53), 37),
+            MapSegment::new(Interval::new(0, 14), 39),
+            MapSegment::new(Interval::new(40, 60), 200),
+        ]);
+        (seed_to_soil, soil_to_fertilizer)
+    }
+
+    #[test]
+    fn test_apply_splits_at_segment_boundaries() {
+        let (seed_to_soil, soil_to_fertilizer) = almanac();
+        assert_eq!(seed_to_soil.map_value(79), 81);
+        assert_eq!(seed_to_soil.map_value(10), 10);
+        // The overlapping segment only covers what earlier ones don't
+        assert_eq!(soil_to_fertilizer.map_value(45), 30);
+        assert_eq!(soil_to_fertilizer.map_value(55), 215);
+
+        let seeds: IntervalSet<u64> = [Interval::new(79, 92), Interval::new(95, 100)].into_iter().collect();
+        let soil = seed_to_soil.apply(&seeds);
+        assert_eq!(soil.intervals(), &[Interval::new(50, 51), Interval::new(81, 94), Interval::new(97, 100)]);
+        assert_eq!(soil.count(), seeds.count());
+    }
+
+    #[test]
+    fn test_composition_matches_sequential_application() {
+        let (seed_to_soil, soil_to_fertilizer) = almanac();
+        let composed = seed_to_soil.then(&soil_to_fertilizer);
+        for value in 0..120 {
+            assert_eq!(composed.map_value(value), soil_to_fertilizer.map_value(seed_to_soil.map_value(value)), "value {}", value);
+        }
+
+        let seeds: IntervalSet<u64> = [Interval::new(0, 30), Interval::new(45, 110)].into_iter().collect();
+        assert_eq!(composed.apply(&seeds), soil_to_fertilizer.apply(&seed_to_soil.apply(&seeds)));
+    }
+}
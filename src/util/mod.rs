@@ -0,0 +1,19 @@
+// Reusable building blocks shared across days
+pub mod cancel;
+pub mod expr;
+pub mod graph;
+pub mod grid;
+pub mod interval;
+pub mod json;
+pub mod output;
+pub mod parse;
+pub mod polyomino;
+pub mod progress;
+pub mod render;
+pub mod reporter;
+pub mod stats;
+pub mod strings;
+pub mod theme;
+pub mod trace;
+#[cfg(test)]
+pub mod snapshot;
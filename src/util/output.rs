@@ -0,0 +1,267 @@
+// Where a day's output goes. Every `run` takes a `&dyn OutputSink` and sends
+// its lines (a sink is a `Reporter`), answers, progress and finally the run
+// report through it, so `--output` can capture all of it as structured events
+// in a file while the terminal stays clean.
+
+use crate::error::{AocError, Result};
+use crate::util::json::{self, Value};
+use crate::util::progress::{self, Hidden, Progress};
+use crate::util::reporter::{Captured, Reporter, Silent, Stdout};
+use crate::util::stats::Report;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub trait OutputSink: Reporter {
+    /// A part's answer; `label` says what it is, as the line would have read
+    /// "label: value"
+    fn answer(&self, _part: u8, label: &str, value: &dyn fmt::Display) {
+        self.line(&format!("{}: {}", label, value));
+    }
+
+    /// An answer shown with how it was worked out, as the line
+    /// "label: working = value"; structured sinks also record the value alone
+    fn worked_answer(&self, _part: u8, label: &str, working: &str, value: &dyn fmt::Display) {
+        self.line(&format!("{}: {} = {}", label, working, value));
+    }
+
+    /// Several lines at once, as `print!` would have written them; a
+    /// trailing newline is dropped rather than becoming an empty line
+    fn block(&self, text: &str) {
+        self.line(text.strip_suffix('\n').unwrap_or(text));
+    }
+
+    /// Where long-running solvers report how far they've got
+    fn progress(&self) -> Box<dyn Progress + '_> {
+        progress::stderr()
+    }
+
+    /// The run report, once the day has finished
+    fn stats(&self, _report: &Report) {}
+
+    /// Flushes anything buffered, failing if an earlier write didn't make it
+    fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The terminal: lines and answers on stdout, progress on stderr
+impl OutputSink for Stdout {}
+
+impl OutputSink for Silent {
+    fn progress(&self) -> Box<dyn Progress + '_> {
+        Box::new(Hidden)
+    }
+}
+
+/// Answers are kept as the lines they'd have printed
+impl OutputSink for Captured {
+    fn progress(&self) -> Box<dyn Progress + '_> {
+        Box::new(Hidden)
+    }
+}
+
+/// A file opened for writing, remembering the first write that failed since
+/// `Reporter::line` has no way to report it
+struct Writer {
+    path: String,
+    file: Mutex<BufWriter<File>>,
+    error: Mutex<Option<io::Error>>,
+}
+
+impl Writer {
+    fn create(path: &str) -> Result<Self> {
+        let file = File::create(path).map_err(|e| AocError::io(path, e))?;
+        Ok(Writer { path: path.to_string(), file: Mutex::new(BufWriter::new(file)), error: Mutex::new(None) })
+    }
+
+    fn write_line(&self, text: &str) {
+        if let Err(err) = writeln!(self.file.lock().unwrap(), "{}", text) {
+            self.error.lock().unwrap().get_or_insert(err);
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        let flushed = self.file.lock().unwrap().flush();
+        match self.error.lock().unwrap().take() {
+            Some(err) => Err(AocError::io(&self.path, err)),
+            None => flushed.map_err(|e| AocError::io(&self.path, e)),
+        }
+    }
+}
+
+/// Plain text, exactly as it would have been printed; no progress
+pub struct TextFile(Writer);
+
+impl TextFile {
+    pub fn create(path: &str) -> Result<Self> {
+        Writer::create(path).map(TextFile)
+    }
+}
+
+impl Reporter for TextFile {
+    fn line(&self, text: &str) {
+        self.0.write_line(text);
+    }
+}
+
+impl OutputSink for TextFile {
+    fn progress(&self) -> Box<dyn Progress + '_> {
+        Box::new(Hidden)
+    }
+
+    fn finish(&self) -> Result<()> {
+        self.0.finish()
+    }
+}
+
+/// One JSON object per line, each with an `event` of `line`, `answer`,
+/// `progress` or `stats` and the day it came from
+pub struct JsonLines {
+    writer: Writer,
+    day: u8,
+}
+
+impl JsonLines {
+    pub fn create(path: &str, day: u8) -> Result<Self> {
+        Ok(JsonLines { writer: Writer::create(path)?, day })
+    }
+
+    fn event(&self, kind: &str, fields: Vec<(&str, Value)>) {
+        let header = [("event", Value::from(kind)), ("day", Value::from(self.day as usize))];
+        self.writer.write_line(&Value::object(header.into_iter().chain(fields)).to_string());
+    }
+}
+
+impl Reporter for JsonLines {
+    fn line(&self, text: &str) {
+        self.event("line", vec![("text", Value::from(text))]);
+    }
+}
+
+impl OutputSink for JsonLines {
+    fn answer(&self, part: u8, label: &str, value: &dyn fmt::Display) {
+        let fields = vec![
+            ("part", Value::from(part as usize)),
+            ("label", Value::from(label.trim())),
+            ("value", Value::from(value.to_string().as_str())),
+        ];
+        self.event("answer", fields);
+    }
+
+    fn worked_answer(&self, part: u8, label: &str, working: &str, value: &dyn fmt::Display) {
+        self.line(&format!("{}: {} = {}", label, working, value));
+        self.answer(part, label, value);
+    }
+
+    fn progress(&self) -> Box<dyn Progress + '_> {
+        Box::new(ProgressEvents { sink: self, phase: Mutex::new(None), done: AtomicU64::new(0) })
+    }
+
+    fn stats(&self, report: &Report) {
+        let report = json::parse(&report.to_json()).expect("the run report is valid JSON");
+        self.event("stats", vec![("report", report)]);
+    }
+
+    fn finish(&self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// Progress for `JsonLines`: an event when each phase starts and one with
+/// its final count and message when it finishes, not one per step
+struct ProgressEvents<'a> {
+    sink: &'a JsonLines,
+    /// Label, total and latest message of the current phase
+    phase: Mutex<Option<(String, u64, String)>>,
+    done: AtomicU64,
+}
+
+impl ProgressEvents<'_> {
+    fn emit(&self, status: &str) {
+        let Some((label, total, message)) = self.phase.lock().unwrap().clone() else {
+            return;
+        };
+        self.sink.event(
+            "progress",
+            vec![
+                ("status", Value::from(status)),
+                ("label", Value::from(label.as_str())),
+                ("done", Value::Number(self.done.load(Ordering::Relaxed) as f64)),
+                ("total", Value::Number(total as f64)),
+                ("message", Value::from(message.as_str())),
+            ],
+        );
+    }
+}
+
+impl Progress for ProgressEvents<'_> {
+    fn start(&self, label: &str, total: u64) {
+        self.done.store(0, Ordering::Relaxed);
+        *self.phase.lock().unwrap() = Some((label.to_string(), total, String::new()));
+        self.emit("start");
+    }
+
+    fn inc(&self, delta: u64) {
+        self.done.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    fn set_message(&self, message: &str) {
+        if let Some((_, _, current)) = self.phase.lock().unwrap().as_mut() {
+            *current = message.to_string();
+        }
+    }
+
+    fn finish(&self) {
+        self.emit("finish");
+    }
+}
+
+/// Whether `--output FILE` writes JSON lines rather than plain text
+pub fn is_json_lines(path: &str) -> bool {
+    path.ends_with(".jsonl")
+}
+
+/// The sink for `--output FILE`: JSON lines for a `.jsonl` file, plain text
+/// for anything else
+pub fn to_file(path: &str, day: u8) -> Result<Box<dyn OutputSink>> {
+    Ok(if is_json_lines(path) { Box::new(JsonLines::create(path, day)?) } else { Box::new(TextFile::create(path)?) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_lines_events() {
+        let path = std::env::temp_dir().join(format!("output-events-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let sink = JsonLines::create(path, 4).unwrap();
+        sink.line("Parsed \"lot\"");
+        sink.answer(2, "  Total rolls removed", &43);
+        let progress = sink.progress();
+        progress.start("Stages", 3);
+        progress.inc(2);
+        progress.set_message("2 stages");
+        progress.finish();
+        sink.worked_answer(1, "Product", "5 * 8", &40);
+        sink.finish().unwrap();
+
+        let events: Vec<Value> = std::fs::read_to_string(path).unwrap().lines().map(|line| json::parse(line).unwrap()).collect();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[0].to_string(), r#"{"event":"line","day":4,"text":"Parsed \"lot\""}"#);
+        assert_eq!(events[1].to_string(), r#"{"event":"answer","day":4,"part":2,"label":"Total rolls removed","value":"43"}"#);
+        assert_eq!(events[3].get("done"), Some(&Value::from(2)));
+        assert_eq!(events[3].get("message"), Some(&Value::from("2 stages")));
+        assert_eq!(events[4].get("text"), Some(&Value::from("Product: 5 * 8 = 40")));
+        assert_eq!(events[5].get("value"), Some(&Value::from("40")));
+
+        let captured = Captured::new();
+        captured.answer(1, "Safe value", &50);
+        captured.worked_answer(2, "Product", "5 * 8", &40);
+        assert_eq!(captured.text(), "Safe value: 50\nProduct: 5 * 8 = 40");
+    }
+}
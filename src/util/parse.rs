@@ -0,0 +1,139 @@
+// Parsing helpers for the input shapes several days share. Line and column
+// numbers in errors are 1-based and refer to the original input, blank
+// lines included.
+
+use crate::error::{AocError, Result};
+use crate::util::grid::Grid;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Non-blank lines with their 1-based line numbers, trimmed
+pub fn lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+/// `text` as a number, reporting `line` and `column` if it isn't one
+pub fn number<T>(text: &str, line: usize, column: usize) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    text.trim()
+        .parse()
+        .map_err(|e| AocError::parse_at_column(line, column, format!("Invalid number '{}': {}", text.trim(), e)))
+}
+
+/// Exactly `N` comma-separated numbers, e.g. `3,-4,5`
+pub fn tuple<T, const N: usize>(line: &str, line_no: usize) -> Result<[T; N]>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let mut values = Vec::with_capacity(N);
+    let mut column = 1;
+    for field in line.split(',') {
+        let indent = field.len() - field.trim_start().len();
+        values.push(number(field, line_no, column + indent)?);
+        column += field.len() + 1;
+    }
+    let found = values.len();
+    values
+        .try_into()
+        .map_err(|_| AocError::parse_at(line_no, format!("{} values, expected {} comma-separated values", found, N)))
+}
+
+/// One `tuple` per non-blank line
+pub fn tuples<T, const N: usize>(input: &str) -> Result<Vec<[T; N]>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    lines(input).map(|(line_no, line)| tuple(line, line_no)).collect()
+}
+
+/// A run of consecutive non-blank lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// 1-based number of the section's first line
+    pub first_line: usize,
+    pub lines: Vec<&'a str>,
+}
+
+impl<'a> Section<'a> {
+    /// The section's lines with their 1-based line numbers, trimmed
+    pub fn numbered(&self) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        self.lines.iter().enumerate().map(|(i, line)| (self.first_line + i, line.trim()))
+    }
+}
+
+/// The input split on blank lines; runs of several blank lines count as one separator
+pub fn sections(input: &str) -> Vec<Section<'_>> {
+    let mut sections: Vec<Section> = Vec::new();
+    let mut in_section = false;
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            in_section = false;
+            continue;
+        }
+        match sections.last_mut() {
+            Some(section) if in_section => section.lines.push(line),
+            _ => sections.push(Section { first_line: i + 1, lines: vec![line] }),
+        }
+        in_section = true;
+    }
+    sections
+}
+
+/// A `label: item item ...` line, split into the label and its whitespace-separated items
+pub fn labeled(line: &str, line_no: usize) -> Result<(&str, Vec<&str>)> {
+    let (label, rest) = line
+        .split_once(':')
+        .filter(|(_, rest)| !rest.contains(':'))
+        .ok_or_else(|| AocError::parse_at(line_no, "invalid format, expected 'label: item item ...'"))?;
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(AocError::parse_at_column(line_no, 1, "missing label before ':'"));
+    }
+    Ok((label, rest.split_whitespace().collect()))
+}
+
+/// A rectangular map of characters, one row per non-blank line
+pub fn char_grid(input: &str) -> Result<Grid<char>> {
+    Grid::parse(input, Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuples_report_line_and_column() {
+        assert_eq!(tuples::<i32, 3>("1,2,3\n\n-4, 5 ,6\n").unwrap(), vec![[1, 2, 3], [-4, 5, 6]]);
+
+        let err = tuples::<i32, 3>("1,2,3\n\n4, x,6\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 3, column 4: Invalid number 'x': invalid digit found in string");
+        let err = tuple::<u32, 2>("1,2,3", 7).unwrap_err();
+        assert_eq!(err.to_string(), "Line 7: 3 values, expected 2 comma-separated values");
+    }
+
+    #[test]
+    fn test_sections() {
+        let sections = sections("a\nb\n\n\nc\n\nd\ne\n\n");
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[1], Section { first_line: 5, lines: vec!["c"] });
+        assert_eq!(sections[2].numbered().collect::<Vec<_>>(), vec![(7, "d"), (8, "e")]);
+    }
+
+    #[test]
+    fn test_labeled() {
+        assert_eq!(labeled("aaa: bbb ccc", 1).unwrap(), ("aaa", vec!["bbb", "ccc"]));
+        assert_eq!(labeled("out:", 1).unwrap(), ("out", vec![]));
+        assert_eq!(labeled("a b c", 4).unwrap_err().to_string(), "Line 4: invalid format, expected 'label: item item ...'");
+        assert!(labeled("a: b: c", 1).is_err());
+        assert!(labeled(": b", 1).is_err());
+    }
+}
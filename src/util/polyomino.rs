@@ -0,0 +1,2578 @@
+// Packing polyominoes into rectangular boards: shape orientations plus SAT,
+// backtracking and Dancing Links backends behind a common `Packer`
+
+use crate::error::{AocError, Result};
+use crate::util::cancel::CancellationToken;
+use crate::util::reporter::{Reporter, Silent};
+use crate::util::stats;
+use crate::util::trace::{self, Event};
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+use web_time::Instant;
+use varisat::{ExtendFormula, CnfFormula, Var, Lit, Solver};
+
+#[derive(Debug, Clone)]
+pub struct Shape {
+    pub id: usize,
+    pub grid: Vec<Vec<char>>, // 3x3 grid
+}
+
+#[derive(Debug, Clone)]
+pub struct ProblemSpace {
+    pub width: usize,
+    pub height: usize, // "long" dimension
+    pub shape_counts: Vec<usize>, // Count for each shape ID (index = shape ID)
+    pub blocked: HashSet<Coords>, // Cells no piece may cover; empty for a plain rectangle
+}
+
+impl ProblemSpace {
+    pub fn is_open(&self, c: Coords) -> bool {
+        c.x >= 0 && c.x < self.width as i32 && c.y >= 0 && c.y < self.height as i32 && !self.blocked.contains(&c)
+    }
+
+    /// Cells pieces may cover
+    pub fn open_cells(&self) -> usize {
+        self.width * self.height - self.blocked.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Coords {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Placement {
+    pub shape_id: usize,
+    pub instance: usize,
+    pub x: i32,
+    pub y: i32,
+    pub cells: Vec<Coords>, // Actual grid cells occupied by this placement
+}
+
+impl Shape {
+    fn get_cells(&self) -> Vec<Coords> {
+        let mut cells = Vec::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, &ch) in row.iter().enumerate() {
+                if ch == '#' {
+                    cells.push(Coords { x: x as i32, y: y as i32 });
+                }
+            }
+        }
+        cells
+    }
+
+    fn rotate_90(cells: &[Coords]) -> Vec<Coords> {
+        cells.iter().map(|c| Coords { x: -c.y, y: c.x }).collect()
+    }
+
+    fn flip_horizontal(cells: &[Coords]) -> Vec<Coords> {
+        cells.iter().map(|c| Coords { x: -c.x, y: c.y }).collect()
+    }
+
+    fn normalize(cells: &[Coords]) -> Vec<Coords> {
+        if cells.is_empty() {
+            return Vec::new();
+        }
+        let min_x = cells.iter().map(|c| c.x).min().unwrap();
+        let min_y = cells.iter().map(|c| c.y).min().unwrap();
+        let mut normalized: Vec<Coords> = cells
+            .iter()
+            .map(|c| Coords { x: c.x - min_x, y: c.y - min_y })
+            .collect();
+        normalized.sort_by_key(|c| (c.y, c.x));
+        normalized
+    }
+
+    pub fn get_unique_transformations(&self) -> Vec<Vec<Coords>> {
+        let base_cells = self.get_cells();
+        let mut transformations = HashSet::new();
+
+        // Try all 4 rotations
+        let mut current = base_cells.clone();
+        for _ in 0..4 {
+            transformations.insert(Self::normalize(&current));
+            current = Self::rotate_90(&current);
+        }
+
+        // Try flipped + 4 rotations
+        let flipped = Self::flip_horizontal(&base_cells);
+        let mut current = flipped;
+        for _ in 0..4 {
+            transformations.insert(Self::normalize(&current));
+            current = Self::rotate_90(&current);
+        }
+
+        // HashSet automatically deduplicates, so symmetric shapes
+        // will have fewer transformations. Sorted so every call enumerates
+        // placements in the same order, which symmetry breaking relies on.
+        let mut transformations: Vec<Vec<Coords>> = transformations.into_iter().collect();
+        transformations.sort_by_key(|cells| cells.iter().map(|c| (c.y, c.x)).collect::<Vec<_>>());
+        transformations
+    }
+
+    pub fn count_cells(&self) -> usize {
+        self.grid.iter()
+            .flat_map(|row| row.iter())
+            .filter(|&&ch| ch == '#')
+            .count()
+    }
+}
+
+/// One orientation as per-row bitmasks (bit x set for a cell in column x)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrientationMask {
+    pub rows: Vec<u64>,
+    pub width: usize,
+}
+
+impl OrientationMask {
+    fn new(cells: &[Coords]) -> Self {
+        let height = cells.iter().map(|c| c.y as usize + 1).max().unwrap_or(0);
+        let width = cells.iter().map(|c| c.x as usize + 1).max().unwrap_or(0);
+        let mut rows = vec![0u64; height];
+        for c in cells {
+            rows[c.y as usize] |= 1 << c.x;
+        }
+        OrientationMask { rows, width }
+    }
+}
+
+/// Unique orientations of every shape, computed once per input and shared
+/// by all solvers
+#[derive(Debug, Clone)]
+pub struct ShapeCatalog {
+    /// Indexed by shape ID; empty for IDs the input doesn't define
+    pub transforms: Vec<Vec<Vec<Coords>>>,
+    /// Bitmask form of `transforms`, for the backtracker's bitboard
+    pub masks: Vec<Vec<OrientationMask>>,
+}
+
+impl ShapeCatalog {
+    /// Every shape needs at least one cell; `day12::parse_input` rejects any
+    /// that don't
+    pub fn new(shapes: &[Shape]) -> Self {
+        let len = shapes.iter().map(|s| s.id + 1).max().unwrap_or(0);
+        let mut transforms = vec![Vec::new(); len];
+        for shape in shapes {
+            transforms[shape.id] = shape.get_unique_transformations();
+        }
+        let masks = transforms
+            .iter()
+            .map(|orientations| orientations.iter().map(|cells| OrientationMask::new(cells)).collect())
+            .collect();
+        ShapeCatalog { transforms, masks }
+    }
+
+    pub fn orientations(&self, shape_id: usize) -> Result<&[Vec<Coords>]> {
+        match self.transforms.get(shape_id) {
+            Some(t) if !t.is_empty() => Ok(t),
+            _ => Err(AocError::parse(format!("Shape {} not found", shape_id))),
+        }
+    }
+
+    pub fn cell_count(&self, shape_id: usize) -> usize {
+        self.transforms[shape_id][0].len()
+    }
+}
+
+pub fn generate_placements(
+    transformations: &[Vec<Coords>],
+    shape_id: usize,
+    instance: usize,
+    space: &ProblemSpace,
+) -> Vec<Placement> {
+    let mut placements = Vec::new();
+
+    for transform in transformations {
+        for y in 0..space.height as i32 {
+            for x in 0..space.width as i32 {
+                let cells: Vec<Coords> = transform
+                    .iter()
+                    .map(|c| Coords { x: x + c.x, y: y + c.y })
+                    .collect();
+
+                if cells.iter().all(|&c| space.is_open(c)) {
+                    placements.push(Placement {
+                        shape_id,
+                        instance,
+                        x,
+                        y,
+                        cells,
+                    });
+                }
+            }
+        }
+    }
+
+    placements
+}
+
+/// Index into `catalog.orientations(placement.shape_id)` of the orientation
+/// that, shifted by the placement's (x, y), covers exactly its cells
+pub fn orientation_index(catalog: &ShapeCatalog, placement: &Placement) -> Option<usize> {
+    let cells: HashSet<Coords> = placement.cells.iter().copied().collect();
+    catalog.transforms.get(placement.shape_id)?.iter().position(|transform| {
+        transform.len() == cells.len()
+            && transform.iter().all(|c| cells.contains(&Coords { x: placement.x + c.x, y: placement.y + c.y }))
+    })
+}
+
+/// Checks that `solution` packs `space` without trusting whichever backend
+/// (or file) it came from: every piece is a real orientation of its shape on
+/// open cells, no cell is covered twice, and each shape appears exactly as
+/// many times as the space asks for, as instances 0, 1, ...
+pub fn check_solution(catalog: &ShapeCatalog, space: &ProblemSpace, solution: &[Placement]) -> Result<()> {
+    let mut covered = HashSet::new();
+    let mut instances: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for placement in solution {
+        let piece = format!("Shape {} instance {}", placement.shape_id, placement.instance);
+        if orientation_index(catalog, placement).is_none() {
+            return Err(AocError::solve(format!("{} at ({}, {}) isn't an orientation of the shape", piece, placement.x, placement.y)));
+        }
+        for &cell in &placement.cells {
+            if !space.is_open(cell) {
+                return Err(AocError::solve(format!("{} covers ({}, {}), which is off the board or blocked", piece, cell.x, cell.y)));
+            }
+            if !covered.insert(cell) {
+                return Err(AocError::solve(format!("{} covers ({}, {}), which another piece already covers", piece, cell.x, cell.y)));
+            }
+        }
+        let wanted = space.shape_counts.get(placement.shape_id).copied().unwrap_or(0);
+        if placement.instance >= wanted {
+            return Err(AocError::solve(format!("{} is one more than the {} the space asks for", piece, wanted)));
+        }
+        if !instances.entry(placement.shape_id).or_default().insert(placement.instance) {
+            return Err(AocError::solve(format!("{} is placed twice", piece)));
+        }
+    }
+
+    for (shape_id, &wanted) in space.shape_counts.iter().enumerate() {
+        let placed = instances.get(&shape_id).map_or(0, HashSet::len);
+        if placed != wanted {
+            return Err(AocError::solve(format!("Shape {} is placed {} times, but the space asks for {}", shape_id, placed, wanted)));
+        }
+    }
+    Ok(())
+}
+
+pub fn solve_with_sat(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+) -> Result<Option<Vec<Placement>>> {
+    solve_with_sat_narrated(catalog, space, &Silent)
+}
+
+/// How "at most one of these literals" constraints are written to CNF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AtMostOne {
+    /// One binary clause per pair: O(k²) clauses, no auxiliary variables
+    Pairwise,
+    /// Sinz's sequential counter: O(k) clauses and k-1 auxiliary variables
+    Sequential,
+}
+
+/// Below this many literals the pairwise encoding is no larger than the
+/// sequential counter, so it's used regardless of the requested encoding
+const PAIRWISE_THRESHOLD: usize = 5;
+
+fn add_at_most_one(formula: &mut CnfFormula, lits: &[Lit], encoding: AtMostOne, next_var: &mut usize) {
+    if encoding == AtMostOne::Pairwise || lits.len() < PAIRWISE_THRESHOLD {
+        for i in 0..lits.len() {
+            for j in i + 1..lits.len() {
+                formula.add_clause(&[!lits[i], !lits[j]]);
+            }
+        }
+        return;
+    }
+
+    // s_i is true when one of lits[0..=i] is true
+    let counters: Vec<Lit> = (0..lits.len() - 1)
+        .map(|_| {
+            let var = Var::from_index(*next_var);
+            *next_var += 1;
+            var.positive()
+        })
+        .collect();
+
+    formula.add_clause(&[!lits[0], counters[0]]);
+    for i in 1..lits.len() - 1 {
+        formula.add_clause(&[!lits[i], counters[i]]);
+        formula.add_clause(&[!counters[i - 1], counters[i]]);
+        formula.add_clause(&[!lits[i], !counters[i - 1]]);
+    }
+    formula.add_clause(&[!lits[lits.len() - 1], !counters[lits.len() - 2]]);
+}
+
+/// Symmetry breaking between interchangeable instances: if `next` picks
+/// placement k then `previous` must have picked one with a smaller index.
+///
+/// `prefix[k]` may only be true when `previous` chose an index <= k, so
+/// "next picks k" implies `prefix[k - 1]`. Both slices list the same
+/// placements in the same order.
+fn add_ordered_after(formula: &mut CnfFormula, previous: &[Lit], next: &[Lit], next_var: &mut usize) {
+    if next.is_empty() {
+        return;
+    }
+    formula.add_clause(&[!next[0]]);
+
+    let mut prefix: Vec<Lit> = Vec::with_capacity(previous.len());
+    for (k, &chosen) in previous.iter().enumerate().take(next.len() - 1) {
+        let var = Var::from_index(*next_var);
+        *next_var += 1;
+        let lit = var.positive();
+        match prefix.last() {
+            Some(&before) => formula.add_clause(&[!lit, before, chosen]),
+            None => formula.add_clause(&[!lit, chosen]),
+        }
+        formula.add_clause(&[!next[k + 1], lit]);
+        prefix.push(lit);
+    }
+}
+
+struct SatEncoding {
+    formula: CnfFormula,
+    var_to_placement: HashMap<Var, Placement>,
+    /// Per shape ID, one literal per instance that must be assumed for the
+    /// instance to be placed; without it the instance may stay off the board
+    selectors: Vec<Vec<Lit>>,
+    num_vars: usize,
+}
+
+impl SatEncoding {
+    /// Selectors requiring the first `counts[shape]` instances of each shape
+    fn assumptions(&self, counts: &[usize]) -> Vec<Lit> {
+        self.selectors
+            .iter()
+            .zip(counts)
+            .flat_map(|(selectors, &count)| selectors[..count.min(selectors.len())].iter().copied())
+            .collect()
+    }
+
+    /// Pieces of each shape named by the solver's failed assumptions. An
+    /// instance is only placed once the instances before it are, so a core
+    /// naming instance i of a shape needs i + 1 of its pieces.
+    fn core_counts(&self, core: &[Lit]) -> Vec<usize> {
+        self.selectors
+            .iter()
+            .map(|selectors| selectors.iter().rposition(|lit| core.contains(lit)).map_or(0, |i| i + 1))
+            .collect()
+    }
+}
+
+/// Where the `count` instances of one shape could go
+struct ShapePlacements {
+    shape_id: usize,
+    count: usize,
+    placements: Vec<Placement>,
+}
+
+/// Placements of every shape `space` needs, or None when that already proves
+/// it unsatisfiable: some shape fits nowhere, or the cells any placement
+/// covers are too few for all the pieces. Placements don't depend on the
+/// instance, so each shape's are generated once, for instance 0.
+fn sat_placements(catalog: &ShapeCatalog, space: &ProblemSpace, out: &dyn Reporter) -> Result<Option<Vec<ShapePlacements>>> {
+    let mut shapes = Vec::new();
+    let mut coverable: HashSet<Coords> = HashSet::new();
+    let mut required_cells = 0;
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let placements = generate_placements(catalog.orientations(shape_idx)?, shape_idx, 0, space);
+        if placements.is_empty() {
+            out.line(&format!("Shape {} fits nowhere on the board", shape_idx));
+            return Ok(None);
+        }
+        coverable.extend(placements.iter().flat_map(|placement| placement.cells.iter().copied()));
+        required_cells += count * catalog.cell_count(shape_idx);
+        shapes.push(ShapePlacements { shape_id: shape_idx, count, placements });
+    }
+
+    if coverable.len() < required_cells {
+        out.line(&format!("Pieces need {} cells but placements only reach {}", required_cells, coverable.len()));
+        return Ok(None);
+    }
+    Ok(Some(shapes))
+}
+
+/// The CNF formula for packing `space`, or None when `sat_placements` proves
+/// it unsatisfiable without one
+fn encode_sat(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    encoding: AtMostOne,
+    out: &dyn Reporter,
+) -> Result<Option<SatEncoding>> {
+    let total_pieces: usize = space.shape_counts.iter().sum();
+    out.line(&format!("Generating placements for {} total pieces...", total_pieces));
+    let Some(shapes) = sat_placements(catalog, space, out)? else {
+        return Ok(None);
+    };
+    Ok(Some(encode_placements(shapes, space.shape_counts.len(), encoding, out)))
+}
+
+/// The CNF formula for packing the pieces `shapes` lists, on a board with
+/// `shape_kinds` shape IDs
+fn encode_placements(shapes: Vec<ShapePlacements>, shape_kinds: usize, encoding: AtMostOne, out: &dyn Reporter) -> SatEncoding {
+    let mut formula = CnfFormula::new();
+    let mut var_to_placement = HashMap::new();
+    let mut cell_to_placements: HashMap<Coords, Vec<Lit>> = HashMap::new();
+    let mut selectors = vec![Vec::new(); shape_kinds];
+    let mut next_var = 1usize;
+
+    for ShapePlacements { shape_id: shape_idx, count, placements: shape_placements } in shapes {
+        let mut previous_instance: Option<Vec<Lit>> = None;
+        for instance in 0..count {
+            let placements = shape_placements.iter().map(|placement| Placement { instance, ..placement.clone() });
+            out.line(&format!("  Shape {} instance {}: {} possible placements", shape_idx, instance, shape_placements.len()));
+
+            let mut vars = Vec::with_capacity(shape_placements.len());
+            for placement in placements {
+                let var = Var::from_index(next_var);
+                next_var += 1;
+                for &cell in &placement.cells {
+                    cell_to_placements.entry(cell).or_default().push(var.positive());
+                }
+                var_to_placement.insert(var, placement);
+                vars.push(var.positive());
+            }
+
+            // Every selected instance is placed exactly once
+            let selector = Var::from_index(next_var).positive();
+            next_var += 1;
+            selectors[shape_idx].push(selector);
+            formula.add_clause(&[&vars[..], &[!selector]].concat());
+            add_at_most_one(&mut formula, &vars, encoding, &mut next_var);
+
+            if let Some(previous) = &previous_instance {
+                add_ordered_after(&mut formula, previous, &vars, &mut next_var);
+            }
+            previous_instance = Some(vars);
+        }
+    }
+
+    out.line(&format!("Total placements (variables): {}", var_to_placement.len()));
+    out.line("Encoding grid cell constraints...");
+    for lits in cell_to_placements.values() {
+        add_at_most_one(&mut formula, lits, encoding, &mut next_var);
+    }
+
+    SatEncoding {
+        formula,
+        var_to_placement,
+        selectors,
+        num_vars: next_var - 1,
+    }
+}
+
+fn solve_with_sat_narrated(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    out: &dyn Reporter,
+) -> Result<Option<Vec<Placement>>> {
+    solve_with_sat_encoding(catalog, space, AtMostOne::Sequential, out)
+}
+
+fn solve_with_sat_encoding(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    encoding: AtMostOne,
+    out: &dyn Reporter,
+) -> Result<Option<Vec<Placement>>> {
+    let Some(sat) = encode_sat(catalog, space, encoding, out)? else {
+        out.line("No solution exists (found before encoding)");
+        return Ok(None);
+    };
+
+    out.line(&format!("Solving SAT problem with {} variables and {} clauses...", sat.num_vars, sat.formula.len()));
+    stats::add("polyomino.sat_encodings", 1);
+    stats::add("polyomino.sat_variables", sat.num_vars as u64);
+    stats::add("polyomino.sat_clauses", sat.formula.len() as u64);
+
+    let mut solver = Solver::new();
+    solver.add_formula(&sat.formula);
+    solver.assume(&sat.assumptions(&space.shape_counts));
+
+    stats::add("polyomino.sat_solves", 1);
+    if solver.solve().unwrap() {
+        out.line("Solution found!");
+        let model = solver.model().unwrap();
+        let solution: Vec<Placement> = model
+            .iter()
+            .filter_map(|&lit| {
+                if lit.is_positive() {
+                    sat.var_to_placement.get(&lit.var()).cloned()
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Some(solution))
+    } else {
+        out.line("No solution exists");
+        Ok(None)
+    }
+}
+
+/// Pieces that can't be packed together, found by `explain_unsat`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsatCore {
+    /// Pieces of each shape in the core, indexed by shape ID
+    pub shape_counts: Vec<usize>,
+    /// SAT calls it took to find
+    pub solves: usize,
+    /// Dancing Links' verdict on the core alone, when `Packer::explain` ran
+    /// it: true when it agrees nothing packs, false when it found a packing,
+    /// which means the SAT encoding is wrong. None if unchecked or timed out.
+    pub confirmed: Option<bool>,
+}
+
+impl UnsatCore {
+    pub fn pieces(&self) -> usize {
+        self.shape_counts.iter().sum()
+    }
+}
+
+impl fmt::Display for UnsatCore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pieces: Vec<String> = self
+            .shape_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(shape, count)| format!("{} of shape {}", count, shape))
+            .collect();
+        write!(f, "{} can't be packed together", pieces.join(", "))
+    }
+}
+
+/// Why `space` can't be packed, as a set of pieces that already can't be.
+/// Each instance's "placed exactly once" clause is guarded by a selector
+/// literal; the solver runs assuming the selectors of every requested piece,
+/// and the failed assumptions are then shrunk one piece at a time until
+/// dropping any further piece makes the rest packable. None when the space
+/// can be packed. Fails when the formula is unsatisfiable with no piece
+/// required at all, which only an encoding bug can cause.
+pub fn explain_unsat(catalog: &ShapeCatalog, space: &ProblemSpace) -> Result<Option<UnsatCore>> {
+    let mut shapes = Vec::new();
+    for (shape_id, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let placements = generate_placements(catalog.orientations(shape_id)?, shape_id, 0, space);
+        if placements.is_empty() {
+            // A piece that fits nowhere is the whole story
+            let mut shape_counts = vec![0; space.shape_counts.len()];
+            shape_counts[shape_id] = 1;
+            return Ok(Some(UnsatCore { shape_counts, solves: 0, confirmed: None }));
+        }
+        shapes.push(ShapePlacements { shape_id, count, placements });
+    }
+
+    let sat = encode_placements(shapes, space.shape_counts.len(), AtMostOne::Sequential, &Silent);
+    let mut solver = Solver::new();
+    solver.add_formula(&sat.formula);
+    let mut solves = 0;
+    let mut packable = |solver: &mut Solver, counts: &[usize]| {
+        solver.assume(&sat.assumptions(counts));
+        solves += 1;
+        stats::add("polyomino.sat_solves", 1);
+        solver.solve().unwrap()
+    };
+
+    if !packable(&mut solver, &[]) {
+        return Err(AocError::solve("The SAT encoding is unsatisfiable with no pieces required; the encoding is broken"));
+    }
+    if packable(&mut solver, &space.shape_counts) {
+        return Ok(None);
+    }
+    let mut core = sat.core_counts(solver.failed_core().unwrap_or_default());
+    for shape in 0..core.len() {
+        while core[shape] > 0 {
+            let mut fewer = core.clone();
+            fewer[shape] -= 1;
+            if packable(&mut solver, &fewer) {
+                break;
+            }
+            core = sat.core_counts(solver.failed_core().unwrap_or_default());
+        }
+    }
+
+    stats::add("polyomino.unsat_core_pieces", core.iter().sum::<usize>() as u64);
+    Ok(Some(UnsatCore { shape_counts: core, solves, confirmed: None }))
+}
+
+/// Cooperative time limit, checked once per node by the search backends.
+/// Expires at its own instant or when the caller's token fires, whichever
+/// comes first.
+#[derive(Debug, Clone)]
+struct Deadline {
+    at: Option<Instant>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Deadline {
+    const NONE: Deadline = Deadline { at: None, cancel: None };
+
+    fn after(timeout: Duration) -> Self {
+        Deadline { at: Instant::now().checked_add(timeout), cancel: None }
+    }
+
+    fn or_cancelled(self, cancel: &CancellationToken) -> Self {
+        Deadline { cancel: Some(cancel.clone()), ..self }
+    }
+
+    fn expired(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at) || self.cancel.as_ref().is_some_and(|c| c.is_cancelled())
+    }
+}
+
+/// Outcome of running one backend on one space
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attempt {
+    Solved(Vec<Placement>),
+    NoSolution,
+    TimedOut,
+    /// Ruled out by `find_infeasibility` before any backend ran
+    Infeasible(String),
+}
+
+impl Attempt {
+    pub fn into_solution(self) -> Option<Vec<Placement>> {
+        match self {
+            Attempt::Solved(solution) => Some(solution),
+            Attempt::NoSolution | Attempt::TimedOut | Attempt::Infeasible(_) => None,
+        }
+    }
+}
+
+pub fn solve_with_backtracking(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+) -> Result<Option<Vec<Placement>>> {
+    Ok(solve_with_backtracking_until(catalog, space, Deadline::NONE)?.into_solution())
+}
+
+fn solve_with_backtracking_until(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    deadline: Deadline,
+) -> Result<Attempt> {
+    if space.width > Bitboard::MAX_WIDTH {
+        backtrack_on::<CellGrid>(catalog, space, deadline)
+    } else {
+        backtrack_on::<Bitboard>(catalog, space, deadline)
+    }
+}
+
+fn backtrack_on<B: Board>(catalog: &ShapeCatalog, space: &ProblemSpace, deadline: Deadline) -> Result<Attempt> {
+    let mut board = B::new(space.width, space.height);
+    for cell in &space.blocked {
+        board.toggle_cell(cell.x as usize, cell.y as usize);
+    }
+
+    let mut remaining = vec![0; space.shape_counts.len()];
+    let mut shape_order = Vec::new();
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count > 0 {
+            catalog.orientations(shape_idx)?;
+            remaining[shape_idx] = count;
+            shape_order.push(shape_idx);
+        }
+    }
+
+    // Try the most constrained shapes first (fewest unique transformations, then largest size)
+    shape_order.sort_by_key(|&shape_idx| {
+        let num_transforms = catalog.transforms[shape_idx].len();
+        let num_cells = catalog.cell_count(shape_idx);
+        (num_transforms, -(num_cells as i32))
+    });
+
+    // Cells that can stay uncovered; not enough space at all means no search
+    let needed: usize = shape_order
+        .iter()
+        .map(|&shape_idx| remaining[shape_idx] * catalog.cell_count(shape_idx))
+        .sum();
+    let Some(slack) = space.open_cells().checked_sub(needed) else {
+        return Ok(Attempt::NoSolution);
+    };
+
+    let mut search = Backtracker {
+        catalog,
+        shape_order: &shape_order,
+        remaining,
+        board,
+        solution: Vec::new(),
+        deadline,
+        timed_out: false,
+        nodes: 0,
+    };
+
+    let stop = search.search(0, slack);
+    stats::add("polyomino.backtracking_nodes", search.nodes);
+    if stop {
+        Ok(if search.timed_out { Attempt::TimedOut } else { Attempt::Solved(search.solution) })
+    } else {
+        Ok(Attempt::NoSolution)
+    }
+}
+
+/// Occupancy for the first-empty-cell search. Orientations come as both
+/// their cells and their `OrientationMask`; each board uses whichever suits it.
+trait Board {
+    fn new(width: usize, height: usize) -> Self;
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn is_empty(&self, x: usize, y: usize) -> bool;
+    /// Whether the orientation with its top-left corner at (x, y) stays on
+    /// the board and only covers empty cells
+    fn fits(&self, orientation: &[Coords], mask: &OrientationMask, x: i32, y: i32) -> bool;
+    /// Places or removes the orientation at (x, y); callers check `fits` first
+    fn toggle(&mut self, orientation: &[Coords], mask: &OrientationMask, x: i32, y: i32);
+    fn toggle_cell(&mut self, x: usize, y: usize);
+    /// First empty cell in reading order at or after index `from` (y * width + x)
+    fn first_empty(&self, from: usize) -> Option<(usize, usize)>;
+}
+
+/// One flag per cell, for boards too wide for a `Bitboard`
+struct CellGrid {
+    filled: Vec<bool>,
+    width: usize,
+    height: usize,
+}
+
+impl Board for CellGrid {
+    fn new(width: usize, height: usize) -> Self {
+        CellGrid { filled: vec![false; width * height], width, height }
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        !self.filled[y * self.width + x]
+    }
+
+    fn fits(&self, orientation: &[Coords], _mask: &OrientationMask, x: i32, y: i32) -> bool {
+        orientation.iter().all(|c| {
+            let (cx, cy) = (x + c.x, y + c.y);
+            cx >= 0 && cy >= 0 && (cx as usize) < self.width && (cy as usize) < self.height
+                && self.is_empty(cx as usize, cy as usize)
+        })
+    }
+
+    fn toggle(&mut self, orientation: &[Coords], _mask: &OrientationMask, x: i32, y: i32) {
+        for c in orientation {
+            self.toggle_cell((x + c.x) as usize, (y + c.y) as usize);
+        }
+    }
+
+    fn toggle_cell(&mut self, x: usize, y: usize) {
+        self.filled[y * self.width + x] ^= true;
+    }
+
+    fn first_empty(&self, from: usize) -> Option<(usize, usize)> {
+        (from..self.filled.len()).find(|&i| !self.filled[i]).map(|i| (i % self.width, i / self.width))
+    }
+}
+
+/// Board occupancy as one bitmask per row, so testing and placing a piece
+/// is a few AND/OR operations per row
+struct Bitboard {
+    rows: Vec<u64>,
+    width: usize,
+    full_row: u64,
+}
+
+impl Bitboard {
+    const MAX_WIDTH: usize = 64;
+
+    fn new(width: usize, height: usize) -> Self {
+        let full_row = if width == 0 { 0 } else { u64::MAX >> (Self::MAX_WIDTH - width) };
+        Bitboard { rows: vec![0; height], width, full_row }
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << x) == 0
+    }
+
+    /// Whether `mask` with its top-left corner at (x, y) stays on the board
+    /// and only covers empty cells
+    fn fits(&self, mask: &OrientationMask, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x as usize + mask.width > self.width || y as usize + mask.rows.len() > self.height() {
+            return false;
+        }
+        mask.rows
+            .iter()
+            .enumerate()
+            .all(|(dy, &row)| self.rows[y as usize + dy] & (row << x) == 0)
+    }
+
+    /// Places or removes `mask` at (x, y); callers check `fits` first
+    fn toggle(&mut self, mask: &OrientationMask, x: i32, y: i32) {
+        for (dy, &row) in mask.rows.iter().enumerate() {
+            self.rows[y as usize + dy] ^= row << x;
+        }
+    }
+
+    fn toggle_cell(&mut self, x: usize, y: usize) {
+        self.rows[y] ^= 1 << x;
+    }
+
+    /// First empty cell in reading order at or after index `from` (y * width + x)
+    fn first_empty(&self, from: usize) -> Option<(usize, usize)> {
+        if self.width == 0 {
+            return None;
+        }
+        let (start_x, start_y) = (from % self.width, from / self.width);
+        for y in start_y..self.height() {
+            let mut free = !self.rows[y] & self.full_row;
+            if y == start_y {
+                free &= !((1u64 << start_x) - 1);
+            }
+            if free != 0 {
+                return Some((free.trailing_zeros() as usize, y));
+            }
+        }
+        None
+    }
+}
+
+impl Board for Bitboard {
+    fn new(width: usize, height: usize) -> Self {
+        Bitboard::new(width, height)
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        Bitboard::height(self)
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        Bitboard::is_empty(self, x, y)
+    }
+
+    fn fits(&self, _orientation: &[Coords], mask: &OrientationMask, x: i32, y: i32) -> bool {
+        Bitboard::fits(self, mask, x, y)
+    }
+
+    fn toggle(&mut self, _orientation: &[Coords], mask: &OrientationMask, x: i32, y: i32) {
+        Bitboard::toggle(self, mask, x, y)
+    }
+
+    fn toggle_cell(&mut self, x: usize, y: usize) {
+        Bitboard::toggle_cell(self, x, y)
+    }
+
+    fn first_empty(&self, from: usize) -> Option<(usize, usize)> {
+        Bitboard::first_empty(self, from)
+    }
+}
+
+/// Cells in the holes next to `cells` that are too small for any
+/// remaining piece. Flood fills stop as soon as a hole reaches `min_piece`.
+fn count_dead_cells_around(cells: &[Coords], board: &impl Board, min_piece: usize) -> usize {
+    let (width, height) = (board.width() as i32, board.height() as i32);
+    let is_open = |c: Coords| c.x >= 0 && c.x < width && c.y >= 0 && c.y < height
+        && board.is_empty(c.x as usize, c.y as usize);
+    let neighbors = |c: Coords| [
+        Coords { x: c.x - 1, y: c.y },
+        Coords { x: c.x + 1, y: c.y },
+        Coords { x: c.x, y: c.y - 1 },
+        Coords { x: c.x, y: c.y + 1 },
+    ];
+
+    // Holes are capped at `min_piece` cells, so a linear scan beats hashing
+    let mut seen: Vec<Coords> = Vec::new();
+    let mut dead = 0;
+
+    for start in cells.iter().flat_map(|&c| neighbors(c)) {
+        if !is_open(start) || seen.contains(&start) {
+            continue;
+        }
+        let mut hole = vec![start];
+        let mut stack = vec![start];
+        seen.push(start);
+        while let Some(c) = stack.pop() {
+            if hole.len() >= min_piece {
+                break;
+            }
+            for n in neighbors(c) {
+                if is_open(n) && !seen.contains(&n) {
+                    seen.push(n);
+                    hole.push(n);
+                    stack.push(n);
+                }
+            }
+        }
+        if hole.len() < min_piece {
+            dead += hole.len();
+        }
+    }
+
+    dead
+}
+
+/// State for the first-empty-cell search
+struct Backtracker<'a, B> {
+    catalog: &'a ShapeCatalog,
+    shape_order: &'a [usize],
+    remaining: Vec<usize>,
+    board: B,
+    solution: Vec<Placement>,
+    deadline: Deadline,
+    timed_out: bool,
+    /// Calls to `search`, for the `--stats` report
+    nodes: u64,
+}
+
+impl<B: Board> Backtracker<'_, B> {
+    /// Classic polyomino search: the topmost-leftmost empty cell is either the
+    /// first cell (in reading order) of some piece, or it stays uncovered.
+    /// Pieces don't have to fill the region, so `slack` is how many more cells
+    /// may be left uncovered. Returns true when solved or out of time.
+    fn search(&mut self, cursor: usize, slack: usize) -> bool {
+        if self.shape_order.iter().all(|&shape_id| self.remaining[shape_id] == 0) {
+            return true;
+        }
+        self.nodes += 1;
+        if self.deadline.expired() {
+            self.timed_out = true;
+            return true;
+        }
+
+        let Some((x, y)) = self.board.first_empty(cursor) else {
+            return false;
+        };
+        let next_cursor = y * self.board.width() + x + 1;
+        let catalog = self.catalog;
+
+        for &shape_id in self.shape_order {
+            if self.remaining[shape_id] == 0 {
+                continue;
+            }
+
+            for (transform, mask) in catalog.transforms[shape_id].iter().zip(&catalog.masks[shape_id]) {
+                // Orientations are normalized and sorted, so the first cell is the
+                // one that has to land on (x, y)
+                let offset_x = x as i32 - transform[0].x;
+                let offset_y = y as i32 - transform[0].y;
+                if !self.board.fits(transform, mask, offset_x, offset_y) {
+                    continue;
+                }
+
+                let cells: Vec<Coords> = transform
+                    .iter()
+                    .map(|c| Coords { x: offset_x + c.x, y: offset_y + c.y })
+                    .collect();
+                let instance = self.solution.iter().filter(|p| p.shape_id == shape_id).count();
+                self.board.toggle(transform, mask, offset_x, offset_y);
+                self.remaining[shape_id] -= 1;
+
+                // Holes this piece sealed off that nothing left can fill eat into the slack
+                let min_piece = self.shape_order
+                    .iter()
+                    .filter(|&&id| self.remaining[id] > 0)
+                    .map(|&id| catalog.cell_count(id))
+                    .min();
+                let fits = match min_piece {
+                    // Each neighbor starts at most one hole of under `min_piece` cells,
+                    // so with this much slack the flood fill can't prune anything
+                    Some(min_piece) if slack >= 4 * cells.len() * (min_piece - 1) => true,
+                    Some(min_piece) => count_dead_cells_around(&cells, &self.board, min_piece) <= slack,
+                    None => true,
+                };
+
+                trace::emit(|| Event::Place { shape: shape_id, cells: cells.iter().map(|c| (c.x, c.y)).collect() });
+                self.solution.push(Placement {
+                    shape_id,
+                    instance,
+                    x: offset_x,
+                    y: offset_y,
+                    cells,
+                });
+
+                if fits && self.search(next_cursor, slack) {
+                    return true;
+                }
+
+                trace::emit(|| Event::Unplace);
+                self.solution.pop();
+                self.remaining[shape_id] += 1;
+                self.board.toggle(transform, mask, offset_x, offset_y);
+            }
+        }
+
+        if slack == 0 {
+            return false;
+        }
+        self.board.toggle_cell(x, y);
+        if self.search(next_cursor, slack - 1) {
+            return true;
+        }
+        self.board.toggle_cell(x, y);
+
+        false
+    }
+}
+
+/// What `maximize_coverage` optimizes when not every piece has to be placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Objective {
+    /// Board cells covered by pieces
+    Cells,
+    /// Pieces placed, regardless of size
+    Pieces,
+}
+
+impl std::str::FromStr for Objective {
+    type Err = AocError;
+
+    /// The names `--maximize` takes, for config files
+    fn from_str(s: &str) -> Result<Self> {
+        <Self as ValueEnum>::from_str(s, false)
+            .map_err(|_| AocError::usage(format!("Unknown objective '{}', expected cells or pieces", s)))
+    }
+}
+
+impl Objective {
+    pub fn label(self) -> &'static str {
+        match self {
+            Objective::Cells => "cells",
+            Objective::Pieces => "pieces",
+        }
+    }
+}
+
+/// Best partial packing found by `maximize_coverage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub placements: Vec<Placement>,
+    /// Value of the objective for `placements`
+    pub score: usize,
+    /// Largest score any packing could reach by counting alone
+    pub upper_bound: usize,
+    /// False when the time limit hit before the search could prove `score` optimal
+    pub optimal: bool,
+}
+
+/// Branch and bound over the same first-empty-cell search as the
+/// backtracker, with every piece optional
+struct Maximizer<'a> {
+    catalog: &'a ShapeCatalog,
+    shape_order: &'a [usize],
+    objective: Objective,
+    remaining: Vec<usize>,
+    board: Bitboard,
+    current: Vec<Placement>,
+    covered: usize,
+    best: Coverage,
+    deadline: Deadline,
+    /// Calls to `search`, for the `--stats` report
+    nodes: u64,
+}
+
+impl Maximizer<'_> {
+    fn score(&self) -> usize {
+        match self.objective {
+            Objective::Cells => self.covered,
+            Objective::Pieces => self.current.len(),
+        }
+    }
+
+    /// Best score reachable from here when `free` cells are still empty,
+    /// counting area only
+    fn bound(&self, free: usize) -> usize {
+        let mut sizes: Vec<(usize, usize)> = self.shape_order
+            .iter()
+            .filter(|&&id| self.remaining[id] > 0)
+            .map(|&id| (self.catalog.cell_count(id), self.remaining[id]))
+            .collect();
+
+        let gain = match self.objective {
+            Objective::Cells => free.min(sizes.iter().map(|&(size, count)| size * count).sum()),
+            Objective::Pieces => {
+                // As many pieces as fit by area, smallest first
+                sizes.sort_unstable();
+                let mut room = free;
+                let mut pieces = 0;
+                for (size, count) in sizes {
+                    let fitting = count.min(room / size);
+                    room -= fitting * size;
+                    pieces += fitting;
+                }
+                pieces
+            }
+        };
+        self.score() + gain
+    }
+
+    /// Returns true once the search should stop: out of time, or the best
+    /// packing reached the upper bound
+    fn search(&mut self, cursor: usize, free: usize) -> bool {
+        let score = self.score();
+        if score > self.best.score {
+            self.best.score = score;
+            self.best.placements = self.current.clone();
+            if score == self.best.upper_bound {
+                return true;
+            }
+        }
+        self.nodes += 1;
+        if self.deadline.expired() {
+            self.best.optimal = false;
+            return true;
+        }
+        if self.bound(free) <= self.best.score {
+            return false;
+        }
+
+        let Some((x, y)) = self.board.first_empty(cursor) else {
+            return false;
+        };
+        let next_cursor = y * self.board.width + x + 1;
+        let catalog = self.catalog;
+
+        for &shape_id in self.shape_order {
+            if self.remaining[shape_id] == 0 {
+                continue;
+            }
+
+            for (transform, mask) in catalog.transforms[shape_id].iter().zip(&catalog.masks[shape_id]) {
+                let offset_x = x as i32 - transform[0].x;
+                let offset_y = y as i32 - transform[0].y;
+                if !self.board.fits(mask, offset_x, offset_y) {
+                    continue;
+                }
+
+                let cells: Vec<Coords> = transform
+                    .iter()
+                    .map(|c| Coords { x: offset_x + c.x, y: offset_y + c.y })
+                    .collect();
+                let instance = self.current.iter().filter(|p| p.shape_id == shape_id).count();
+                self.board.toggle(mask, offset_x, offset_y);
+                self.remaining[shape_id] -= 1;
+                self.covered += cells.len();
+                self.current.push(Placement { shape_id, instance, x: offset_x, y: offset_y, cells });
+
+                let stop = self.search(next_cursor, free - transform.len());
+
+                self.current.pop();
+                self.covered -= transform.len();
+                self.remaining[shape_id] += 1;
+                self.board.toggle(mask, offset_x, offset_y);
+                if stop {
+                    return true;
+                }
+            }
+        }
+
+        self.board.toggle_cell(x, y);
+        let stop = self.search(next_cursor, free - 1);
+        self.board.toggle_cell(x, y);
+        stop
+    }
+}
+
+/// Places as many of the space's pieces as possible when they don't all
+/// fit, maximizing `objective`. Stops at `timeout` with the best packing
+/// found so far.
+pub fn maximize_coverage(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    objective: Objective,
+    timeout: Duration,
+) -> Result<Coverage> {
+    if space.width > Bitboard::MAX_WIDTH {
+        return Err(AocError::solve(format!(
+            "Coverage search supports boards up to {} cells wide, got {}x{}",
+            Bitboard::MAX_WIDTH, space.width, space.height
+        )));
+    }
+    let mut board = Bitboard::new(space.width, space.height);
+    for cell in &space.blocked {
+        board.toggle_cell(cell.x as usize, cell.y as usize);
+    }
+
+    let mut remaining = vec![0; space.shape_counts.len()];
+    let mut shape_order = Vec::new();
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count > 0 {
+            catalog.orientations(shape_idx)?;
+            remaining[shape_idx] = count;
+            shape_order.push(shape_idx);
+        }
+    }
+    // Largest pieces first, so early packings already score well on cells
+    shape_order.sort_by_key(|&shape_idx| std::cmp::Reverse(catalog.cell_count(shape_idx)));
+
+    let mut search = Maximizer {
+        catalog,
+        shape_order: &shape_order,
+        objective,
+        remaining,
+        board,
+        current: Vec::new(),
+        covered: 0,
+        best: Coverage { placements: Vec::new(), score: 0, upper_bound: 0, optimal: true },
+        deadline: Deadline::after(timeout),
+        nodes: 0,
+    };
+    search.best.upper_bound = search.bound(space.open_cells());
+    search.search(0, space.open_cells());
+    stats::add("polyomino.coverage_nodes", search.nodes);
+
+    Ok(search.best)
+}
+
+/// Dancing Links matrix for the packing problem.
+///
+/// Pieces don't have to tile the whole region, so this is exact cover with
+/// secondary columns: each shape is a primary column that must be chosen
+/// `remaining` times, each board cell is a secondary column that may be
+/// covered at most once. Rows are the placements of a shape (not of an
+/// instance), so identical pieces don't multiply the matrix.
+struct Dlx {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row_of: Vec<usize>,
+    size: Vec<usize>,
+    remaining: Vec<usize>,
+    cells_per_copy: Vec<usize>,
+    rows: Vec<Placement>,
+    deadline: Deadline,
+    timed_out: bool,
+    /// Calls to `search`, for the `--stats` report
+    nodes: u64,
+}
+
+const DLX_ROOT: usize = 0;
+
+impl Dlx {
+    fn new(primary: &[(usize, usize)], secondary: usize) -> Self {
+        let headers = primary.len() + secondary;
+        let mut dlx = Dlx {
+            left: Vec::new(),
+            right: Vec::new(),
+            up: Vec::new(),
+            down: Vec::new(),
+            column: Vec::new(),
+            row_of: Vec::new(),
+            size: vec![0; headers + 1],
+            remaining: vec![0; headers + 1],
+            cells_per_copy: vec![0; headers + 1],
+            rows: Vec::new(),
+            deadline: Deadline::NONE,
+            timed_out: false,
+            nodes: 0,
+        };
+
+        for node in 0..=headers {
+            dlx.up.push(node);
+            dlx.down.push(node);
+            dlx.column.push(node);
+            dlx.row_of.push(usize::MAX);
+            if node <= primary.len() {
+                // Root and primary headers form the circular header list
+                dlx.left.push(if node == 0 { primary.len() } else { node - 1 });
+                dlx.right.push(if node == primary.len() { 0 } else { node + 1 });
+            } else {
+                dlx.left.push(node);
+                dlx.right.push(node);
+            }
+        }
+
+        for (i, &(count, cells)) in primary.iter().enumerate() {
+            dlx.remaining[i + 1] = count;
+            dlx.cells_per_copy[i + 1] = cells;
+        }
+
+        dlx
+    }
+
+    fn add_row(&mut self, columns: &[usize], placement: Placement) {
+        let row = self.rows.len();
+        self.rows.push(placement);
+        let first = self.column.len();
+
+        for (k, &col) in columns.iter().enumerate() {
+            let node = self.column.len();
+            self.column.push(col);
+            self.row_of.push(row);
+            self.up.push(self.up[col]);
+            self.down.push(col);
+            let last = self.up[col];
+            self.down[last] = node;
+            self.up[col] = node;
+            self.size[col] += 1;
+
+            let prev = if k == 0 { node } else { node - 1 };
+            self.left.push(prev);
+            self.right.push(first);
+            self.right[prev] = node;
+            self.left[first] = node;
+        }
+    }
+
+    fn cover(&mut self, col: usize) {
+        let (l, r) = (self.left[col], self.right[col]);
+        self.right[l] = r;
+        self.left[r] = l;
+
+        let mut i = self.down[col];
+        while i != col {
+            let mut j = self.right[i];
+            while j != i {
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = d;
+                self.up[d] = u;
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, col: usize) {
+        let mut i = self.up[col];
+        while i != col {
+            let mut j = self.left[i];
+            while j != i {
+                self.size[self.column[j]] += 1;
+                let (u, d) = (self.up[j], self.down[j]);
+                self.down[u] = j;
+                self.up[d] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        let (l, r) = (self.left[col], self.right[col]);
+        self.right[l] = col;
+        self.left[r] = col;
+    }
+
+    /// Algorithm X with multiplicities. `last_row` holds, per shape, the
+    /// index of the most recently chosen row so copies of the same shape are
+    /// always picked in increasing row order. Every solution is passed to
+    /// `on_solution`, which returns true to stop the search.
+    fn search(
+        &mut self,
+        free_cells: usize,
+        last_row: &mut [Option<usize>],
+        chosen: &mut Vec<usize>,
+        on_solution: &mut dyn FnMut(&[usize]) -> bool,
+    ) -> bool {
+        if self.right[DLX_ROOT] == DLX_ROOT {
+            return on_solution(chosen);
+        }
+        self.nodes += 1;
+        if self.deadline.expired() {
+            self.timed_out = true;
+            return true;
+        }
+
+        let mut needed = 0;
+        let mut best = DLX_ROOT;
+        let mut col = self.right[DLX_ROOT];
+        while col != DLX_ROOT {
+            needed += self.remaining[col] * self.cells_per_copy[col];
+            if best == DLX_ROOT || self.size[col] < self.size[best] {
+                best = col;
+            }
+            col = self.right[col];
+        }
+
+        if needed > free_cells || self.size[best] == 0 {
+            return false;
+        }
+
+        let previous = last_row[best];
+        let mut r = self.down[best];
+        while r != best {
+            let row = self.row_of[r];
+            if previous.is_some_and(|p| row <= p) {
+                r = self.down[r];
+                continue;
+            }
+
+            let mut j = self.right[r];
+            while j != r {
+                self.cover(self.column[j]);
+                j = self.right[j];
+            }
+            self.remaining[best] -= 1;
+            let exhausted = self.remaining[best] == 0;
+            if exhausted {
+                self.cover(best);
+            }
+            last_row[best] = Some(row);
+            chosen.push(row);
+            trace::emit(|| Event::Place {
+                shape: self.rows[row].shape_id,
+                cells: self.rows[row].cells.iter().map(|c| (c.x, c.y)).collect(),
+            });
+
+            if self.search(free_cells - self.cells_per_copy[best], last_row, chosen, on_solution) {
+                return true;
+            }
+
+            trace::emit(|| Event::Unplace);
+            chosen.pop();
+            last_row[best] = previous;
+            if exhausted {
+                self.uncover(best);
+            }
+            self.remaining[best] += 1;
+            let mut j = self.left[r];
+            while j != r {
+                self.uncover(self.column[j]);
+                j = self.left[j];
+            }
+
+            r = self.down[r];
+        }
+
+        false
+    }
+}
+
+impl Dlx {
+    /// Turns chosen row indices back into placements, numbering the copies
+    /// of each shape in the order they were chosen
+    fn solution(&self, chosen: &[usize], shape_count: usize) -> Vec<Placement> {
+        let mut instances = vec![0; shape_count];
+        chosen
+            .iter()
+            .map(|&row| {
+                let mut placement = self.rows[row].clone();
+                placement.instance = instances[placement.shape_id];
+                instances[placement.shape_id] += 1;
+                placement
+            })
+            .collect()
+    }
+
+    /// Runs the search from scratch, reporting each solution's rows
+    fn run(&mut self, free_cells: usize, on_solution: &mut dyn FnMut(&[usize]) -> bool) {
+        let mut last_row = vec![None; self.size.len()];
+        let mut chosen = Vec::new();
+        let nodes = self.nodes;
+        self.search(free_cells, &mut last_row, &mut chosen, on_solution);
+        stats::add("polyomino.dlx_nodes", self.nodes - nodes);
+    }
+}
+
+/// Builds the cover matrix for a space, or `None` when the pieces can't fit
+/// by area alone
+fn build_dlx(catalog: &ShapeCatalog, space: &ProblemSpace) -> Result<Option<Dlx>> {
+    let mut primary = Vec::new();
+    let mut used_shapes = Vec::new();
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let transformations = catalog.orientations(shape_idx)?;
+        primary.push((count, catalog.cell_count(shape_idx)));
+        used_shapes.push((shape_idx, transformations));
+    }
+
+    let total_cells: usize = primary.iter().map(|&(count, cells)| count * cells).sum();
+    if total_cells > space.open_cells() {
+        return Ok(None);
+    }
+
+    let cell_column = |c: &Coords| 1 + primary.len() + c.y as usize * space.width + c.x as usize;
+    let mut dlx = Dlx::new(&primary, space.width * space.height);
+
+    for (i, &(shape_idx, transformations)) in used_shapes.iter().enumerate() {
+        for placement in generate_placements(transformations, shape_idx, 0, space) {
+            let columns: Vec<usize> = std::iter::once(i + 1)
+                .chain(placement.cells.iter().map(cell_column))
+                .collect();
+            dlx.add_row(&columns, placement);
+        }
+    }
+
+    Ok(Some(dlx))
+}
+
+pub fn solve_with_dlx(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+) -> Result<Option<Vec<Placement>>> {
+    Ok(solve_with_dlx_until(catalog, space, Deadline::NONE)?.into_solution())
+}
+
+fn solve_with_dlx_until(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    deadline: Deadline,
+) -> Result<Attempt> {
+    let Some(mut dlx) = build_dlx(catalog, space)? else {
+        return Ok(Attempt::NoSolution);
+    };
+    dlx.deadline = deadline;
+
+    let mut found = None;
+    dlx.run(space.open_cells(), &mut |chosen| {
+        found = Some(chosen.to_vec());
+        true
+    });
+
+    Ok(match found {
+        Some(chosen) => Attempt::Solved(dlx.solution(&chosen, space.shape_counts.len())),
+        None if dlx.timed_out => Attempt::TimedOut,
+        None => Attempt::NoSolution,
+    })
+}
+
+/// Up to `limit` distinct packings of a space. Copies of the same shape are
+/// interchangeable, so packings that only swap identical pieces count once.
+pub fn enumerate_solutions(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    limit: usize,
+) -> Result<Vec<Vec<Placement>>> {
+    let mut solutions = Vec::new();
+    if limit == 0 {
+        return Ok(solutions);
+    }
+    let Some(mut dlx) = build_dlx(catalog, space)? else {
+        return Ok(solutions);
+    };
+
+    let mut found = Vec::new();
+    dlx.run(space.open_cells(), &mut |chosen| {
+        found.push(chosen.to_vec());
+        found.len() >= limit
+    });
+    solutions.extend(found.iter().map(|chosen| dlx.solution(chosen, space.shape_counts.len())));
+
+    Ok(solutions)
+}
+
+/// Number of distinct packings of a space, stopping once `limit` is reached
+pub fn count_solutions(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    limit: usize,
+) -> Result<usize> {
+    if limit == 0 {
+        return Ok(0);
+    }
+    let Some(mut dlx) = build_dlx(catalog, space)? else {
+        return Ok(0);
+    };
+
+    let mut count = 0;
+    dlx.run(space.open_cells(), &mut |_| {
+        count += 1;
+        count >= limit
+    });
+
+    Ok(count)
+}
+
+/// One of the eight symmetries of a rectangle: an optional transpose (square
+/// boards only) followed by optional mirrors along each axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardSymmetry {
+    pub transpose: bool,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl BoardSymmetry {
+    pub fn apply(self, c: Coords, space: &ProblemSpace) -> Coords {
+        let (mut x, mut y) = if self.transpose { (c.y, c.x) } else { (c.x, c.y) };
+        if self.flip_x {
+            x = space.width as i32 - 1 - x;
+        }
+        if self.flip_y {
+            y = space.height as i32 - 1 - y;
+        }
+        Coords { x, y }
+    }
+}
+
+/// Symmetries of the board that also map its blocked cells onto themselves.
+/// Always contains the identity.
+pub fn board_symmetries(space: &ProblemSpace) -> Vec<BoardSymmetry> {
+    let transposes: &[bool] = if space.width == space.height { &[false, true] } else { &[false] };
+    let mut symmetries = Vec::new();
+    for &transpose in transposes {
+        for flip_x in [false, true] {
+            for flip_y in [false, true] {
+                let symmetry = BoardSymmetry { transpose, flip_x, flip_y };
+                if space.blocked.iter().all(|&c| space.blocked.contains(&symmetry.apply(c, space))) {
+                    symmetries.push(symmetry);
+                }
+            }
+        }
+    }
+    symmetries
+}
+
+/// Order-independent form of a packing: each piece as (shape, sorted cells), pieces sorted
+type PackingKey = Vec<(usize, Vec<(i32, i32)>)>;
+
+fn packing_key<'a>(
+    placements: impl Iterator<Item = &'a Placement>,
+    space: &ProblemSpace,
+    symmetry: BoardSymmetry,
+) -> PackingKey {
+    let mut key: PackingKey = placements
+        .map(|placement| {
+            let mut cells: Vec<(i32, i32)> = placement
+                .cells
+                .iter()
+                .map(|&c| {
+                    let c = symmetry.apply(c, space);
+                    (c.y, c.x)
+                })
+                .collect();
+            cells.sort_unstable();
+            (placement.shape_id, cells)
+        })
+        .collect();
+    key.sort_unstable();
+    key
+}
+
+/// Smallest `packing_key` over the board's symmetries, so packings that are
+/// rotations or reflections of each other share one canonical form
+fn canonical_packing<'a>(
+    placements: impl Iterator<Item = &'a Placement> + Clone,
+    space: &ProblemSpace,
+    symmetries: &[BoardSymmetry],
+) -> PackingKey {
+    symmetries
+        .iter()
+        .map(|&symmetry| packing_key(placements.clone(), space, symmetry))
+        .min()
+        .expect("the identity is always a symmetry")
+}
+
+/// Up to `limit` packings that are distinct up to the board's rotations and
+/// reflections; the first packing found stands for each class
+pub fn enumerate_distinct_solutions(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    limit: usize,
+) -> Result<Vec<Vec<Placement>>> {
+    let mut solutions = Vec::new();
+    if limit == 0 {
+        return Ok(solutions);
+    }
+    let Some(mut dlx) = build_dlx(catalog, space)? else {
+        return Ok(solutions);
+    };
+
+    let symmetries = board_symmetries(space);
+    let rows = dlx.rows.clone();
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    dlx.run(space.open_cells(), &mut |chosen| {
+        if seen.insert(canonical_packing(chosen.iter().map(|&row| &rows[row]), space, &symmetries)) {
+            found.push(chosen.to_vec());
+        }
+        found.len() >= limit
+    });
+    solutions.extend(found.iter().map(|chosen| dlx.solution(chosen, space.shape_counts.len())));
+
+    Ok(solutions)
+}
+
+/// Number of packings distinct up to board symmetry, stopping once `limit` is reached
+pub fn count_distinct_solutions(
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    limit: usize,
+) -> Result<usize> {
+    if limit == 0 {
+        return Ok(0);
+    }
+    let Some(mut dlx) = build_dlx(catalog, space)? else {
+        return Ok(0);
+    };
+
+    let symmetries = board_symmetries(space);
+    let rows = dlx.rows.clone();
+    let mut seen = HashSet::new();
+    dlx.run(space.open_cells(), &mut |chosen| {
+        seen.insert(canonical_packing(chosen.iter().map(|&row| &rows[row]), space, &symmetries));
+        seen.len() >= limit
+    });
+
+    Ok(seen.len())
+}
+
+/// A single search backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverKind {
+    Sat,
+    Backtracking,
+    Dlx,
+}
+
+impl SolverKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            SolverKind::Sat => "SAT",
+            SolverKind::Backtracking => "Backtracking",
+            SolverKind::Dlx => "Dancing Links",
+        }
+    }
+}
+
+fn solve_space(
+    kind: SolverKind,
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    out: &dyn Reporter,
+    deadline: Deadline,
+) -> Result<Attempt> {
+    trace::emit(|| Event::Board { width: space.width, height: space.height });
+    match kind {
+        // varisat can't be interrupted, so SAT ignores the deadline; its
+        // search isn't visible, so only the final packing is traced
+        SolverKind::Sat => Ok(match solve_with_sat_narrated(catalog, space, out)? {
+            Some(solution) => {
+                for placement in &solution {
+                    trace::emit(|| Event::Place {
+                        shape: placement.shape_id,
+                        cells: placement.cells.iter().map(|c| (c.x, c.y)).collect(),
+                    });
+                }
+                Attempt::Solved(solution)
+            }
+            None => Attempt::NoSolution,
+        }),
+        SolverKind::Backtracking => solve_with_backtracking_until(catalog, space, deadline),
+        SolverKind::Dlx => solve_with_dlx_until(catalog, space, deadline),
+    }
+}
+
+/// How `solve_with_strategy` picks a backend per space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Pick from the space's size and fall back to the next backend when one
+    /// runs past the timeout
+    Auto,
+    /// Always use this backend, without a time limit
+    Fixed(SolverKind),
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Strategy::Auto),
+            "sat" => Ok(Strategy::Fixed(SolverKind::Sat)),
+            "backtracking" => Ok(Strategy::Fixed(SolverKind::Backtracking)),
+            "dlx" => Ok(Strategy::Fixed(SolverKind::Dlx)),
+            _ => Err(AocError::usage(format!("Unknown solver '{}', expected auto, sat, backtracking or dlx", s))),
+        }
+    }
+}
+
+impl Strategy {
+    pub fn label(self) -> &'static str {
+        match self {
+            Strategy::Auto => "Auto",
+            Strategy::Fixed(kind) => kind.label(),
+        }
+    }
+}
+
+/// Rough size of a space, used by `Strategy::Auto`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SizeEstimate {
+    cells: usize,
+    pieces: usize,
+    placements: usize,
+    /// Cells left uncovered by a full packing; `None` if the pieces don't fit
+    slack: Option<usize>,
+}
+
+fn estimate_size(catalog: &ShapeCatalog, space: &ProblemSpace) -> Result<SizeEstimate> {
+    let cells = space.open_cells();
+    let mut pieces = 0;
+    let mut placements = 0;
+    let mut needed = 0;
+
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        catalog.orientations(shape_idx)?;
+        pieces += count;
+        needed += count * catalog.cell_count(shape_idx);
+        let positions: usize = catalog.masks[shape_idx]
+            .iter()
+            .map(|mask| {
+                (space.width + 1).saturating_sub(mask.width) * (space.height + 1).saturating_sub(mask.rows.len())
+            })
+            .sum();
+        placements += count * positions;
+    }
+
+    Ok(SizeEstimate { cells, pieces, placements, slack: cells.checked_sub(needed) })
+}
+
+/// Above this many placement variables SAT is slower than the search backends
+const AUTO_SAT_MAX_PLACEMENTS: usize = 200;
+
+/// Backends to try in order for a space of the given size
+fn auto_backends(estimate: &SizeEstimate, width: usize) -> Vec<SolverKind> {
+    if estimate.placements <= AUTO_SAT_MAX_PLACEMENTS {
+        return vec![SolverKind::Sat];
+    }
+    if width > Bitboard::MAX_WIDTH {
+        return vec![SolverKind::Dlx];
+    }
+    if estimate.slack == Some(0) {
+        // Exact tilings are what Algorithm X is built for
+        vec![SolverKind::Dlx, SolverKind::Backtracking]
+    } else {
+        vec![SolverKind::Backtracking, SolverKind::Dlx]
+    }
+}
+
+/// Checkerboard color balance of a shape: how many more cells land on one
+/// color than the other. The same in every orientation and position.
+fn color_imbalance(cells: &[Coords]) -> usize {
+    let dark = cells.iter().filter(|c| (c.x + c.y) % 2 == 0).count();
+    dark.abs_diff(cells.len() - dark)
+}
+
+/// Cheap proofs that `space` has no packing, tried before any solver runs.
+/// Returns the reason for the first check that fails.
+///
+/// - area: the pieces need more cells than the board has open
+/// - coloring: on a checkerboard every piece covers a fixed surplus of one
+///   color, and no choice of signs fits the open cells of each color
+/// - placements: some shape fits nowhere, or more open cells can't be
+///   covered by any piece (typically notched corners) than may stay empty
+///
+/// The placement checks use the bitboard and are skipped for boards wider
+/// than 64 cells.
+pub fn find_infeasibility(catalog: &ShapeCatalog, space: &ProblemSpace) -> Result<Option<String>> {
+    let open = space.open_cells();
+    let mut needed = 0;
+    let mut imbalances = Vec::new();
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let cells = &catalog.orientations(shape_idx)?[0];
+        needed += count * cells.len();
+        imbalances.extend(std::iter::repeat_n(color_imbalance(cells), count));
+    }
+
+    let Some(slack) = open.checked_sub(needed) else {
+        return Ok(Some(format!("area: pieces need {} cells, board has {} open", needed, open)));
+    };
+
+    // Covered dark minus covered light cells is a signed sum of the
+    // imbalances, and each color can't exceed the open cells of that color
+    let dark = (0..space.height as i32)
+        .flat_map(|y| (0..space.width as i32).map(move |x| Coords { x, y }))
+        .filter(|&c| (c.x + c.y) % 2 == 0 && space.is_open(c))
+        .count();
+    let light = open - dark;
+    let (low, high) = (needed as i64 - 2 * light as i64, 2 * dark as i64 - needed as i64);
+    let total: usize = imbalances.iter().sum();
+    let max_imbalance = imbalances.iter().copied().max().unwrap_or(0);
+    // Flipping one piece moves the sum by at most 2 * max_imbalance, so a
+    // window at least that wide that overlaps the range always hits a sum
+    let reachable = if high < -(total as i64) || low > total as i64 {
+        false
+    } else if slack >= max_imbalance {
+        true
+    } else {
+        let mut sums = vec![false; 2 * total + 1];
+        sums[total] = true;
+        for &d in &imbalances {
+            let mut next = vec![false; sums.len()];
+            for (i, _) in sums.iter().enumerate().filter(|(_, &hit)| hit) {
+                next[i - d] = true;
+                next[i + d] = true;
+            }
+            sums = next;
+        }
+        (low.max(-(total as i64))..=high.min(total as i64)).any(|sum| sums[(sum + total as i64) as usize])
+    };
+    if !reachable {
+        return Ok(Some(format!(
+            "coloring: pieces can't be split over {} dark and {} light open cells", dark, light
+        )));
+    }
+
+    if space.width > Bitboard::MAX_WIDTH {
+        return Ok(None);
+    }
+    let mut board = Bitboard::new(space.width, space.height);
+    for cell in &space.blocked {
+        board.toggle_cell(cell.x as usize, cell.y as usize);
+    }
+    let open_rows: Vec<u64> = board.rows.iter().map(|&taken| !taken & board.full_row).collect();
+    let mut coverable = vec![0u64; space.height];
+    let mut all_coverable = false;
+    for (shape_idx, &count) in space.shape_counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let mut fits_somewhere = false;
+        'masks: for mask in &catalog.masks[shape_idx] {
+            for y in 0..space.height as i32 {
+                for x in 0..space.width as i32 {
+                    if board.fits(mask, x, y) {
+                        fits_somewhere = true;
+                        // Once every open cell is known to be coverable only
+                        // the first fit of each shape matters
+                        if all_coverable {
+                            break 'masks;
+                        }
+                        for (dy, &row) in mask.rows.iter().enumerate() {
+                            coverable[y as usize + dy] |= row << x;
+                        }
+                    }
+                }
+            }
+            all_coverable = coverable == open_rows;
+        }
+        if !fits_somewhere {
+            return Ok(Some(format!("placements: shape {} fits nowhere on the board", shape_idx)));
+        }
+    }
+    let uncoverable: usize = coverable
+        .iter()
+        .zip(&open_rows)
+        .map(|(&covered, &open)| (open & !covered).count_ones() as usize)
+        .sum();
+    if needed > 0 && uncoverable > slack {
+        return Ok(Some(format!(
+            "placements: {} open cells can't be covered by any piece, only {} may stay empty",
+            uncoverable, slack
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Runs the feasibility checks, then the backend(s) `strategy` names.
+/// `timeout` only applies to `Strategy::Auto`; `cancel` aborts the whole
+/// solve with `AocError::Timeout` (except inside SAT, which can't be
+/// interrupted).
+pub fn solve_with_strategy(
+    strategy: Strategy,
+    catalog: &ShapeCatalog,
+    space: &ProblemSpace,
+    timeout: Duration,
+    out: &dyn Reporter,
+    cancel: &CancellationToken,
+) -> Result<Attempt> {
+    cancel.check()?;
+    if let Some(reason) = find_infeasibility(catalog, space)? {
+        return Ok(Attempt::Infeasible(reason));
+    }
+
+    let backends = match strategy {
+        Strategy::Fixed(kind) => {
+            let attempt = solve_space(kind, catalog, space, out, Deadline::NONE.or_cancelled(cancel))?;
+            cancel.check()?;
+            return Ok(attempt);
+        }
+        Strategy::Auto => {
+            let estimate = estimate_size(catalog, space)?;
+            out.line(&format!("Size: {} cells, {} pieces, ~{} placements",
+                    estimate.cells, estimate.pieces, estimate.placements));
+            auto_backends(&estimate, space.width)
+        }
+    };
+
+    for kind in backends {
+        out.line(&format!("Trying {} (timeout {:.1}s)", kind.label(), timeout.as_secs_f64()));
+        let attempt = solve_space(kind, catalog, space, out, Deadline::after(timeout).or_cancelled(cancel))?;
+        cancel.check()?;
+        match attempt {
+            Attempt::TimedOut => {
+                out.line(&format!("{} timed out", kind.label()));
+            }
+            attempt => return Ok(attempt),
+        }
+    }
+
+    Ok(Attempt::TimedOut)
+}
+
+/// Builder-style front end: register shapes, set a board, then solve,
+/// count or enumerate packings.
+#[derive(Debug, Clone)]
+pub struct Packer {
+    shapes: Vec<Shape>,
+    catalog: ShapeCatalog,
+    board: ProblemSpace,
+    strategy: Strategy,
+    timeout: Duration,
+    cancel: CancellationToken,
+}
+
+impl Default for Packer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Packer {
+    /// Default time budget per backend for `Strategy::Auto`
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new() -> Self {
+        Packer {
+            shapes: Vec::new(),
+            catalog: ShapeCatalog::new(&[]),
+            board: ProblemSpace { width: 0, height: 0, shape_counts: Vec::new(), blocked: HashSet::new() },
+            strategy: Strategy::Auto,
+            timeout: Self::DEFAULT_TIMEOUT,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Packer for an already parsed shape list
+    pub fn with_shapes(shapes: &[Shape]) -> Self {
+        Packer { shapes: shapes.to_vec(), catalog: ShapeCatalog::new(shapes), ..Self::new() }
+    }
+
+    /// Adds a shape, replacing any earlier one with the same ID
+    pub fn add_shape(&mut self, shape: Shape) -> &mut Self {
+        self.shapes.retain(|s| s.id != shape.id);
+        self.shapes.push(shape);
+        self.catalog = ShapeCatalog::new(&self.shapes);
+        self
+    }
+
+    pub fn set_board(&mut self, board: ProblemSpace) -> &mut Self {
+        self.board = board;
+        self
+    }
+
+    pub fn set_strategy(&mut self, strategy: Strategy, timeout: Duration) -> &mut Self {
+        self.strategy = strategy;
+        self.timeout = timeout;
+        self
+    }
+
+    /// Token that aborts `solve` with `AocError::Timeout` when it fires
+    pub fn set_cancellation(&mut self, cancel: CancellationToken) -> &mut Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn catalog(&self) -> &ShapeCatalog {
+        &self.catalog
+    }
+
+    pub fn board(&self) -> &ProblemSpace {
+        &self.board
+    }
+
+    pub fn solve(&self) -> Result<Attempt> {
+        self.solve_narrated(&Silent)
+    }
+
+    /// `solve`, telling `out` the encoding sizes and backend choices as it goes
+    pub fn solve_narrated(&self, out: &dyn Reporter) -> Result<Attempt> {
+        solve_with_strategy(self.strategy, &self.catalog, &self.board, self.timeout, out, &self.cancel)
+    }
+
+    /// Number of distinct packings, stopping once `limit` is reached
+    pub fn count(&self, limit: usize) -> Result<usize> {
+        count_solutions(&self.catalog, &self.board, limit)
+    }
+
+    /// Up to `limit` distinct packings
+    pub fn enumerate(&self, limit: usize) -> Result<Vec<Vec<Placement>>> {
+        enumerate_solutions(&self.catalog, &self.board, limit)
+    }
+
+    /// Number of packings distinct up to board symmetry, stopping once `limit` is reached
+    pub fn count_distinct(&self, limit: usize) -> Result<usize> {
+        count_distinct_solutions(&self.catalog, &self.board, limit)
+    }
+
+    /// Up to `limit` packings distinct up to board symmetry
+    pub fn enumerate_distinct(&self, limit: usize) -> Result<Vec<Vec<Placement>>> {
+        enumerate_distinct_solutions(&self.catalog, &self.board, limit)
+    }
+
+    /// `explain_unsat` for the board, with the core then re-checked by
+    /// Dancing Links within the configured timeout
+    pub fn explain(&self) -> Result<Option<UnsatCore>> {
+        let Some(mut core) = explain_unsat(&self.catalog, &self.board)? else {
+            return Ok(None);
+        };
+        let pieces = ProblemSpace { shape_counts: core.shape_counts.clone(), ..self.board.clone() };
+        let deadline = Deadline::after(self.timeout).or_cancelled(&self.cancel);
+        core.confirmed = match solve_with_dlx_until(&self.catalog, &pieces, deadline)? {
+            Attempt::Solved(_) => Some(false),
+            Attempt::NoSolution => Some(true),
+            Attempt::TimedOut | Attempt::Infeasible(_) => None,
+        };
+        self.cancel.check()?;
+        Ok(Some(core))
+    }
+
+    /// Best partial packing of the board, within the configured timeout
+    pub fn maximize(&self, objective: Objective) -> Result<Coverage> {
+        maximize_coverage(&self.catalog, &self.board, objective, self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::day12::parse_input;
+    use crate::input::Input;
+
+    #[test]
+    fn test_packer() {
+        let bar = |id| Shape { id, grid: vec!["###".chars().collect(), "...".chars().collect(), "...".chars().collect()] };
+        let mut packer = Packer::new();
+        packer.add_shape(Shape { id: 0, grid: vec![vec!['#'; 3]; 3] }).add_shape(bar(0));
+        assert_eq!(packer.catalog().cell_count(0), 3);
+
+        let board = |width, height, count| ProblemSpace { width, height, shape_counts: vec![count], blocked: HashSet::new() };
+        packer.set_board(board(3, 2, 2));
+        let Attempt::Solved(solution) = packer.solve().unwrap() else {
+            panic!("two bars fill a 3x2 board");
+        };
+        assert_eq!(solution.len(), 2);
+        assert_eq!(packer.count(usize::MAX).unwrap(), 1);
+        assert_eq!(packer.enumerate(10).unwrap().len(), 1);
+
+        // All three bars horizontal or all three vertical
+        packer.set_board(board(3, 3, 3));
+        assert_eq!(packer.count(usize::MAX).unwrap(), 2);
+        packer.set_strategy(Strategy::Fixed(SolverKind::Backtracking), Duration::MAX);
+        assert!(packer.solve().unwrap().into_solution().is_some());
+
+        packer.set_board(board(2, 2, 1));
+        assert!(matches!(packer.solve().unwrap(), Attempt::Infeasible(_)));
+    }
+
+    #[test]
+    fn test_check_solution() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let space = &spaces[0];
+        let solution = solve_with_dlx(&catalog, space).unwrap().unwrap();
+        check_solution(&catalog, space, &solution).unwrap();
+        for placement in &solution {
+            let index = orientation_index(&catalog, placement).unwrap();
+            assert_eq!(catalog.transforms[placement.shape_id][index].len(), placement.cells.len());
+        }
+
+        let broken = |edit: &dyn Fn(&mut Vec<Placement>)| {
+            let mut solution = solution.clone();
+            edit(&mut solution);
+            check_solution(&catalog, space, &solution).unwrap_err().to_string()
+        };
+        assert!(broken(&|s| { s.pop(); }).contains("placed"));
+        assert!(broken(&|s| s[0].cells[0].x += 100).contains("isn't an orientation"));
+        assert!(broken(&|s| {
+            for placement in s.iter_mut() {
+                placement.x += space.width as i32;
+                placement.cells.iter_mut().for_each(|c| c.x += space.width as i32);
+            }
+        }).contains("off the board"));
+        assert!(broken(&|s| { let extra = s[0].clone(); s.push(extra); }).contains("already covers"));
+        assert!(broken(&|s| s[0].instance = 99).contains("one more than"));
+        assert!(broken(&|s| {
+            let last = s.iter().rposition(|p| p.shape_id == s[0].shape_id && p.instance != s[0].instance).unwrap();
+            s[last].instance = s[0].instance;
+        }).contains("placed twice"));
+    }
+
+    #[test]
+    fn test_distinct_up_to_board_symmetry() {
+        let bar = Shape { id: 0, grid: vec!["###".chars().collect(), "...".chars().collect(), "...".chars().collect()] };
+        let board = |width, height, count, blocked: &[(i32, i32)]| ProblemSpace {
+            width,
+            height,
+            shape_counts: vec![count],
+            blocked: blocked.iter().map(|&(x, y)| Coords { x, y }).collect(),
+        };
+        let mut packer = Packer::with_shapes(&[bar]);
+
+        // Three horizontal or three vertical bars: one tiling up to a quarter turn
+        packer.set_board(board(3, 3, 3, &[]));
+        assert_eq!(board_symmetries(packer.board()).len(), 8);
+        assert_eq!(packer.count(usize::MAX).unwrap(), 2);
+        assert_eq!(packer.count_distinct(usize::MAX).unwrap(), 1);
+        assert_eq!(packer.enumerate_distinct(10).unwrap().len(), 1);
+
+        // One bar on a 3x2 board: top row and bottom row are mirror images
+        packer.set_board(board(3, 2, 1, &[]));
+        assert_eq!(board_symmetries(packer.board()).len(), 4);
+        assert_eq!(packer.count(usize::MAX).unwrap(), 2);
+        assert_eq!(packer.count_distinct(usize::MAX).unwrap(), 1);
+
+        // A blocked corner breaks the symmetry, so both rows count again
+        packer.set_board(board(4, 2, 1, &[(0, 0)]));
+        assert_eq!(board_symmetries(packer.board()).len(), 1);
+        assert_eq!(packer.count(usize::MAX).unwrap(), packer.count_distinct(usize::MAX).unwrap());
+    }
+
+    #[test]
+    fn test_maximize_coverage() {
+        let square = Shape { id: 0, grid: vec!["##.".chars().collect(), "##.".chars().collect(), "...".chars().collect()] };
+        let bar = Shape { id: 1, grid: vec!["###".chars().collect(), "...".chars().collect(), "...".chars().collect()] };
+        let catalog = ShapeCatalog::new(&[square, bar]);
+        let board = |shape_counts| ProblemSpace { width: 3, height: 3, shape_counts, blocked: HashSet::new() };
+        let timeout = Duration::from_secs(60);
+
+        // Only one of two squares fits, which area alone can't tell
+        let coverage = maximize_coverage(&catalog, &board(vec![2, 0]), Objective::Cells, timeout).unwrap();
+        assert_eq!((coverage.score, coverage.upper_bound, coverage.optimal), (4, 8, true));
+        assert_eq!(coverage.placements.len(), 1);
+        let coverage = maximize_coverage(&catalog, &board(vec![2, 0]), Objective::Pieces, timeout).unwrap();
+        assert_eq!((coverage.score, coverage.upper_bound, coverage.optimal), (1, 2, true));
+
+        // Three bars fill the board; a square would leave room for only one bar
+        let coverage = maximize_coverage(&catalog, &board(vec![1, 3]), Objective::Cells, timeout).unwrap();
+        assert_eq!((coverage.score, coverage.optimal), (9, true));
+        assert!(coverage.placements.iter().all(|p| p.shape_id == 1));
+        let coverage = maximize_coverage(&catalog, &board(vec![1, 3]), Objective::Pieces, timeout).unwrap();
+        assert_eq!(coverage.score, 3);
+
+        let coverage = maximize_coverage(&catalog, &board(vec![2, 0]), Objective::Cells, Duration::ZERO).unwrap();
+        assert!(!coverage.optimal);
+    }
+
+    #[test]
+    fn test_sequential_at_most_one() {
+        let mut next_var = 0;
+        let lits: Vec<Lit> = (0..6).map(|_| {
+            let var = Var::from_index(next_var);
+            next_var += 1;
+            var.positive()
+        }).collect();
+        let mut formula = CnfFormula::new();
+        add_at_most_one(&mut formula, &lits, AtMostOne::Sequential, &mut next_var);
+        assert_eq!(formula.len(), 3 * 6 - 4);
+        assert_eq!(next_var, 6 + 5);
+
+        let solve_with = |assumed: &[Lit]| {
+            let mut solver = Solver::new();
+            solver.add_formula(&formula);
+            solver.assume(assumed);
+            solver.solve().unwrap()
+        };
+        assert!(solve_with(&[lits[2]]));
+        assert!(solve_with(&lits.iter().map(|&l| !l).collect::<Vec<_>>()));
+        assert!(!solve_with(&[lits[0], lits[5]]));
+        assert!(!solve_with(&[lits[3], lits[4]]));
+    }
+
+    #[test]
+    fn test_ordered_instances() {
+        let mut next_var = 0;
+        let mut fresh = |n: usize| -> Vec<Lit> {
+            (0..n).map(|_| {
+                let var = Var::from_index(next_var);
+                next_var += 1;
+                var.positive()
+            }).collect()
+        };
+        let first = fresh(4);
+        let second = fresh(4);
+
+        let mut formula = CnfFormula::new();
+        for lits in [&first, &second] {
+            formula.add_clause(lits);
+            add_at_most_one(&mut formula, lits, AtMostOne::Pairwise, &mut next_var);
+        }
+        add_ordered_after(&mut formula, &first, &second, &mut next_var);
+
+        for (i, &a) in first.iter().enumerate() {
+            for (j, &b) in second.iter().enumerate() {
+                let mut solver = Solver::new();
+                solver.add_formula(&formula);
+                solver.assume(&[a, b]);
+                assert_eq!(solver.solve().unwrap(), i < j, "first={} second={}", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequential_encoding_shrinks_formula() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        for space in &spaces {
+            let pairwise = encode_sat(&catalog, space, AtMostOne::Pairwise, &Silent).unwrap().unwrap();
+            let sequential = encode_sat(&catalog, space, AtMostOne::Sequential, &Silent).unwrap().unwrap();
+            assert!(sequential.formula.len() < pairwise.formula.len());
+            assert_eq!(sequential.var_to_placement.len(), pairwise.var_to_placement.len());
+        }
+    }
+
+    #[test]
+    fn test_sat_preprocessing_rejects_impossible_spaces() {
+        let domino = Shape { id: 0, grid: vec!["##.".chars().collect(), "...".chars().collect(), "...".chars().collect()] };
+        let catalog = ShapeCatalog::new(&[domino]);
+        let board = |width, height, count, blocked: &[(i32, i32)]| ProblemSpace {
+            width,
+            height,
+            shape_counts: vec![count],
+            blocked: blocked.iter().map(|&(x, y)| Coords { x, y }).collect(),
+        };
+
+        // A domino fits nowhere on a single cell
+        assert!(encode_sat(&catalog, &board(1, 1, 1, &[]), AtMostOne::Sequential, &Silent).unwrap().is_none());
+        // 1x5 strip with x=1 blocked: four open cells, but x=0 is unreachable,
+        // so two dominoes are ruled out without a formula
+        let strip = board(5, 1, 2, &[(1, 0)]);
+        assert_eq!(strip.open_cells(), 4);
+        assert!(encode_sat(&catalog, &strip, AtMostOne::Sequential, &Silent).unwrap().is_none());
+        assert_eq!(solve_with_sat(&catalog, &strip).unwrap(), None);
+
+        let fits = board(5, 1, 1, &[(1, 0)]);
+        let encoding = encode_sat(&catalog, &fits, AtMostOne::Sequential, &Silent).unwrap().unwrap();
+        assert_eq!(encoding.var_to_placement.len(), 2);
+        assert!(solve_with_sat(&catalog, &fits).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unsat_core() {
+        let shape = |id, rows: [&str; 3]| Shape { id, grid: rows.iter().map(|row| row.chars().collect()).collect() };
+        let domino = shape(0, ["##.", "...", "..."]);
+        let cell = shape(1, ["#..", "...", "..."]);
+        let ell = shape(2, ["#..", "##.", "..."]);
+        let catalog = ShapeCatalog::new(&[domino.clone(), cell.clone(), ell.clone()]);
+        let strip = |counts: Vec<usize>| ProblemSpace { width: 3, height: 1, shape_counts: counts, blocked: HashSet::new() };
+
+        assert_eq!(explain_unsat(&catalog, &strip(vec![1, 1, 0])).unwrap(), None);
+        // The single cells don't matter: two dominoes alone overflow the strip
+        let core = explain_unsat(&catalog, &strip(vec![2, 3, 0])).unwrap().unwrap();
+        assert_eq!(core.shape_counts, vec![2, 0, 0]);
+        assert_eq!(core.to_string(), "2 of shape 0 can't be packed together");
+        assert!(core.solves > 2);
+        // Four single cells are too many, and so is any domino with two of them
+        let core = explain_unsat(&catalog, &strip(vec![1, 4, 0])).unwrap().unwrap();
+        assert_eq!(core.shape_counts, vec![1, 2, 0]);
+        assert_eq!(core.to_string(), "1 of shape 0, 2 of shape 1 can't be packed together");
+        // An L fits nowhere on a strip, no solver needed
+        let core = explain_unsat(&catalog, &strip(vec![1, 0, 1])).unwrap().unwrap();
+        assert_eq!((core.shape_counts, core.solves), (vec![0, 0, 1], 0));
+
+        let mut packer = Packer::with_shapes(&[domino, cell, ell]);
+        packer.set_board(strip(vec![2, 3, 0]));
+        let core = packer.explain().unwrap().unwrap();
+        assert_eq!((core.pieces(), core.confirmed), (2, Some(true)));
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture bench_sat_encodings`
+    #[test]
+    #[ignore]
+    fn bench_sat_encodings() {
+        use std::time::Instant;
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let (big_shapes, big_spaces) = parse_input(Input::for_day(12).read("trees2").unwrap()).unwrap();
+        let big_catalog = ShapeCatalog::new(&big_shapes);
+
+        let mut cases: Vec<(&ShapeCatalog, &ProblemSpace)> = spaces.iter().map(|s| (&catalog, s)).collect();
+        // The smallest Part 2 spaces that fit by area; pairwise is impractical beyond these
+        let mut fitting: Vec<&ProblemSpace> = big_spaces
+            .iter()
+            .filter(|space| {
+                let cells: usize = space.shape_counts.iter().enumerate()
+                    .map(|(id, &count)| count * big_catalog.cell_count(id))
+                    .sum();
+                cells <= space.width * space.height
+            })
+            .collect();
+        fitting.sort_by_key(|space| space.width * space.height);
+        cases.extend(fitting.iter().take(3).map(|&s| (&big_catalog, s)));
+
+        for (catalog, space) in cases {
+            for encoding in [AtMostOne::Pairwise, AtMostOne::Sequential] {
+                let start = Instant::now();
+                let clauses = encode_sat(catalog, space, encoding, &Silent).unwrap().map_or(0, |sat| sat.formula.len());
+                let solved = solve_with_sat_encoding(catalog, space, encoding, &Silent).unwrap().is_some();
+                println!("{}x{} {:?}: {} clauses, solved={}, {:.2}s",
+                    space.width, space.height, encoding, clauses, solved, start.elapsed().as_secs_f64());
+            }
+        }
+    }
+
+    #[test]
+    fn test_shape_catalog() {
+        let (shapes, _) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        for shape in &shapes {
+            assert_eq!(catalog.transforms[shape.id], shape.get_unique_transformations());
+            assert_eq!(catalog.cell_count(shape.id), shape.count_cells());
+        }
+        assert!(catalog.orientations(shapes.len()).is_err());
+    }
+
+    #[test]
+    fn test_bitboard() {
+        let mut board = Bitboard::new(4, 3);
+        let ell = OrientationMask::new(&[
+            Coords { x: 0, y: 0 },
+            Coords { x: 0, y: 1 },
+            Coords { x: 1, y: 1 },
+        ]);
+        assert_eq!(ell.rows, vec![0b01, 0b11]);
+        assert_eq!(ell.width, 2);
+
+        assert!(board.fits(&ell, 2, 1));
+        assert!(!board.fits(&ell, 3, 1));
+        assert!(!board.fits(&ell, 0, 2));
+        assert!(!board.fits(&ell, -1, 0));
+
+        board.toggle(&ell, 0, 0);
+        assert!(!board.fits(&ell, 0, 0));
+        assert!(!board.fits(&ell, 1, 0));
+        assert!(board.fits(&ell, 2, 0));
+        assert_eq!(board.first_empty(0), Some((1, 0)));
+        assert_eq!(board.first_empty(4), Some((2, 1)));
+
+        board.toggle(&ell, 0, 0);
+        assert!(board.rows.iter().all(|&row| row == 0));
+
+        let full = Bitboard::new(64, 1);
+        assert_eq!(full.first_empty(63), Some((63, 0)));
+        assert_eq!(full.first_empty(64), None);
+    }
+
+    #[test]
+    fn test_backtracking_beyond_bitboard_width() {
+        let (shapes, _) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let mut shape_counts = vec![0; shapes.len()];
+        shape_counts[0] = 3;
+        shape_counts[4] = 2;
+        for width in [Bitboard::MAX_WIDTH, Bitboard::MAX_WIDTH + 6] {
+            let space = ProblemSpace {
+                width,
+                height: 3,
+                shape_counts: shape_counts.clone(),
+                blocked: (0..3).map(|y| Coords { x: 0, y }).collect(),
+            };
+            let solution = solve_with_backtracking(&catalog, &space).unwrap().expect("a wide strip fits five pieces");
+            check_solution(&catalog, &space, &solution).unwrap();
+        }
+
+        let mut grid = CellGrid::new(70, 2);
+        let ell = [Coords { x: 0, y: 0 }, Coords { x: 0, y: 1 }, Coords { x: 1, y: 1 }];
+        let mask = OrientationMask::new(&ell);
+        assert!(Board::fits(&grid, &ell, &mask, 68, 0));
+        assert!(!Board::fits(&grid, &ell, &mask, 69, 0));
+        Board::toggle(&mut grid, &ell, &mask, 68, 0);
+        assert_eq!(Board::first_empty(&grid, 68), Some((69, 0)));
+        assert_eq!(Board::first_empty(&grid, 138), None);
+    }
+
+    #[test]
+    fn test_count_single_piece_placements() {
+        let (shapes, _) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        let space = ProblemSpace { width: 3, height: 4, shape_counts: vec![1], blocked: HashSet::new() };
+
+        let placements = generate_placements(&catalog.transforms[0], 0, 0, &space).len();
+        assert_eq!(count_solutions(&catalog, &space, usize::MAX).unwrap(), placements);
+        assert_eq!(count_solutions(&catalog, &space, 2).unwrap(), 2);
+        assert_eq!(count_solutions(&catalog, &space, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_auto_strategy_times_out() {
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+
+        let estimate = estimate_size(&catalog, &spaces[1]).unwrap();
+        assert_eq!(auto_backends(&estimate, spaces[1].width), vec![SolverKind::Backtracking, SolverKind::Dlx]);
+
+        let attempt = solve_with_strategy(Strategy::Auto, &catalog, &spaces[1], Duration::ZERO, &Silent, &CancellationToken::new()).unwrap();
+        assert_eq!(attempt, Attempt::TimedOut);
+
+        // The caller's token cuts the search short no matter the per-backend timeout
+        let cancel = CancellationToken::with_timeout(Duration::from_millis(50));
+        let attempt = solve_with_strategy(Strategy::Fixed(SolverKind::Backtracking), &catalog, &spaces[2], Duration::MAX, &Silent, &cancel);
+        assert!(matches!(attempt, Err(AocError::Timeout(_))), "{:?}", attempt);
+    }
+
+    #[test]
+    fn test_auto_backend_choice() {
+        let estimate = |placements, slack| SizeEstimate { cells: 100, pieces: 10, placements, slack };
+
+        assert_eq!(auto_backends(&estimate(50, Some(3)), 10), vec![SolverKind::Sat]);
+        assert_eq!(auto_backends(&estimate(5000, Some(0)), 10), vec![SolverKind::Dlx, SolverKind::Backtracking]);
+        assert_eq!(auto_backends(&estimate(5000, Some(9)), 10), vec![SolverKind::Backtracking, SolverKind::Dlx]);
+        assert_eq!(auto_backends(&estimate(5000, Some(9)), 65), vec![SolverKind::Dlx]);
+    }
+
+    #[test]
+    fn test_feasibility_filters() {
+        let reason = |input: &str| {
+            let (shapes, spaces) = parse_input(input).unwrap();
+            find_infeasibility(&ShapeCatalog::new(&shapes), &spaces[0]).unwrap()
+        };
+
+        let area = reason("0:\n###\n###\n###\n\n3x3: 2\n").unwrap();
+        assert!(area.starts_with("area:"), "{}", area);
+        // Five T pieces each cover 3 cells of one color and 1 of the other,
+        // so they can never split evenly over a 4x5 board
+        let coloring = reason("0:\n###\n.#.\n...\n\n4x5: 5\n").unwrap();
+        assert!(coloring.starts_with("coloring:"), "{}", coloring);
+        let nowhere = reason("0:\n###\n###\n###\n\n5x2: 1\n").unwrap();
+        assert_eq!(nowhere, "placements: shape 0 fits nowhere on the board");
+        // A plus sign can't reach the four corners, and two of them leave only two cells empty
+        let corners = reason("0:\n.#.\n###\n.#.\n\n3x4: 2\n").unwrap();
+        assert_eq!(corners, "placements: 4 open cells can't be covered by any piece, only 2 may stay empty");
+        assert_eq!(reason("0:\n.#.\n###\n.#.\n\n3x4: 1\n"), None);
+
+        let (shapes, spaces) = parse_input(Input::for_day(12).read("trees1").unwrap()).unwrap();
+        let catalog = ShapeCatalog::new(&shapes);
+        for space in &spaces {
+            assert_eq!(find_infeasibility(&catalog, space).unwrap(), None);
+        }
+        let attempt = solve_with_strategy(Strategy::Auto, &catalog, &ProblemSpace {
+            width: 3,
+            height: 3,
+            shape_counts: vec![0, 0, 0, 0, 2, 0],
+            blocked: HashSet::new(),
+        }, Duration::MAX, &Silent, &CancellationToken::new()).unwrap();
+        assert!(matches!(attempt, Attempt::Infeasible(_)));
+    }
+}
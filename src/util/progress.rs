@@ -0,0 +1,158 @@
+// Progress reporting for long-running solvers. Solvers take a `&dyn Progress`
+// so the caller decides between a terminal bar, plain status lines, or nothing.
+// Everything goes to stderr, leaving stdout clean for answers.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub trait Progress: Sync {
+    /// Begins a new phase of `total` steps, replacing any previous one
+    fn start(&self, label: &str, total: u64);
+    fn inc(&self, delta: u64);
+    /// Status text shown next to the count, e.g. running tallies
+    fn set_message(&self, message: &str);
+    fn finish(&self);
+}
+
+/// Reports nothing; for tests, benchmarks and `--no-progress`
+pub struct Hidden;
+
+impl Progress for Hidden {
+    fn start(&self, _label: &str, _total: u64) {}
+    fn inc(&self, _delta: u64) {}
+    fn set_message(&self, _message: &str) {}
+    fn finish(&self) {}
+}
+
+/// indicatif bar, for interactive terminals
+pub struct Bar(ProgressBar);
+
+impl Bar {
+    pub fn new() -> Self {
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} {msg}")
+                .expect("valid template")
+                .progress_chars("=> "),
+        );
+        Bar(bar)
+    }
+}
+
+impl Default for Bar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress for Bar {
+    fn start(&self, label: &str, total: u64) {
+        self.0.reset();
+        self.0.set_length(total);
+        self.0.set_prefix(label.to_string());
+        self.0.set_message("");
+    }
+
+    fn inc(&self, delta: u64) {
+        self.0.inc(delta);
+    }
+
+    fn set_message(&self, message: &str) {
+        self.0.set_message(message.to_string());
+    }
+
+    fn finish(&self) {
+        self.0.finish_and_clear();
+    }
+}
+
+/// One line per 10% of a phase, for logs and redirected stderr
+pub struct Lines {
+    state: Mutex<LinesState>,
+    done: AtomicU64,
+}
+
+#[derive(Default)]
+struct LinesState {
+    label: String,
+    total: u64,
+    message: String,
+    /// Tenths of the phase already reported
+    reported: u64,
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        Lines { state: Mutex::new(LinesState::default()), done: AtomicU64::new(0) }
+    }
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress for Lines {
+    fn start(&self, label: &str, total: u64) {
+        let mut state = self.state.lock().unwrap();
+        *state = LinesState { label: label.to_string(), total, ..LinesState::default() };
+        self.done.store(0, Ordering::Relaxed);
+    }
+
+    fn inc(&self, delta: u64) {
+        let done = self.done.fetch_add(delta, Ordering::Relaxed) + delta;
+        let mut state = self.state.lock().unwrap();
+        let tenths = (done * 10).checked_div(state.total).unwrap_or(10).min(10);
+        if tenths > state.reported {
+            state.reported = tenths;
+            eprintln!("{}: {}/{} {}", state.label, done, state.total, state.message);
+        }
+    }
+
+    fn set_message(&self, message: &str) {
+        self.state.lock().unwrap().message = message.to_string();
+    }
+
+    fn finish(&self) {}
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns `stderr()` into `Hidden` for the rest of the process
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// The reporter `run` functions should use: a bar on a terminal, status
+/// lines when stderr is redirected, nothing when disabled
+pub fn stderr() -> Box<dyn Progress> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        Box::new(Hidden)
+    } else if std::io::stderr().is_terminal() {
+        Box::new(Bar::new())
+    } else {
+        Box::new(Lines::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_reports_each_tenth_once() {
+        let lines = Lines::new();
+        lines.start("work", 20);
+        for _ in 0..20 {
+            lines.inc(1);
+        }
+        assert_eq!(lines.state.lock().unwrap().reported, 10);
+
+        lines.start("empty", 0);
+        lines.inc(0);
+        assert_eq!(lines.state.lock().unwrap().reported, 10);
+    }
+}
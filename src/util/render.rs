@@ -0,0 +1,241 @@
+// Rendering for grids partitioned into regions (packed pieces, clusters, ...)
+
+use crate::error::{AocError, Result};
+use crate::util::grid::Grid;
+use crate::util::theme::Theme;
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Contents of one grid cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cell {
+    #[default]
+    Empty,
+    Blocked,
+    /// Part of the region with this index; each index gets its own color
+    Region(usize),
+}
+
+/// A grid of `Cell`s, starting out empty
+pub type RegionGrid = Grid<Cell>;
+
+const EMPTY_RGB: [u8; 3] = [245, 245, 245];
+const BLOCKED_RGB: [u8; 3] = [60, 60, 60];
+const BORDER_RGB: [u8; 3] = [20, 20, 20];
+
+/// Whether a border should be drawn between (x, y) and the cell at
+/// (x + dx, y + dy); the grid edge counts as a border
+fn border(grid: &RegionGrid, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+    match grid.offset(x, y, dx, dy) {
+        Some(other) => grid[(x, y)] != grid[other],
+        None => true,
+    }
+}
+
+/// Distinct, reasonably saturated color for a region index. Hues step by
+/// the golden angle so neighbouring indices never look alike.
+pub fn region_color(index: usize) -> [u8; 3] {
+    let hue = (index as f64 * 137.507_764) % 360.0;
+    let (saturation, value) = if index.is_multiple_of(2) { (0.65, 0.95) } else { (0.8, 0.75) };
+    hsv_to_rgb(hue, saturation, value)
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+fn cell_color(cell: Cell) -> [u8; 3] {
+    match cell {
+        Cell::Empty => EMPTY_RGB,
+        Cell::Blocked => BLOCKED_RGB,
+        Cell::Region(index) => region_color(index),
+    }
+}
+
+/// Terminal rendering with a 24-bit background color per region, or just
+/// the characters when `theme` is plain. `label` gives the character printed
+/// in a region's cells.
+pub fn to_ansi(grid: &RegionGrid, theme: Theme, label: impl Fn(usize) -> char) -> String {
+    let mut out = String::new();
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            let cell = grid[(x, y)];
+            let ch = match cell {
+                Cell::Empty => '.',
+                Cell::Blocked => '#',
+                Cell::Region(index) => label(index),
+            };
+            if theme.is_colored() {
+                let [r, g, b] = cell_color(cell);
+                out.push_str(&format!("\x1b[48;2;{};{};{}m\x1b[38;2;0;0;0m{}\x1b[0m", r, g, b, ch));
+            } else {
+                out.push(ch);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// SVG with one square per cell and lines along region boundaries
+pub fn to_svg(grid: &RegionGrid, cell_size: usize) -> String {
+    let (w, h) = (grid.width() * cell_size, grid.height() * cell_size);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        w, h, w, h
+    );
+
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            let [r, g, b] = cell_color(grid[(x, y)]);
+            out.push_str(&format!(
+                "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>\n",
+                x * cell_size, y * cell_size, cell_size, cell_size, r, g, b
+            ));
+        }
+    }
+
+    let stroke = format!("#{:02x}{:02x}{:02x}", BORDER_RGB[0], BORDER_RGB[1], BORDER_RGB[2]);
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            let (left, top) = (x * cell_size, y * cell_size);
+            let (right, bottom) = (left + cell_size, top + cell_size);
+            // Each cell draws its top and left edges; the grid's right and
+            // bottom edges are drawn by the last column and row
+            let mut edges = Vec::new();
+            if border(grid, x, y, 0, -1) {
+                edges.push((left, top, right, top));
+            }
+            if border(grid, x, y, -1, 0) {
+                edges.push((left, top, left, bottom));
+            }
+            if x + 1 == grid.width() {
+                edges.push((right, top, right, bottom));
+            }
+            if y + 1 == grid.height() {
+                edges.push((left, bottom, right, bottom));
+            }
+            for (x1, y1, x2, y2) in edges {
+                out.push_str(&format!(
+                    "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+                    x1, y1, x2, y2, stroke
+                ));
+            }
+        }
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+/// RGB raster of the grid, `cell_size` pixels per cell, with a one pixel
+/// border wherever two different regions meet
+pub fn to_rgb_pixels(grid: &RegionGrid, cell_size: usize) -> Vec<u8> {
+    let (w, h) = (grid.width() * cell_size, grid.height() * cell_size);
+    let mut pixels = Vec::with_capacity(w * h * 3);
+
+    for py in 0..h {
+        for px in 0..w {
+            let (x, y) = (px / cell_size, py / cell_size);
+            let (ix, iy) = (px % cell_size, py % cell_size);
+            let on_border = (ix == 0 && border(grid, x, y, -1, 0))
+                || (iy == 0 && border(grid, x, y, 0, -1))
+                || (ix + 1 == cell_size && border(grid, x, y, 1, 0))
+                || (iy + 1 == cell_size && border(grid, x, y, 0, 1));
+            let rgb = if on_border { BORDER_RGB } else { cell_color(grid[(x, y)]) };
+            pixels.extend_from_slice(&rgb);
+        }
+    }
+
+    pixels
+}
+
+pub fn write_png(grid: &RegionGrid, cell_size: usize, path: &str) -> Result<()> {
+    let file = File::create(path).map_err(|e| AocError::io(path, e))?;
+    let (w, h) = (grid.width() * cell_size, grid.height() * cell_size);
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| AocError::io(path, e.into()))?;
+    writer
+        .write_image_data(&to_rgb_pixels(grid, cell_size))
+        .map_err(|e| AocError::io(path, e.into()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x2 grid: region 0 on the left column, region 1 top right, one blocked cell
+    fn sample() -> RegionGrid {
+        let mut grid = RegionGrid::new(3, 2);
+        grid.set(0, 0, Cell::Region(0));
+        grid.set(0, 1, Cell::Region(0));
+        grid.set(1, 0, Cell::Region(1));
+        grid.set(2, 0, Cell::Region(1));
+        grid.set(2, 1, Cell::Blocked);
+        grid
+    }
+
+    #[test]
+    fn test_region_colors_are_distinct() {
+        let colors: std::collections::HashSet<[u8; 3]> = (0..64).map(region_color).collect();
+        assert_eq!(colors.len(), 64);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let label = |index: usize| char::from_digit(index as u32, 10).unwrap();
+        let out = to_ansi(&sample(), Theme::COLOR, label);
+        let plain: String = out
+            .split("\x1b[")
+            .map(|part| part.split_once('m').map_or(part, |(_, rest)| rest))
+            .collect();
+        assert_eq!(plain, "011\n0.#\n");
+        assert_eq!(out.matches("\x1b[0m").count(), 6);
+        assert_eq!(to_ansi(&sample(), Theme::PLAIN, label), plain);
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = to_svg(&sample(), 10);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 6);
+        let [r, g, b] = region_color(1);
+        assert!(svg.contains(&format!("#{:02x}{:02x}{:02x}", r, g, b)));
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let path = std::env::temp_dir().join(format!("render-test-{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_png(&sample(), 4, path).unwrap();
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(File::open(path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        std::fs::remove_file(path).ok();
+
+        assert_eq!((info.width, info.height), (12, 8));
+        // Interior pixel of the region 0 cell, away from any border
+        let offset = (info.width as usize + 1) * 3;
+        assert_eq!(buf[offset..offset + 3], region_color(0));
+        // Region edge against the grid border
+        assert_eq!(buf[0..3], BORDER_RGB);
+    }
+}
@@ -0,0 +1,74 @@
+// Narration from inside solvers. Functions that explain their work as they go
+// take a `&dyn Reporter` instead of printing, so `run` can send it to stdout
+// while tests, benchmarks and `part1`/`part2` keep the pure logic quiet.
+
+use std::sync::Mutex;
+
+pub trait Reporter: Sync {
+    /// One line of human-readable output, without its trailing newline
+    fn line(&self, text: &str);
+}
+
+/// Prints every line to stdout; what `run` passes
+pub struct Stdout;
+
+impl Reporter for Stdout {
+    fn line(&self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Drops everything; for tests, benchmarks and library callers
+pub struct Silent;
+
+impl Reporter for Silent {
+    fn line(&self, _text: &str) {}
+}
+
+/// Keeps every line so tests can check what a solver said, even when
+/// several run at once on different threads
+#[derive(Default)]
+pub struct Captured(Mutex<Vec<String>>);
+
+impl Captured {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Every line joined with newlines, as it would have been printed
+    pub fn text(&self) -> String {
+        self.lines().join("\n")
+    }
+}
+
+impl Reporter for Captured {
+    fn line(&self, text: &str) {
+        self.0.lock().unwrap().push(text.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_captured_collects_lines_across_threads() {
+        let captured = Captured::new();
+        (0..50).into_par_iter().for_each(|i| captured.line(&format!("line {}", i)));
+        let mut lines = captured.lines();
+        assert_eq!(lines.len(), 50);
+        lines.sort();
+        assert!(lines.contains(&"line 7".to_string()));
+
+        Silent.line("nothing");
+        let single = Captured::new();
+        single.line("a");
+        single.line("b");
+        assert_eq!(single.text(), "a\nb");
+    }
+}
@@ -0,0 +1,85 @@
+// Golden-value checks for puzzle answers. Each day keeps its expected
+// answers in snapshots/dayNN.snap as `name: value` lines, with values in
+// `Debug` formatting. After an intentional change, rewrite them with
+//
+//     AOC_BLESS=1 cargo test
+//
+// (libtest rejects unknown flags, so `--bless` can't be passed through
+// `cargo test --`; the environment variable plays that role)
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Set to anything to write snapshots instead of comparing against them
+pub const BLESS_VAR: &str = "AOC_BLESS";
+
+/// Serializes blessing: tests of the same day run in parallel and rewrite
+/// the same file
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+fn snapshot_path(group: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("snapshots").join(format!("{}.snap", group))
+}
+
+fn parse(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn render(entries: &BTreeMap<String, String>) -> String {
+    let mut out = String::from("# Expected answers; regenerate with AOC_BLESS=1 cargo test\n");
+    for (name, value) in entries {
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+    out
+}
+
+/// Compares `value` with the `name` entry of `snapshots/<group>.snap`, or
+/// records it there when blessing
+#[track_caller]
+pub fn check(group: &str, name: &str, value: impl Debug) {
+    let actual = format!("{:?}", value);
+    assert!(!actual.contains('\n'), "snapshot {}/{} must fit on one line", group, name);
+    let path = snapshot_path(group);
+
+    if std::env::var_os(BLESS_VAR).is_some() {
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entries = parse(&fs::read_to_string(&path).unwrap_or_default());
+        entries.insert(name.to_string(), actual);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, render(&entries)).unwrap();
+        return;
+    }
+
+    let entries = parse(&fs::read_to_string(&path).unwrap_or_default());
+    match entries.get(name) {
+        Some(expected) => assert_eq!(
+            &actual, expected,
+            "{}/{} differs from {}; rerun with {}=1 if the change is intended",
+            group, name, path.display(), BLESS_VAR
+        ),
+        None => panic!("No snapshot {}/{} (got {}); run with {}=1 to record it", group, name, actual, BLESS_VAR),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut entries = BTreeMap::new();
+        entries.insert("b".to_string(), "Some(2)".to_string());
+        entries.insert("a".to_string(), "\"x: y\"".to_string());
+        let text = render(&entries);
+        assert!(text.starts_with('#'));
+        assert_eq!(parse(&text), entries);
+    }
+}
@@ -0,0 +1,291 @@
+// Timing, peak heap and solver counters for `--stats`. Heap tracking needs
+// the `mem-stats` feature, which installs `TrackingAllocator` as the global
+// allocator; without it only timings and counters are reported.
+//
+// Solvers feed counters into one shared report with `add` and `max`, and
+// measured values such as distances with `record`; the binary prints (or
+// writes out) the whole report once the run is over.
+
+use crate::util::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static REPORT: Mutex<Report> = Mutex::new(Report::new());
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// One `measure`d span
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    pub label: String,
+    pub seconds: f64,
+    pub peak_heap: Option<usize>,
+}
+
+/// Everything gathered during a run: timed spans in the order they finished,
+/// named counters and named values, kept sorted so reports diff cleanly
+/// between runs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub timings: Vec<Timing>,
+    pub metrics: BTreeMap<String, u64>,
+    pub values: BTreeMap<String, f64>,
+}
+
+impl Report {
+    pub const fn new() -> Self {
+        Report { timings: Vec::new(), metrics: BTreeMap::new(), values: BTreeMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timings.is_empty() && self.metrics.is_empty() && self.values.is_empty()
+    }
+
+    pub fn add(&mut self, metric: &str, amount: u64) {
+        let total = self.metrics.entry(metric.to_string()).or_insert(0);
+        *total = total.saturating_add(amount);
+    }
+
+    pub fn max(&mut self, metric: &str, value: u64) {
+        let peak = self.metrics.entry(metric.to_string()).or_insert(0);
+        *peak = (*peak).max(value);
+    }
+
+    /// Sets a value, replacing any recorded under the same name
+    pub fn record(&mut self, name: &str, value: f64) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn to_json(&self) -> String {
+        let timings: Vec<String> = self
+            .timings
+            .iter()
+            .map(|t| {
+                let heap = t.peak_heap.map_or("null".to_string(), |bytes| bytes.to_string());
+                format!("{{\"label\":{},\"seconds\":{:.6},\"peak_heap_bytes\":{}}}", json::quote(&t.label), t.seconds, heap)
+            })
+            .collect();
+        let metrics: Vec<String> = self
+            .metrics
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json::quote(name), value))
+            .collect();
+        // JSON has no NaN or infinity
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|(name, value)| match value.is_finite() {
+                true => format!("{}:{}", json::quote(name), value),
+                false => format!("{}:null", json::quote(name)),
+            })
+            .collect();
+        format!(
+            "{{\"timings\":[{}],\"metrics\":{{{}}},\"values\":{{{}}}}}\n",
+            timings.join(","),
+            metrics.join(","),
+            values.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[stats] run report")?;
+        for t in &self.timings {
+            match t.peak_heap {
+                Some(peak) => writeln!(f, "  {}: {:.3}s, peak heap {}", t.label, t.seconds, format_bytes(peak))?,
+                None => writeln!(f, "  {}: {:.3}s", t.label, t.seconds)?,
+            }
+        }
+        let width = self.metrics.keys().chain(self.values.keys()).map(|name| name.len()).max().unwrap_or(0);
+        for (name, value) in &self.metrics {
+            writeln!(f, "  {:<width$}  {}", name, value, width = width)?;
+        }
+        for (name, value) in &self.values {
+            writeln!(f, "  {:<width$}  {:.3}", name, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds `amount` to a counter in the run report; a no-op without `--stats`
+pub fn add(metric: &str, amount: u64) {
+    if enabled() {
+        REPORT.lock().unwrap().add(metric, amount);
+    }
+}
+
+/// Raises a high-water mark in the run report; a no-op without `--stats`
+pub fn max(metric: &str, value: u64) {
+    if enabled() {
+        REPORT.lock().unwrap().max(metric, value);
+    }
+}
+
+/// Sets a measured value in the run report; a no-op without `--stats`
+pub fn record(name: &str, value: f64) {
+    if enabled() {
+        REPORT.lock().unwrap().record(name, value);
+    }
+}
+
+/// Hands over everything gathered so far, leaving an empty report behind
+pub fn take_report() -> Report {
+    std::mem::take(&mut *REPORT.lock().unwrap())
+}
+
+#[cfg(feature = "mem-stats")]
+mod tracking {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub static CURRENT: AtomicUsize = AtomicUsize::new(0);
+    pub static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    /// System allocator that counts live bytes and their high-water mark
+    pub struct TrackingAllocator;
+
+    fn grow(size: usize) {
+        let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc(layout) };
+            if !ptr.is_null() {
+                grow(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = unsafe { System.alloc_zeroed(layout) };
+            if !ptr.is_null() {
+                grow(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+            if !new_ptr.is_null() {
+                CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+                grow(new_size);
+            }
+            new_ptr
+        }
+    }
+}
+
+#[cfg(feature = "mem-stats")]
+pub use tracking::TrackingAllocator;
+
+/// Starts a new peak measurement, returning the peak it replaces
+#[cfg(feature = "mem-stats")]
+fn begin_peak() -> Option<usize> {
+    let current = tracking::CURRENT.load(Ordering::Relaxed);
+    Some(tracking::PEAK.swap(current, Ordering::Relaxed))
+}
+
+#[cfg(not(feature = "mem-stats"))]
+fn begin_peak() -> Option<usize> {
+    None
+}
+
+/// Peak since `begin_peak`; folds it back into the outer measurement so
+/// nested `measure` calls don't hide each other's peaks
+#[cfg(feature = "mem-stats")]
+fn end_peak(outer: Option<usize>) -> Option<usize> {
+    let peak = tracking::PEAK.load(Ordering::Relaxed);
+    tracking::PEAK.fetch_max(outer.unwrap_or(0), Ordering::Relaxed);
+    Some(peak)
+}
+
+#[cfg(not(feature = "mem-stats"))]
+fn end_peak(_outer: Option<usize>) -> Option<usize> {
+    None
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Runs `f`, and with `--stats` records its wall time and (with `mem-stats`)
+/// peak heap in the run report under `label`
+pub fn measure<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    if !enabled() {
+        return f();
+    }
+
+    let outer = begin_peak();
+    let start = Instant::now();
+    let result = f();
+    let seconds = start.elapsed().as_secs_f64();
+    let peak_heap = end_peak(outer);
+    REPORT.lock().unwrap().timings.push(Timing { label: label.to_string(), seconds, peak_heap });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_report_counters_and_json() {
+        let mut report = Report::new();
+        assert!(report.is_empty());
+        report.add("day10.search_nodes", 5);
+        report.add("day10.search_nodes", 7);
+        report.max("day08.heap_pairs", 40);
+        report.max("day08.heap_pairs", 30);
+        report.timings.push(Timing { label: "Day \"1\"".to_string(), seconds: 0.5, peak_heap: None });
+        report.record("day08.median", 2.0);
+        report.record("day08.median", 12.25);
+        report.record("day08.nan", f64::NAN);
+
+        assert_eq!(report.metrics["day10.search_nodes"], 12);
+        assert_eq!(report.metrics["day08.heap_pairs"], 40);
+        assert_eq!(
+            report.to_json(),
+            "{\"timings\":[{\"label\":\"Day \\\"1\\\"\",\"seconds\":0.500000,\"peak_heap_bytes\":null}],\
+             \"metrics\":{\"day08.heap_pairs\":40,\"day10.search_nodes\":12},\
+             \"values\":{\"day08.median\":12.25,\"day08.nan\":null}}\n"
+        );
+        assert!(report.to_string().ends_with("  day08.median        12.250\n  day08.nan           NaN\n"), "{}", report);
+    }
+}
@@ -0,0 +1,45 @@
+// Sequence helpers for puzzles about repeated digit or character patterns
+
+/// Length of the shortest block that `s` is a whole number of copies of, so
+/// `abcabc` has period 3 and `abcab` (no whole repetition) has period 5.
+/// Computed from the KMP failure function in O(len); empty input has period 0.
+pub fn smallest_period<T: PartialEq>(s: &[T]) -> usize {
+    let n = s.len();
+    if n == 0 {
+        return 0;
+    }
+
+    // failure[i]: length of the longest proper prefix of s[..=i] that is also its suffix
+    let mut failure = vec![0; n];
+    let mut k = 0;
+    for i in 1..n {
+        while k > 0 && s[i] != s[k] {
+            k = failure[k - 1];
+        }
+        if s[i] == s[k] {
+            k += 1;
+        }
+        failure[i] = k;
+    }
+
+    let period = n - failure[n - 1];
+    if n.is_multiple_of(period) { period } else { n }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smallest_period() {
+        assert_eq!(smallest_period(b"abcabc"), 3);
+        assert_eq!(smallest_period(b"abcab"), 5);
+        assert_eq!(smallest_period(b"7777"), 1);
+        assert_eq!(smallest_period(b"12121212"), 2);
+        assert_eq!(smallest_period(b"1212121"), 7);
+        assert_eq!(smallest_period(b"x"), 1);
+        assert_eq!(smallest_period::<u8>(b""), 0);
+        // Border of 4 ("aaba") but 6 doesn't divide 10
+        assert_eq!(smallest_period(b"aabaaabaab"), 10);
+    }
+}
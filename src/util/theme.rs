@@ -0,0 +1,153 @@
+// Terminal colors for everything the days draw or summarize. Colors come
+// from the Okabe-Ito palette, which stays distinguishable under the common
+// kinds of color blindness, and every colored element also differs in its
+// character or wording so nothing is told apart by color alone.
+
+use crate::util::grid::Grid;
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// What a piece of output means; the theme decides how that looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Heading,
+    /// Successes: solved spaces, removable rolls
+    Good,
+    /// Failures
+    Bad,
+    /// Partial or uncertain outcomes, e.g. timeouts
+    Warning,
+    /// Background detail: empty cells, blocked cells
+    Muted,
+    /// The thing a drawing is about, e.g. beams
+    Highlight,
+    /// One of several like items (shapes, pieces), cycling through the palette
+    Category(usize),
+}
+
+/// Okabe-Ito colors, black left out so text stays readable on dark terminals
+const PALETTE: [[u8; 3]; 7] = [
+    [230, 159, 0],   // orange
+    [86, 180, 233],  // sky blue
+    [0, 158, 115],   // bluish green
+    [240, 228, 66],  // yellow
+    [0, 114, 178],   // blue
+    [213, 94, 0],    // vermillion
+    [204, 121, 167], // reddish purple
+];
+
+const ORANGE: usize = 0;
+const SKY_BLUE: usize = 1;
+const BLUISH_GREEN: usize = 2;
+const VERMILLION: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    color: bool,
+}
+
+impl Theme {
+    /// No escape codes at all
+    pub const PLAIN: Theme = Theme { color: false };
+    pub const COLOR: Theme = Theme { color: true };
+
+    /// Colored unless `NO_COLOR` is set to anything non-empty or stdout
+    /// isn't a terminal
+    pub fn detect() -> Theme {
+        Self::from_env(std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()), std::io::stdout().is_terminal())
+    }
+
+    fn from_env(no_color: bool, terminal: bool) -> Theme {
+        Theme { color: terminal && !no_color }
+    }
+
+    pub fn is_colored(&self) -> bool {
+        self.color
+    }
+
+    fn sgr(style: Style) -> Option<String> {
+        let fg = |index: usize| {
+            let [r, g, b] = PALETTE[index % PALETTE.len()];
+            format!("38;2;{};{};{}", r, g, b)
+        };
+        match style {
+            Style::Plain => None,
+            Style::Heading => Some("1".to_string()),
+            Style::Good => Some(format!("1;{}", fg(BLUISH_GREEN))),
+            Style::Bad => Some(format!("1;{}", fg(VERMILLION))),
+            Style::Warning => Some(fg(ORANGE)),
+            Style::Muted => Some("2".to_string()),
+            Style::Highlight => Some(fg(SKY_BLUE)),
+            Style::Category(index) => Some(fg(index)),
+        }
+    }
+
+    /// `text` wrapped in the escape codes for `style`, or just `text` when plain
+    pub fn paint(&self, style: Style, text: impl fmt::Display) -> String {
+        match Self::sgr(style).filter(|_| self.color) {
+            Some(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Draws a grid one character per cell, `cell` giving each cell's
+    /// character and style. Runs of equally styled cells share one escape
+    /// sequence so big grids stay cheap to print.
+    pub fn paint_grid<T>(&self, grid: &Grid<T>, cell: impl Fn(&T) -> (char, Style)) -> String {
+        let mut out = String::with_capacity((grid.width() + 1) * grid.height());
+        for row in grid.rows() {
+            let mut run = String::new();
+            let mut run_style = Style::Plain;
+            for value in row {
+                let (ch, style) = cell(value);
+                if style != run_style && !run.is_empty() {
+                    out.push_str(&self.paint(run_style, &run));
+                    run.clear();
+                }
+                run_style = style;
+                run.push(ch);
+            }
+            out.push_str(&self.paint(run_style, &run));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The theme detected at first use, shared by every day
+pub fn current() -> Theme {
+    *THEME.get_or_init(Theme::detect)
+}
+
+/// Uses `theme` instead of detecting one, e.g. plain text when the output
+/// goes to a file whatever stdout is; no effect once anything is painted
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection() {
+        assert!(Theme::from_env(false, true).is_colored());
+        assert!(!Theme::from_env(true, true).is_colored());
+        assert!(!Theme::from_env(false, false).is_colored());
+    }
+
+    #[test]
+    fn test_paint_grid() {
+        let grid = Grid::parse("ab.\n..a\n", Some).unwrap();
+        let style = |&c: &char| (c, if c == '.' { Style::Muted } else { Style::Category(c as usize - 'a' as usize) });
+
+        assert_eq!(Theme::PLAIN.paint_grid(&grid, style), "ab.\n..a\n");
+        let colored = Theme::COLOR.paint_grid(&grid, style);
+        assert_eq!(colored.matches("\x1b[0m").count(), 5);
+        assert!(colored.starts_with("\x1b[38;2;230;159;0ma\x1b[0m\x1b[38;2;86;180;233mb\x1b[0m\x1b[2m.\x1b[0m\n\x1b[2m..\x1b[0m"));
+    }
+}
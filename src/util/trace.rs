@@ -0,0 +1,302 @@
+// Replay logs for `--trace`. Solvers report their key decisions through
+// `emit`, which costs one atomic load while tracing is off. The log is plain
+// text with one event per line, so a run can be replayed step by step later.
+
+use crate::error::{AocError, Result};
+use crate::util::grid::Grid;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Events kept per run; later ones are dropped and the log is marked truncated
+pub const MAX_EVENTS: usize = 1_000_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDING: Mutex<Recording> = Mutex::new(Recording { events: Vec::new(), truncated: false });
+
+struct Recording {
+    events: Vec<Event>,
+    truncated: bool,
+}
+
+/// A solver decision worth replaying
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Day 12: a backend starts packing an empty `width` x `height` board
+    Board { width: usize, height: usize },
+    /// Day 12: a piece of `shape` covers `cells`, given as (x, y)
+    Place { shape: usize, cells: Vec<(i32, i32)> },
+    /// Day 12: the most recent piece still on the board is taken off again
+    Unplace,
+    /// Day 10: the solver moves on to machine `number`
+    Machine { number: usize },
+    /// Day 10: a full assignment of the free variables, with its total presses
+    /// or None when it leaves some button with a negative or fractional count
+    Assign { values: Vec<usize>, presses: Option<usize> },
+    /// Day 8: boxes `a` and `b` joined two circuits into one of `size` boxes
+    Merge { a: usize, b: usize, size: usize },
+}
+
+/// Starts recording, discarding anything recorded before
+pub fn start() {
+    let mut recording = RECORDING.lock().unwrap();
+    recording.events.clear();
+    recording.truncated = false;
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records the event `event` builds; the closure only runs while tracing
+pub fn emit(event: impl FnOnce() -> Event) {
+    if !enabled() {
+        return;
+    }
+    let mut recording = RECORDING.lock().unwrap();
+    if recording.events.len() < MAX_EVENTS {
+        recording.events.push(event());
+    } else {
+        recording.truncated = true;
+    }
+}
+
+/// Stops recording and returns the log of everything since `start`
+pub fn finish(day: u8) -> Trace {
+    ENABLED.store(false, Ordering::Relaxed);
+    let mut recording = RECORDING.lock().unwrap();
+    Trace { day, events: std::mem::take(&mut recording.events), truncated: recording.truncated }
+}
+
+/// A recorded run, as written by `--trace`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub day: u8,
+    pub events: Vec<Event>,
+    /// More than `MAX_EVENTS` happened and the rest were dropped
+    pub truncated: bool,
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+    if values.is_empty() {
+        return "-".to_string();
+    }
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Board { width, height } => write!(f, "board {} {}", width, height),
+            Event::Place { shape, cells } => {
+                let cells: Vec<String> = cells.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+                write!(f, "place {} {}", shape, cells.join(" "))
+            }
+            Event::Unplace => write!(f, "unplace"),
+            Event::Machine { number } => write!(f, "machine {}", number),
+            Event::Assign { values, presses } => {
+                let presses = presses.map_or("-".to_string(), |p| p.to_string());
+                write!(f, "assign {} {}", join(values, ","), presses)
+            }
+            Event::Merge { a, b, size } => write!(f, "merge {} {} {}", a, b, size),
+        }
+    }
+}
+
+fn field<T: FromStr>(text: Option<&str>, what: &str) -> Result<T> {
+    let text = text.ok_or_else(|| AocError::parse(format!("missing {}", what)))?;
+    text.parse().map_err(|_| AocError::parse(format!("invalid {} '{}'", what, text)))
+}
+
+impl FromStr for Event {
+    type Err = AocError;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let event = match words.next() {
+            Some("board") => Event::Board { width: field(words.next(), "width")?, height: field(words.next(), "height")? },
+            Some("place") => {
+                let shape = field(words.next(), "shape")?;
+                let cells = words
+                    .by_ref()
+                    .map(|cell| {
+                        let (x, y) = cell.split_once(',').ok_or_else(|| AocError::parse(format!("invalid cell '{}'", cell)))?;
+                        Ok((field(Some(x), "x")?, field(Some(y), "y")?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Event::Place { shape, cells }
+            }
+            Some("unplace") => Event::Unplace,
+            Some("machine") => Event::Machine { number: field(words.next(), "machine number")? },
+            Some("assign") => {
+                let values = match words.next() {
+                    Some("-") => Vec::new(),
+                    values => field::<String>(values, "assignment")?
+                        .split(',')
+                        .map(|v| field(Some(v), "free variable value"))
+                        .collect::<Result<Vec<_>>>()?,
+                };
+                let presses = match words.next() {
+                    Some("-") => None,
+                    presses => Some(field(presses, "presses")?),
+                };
+                Event::Assign { values, presses }
+            }
+            Some("merge") => Event::Merge {
+                a: field(words.next(), "box")?,
+                b: field(words.next(), "box")?,
+                size: field(words.next(), "circuit size")?,
+            },
+            Some(other) => return Err(AocError::parse(format!("unknown event '{}'", other))),
+            None => return Err(AocError::parse("empty event")),
+        };
+        match words.next() {
+            Some(extra) => Err(AocError::parse(format!("unexpected '{}' after the event", extra))),
+            None => Ok(event),
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# day {}", self.day)?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        if self.truncated {
+            writeln!(f, "# truncated after {} events", self.events.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = AocError;
+
+    fn from_str(log: &str) -> Result<Self> {
+        let mut lines = log.lines().enumerate();
+        let day = lines
+            .next()
+            .and_then(|(_, header)| header.strip_prefix("# day "))
+            .and_then(|day| day.trim().parse().ok())
+            .ok_or_else(|| AocError::parse_at(1, "expected a '# day N' header"))?;
+
+        let mut trace = Trace { day, events: Vec::new(), truncated: false };
+        for (i, line) in lines {
+            let line = line.trim();
+            if line.starts_with("# truncated") {
+                trace.truncated = true;
+            } else if !line.is_empty() && !line.starts_with('#') {
+                trace.events.push(line.parse().map_err(|e: AocError| e.on_line(i + 1))?);
+            }
+        }
+        Ok(trace)
+    }
+}
+
+/// Rebuilds solver state from a trace one event at a time, describing each step
+#[derive(Debug, Default)]
+pub struct Replay {
+    board: Option<Grid<char>>,
+    /// Pieces on the board, most recent last
+    placed: Vec<(usize, Vec<(i32, i32)>)>,
+    machine: Option<usize>,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn draw(&self) -> String {
+        self.board.as_ref().map_or_else(String::new, |board| board.to_string())
+    }
+
+    fn paint(&mut self, cells: &[(i32, i32)], symbol: char) {
+        if let Some(board) = &mut self.board {
+            for &(x, y) in cells {
+                if x >= 0 && y >= 0 {
+                    board.set(x as usize, y as usize, symbol);
+                }
+            }
+        }
+    }
+
+    /// Applies `event` and renders the resulting step
+    pub fn step(&mut self, event: &Event) -> String {
+        match event {
+            Event::Board { width, height } => {
+                self.board = Some(Grid::filled(*width, *height, '.'));
+                self.placed.clear();
+                format!("New {}x{} board\n{}", width, height, self.draw())
+            }
+            Event::Place { shape, cells } => {
+                let symbol = char::from_digit(*shape as u32 % 36, 36).unwrap_or('?');
+                self.paint(cells, symbol);
+                self.placed.push((*shape, cells.clone()));
+                format!("Place shape {} ({} on the board)\n{}", shape, self.placed.len(), self.draw())
+            }
+            Event::Unplace => match self.placed.pop() {
+                Some((shape, cells)) => {
+                    self.paint(&cells, '.');
+                    format!("Remove shape {} ({} on the board)\n{}", shape, self.placed.len(), self.draw())
+                }
+                None => "Remove: nothing on the board\n".to_string(),
+            },
+            Event::Machine { number } => {
+                self.machine = Some(*number);
+                format!("Machine {}\n", number)
+            }
+            Event::Assign { values, presses } => {
+                let machine = self.machine.map_or(String::new(), |m| format!("machine {}: ", m));
+                match presses {
+                    Some(presses) => format!("  {}free variables {:?} -> {} presses\n", machine, values, presses),
+                    None => format!("  {}free variables {:?} -> invalid\n", machine, values),
+                }
+            }
+            Event::Merge { a, b, size } => format!("Join boxes {} and {} into a circuit of {}\n", a, b, size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_round_trip() {
+        let trace = Trace {
+            day: 12,
+            events: vec![
+                Event::Board { width: 3, height: 2 },
+                Event::Place { shape: 4, cells: vec![(0, 0), (1, 0), (2, 0)] },
+                Event::Unplace,
+                Event::Machine { number: 7 },
+                Event::Assign { values: vec![1, 0, 2], presses: Some(9) },
+                Event::Assign { values: vec![], presses: None },
+                Event::Merge { a: 3, b: 17, size: 5 },
+            ],
+            truncated: true,
+        };
+        let log = trace.to_string();
+        assert!(log.starts_with("# day 12\nboard 3 2\nplace 4 0,0 1,0 2,0\nunplace\n"), "{}", log);
+        assert_eq!(log.parse::<Trace>().unwrap(), trace);
+
+        let err = "# day 8\nmerge 1 2\n".parse::<Trace>().unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: missing circuit size");
+        assert!("board 3 2\n".parse::<Trace>().is_err());
+    }
+
+    #[test]
+    fn test_replay_redraws_the_board() {
+        let mut replay = Replay::new();
+        replay.step(&Event::Board { width: 3, height: 2 });
+        let placed = replay.step(&Event::Place { shape: 1, cells: vec![(0, 1), (1, 1), (2, 1)] });
+        assert!(placed.ends_with("...\n111\n"), "{}", placed);
+        let removed = replay.step(&Event::Unplace);
+        assert!(removed.ends_with("...\n...\n"), "{}", removed);
+        assert_eq!(replay.step(&Event::Unplace), "Remove: nothing on the board\n");
+    }
+}
@@ -0,0 +1,14 @@
+// Browser entry point, built with `--features wasm`. Inputs arrive as strings
+// since there is no filesystem to read puzzle files from.
+
+use wasm_bindgen::prelude::*;
+
+/// Answer to `part` of `day` for `input`, or the error text prefixed with
+/// `Error: `
+#[wasm_bindgen]
+pub fn solve(day: u8, part: u8, input: &str) -> String {
+    match crate::days::solve(day, part, input) {
+        Ok(answer) => answer,
+        Err(err) => format!("Error: {}", err),
+    }
+}